@@ -0,0 +1,18 @@
+//! Scene JSON is the input the server and the C interface take from others, so it must give an
+//! error rather than a panic however it is mangled. Run with `cargo fuzz run scene_json`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ray_tracer::registry::SceneRegistry;
+use ray_tracer::settings::RenderSettings;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok((world, camera)) = ray_tracer::scene::from_json(text, &SceneRegistry::builtin()) {
+        let camera = camera.with_hsize(4).with_vsize(3).build();
+        let _ = camera.render(&world, &RenderSettings::default());
+    }
+});
@@ -0,0 +1,91 @@
+"""Python access to the ray tracer through its C interface.
+
+Build the shared library with `cargo build --release --features ffi`, which this module finds in
+`target/release` (or `target/debug`), or set RAY_TRACER_LIB to its path. Then:
+
+    >>> from ray_tracer import Scene
+    >>> image = Scene.default(320, 240).render()   # numpy array, 240 x 320 x 4, uint8 RGBA
+
+Scenes can also be read from the JSON that `scene::from_json` takes, with `Scene.from_json`.
+`render` needs numpy; `render_bytes` gives the same pixels without it.
+"""
+
+import ctypes
+import os
+
+
+def _find_library():
+    if "RAY_TRACER_LIB" in os.environ:
+        return os.environ["RAY_TRACER_LIB"]
+    root = os.path.join(os.path.dirname(os.path.abspath(__file__)), os.pardir, "target")
+    for profile in ("release", "debug"):
+        path = os.path.join(root, profile, "libray_tracer.so")
+        if os.path.exists(path):
+            return path
+    return "libray_tracer.so"
+
+
+_lib = ctypes.CDLL(_find_library())
+
+_lib.rt_scene_default.argtypes = [ctypes.c_size_t, ctypes.c_size_t]
+_lib.rt_scene_default.restype = ctypes.c_void_p
+_lib.rt_scene_from_json.argtypes = [ctypes.c_char_p]
+_lib.rt_scene_from_json.restype = ctypes.c_void_p
+_lib.rt_scene_free.argtypes = [ctypes.c_void_p]
+_lib.rt_scene_free.restype = None
+_lib.rt_scene_width.argtypes = [ctypes.c_void_p]
+_lib.rt_scene_width.restype = ctypes.c_size_t
+_lib.rt_scene_height.argtypes = [ctypes.c_void_p]
+_lib.rt_scene_height.restype = ctypes.c_size_t
+_lib.rt_render_rgba.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+_lib.rt_render_rgba.restype = ctypes.c_int
+_lib.rt_progress.argtypes = [ctypes.c_void_p]
+_lib.rt_progress.restype = ctypes.c_float
+
+
+class Scene:
+    """A world and the camera that renders it."""
+
+    def __init__(self, handle):
+        self._handle = handle
+        self.width = _lib.rt_scene_width(handle)
+        self.height = _lib.rt_scene_height(handle)
+
+    @classmethod
+    def default(cls, width, height):
+        """The default world of two spheres and a light, framed to fill the image."""
+        handle = _lib.rt_scene_default(width, height)
+        if not handle:
+            raise ValueError("the image must be at least one pixel in each direction")
+        return cls(handle)
+
+    @classmethod
+    def from_json(cls, text):
+        """The scene described by the JSON `text`."""
+        handle = _lib.rt_scene_from_json(text.encode("utf-8"))
+        if not handle:
+            raise ValueError("the text does not describe a scene")
+        return cls(handle)
+
+    def render_bytes(self):
+        """Render the scene as 8-bit sRGB RGBA, row by row from the top left."""
+        image = (ctypes.c_uint8 * (self.width * self.height * 4))()
+        if _lib.rt_render_rgba(self._handle, image, len(image)) != 0:
+            raise RuntimeError("rendering failed")
+        return bytes(image)
+
+    def render(self):
+        """Render the scene as a height x width x 4 numpy array of 8-bit sRGB RGBA."""
+        import numpy as np
+
+        image = np.frombuffer(self.render_bytes(), dtype=np.uint8)
+        return image.reshape((self.height, self.width, 4))
+
+    def progress(self):
+        """How far this scene's render in progress has got, from 0 to 1."""
+        return _lib.rt_progress(self._handle)
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _lib.rt_scene_free(self._handle)
+            self._handle = None
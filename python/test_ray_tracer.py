@@ -0,0 +1,54 @@
+"""Tests for the Python wrapper, run against the built library with
+
+    cargo build --release --features ffi && python3 -m unittest discover python
+"""
+
+import unittest
+
+from ray_tracer import Scene
+
+try:
+    import numpy
+except ImportError:
+    numpy = None
+
+
+class SceneTest(unittest.TestCase):
+    def test_rendering_the_default_scene(self):
+        scene = Scene.default(8, 6)
+
+        pixels = scene.render_bytes()
+
+        self.assertEqual(len(pixels), 8 * 6 * 4)
+        self.assertEqual(scene.progress(), 1.0)
+        # The framed sphere fills the middle and leaves the corners clear
+        self.assertEqual(pixels[(3 * 8 + 4) * 4 + 3], 255)
+        self.assertEqual(pixels[3], 0)
+
+    def test_a_scene_from_json(self):
+        scene = Scene.from_json(
+            '{ "camera": { "width": 5, "height": 4 }, "objects": [ { "type": "sphere" } ] }'
+        )
+
+        self.assertEqual((scene.width, scene.height), (5, 4))
+        self.assertEqual(scene.progress(), 0.0)
+        self.assertEqual(len(scene.render_bytes()), 5 * 4 * 4)
+
+    def test_json_that_isnt_a_scene_is_refused(self):
+        with self.assertRaises(ValueError):
+            Scene.from_json('{ "objects": [ { "type": "torus" } ] }')
+
+    def test_an_empty_image_is_refused(self):
+        with self.assertRaises(ValueError):
+            Scene.default(0, 10)
+
+    @unittest.skipIf(numpy is None, "numpy is not installed")
+    def test_rendering_into_an_array(self):
+        image = Scene.default(8, 6).render()
+
+        self.assertEqual(image.shape, (6, 8, 4))
+        self.assertEqual(image.dtype, numpy.uint8)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,295 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::filter::PixelFilter;
+
+/// A robust mean of `values`: they are dealt out in turn into `groups` groups, and the median of
+/// the groups' means is taken. A few wild values spoil only the groups they land in, so unlike
+/// the plain mean the result barely moves. Zero if there are no values.
+pub fn median_of_means(values: &[f32], groups: usize) -> f32 {
+    let groups = groups.clamp(1, values.len().max(1));
+    let mut means: Vec<f32> = (0..groups)
+        .map(|group| {
+            let members: Vec<f32> = values.iter().skip(group).step_by(groups).copied().collect();
+            members.iter().sum::<f32>() / members.len().max(1) as f32
+        })
+        .collect();
+    means.sort_by(f32::total_cmp);
+
+    match groups % 2 {
+        0 => (means[groups / 2 - 1] + means[groups / 2]) / 2.0,
+        _ => means[groups / 2],
+    }
+}
+
+pub struct AccumulationBuffer {
+    width: usize,
+    height: usize,
+    sums: Vec<Color>,
+    /// The weighted sums of the squares of the samples, for their variance.
+    squares: Vec<Color>,
+    counts: Vec<u32>,
+    weights: Vec<f32>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            sums: vec![Color::black(); width * height],
+            squares: vec![Color::black(); width * height],
+            counts: vec![0; width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn add_sample(&mut self, x: usize, y: usize, color: Color) {
+        let index = self.index(x, y);
+        self.sums[index] += color;
+        self.squares[index] += color * color;
+        self.counts[index] += 1;
+        self.weights[index] += 1.0;
+    }
+
+    /// Add a sample that counts for `weight` samples, which may be less than one or even negative,
+    /// as when a reconstruction filter spreads a sample over the pixels around it.
+    pub fn add_weighted_sample(&mut self, x: usize, y: usize, color: Color, weight: f32) {
+        let index = self.index(x, y);
+        self.sums[index] += color * weight;
+        self.squares[index] += color * color * weight;
+        self.counts[index] += 1;
+        self.weights[index] += weight;
+    }
+
+    /// Add a sample taken `offset` of the way across and down pixel `(x, y)` to every pixel
+    /// `filter` reaches from there, weighted by how far it is from each pixel's centre. A box
+    /// filter keeps the sample in its own pixel.
+    pub fn add_filtered_sample(
+        &mut self,
+        x: usize,
+        y: usize,
+        offset: (f32, f32),
+        color: Color,
+        filter: PixelFilter,
+    ) {
+        if filter == PixelFilter::Box {
+            self.add_sample(x, y, color);
+            return;
+        }
+
+        let (sx, sy) = (x as f32 + offset.0, y as f32 + offset.1);
+        let reach = filter.reach();
+        for ny in y.saturating_sub(reach)..(y + reach + 1).min(self.height) {
+            for nx in x.saturating_sub(reach)..(x + reach + 1).min(self.width) {
+                let weight = filter.weight(sx - (nx as f32 + 0.5), sy - (ny as f32 + 0.5));
+                if weight != 0.0 {
+                    self.add_weighted_sample(nx, ny, color, weight);
+                }
+            }
+        }
+    }
+
+    pub fn sample_count(&self, x: usize, y: usize) -> u32 {
+        self.counts[self.index(x, y)]
+    }
+
+    /// The weighted mean of all samples added at the pixel, or black if there are none yet or
+    /// their weights cancel out.
+    pub fn pixel_at(&self, x: usize, y: usize) -> Color {
+        let index = self.index(x, y);
+        if self.weights[index] <= 0.0 {
+            return Color::black();
+        }
+
+        self.sums[index] / self.weights[index]
+    }
+
+    /// How much the samples at the pixel differ from their mean, as the largest of the variances
+    /// of the red, green and blue channels. Zero until there are at least two samples.
+    pub fn variance(&self, x: usize, y: usize) -> f32 {
+        let index = self.index(x, y);
+        if self.counts[index] < 2 || self.weights[index] <= 0.0 {
+            return 0.0;
+        }
+
+        let mean = self.sums[index] / self.weights[index];
+        let variance = self.squares[index] / self.weights[index] - mean * mean;
+        variance.r.max(variance.g).max(variance.b).max(0.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.sums.iter_mut().for_each(|sum| *sum = Color::black());
+        self.squares.iter_mut().for_each(|square| *square = Color::black());
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.weights.iter_mut().for_each(|weight| *weight = 0.0);
+    }
+
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                canvas.write_pixel(x, y, &self.pixel_at(x, y));
+            }
+        }
+
+        canvas
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel is outside the buffer");
+        y * self.width + x
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn new_buffer_has_width_and_height() {
+        let buffer = AccumulationBuffer::new(10, 20);
+
+        assert_that!(buffer.width()).is_equal_to(10);
+        assert_that!(buffer.height()).is_equal_to(20);
+    }
+
+    #[test]
+    fn new_buffer_has_no_samples() {
+        let buffer = AccumulationBuffer::new(3, 3);
+
+        assert_that!(buffer.sample_count(1, 2)).is_equal_to(0);
+        assert_that!(buffer.pixel_at(1, 2)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn adding_samples_averages_them() {
+        let mut buffer = AccumulationBuffer::new(3, 3);
+
+        buffer.add_sample(1, 2, Color::new(1.0, 0.0, 0.5));
+        buffer.add_sample(1, 2, Color::new(0.0, 1.0, 0.5));
+
+        assert_that!(buffer.sample_count(1, 2)).is_equal_to(2);
+        assert_that!(buffer.pixel_at(1, 2)).is_equal_to(Color::new(0.5, 0.5, 0.5));
+        assert_that!(buffer.sample_count(2, 1)).is_equal_to(0);
+    }
+
+    #[test]
+    fn clearing_the_buffer_discards_samples() {
+        let mut buffer = AccumulationBuffer::new(3, 3);
+        buffer.add_sample(0, 0, Color::white());
+
+        buffer.clear();
+
+        assert_that!(buffer.sample_count(0, 0)).is_equal_to(0);
+        assert_that!(buffer.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn converting_to_a_canvas_resolves_the_averages() {
+        let mut buffer = AccumulationBuffer::new(2, 2);
+        buffer.add_sample(0, 1, Color::new(0.25, 0.5, 0.75));
+        buffer.add_sample(0, 1, Color::new(0.75, 0.0, 0.25));
+        buffer.add_sample(1, 0, Color::white());
+
+        let canvas = buffer.to_canvas();
+
+        assert_that!(canvas.width()).is_equal_to(2);
+        assert_that!(canvas.height()).is_equal_to(2);
+        assert_that!(canvas.pixel_at(0, 1)).is_equal_to(Color::new(0.5, 0.25, 0.5));
+        assert_that!(canvas.pixel_at(1, 0)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    #[should_panic(expected = "Pixel is outside the buffer")]
+    fn adding_a_sample_outside_the_buffer_causes_panic() {
+        let mut buffer = AccumulationBuffer::new(2, 2);
+
+        buffer.add_sample(2, 0, Color::white());
+    }
+
+    #[test]
+    fn weighted_samples_give_a_weighted_mean() {
+        let mut buffer = AccumulationBuffer::new(1, 1);
+
+        buffer.add_weighted_sample(0, 0, Color::white(), 3.0);
+        buffer.add_weighted_sample(0, 0, Color::black(), 1.0);
+        buffer.add_weighted_sample(0, 0, Color::new(1.0, 0.0, 0.0), -0.5);
+
+        assert_that!(buffer.sample_count(0, 0)).is_equal_to(3);
+        let expected = Color::new(2.5 / 3.5, 3.0 / 3.5, 3.0 / 3.5);
+        assert_that!(buffer.pixel_at(0, 0)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn a_tent_filter_keeps_a_centred_sample_in_its_pixel() {
+        let mut buffer = AccumulationBuffer::new(3, 3);
+
+        buffer.add_filtered_sample(1, 1, (0.5, 0.5), Color::white(), PixelFilter::Tent);
+
+        assert_that!(buffer.sample_count(1, 1)).is_equal_to(1);
+        assert_that!(buffer.sample_count(0, 1)).is_equal_to(0);
+        assert_that!(buffer.pixel_at(1, 1)).is_equal_to(Color::white());
+    }
+
+    #[test]
+    fn a_filter_spreads_an_off_centre_sample_into_its_neighbours() {
+        let mut buffer = AccumulationBuffer::new(3, 3);
+
+        buffer.add_filtered_sample(1, 1, (0.9, 0.5), Color::white(), PixelFilter::Tent);
+
+        assert_that!(buffer.sample_count(2, 1)).is_equal_to(1);
+        assert_that!(buffer.sample_count(0, 1)).is_equal_to(0);
+        assert_that!(buffer.sample_count(1, 0)).is_equal_to(0);
+    }
+
+    #[test]
+    fn the_variance_of_the_samples_at_a_pixel() {
+        let mut buffer = AccumulationBuffer::new(2, 1);
+
+        buffer.add_sample(0, 0, Color::new(0.2, 0.5, 0.5));
+        buffer.add_sample(0, 0, Color::new(0.6, 0.5, 0.4));
+        buffer.add_sample(1, 0, Color::white());
+        buffer.add_sample(1, 0, Color::white());
+
+        assert_that!(buffer.variance(0, 0)).is_close_to(0.04, 0.00001);
+        assert_that!(buffer.variance(1, 0)).is_equal_to(0.0);
+    }
+
+    #[test]
+    fn a_single_sample_has_no_variance() {
+        let mut buffer = AccumulationBuffer::new(1, 1);
+
+        buffer.add_sample(0, 0, Color::new(0.2, 0.5, 0.5));
+
+        assert_that!(buffer.variance(0, 0)).is_equal_to(0.0);
+    }
+
+    #[test]
+    fn the_median_of_means_ignores_a_wild_value() {
+        let values = [0.5, 0.5, 0.5, 0.5, 90.0, 0.5, 0.5, 0.5];
+
+        assert_that!(median_of_means(&values, 4)).is_close_to(0.5, 0.00001);
+        assert_that!(median_of_means(&values, 1)).is_close_to(11.6875, 0.00001);
+    }
+
+    #[test]
+    fn the_median_of_means_with_fewer_values_than_groups() {
+        assert_that!(median_of_means(&[0.2, 0.8, 0.3], 8)).is_close_to(0.3, 0.00001);
+        assert_that!(median_of_means(&[], 4)).is_equal_to(0.0);
+    }
+}
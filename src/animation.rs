@@ -0,0 +1,222 @@
+//! Values that change over the course of an animation, set by keyframes with easing between
+//! them, and the camera settings they can drive, such as a rack focus from one subject to
+//! another.
+
+use crate::camera::Camera;
+
+/// How a value moves from one keyframe to the next, as a function of the fraction of the way
+/// between them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Easing {
+    /// At a steady rate.
+    #[default]
+    Linear,
+    /// Starting slowly and speeding up.
+    EaseIn,
+    /// Starting quickly and slowing down.
+    EaseOut,
+    /// Slowly at both ends, as a focus puller turning the ring by hand.
+    EaseInOut,
+    /// Staying at the earlier value until the later keyframe, then jumping to it.
+    Hold,
+}
+
+impl Easing {
+    /// How far the value has moved, from 0 to 1, at fraction `t` of the way between keyframes.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Hold => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// A value at a time, reached from the keyframe before it by `easing`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+    pub easing: Easing,
+}
+
+/// A value that changes over time, set by keyframes. Before the first keyframe it has that
+/// keyframe's value and after the last it has the last one's.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    // Note: kept in order of time
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a keyframe, reached from the one before it by `easing`. A keyframe already at `time`
+    /// is replaced.
+    pub fn with_key(mut self, time: f32, value: f32, easing: Easing) -> Self {
+        self.keys.retain(|key| key.time != time);
+        let index = self.keys.partition_point(|key| key.time < time);
+        self.keys.insert(
+            index,
+            Keyframe {
+                time,
+                value,
+                easing,
+            },
+        );
+
+        self
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    /// The value at `time`, or `None` if the track has no keyframes.
+    pub fn value_at(&self, time: f32) -> Option<f32> {
+        let after = self.keys.partition_point(|key| key.time <= time);
+        match (
+            after.checked_sub(1).map(|i| &self.keys[i]),
+            self.keys.get(after),
+        ) {
+            (None, None) => None,
+            (Some(key), None) | (None, Some(key)) => Some(key.value),
+            (Some(from), Some(to)) => {
+                let t = (time - from.time) / (to.time - from.time);
+                Some(from.value + (to.value - from.value) * to.easing.apply(t))
+            }
+        }
+    }
+}
+
+/// The camera's lens settings over the course of an animation. Settings without a track keep
+/// the camera's own value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraAnimation {
+    aperture: Track,
+    focal_distance: Track,
+}
+
+impl CameraAnimation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_aperture(mut self, aperture: Track) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn with_focal_distance(mut self, focal_distance: Track) -> Self {
+        self.focal_distance = focal_distance;
+        self
+    }
+
+    /// `camera` as it is at `time`.
+    pub fn camera_at(&self, camera: &Camera, time: f32) -> Camera {
+        let aperture = self.aperture.value_at(time).unwrap_or(camera.aperture());
+        let focal_distance = self
+            .focal_distance
+            .value_at(time)
+            .unwrap_or(camera.focal_distance());
+
+        camera.refocused(aperture, focal_distance)
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::camera::CameraBuilder;
+
+    #[rstest]
+    #[case(Easing::Linear, 0.25, 0.25)]
+    #[case(Easing::EaseIn, 0.5, 0.25)]
+    #[case(Easing::EaseOut, 0.5, 0.75)]
+    #[case(Easing::EaseInOut, 0.25, 0.15625)]
+    #[case(Easing::EaseInOut, 0.5, 0.5)]
+    #[case(Easing::Hold, 0.99, 0.0)]
+    #[case(Easing::Hold, 1.0, 1.0)]
+    #[case(Easing::EaseIn, 1.5, 1.0)]
+    fn easing_between_keyframes(#[case] easing: Easing, #[case] t: f32, #[case] expected: f32) {
+        assert_that!(easing.apply(t)).is_close_to(expected, 0.00001);
+    }
+
+    #[fixture]
+    fn track() -> Track {
+        Track::new()
+            .with_key(2.0, 10.0, Easing::EaseInOut)
+            .with_key(0.0, 2.0, Easing::Linear)
+            .with_key(3.0, 0.0, Easing::Linear)
+    }
+
+    #[rstest]
+    #[case(-1.0, 2.0)]
+    #[case(0.0, 2.0)]
+    #[case(1.0, 6.0)]
+    #[case(0.5, 2.0 + 8.0 * 0.15625)]
+    #[case(2.0, 10.0)]
+    #[case(2.5, 5.0)]
+    #[case(4.0, 0.0)]
+    fn the_value_of_a_track(track: Track, #[case] time: f32, #[case] expected: f32) {
+        assert_that!(track.value_at(time))
+            .is_some()
+            .is_close_to(expected, 0.0001);
+    }
+
+    #[rstest]
+    fn keyframes_are_kept_in_order_and_replaced(track: Track) {
+        let track = track.with_key(2.0, 4.0, Easing::Linear);
+
+        let times: Vec<f32> = track.keys().iter().map(|key| key.time).collect();
+        assert_that!(times).is_equal_to(vec![0.0, 2.0, 3.0]);
+        assert_that!(track.value_at(2.0)).is_equal_to(Some(4.0));
+    }
+
+    #[test]
+    fn an_empty_track_has_no_value() {
+        assert_that!(Track::new().value_at(1.0)).is_none();
+    }
+
+    #[test]
+    fn pulling_focus_over_an_animation() {
+        let camera = CameraBuilder::new()
+            .with_hsize(8)
+            .with_vsize(6)
+            .with_field_of_view(PI / 3.0)
+            .with_depth_of_field(0.1, 3.0)
+            .build();
+        let animation = CameraAnimation::new().with_focal_distance(
+            Track::new()
+                .with_key(0.0, 2.0, Easing::Linear)
+                .with_key(1.0, 6.0, Easing::EaseInOut),
+        );
+
+        let start = animation.camera_at(&camera, 0.0);
+        let middle = animation.camera_at(&camera, 0.5);
+
+        assert_that!(start.focal_distance()).is_equal_to(2.0);
+        assert_that!(middle.focal_distance()).is_close_to(4.0, 0.0001);
+        assert_that!(middle.aperture()).is_equal_to(0.1);
+    }
+}
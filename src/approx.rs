@@ -0,0 +1,105 @@
+//! Comparing floating point values to within a tolerance, for tests that can't expect exact
+//! answers.
+
+use crate::color::Color;
+use crate::matrix::Matrix;
+use crate::vector4::Vector4;
+
+/// Values that can be compared component by component to within a tolerance.
+pub trait ApproxEq {
+    /// Is every component of `self` within `tolerance` of the same component of `other`?
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool;
+}
+
+impl ApproxEq for f32 {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        (self - other).abs() <= tolerance
+    }
+}
+
+impl ApproxEq for Vector4 {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        (0..4).all(|i| self[i].approx_eq(&other[i], tolerance))
+    }
+}
+
+impl ApproxEq for Color {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.r.approx_eq(&other.r, tolerance)
+            && self.g.approx_eq(&other.g, tolerance)
+            && self.b.approx_eq(&other.b, tolerance)
+    }
+}
+
+impl<const L: usize> ApproxEq for Matrix<L> {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        (0..L)
+            .all(|row| (0..L).all(|col| self[[row, col]].approx_eq(&other[[row, col]], tolerance)))
+    }
+}
+
+/// Assert that two values are the same to within a tolerance, as in
+/// `assert_approx_eq!(color, Color::new(0.1, 0.2, 0.3), 1e-4)`, showing both if they aren't.
+#[macro_export]
+macro_rules! assert_approx_eq {
+    ($actual:expr, $expected:expr, $tolerance:expr $(,)?) => {{
+        let (actual, expected, tolerance) = (&$actual, &$expected, $tolerance);
+        assert!(
+            $crate::approx::ApproxEq::approx_eq(actual, expected, tolerance),
+            "expected {:?} to within {} but was {:?}",
+            expected,
+            tolerance,
+            actual
+        );
+    }};
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Color::new(0.5, 0.5, 0.5), Color::new(0.50005, 0.49995, 0.5), true)]
+    #[case(Color::new(0.5, 0.5, 0.5), Color::new(0.5, 0.5, 0.5002), false)]
+    fn comparing_colors(#[case] a: Color, #[case] b: Color, #[case] expected: bool) {
+        assert_that!(a.approx_eq(&b, 0.0001)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn comparing_vectors_looks_at_w_too() {
+        let v = Vector4::vector(1.0, 2.0, 3.0);
+
+        assert_that!(v.approx_eq(&Vector4::vector(1.0, 2.00001, 3.0), 0.0001)).is_true();
+        assert_that!(v.approx_eq(&Vector4::point(1.0, 2.0, 3.0), 0.0001)).is_false();
+    }
+
+    #[test]
+    fn comparing_matrices() {
+        let m = Matrix::from([[1.0, 2.0], [3.0, 4.0]]);
+
+        assert_that!(m.approx_eq(&Matrix::from([[1.0, 2.0], [3.0, 4.001]]), 0.01)).is_true();
+        assert_that!(m.approx_eq(&Matrix::from([[1.0, 2.0], [3.1, 4.0]]), 0.01)).is_false();
+    }
+
+    #[test]
+    fn asserting_close_values() {
+        crate::assert_approx_eq!(
+            Vector4::point(0.1, 0.2, 0.3),
+            Vector4::point(0.1, 0.2, 0.3),
+            0.0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "to within 0.0001 but was")]
+    fn asserting_values_that_differ_panics() {
+        crate::assert_approx_eq!(Color::new(0.1, 0.2, 0.3), Color::new(0.1, 0.2, 0.4), 0.0001);
+    }
+}
@@ -0,0 +1,335 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use crate::cylinder::CylinderBuilder;
+use crate::group::{Group, GroupBuilder};
+use crate::material::Material;
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::vector4::Vector4;
+
+/// An axis-aligned bounding box. A box with no points added is empty, with `min` above `max`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vector4,
+    pub max: Vector4,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Vector4, max: Vector4) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(
+            Vector4::point(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            Vector4::point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        )
+    }
+
+    pub fn infinite() -> Self {
+        Self::new(
+            Vector4::point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            Vector4::point(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Is the box non-empty and finite along every axis?
+    pub fn is_bounded(&self) -> bool {
+        !self.is_empty()
+            && [self.min, self.max]
+                .iter()
+                .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+    }
+
+    pub fn add_point(&mut self, point: Vector4) {
+        self.min = Vector4::point(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Vector4::point(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    pub fn merge(&mut self, other: &BoundingBox) {
+        if !other.is_empty() {
+            self.add_point(other.min);
+            self.add_point(other.max);
+        }
+    }
+
+    pub fn contains_point(&self, point: Vector4) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    /// Where the ray enters and leaves the box, if it passes through it.
+    pub fn span(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let axis = |origin: f32, direction: f32, min: f32, max: f32| {
+            let (t0, t1) = ((min - origin) / direction, (max - origin) / direction);
+            if t0 > t1 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            }
+        };
+        let (o, d) = (ray.origin, ray.direction);
+        let (xmin, xmax) = axis(o.x, d.x, self.min.x, self.max.x);
+        let (ymin, ymax) = axis(o.y, d.y, self.min.y, self.max.y);
+        let (zmin, zmax) = axis(o.z, d.z, self.min.z, self.max.z);
+
+        let tmin = xmin.max(ymin).max(zmin);
+        let tmax = xmax.min(ymax).min(zmax);
+        if tmin > tmax || tmax.is_nan() || tmin.is_nan() {
+            return None;
+        }
+
+        Some((tmin, tmax))
+    }
+
+    /// The box enclosing this one after `transform` is applied to it.
+    ///
+    /// An unbounded box stays unbounded on every axis, as rotating an infinite extent can spread
+    /// it onto any axis.
+    pub fn transform(&self, transform: &Matrix<4>) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_bounded() {
+            return Self::infinite();
+        }
+
+        let mut transformed = Self::empty();
+        for x in [self.min.x, self.max.x] {
+            for y in [self.min.y, self.max.y] {
+                for z in [self.min.z, self.max.z] {
+                    transformed.add_point(*transform * Vector4::point(x, y, z));
+                }
+            }
+        }
+
+        transformed
+    }
+
+    /// Thin cylinders along each of the box's twelve edges, for visualising the box in a scene.
+    pub fn wireframe(&self, radius: f32, material: &Material) -> Group {
+        let mut group = GroupBuilder::new();
+        if !self.is_bounded() {
+            return group.build();
+        }
+
+        let (min, max) = (self.min, self.max);
+        let scale = Matrix::scaling(radius, 1.0, radius);
+        for a in [0, 1] {
+            for b in [0, 1] {
+                let pick = |axis: usize, i: usize| if i == 0 { min[axis] } else { max[axis] };
+
+                // (extent along the edge, transform taking the cylinder's y axis onto the edge)
+                let edges = [
+                    (
+                        (min.x, max.x),
+                        Matrix::translation(0.0, pick(1, a), pick(2, b))
+                            * Matrix::rotation_z(-PI / 2.0),
+                    ),
+                    (
+                        (min.y, max.y),
+                        Matrix::translation(pick(0, a), 0.0, pick(2, b)),
+                    ),
+                    (
+                        (min.z, max.z),
+                        Matrix::translation(pick(0, a), pick(1, b), 0.0)
+                            * Matrix::rotation_x(PI / 2.0),
+                    ),
+                ];
+                for ((from, to), transform) in edges {
+                    let edge = CylinderBuilder::new()
+                        .with_minimum(from)
+                        .with_maximum(to)
+                        .with_closed(true)
+                        .with_transform(transform * scale)
+                        .with_material(material.clone())
+                        .build();
+                    group = group.with_child(Rc::new(edge));
+                }
+            }
+        }
+
+        group.build()
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_1_SQRT_2, SQRT_2};
+
+    use spectral::prelude::*;
+
+    use crate::material::MaterialBuilder;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+
+    use super::*;
+
+    #[test]
+    fn creating_an_empty_bounding_box() {
+        let b = BoundingBox::empty();
+
+        assert_that!(b.is_empty()).is_true();
+        assert_that!(b.is_bounded()).is_false();
+    }
+
+    #[test]
+    fn adding_points_to_an_empty_bounding_box() {
+        let mut b = BoundingBox::empty();
+
+        b.add_point(Vector4::point(-5.0, 2.0, 0.0));
+        b.add_point(Vector4::point(7.0, 0.0, -3.0));
+
+        assert_that!(b.min).is_equal_to(Vector4::point(-5.0, 0.0, -3.0));
+        assert_that!(b.max).is_equal_to(Vector4::point(7.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn merging_a_bounding_box_into_another() {
+        let mut b1 = BoundingBox::new(
+            Vector4::point(-5.0, -2.0, 0.0),
+            Vector4::point(7.0, 4.0, 4.0),
+        );
+        let b2 = BoundingBox::new(
+            Vector4::point(8.0, -7.0, -2.0),
+            Vector4::point(14.0, 2.0, 8.0),
+        );
+
+        b1.merge(&b2);
+        b1.merge(&BoundingBox::empty());
+
+        assert_that!(b1.min).is_equal_to(Vector4::point(-5.0, -7.0, -2.0));
+        assert_that!(b1.max).is_equal_to(Vector4::point(14.0, 4.0, 8.0));
+    }
+
+    #[test]
+    fn checking_whether_a_box_contains_a_point() {
+        let b = BoundingBox::new(
+            Vector4::point(5.0, -2.0, 0.0),
+            Vector4::point(11.0, 4.0, 7.0),
+        );
+
+        assert_that!(b.contains_point(Vector4::point(5.0, -2.0, 0.0))).is_true();
+        assert_that!(b.contains_point(Vector4::point(8.0, 1.0, 3.0))).is_true();
+        assert_that!(b.contains_point(Vector4::point(3.0, 0.0, 3.0))).is_false();
+        assert_that!(b.contains_point(Vector4::point(8.0, 1.0, 8.0))).is_false();
+    }
+
+    #[test]
+    fn finding_where_a_ray_passes_through_a_box() {
+        let b = BoundingBox::new(
+            Vector4::point(5.0, -2.0, 0.0),
+            Vector4::point(11.0, 4.0, 7.0),
+        );
+        let through = Ray::new(
+            Vector4::point(0.0, 1.0, 3.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        );
+        let past = Ray::new(
+            Vector4::point(0.0, 5.0, 3.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        );
+
+        assert_that!(b.span(&through)).is_equal_to(Some((5.0, 11.0)));
+        assert_that!(b.span(&past)).is_none();
+    }
+
+    #[test]
+    fn transforming_a_bounding_box() {
+        let b = BoundingBox::new(
+            Vector4::point(-1.0, -1.0, -1.0),
+            Vector4::point(1.0, 1.0, 1.0),
+        );
+        let matrix = Matrix::rotation_x(PI / 4.0) * Matrix::rotation_y(PI / 4.0);
+
+        let b2 = b.transform(&matrix);
+
+        let diagonal = 1.0 + FRAC_1_SQRT_2;
+        assert_that!(b2.min).is_equal_to(Vector4::point(-SQRT_2, -diagonal, -diagonal));
+        assert_that!(b2.max).is_equal_to(Vector4::point(SQRT_2, diagonal, diagonal));
+    }
+
+    #[test]
+    fn transforming_an_unbounded_box_leaves_it_unbounded() {
+        let b = BoundingBox::new(
+            Vector4::point(f32::NEG_INFINITY, 0.0, f32::NEG_INFINITY),
+            Vector4::point(f32::INFINITY, 0.0, f32::INFINITY),
+        );
+
+        let b2 = b.transform(&Matrix::rotation_z(PI / 2.0));
+
+        assert_that!(b2.min.y).is_equal_to(f32::NEG_INFINITY);
+        assert_that!(b2.max.x).is_equal_to(f32::INFINITY);
+    }
+
+    #[test]
+    fn a_wireframe_has_a_cylinder_for_each_edge() {
+        let b = BoundingBox::new(
+            Vector4::point(-1.0, -2.0, -3.0),
+            Vector4::point(1.0, 2.0, 3.0),
+        );
+
+        let wireframe = b.wireframe(0.05, &MaterialBuilder::new().build());
+
+        assert_that!(wireframe.children().len()).is_equal_to(12);
+    }
+
+    #[test]
+    fn a_wireframe_follows_the_edges_of_the_box() {
+        let b = BoundingBox::new(
+            Vector4::point(-1.0, -2.0, -3.0),
+            Vector4::point(1.0, 2.0, 3.0),
+        );
+        let wireframe = b.wireframe(0.05, &MaterialBuilder::new().build());
+
+        // Across the two bottom edges that run along x
+        let r = Ray::new(
+            Vector4::point(0.0, -2.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = wireframe.intersect(&r);
+        assert_that!(xs).has_length(4);
+        for (actual, expected) in xs.into_iter().zip([1.95, 2.05, 7.95, 8.05]) {
+            assert_that!(actual).is_close_to(expected, 0.0001);
+        }
+
+        // Through the middle of the box, missing every edge
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        assert_that!(wireframe.intersect(&r)).is_empty();
+    }
+
+    #[test]
+    fn an_unbounded_box_has_no_wireframe() {
+        let wireframe = BoundingBox::infinite().wireframe(0.05, &MaterialBuilder::new().build());
+
+        assert_that!(wireframe.children().len()).is_equal_to(0);
+    }
+}
@@ -1,9 +1,25 @@
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::io::{Error, Write};
+use std::str::FromStr;
+
+use crate::accumulation::AccumulationBuffer;
 use crate::canvas::Canvas;
+use crate::color::{Color, ColorSpace};
+use crate::consts::EPSILON;
+use crate::exr::{self, Channel};
+use crate::filter::{self, PixelFilter};
+use crate::info_span;
+use crate::lens::Aperture;
 use crate::matrix::Matrix;
 use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::settings::{Integrator, RenderSettings};
+use crate::transform::Transform;
 use crate::vector4::Vector4;
-use crate::world::World;
+use crate::world::{ObjectId, World};
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Camera {
     hsize: usize,
     vsize: usize,
@@ -11,6 +27,211 @@ pub struct Camera {
     pixel_size: f32,
     half_width: f32,
     half_height: f32,
+    shift: f32, // Note: how far the canvas is moved to the right, in the same units as its width
+    end_inv_transform: Option<Matrix<4>>, // Note: where a moving camera ends up, at time 1
+    shutter: Shutter,
+    aperture: f32, // Note: the radius of the lens, or 0 for a pinhole
+    aperture_shape: Aperture,
+    focal_distance: f32,
+    exposure: Option<Exposure>,
+    near_clip: f32, // Note: how far along a camera ray things start to be seen
+    far_clip: f32,
+}
+
+/// A photographic exposure, for lights given in physical units: the sun lights a scene with
+/// around 100,000 lux and a bright room with a few hundred, and the same settings a real camera
+/// would use for each bring it out well exposed.
+///
+/// The f-number only sets how much light gets in. How much of the scene is in focus is set
+/// separately, by the lens in `CameraBuilder::with_depth_of_field`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exposure {
+    pub iso: f32,
+    /// How long the shutter is open, in seconds.
+    pub shutter_speed: f32,
+    pub f_stop: f32,
+}
+
+impl Default for Exposure {
+    /// The "sunny 16" rule: f/16 at ISO 100 with a shutter speed of 1/100 s, for bright daylight.
+    fn default() -> Self {
+        Self {
+            iso: 100.0,
+            shutter_speed: 0.01,
+            f_stop: 16.0,
+        }
+    }
+}
+
+impl Exposure {
+    /// The exposure value these settings amount to at ISO 100. Each step up halves the light.
+    pub fn ev100(&self) -> f32 {
+        (self.f_stop * self.f_stop / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    /// What radiance is multiplied by, so that the brightest light the settings capture without
+    /// burning out comes out as white. The 1.2 is the usual allowance for the lens letting less
+    /// than all of the light through, as in the ISO 12232 saturation-based speed.
+    pub fn scale(&self) -> f32 {
+        1.0 / (1.2 * 2.0_f32.powf(self.ev100()))
+    }
+}
+
+/// Where one camera ray goes through its pixel, when it is taken, and where on the lens it
+/// starts, each as fractions in [0, 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct CameraSample {
+    pub offset: (f32, f32),
+    pub time: f32,
+    pub lens: (f32, f32),
+}
+
+/// When each row of the image is exposed, in the time over which a moving camera goes from its
+/// start at 0 to its end at 1.
+///
+/// A global shutter exposes every row from `open` to `close`. A rolling shutter reads the rows out
+/// one after another from the top, so each row's exposure starts `rolling` later in all than the
+/// top row's by the time it reaches the bottom, and a camera panning quickly skews upright
+/// objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+    pub rolling: f32,
+}
+
+impl Shutter {
+    /// A global shutter open from `open` to `close`.
+    pub fn new(open: f32, close: f32) -> Self {
+        Self {
+            open,
+            close,
+            rolling: 0.0,
+        }
+    }
+
+    /// The time `fraction` of the way through the exposure of row `row` of `rows`.
+    pub fn time(&self, row: usize, rows: usize, fraction: f32) -> f32 {
+        let delay = self.rolling * row as f32 / rows.max(1) as f32;
+
+        self.open + delay + (self.close - self.open) * fraction
+    }
+}
+
+/// False-colour views of the scene for diagnosing problems the beauty pass hides.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugChannel {
+    /// World-space normals at the hit, mapped from [-1, 1] to [0, 1] per channel.
+    Normals,
+    /// Distance to the hit, brightest for the nearest surface in the image.
+    Depth,
+    /// A distinct colour for each object in the world.
+    ObjectId,
+    /// White where the hit is lit, black where it is in shadow, grey where only some lights reach.
+    ShadowMask,
+    /// Number of intersections in front of the camera, from blue (few) to red (most).
+    IntersectionCount,
+}
+
+/// The separate layers of a render, for compositing or feeding to an external denoiser.
+pub struct Aovs {
+    /// The finished image, as `Camera::render` would produce before denoising and post-processing.
+    pub beauty: Canvas,
+    /// Distance from the camera to the hit, the same in every channel, or 0 where nothing is hit.
+    pub depth: Canvas,
+    /// World-space normals at the hit, in [-1, 1] per channel.
+    pub normals: Canvas,
+    /// The colour of the surface at the hit, before any lighting.
+    pub albedo: Canvas,
+    /// Light falling straight onto the surface from the lights and environment.
+    pub direct: Canvas,
+    /// Light reflected or refracted onto the surface from elsewhere in the scene.
+    pub indirect: Canvas,
+    /// The object seen through the middle of each pixel, row by row.
+    pub object_ids: Vec<Vec<Option<ObjectId>>>,
+}
+
+/// How `CameraBuilder::framing` fits the scene into the view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FitMode {
+    /// Show the whole scene, leaving space either side along the longer edge of the image.
+    Fit,
+    /// Fill the image with the scene, cropping it along the longer edge.
+    Fill,
+}
+
+/// The size of the image, either one of the usual ones or the ratio of its width to its height
+/// and how many pixels high it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    /// 1280 by 720.
+    Hd720,
+    /// 1920 by 1080.
+    Hd1080,
+    /// 3840 by 2160.
+    Uhd4k,
+    /// 1080 by 1080.
+    Square,
+    /// As wide as the ratio says, and as many pixels high as the size.
+    Aspect(f32, usize),
+}
+
+#[derive(Debug)]
+pub struct ParseResolutionError(String);
+
+impl std::error::Error for ParseResolutionError {}
+
+impl Display for ParseResolutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown resolution '{}': expected 720p, 1080p, 4k, square or a ratio and height \
+             such as 16:9@480",
+            self.0
+        )
+    }
+}
+
+impl Resolution {
+    /// The width and height in pixels.
+    pub fn size(self) -> (usize, usize) {
+        match self {
+            Resolution::Hd720 => (1280, 720),
+            Resolution::Hd1080 => (1920, 1080),
+            Resolution::Uhd4k => (3840, 2160),
+            Resolution::Square => (1080, 1080),
+            Resolution::Aspect(aspect, vsize) => {
+                (((aspect * vsize as f32).round() as usize).max(1), vsize)
+            }
+        }
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = ParseResolutionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseResolutionError(s.to_string());
+        match s.to_ascii_lowercase().as_str() {
+            "720p" => Ok(Resolution::Hd720),
+            "1080p" => Ok(Resolution::Hd1080),
+            "4k" | "2160p" => Ok(Resolution::Uhd4k),
+            "square" => Ok(Resolution::Square),
+            other => {
+                // A ratio of width to height, and the height, as in 16:9@480
+                let (ratio, height) = other.split_once('@').ok_or_else(error)?;
+                let (width, tall) = ratio.split_once(':').ok_or_else(error)?;
+                let parse = |text: &str| text.trim().parse::<f32>().map_err(|_| error());
+                let aspect = parse(width)? / parse(tall)?;
+                let vsize = height.trim().parse::<usize>().map_err(|_| error())?;
+                if !aspect.is_finite() || aspect <= 0.0 || vsize == 0 {
+                    return Err(error());
+                }
+
+                Ok(Resolution::Aspect(aspect, vsize))
+            }
+        }
+    }
 }
 
 pub struct CameraBuilder {
@@ -18,10 +239,26 @@ pub struct CameraBuilder {
     vsize: usize,
     field_of_view: f32,
     transform: Matrix<4>,
+    lens_shift: f32,
+    roll: f32,
+    end_transform: Option<Matrix<4>>,
+    shutter: Shutter,
+    aperture: f32,
+    aperture_shape: Aperture,
+    focal_distance: f32,
+    exposure: Option<Exposure>,
+    near_clip: f32,
+    far_clip: f32,
 }
 
 impl Camera {
-    fn new(hsize: usize, vsize: usize, field_of_view: f32, transform: Matrix<4>) -> Self {
+    fn new(
+        hsize: usize,
+        vsize: usize,
+        field_of_view: f32,
+        transform: Matrix<4>,
+        lens_shift: f32,
+    ) -> Self {
         let half_view = (field_of_view / 2.0).tan();
         let aspect = hsize as f32 / vsize as f32;
         let half_width: f32;
@@ -42,41 +279,565 @@ impl Camera {
             pixel_size,
             half_width,
             half_height,
+            shift: lens_shift * half_width * 2.0,
+            end_inv_transform: None,
+            shutter: Shutter::default(),
+            aperture: 0.0,
+            aperture_shape: Aperture::default(),
+            focal_distance: 1.0,
+            exposure: None,
+            near_clip: 0.0,
+            far_clip: f32::INFINITY,
+        }
+    }
+
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
+    /// Half the width of the view one unit in front of the camera.
+    pub fn half_width(&self) -> f32 {
+        self.half_width
+    }
+
+    /// Half the height of the view one unit in front of the camera.
+    pub fn half_height(&self) -> f32 {
+        self.half_height
+    }
+
+    /// The width of a pixel one unit in front of the camera; pixels are square.
+    pub fn pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    /// The angle across the longer side of the view, in radians.
+    pub fn field_of_view(&self) -> f32 {
+        2.0 * self.half_width.max(self.half_height).atan()
+    }
+
+    /// The view transform, including any roll, taking world space to the camera's space.
+    pub fn transform(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    /// The width of the view one unit in front of the camera.
+    pub fn view_width(&self) -> f32 {
+        self.half_width * 2.0
+    }
+
+    /// Where `point` lands on the canvas, in pixels from its top left corner, or `None` if it is
+    /// level with or behind the camera. The result may lie outside the canvas; a pixel `(x, y)`
+    /// covers from `x` to `x + 1` across and `y` to `y + 1` down.
+    pub fn world_to_pixel(&self, point: Vector4) -> Option<(f32, f32)> {
+        let p = self.transform() * point;
+        if p.z > -EPSILON {
+            return None;
+        }
+
+        // Project onto the canvas at z = -1, then undo the steps in `ray_through_pixel`
+        let (canvas_x, canvas_y) = (p.x / -p.z, p.y / -p.z);
+        let x = (self.half_width - self.shift - canvas_x) / self.pixel_size;
+        let y = (self.half_height - canvas_y) / self.pixel_size;
+
+        Some((x, y))
+    }
+
+    /// The same view at a lower resolution, with its longer side no more than `largest` pixels,
+    /// for quick renders while a scene is being worked on. Cameras already that small are kept
+    /// as they are.
+    pub fn preview(&self, largest: usize) -> Camera {
+        let scale = (largest as f32 / self.hsize.max(self.vsize) as f32).min(1.0);
+        let hsize = ((self.hsize as f32 * scale).round() as usize).max(1);
+        let vsize = ((self.vsize as f32 * scale).round() as usize).max(1);
+
+        Camera {
+            hsize,
+            vsize,
+            pixel_size: (self.half_width * 2.0) / hsize as f32,
+            ..self.clone()
+        }
+    }
+
+    /// The same camera carried `angle` radians around the world's y axis, still looking at the
+    /// same point on the axis, as for a turntable animation.
+    pub fn orbited(&self, angle: f32) -> Camera {
+        Camera {
+            inv_transform: Matrix::rotation_y(angle) * self.inv_transform,
+            end_inv_transform: self
+                .end_inv_transform
+                .map(|end| Matrix::rotation_y(angle) * end),
+            ..self.clone()
         }
     }
 
-    fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        // the offset from the edge of the canvas to the pixel's center
-        let xoffset = (px as f32 + 0.5) * self.pixel_size;
-        let yoffset = (py as f32 + 0.5) * self.pixel_size;
+    /// The radius of the lens, or 0 for a pinhole camera.
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    /// How far in front of the camera things are in focus.
+    pub fn focal_distance(&self) -> f32 {
+        self.focal_distance
+    }
+
+    /// The same camera with its lens opened to `aperture` and focused `focal_distance` away.
+    pub fn refocused(&self, aperture: f32, focal_distance: f32) -> Camera {
+        Camera {
+            aperture: aperture.max(0.0),
+            focal_distance,
+            ..self.clone()
+        }
+    }
+
+    /// What the rendered radiance is multiplied by to expose the image: 1 unless the camera has
+    /// a photographic exposure.
+    pub fn exposure_scale(&self) -> f32 {
+        self.exposure.map_or(1.0, |exposure| exposure.scale())
+    }
+
+    /// Scale the radiance in `canvas` by the camera's exposure.
+    pub(crate) fn expose(&self, canvas: &mut Canvas) {
+        let scale = self.exposure_scale();
+        if scale == 1.0 {
+            return;
+        }
+
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                canvas.write_pixel(x, y, &(canvas.pixel_at(x, y) * scale));
+            }
+        }
+    }
+
+    /// The ray through the middle of pixel `(px, py)`, counted from the top left corner.
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
+        self.ray_through_pixel(px, py, 0.5, 0.5)
+    }
+
+    /// The ray through the point `(dx, dy)` of the way across the pixel, each in [0, 1), halfway
+    /// through the exposure of the pixel's row and from the middle of the lens.
+    pub(crate) fn ray_through_pixel(&self, px: usize, py: usize, dx: f32, dy: f32) -> Ray {
+        let sample = CameraSample {
+            offset: (dx, dy),
+            time: self.shutter.time(py, self.vsize, 0.5),
+            lens: (0.5, 0.5),
+        };
+
+        self.ray_for_sample(px, py, &sample)
+    }
+
+    /// The ray for one sample of pixel `(px, py)`: through the sample's point in the pixel, at
+    /// its time, and from its point on the lens.
+    pub(crate) fn ray_for_sample(&self, px: usize, py: usize, sample: &CameraSample) -> Ray {
+        // the offset from the edge of the canvas to the point in the pixel
+        let xoffset = (px as f32 + sample.offset.0) * self.pixel_size;
+        let yoffset = (py as f32 + sample.offset.1) * self.pixel_size;
 
         // the untransformed coordinates of the pixel in world space.
         // (remember that the camera looks toward -z, so +x is to the *left*.)
-        let world_x = self.half_width - xoffset;
+        let world_x = self.half_width - xoffset - self.shift;
         let world_y = self.half_height - yoffset;
 
+        // With a lens, the ray leaves from a point on it towards where the ray through its middle
+        // meets the plane in focus, so only things at the focal distance come out sharp
+        let (target, start) = if self.aperture > 0.0 {
+            let f = self.focal_distance;
+            let (lx, ly) = self.aperture_shape.sample(sample.lens.0, sample.lens.1);
+            (
+                Vector4::point(world_x * f, world_y * f, -f),
+                Vector4::point(lx * self.aperture, ly * self.aperture, 0.0),
+            )
+        } else {
+            // (remember that the canvas is at z=-1)
+            (
+                Vector4::point(world_x, world_y, -1.0),
+                Vector4::point(0.0, 0.0, 0.0),
+            )
+        };
+
         // using the camera matrix, transform the canvas point and the origin,
         // and then compute the ray's direction vector.
-        // (remember that the canvas is at z=-1)
-        let pixel = self.inv_transform * Vector4::point(world_x, world_y, -1.0);
-        let origin = self.inv_transform * Vector4::point(0.0, 0.0, 0.0);
+        let pixel = self.inv_transform * target;
+        let origin = self.inv_transform * start;
+        // A moving camera is carried in a straight line from where it starts to where it ends
+        let (pixel, origin) = match self.end_inv_transform {
+            Some(end) if sample.time != 0.0 => (
+                pixel + (end * target - pixel) * sample.time,
+                origin + (end * start - origin) * sample.time,
+            ),
+            _ => (pixel, origin),
+        };
         let direction = (pixel - origin).normalize();
 
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_range(self.near_clip, self.far_clip)
+    }
+
+    pub fn render(&self, world: &World, settings: &RenderSettings) -> Canvas {
+        if let Integrator::Debug(channel) = settings.integrator {
+            return self.render_debug(world, channel);
+        }
+
+        let _span = info_span!(
+            "render",
+            width = self.hsize,
+            height = self.vsize,
+            samples = settings.samples_per_pixel
+        )
+        .entered();
+        let mut image = AccumulationBuffer::new(self.hsize, self.vsize);
+        let mut coverage = vec![vec![0.0; self.hsize]; self.vsize];
+        for (y, row) in coverage.iter_mut().enumerate() {
+            let span = info_span!("render.row", y = y).entered();
+            let mut row_samples = 0;
+            for (x, alpha) in row.iter_mut().enumerate() {
+                let samples = self.pixel_samples(world, x, y, settings);
+                row_samples += samples.len();
+                for &(offset, color, _) in &samples {
+                    image.add_filtered_sample(x, y, offset, color, settings.filter);
+                }
+                *alpha = samples.iter().filter(|s| s.2).count() as f32 / samples.len() as f32;
+            }
+            // Adaptive sampling makes some rows much more work than others
+            span.record("samples", row_samples);
+        }
+
+        let mut canvas = image.to_canvas();
+        self.expose(&mut canvas);
+        if let Some(denoiser) = settings.denoise {
+            let _span = info_span!("render.denoise").entered();
+            let normals = self.render_debug(world, DebugChannel::Normals);
+            let depth = self.render_debug(world, DebugChannel::Depth);
+            canvas = denoiser.apply(&canvas, &normals, &depth);
+        }
+        settings.post.apply(&mut canvas);
+        // Only the primary rays count towards coverage, so objects seen in reflections or through
+        // glass don't make the background opaque
+        for (y, row) in coverage.iter().enumerate() {
+            for (x, alpha) in row.iter().enumerate() {
+                canvas.write_alpha(x, y, *alpha);
+            }
+        }
+
+        canvas
+    }
+
+    /// The colour of one pixel, as `render` would give it with the default settings. Rendering a
+    /// single pixel is much quicker than the whole image when chasing a problem in one place.
+    pub fn render_pixel(&self, world: &World, x: usize, y: usize) -> Color {
+        self.render_pixel_with(world, x, y, &RenderSettings::default())
+    }
+
+    /// The colour of one pixel with the given settings. Denoising, post-processing and the debug
+    /// views all work on whole images, so they are left out and the pixel is shaded as usual.
+    pub fn render_pixel_with(
+        &self,
+        world: &World,
+        x: usize,
+        y: usize,
+        settings: &RenderSettings,
+    ) -> Color {
+        // Gather the samples of the neighbouring pixels the filter reaches, so the pixel is
+        // weighted just as it is in the whole image
+        let (mut sum, mut weights) = (Color::black(), 0.0);
+        let reach = settings.filter.reach();
+        for ny in y.saturating_sub(reach)..(y + reach + 1).min(self.vsize) {
+            for nx in x.saturating_sub(reach)..(x + reach + 1).min(self.hsize) {
+                if settings.filter == PixelFilter::Box && (nx, ny) != (x, y) {
+                    continue;
+                }
+                for ((dx, dy), color, _) in self.pixel_samples(world, nx, ny, settings) {
+                    let weight = settings.filter.weight(
+                        (nx as f32 + dx) - (x as f32 + 0.5),
+                        (ny as f32 + dy) - (y as f32 + 0.5),
+                    );
+                    if weight != 0.0 {
+                        sum += color * weight;
+                        weights += weight;
+                    }
+                }
+            }
+        }
+
+        if weights <= 0.0 {
+            return Color::black();
+        }
+
+        sum / weights * self.exposure_scale()
+    }
+
+    /// Where in the pixel each of its samples was taken, as fractions of the way across and
+    /// down, what colour it came out, and whether it hit anything.
+    fn pixel_samples(
+        &self,
+        world: &World,
+        x: usize,
+        y: usize,
+        settings: &RenderSettings,
+    ) -> Vec<((f32, f32), Color, bool)> {
+        let sample = |camera_sample: CameraSample| {
+            let ray = self.ray_for_sample(x, y, &camera_sample);
+            let (color, hit) = world.sample(&ray, settings);
+            (camera_sample.offset, color, hit)
+        };
+        let Some(adaptive) = settings.adaptive else {
+            let samples = self.camera_samples(x, y, settings).into_iter().map(sample).collect();
+            return Self::without_outliers(samples, settings);
+        };
+
+        // Keep sampling until the pixel's own samples agree closely enough
+        let rotation = self.pixel_rotation(x, y, settings);
+        let mut stats = AccumulationBuffer::new(1, 1);
+        let mut samples = Vec::new();
+        while adaptive.needs_more(samples.len(), stats.variance(0, 0)) {
+            let taken = sample(self.sample_position(y, samples.len() as u32, rotation));
+            stats.add_sample(0, 0, taken.1);
+            samples.push(taken);
+        }
+
+        Self::without_outliers(samples, settings)
+    }
+
+    /// `samples` with the colours `settings` picks out as outliers scaled down.
+    fn without_outliers(
+        mut samples: Vec<((f32, f32), Color, bool)>,
+        settings: &RenderSettings,
+    ) -> Vec<((f32, f32), Color, bool)> {
+        let mut colors: Vec<Color> = samples.iter().map(|s| s.1).collect();
+        settings.reject_outliers(&mut colors);
+        for (sample, color) in samples.iter_mut().zip(colors) {
+            sample.1 = color;
+        }
+
+        samples
+    }
+
+    /// Where the samples for a pixel go, when they are taken, and where on the lens they start
+    /// from: the middle of the pixel, of its row's exposure and of the lens for a single sample,
+    /// or otherwise spread over all three along a Halton sequence shifted by a random amount for
+    /// each pixel.
+    fn camera_samples(&self, x: usize, y: usize, settings: &RenderSettings) -> Vec<CameraSample> {
+        if settings.samples_per_pixel <= 1 {
+            return vec![CameraSample {
+                offset: (0.5, 0.5),
+                time: self.shutter.time(y, self.vsize, 0.5),
+                lens: (0.5, 0.5),
+            }];
+        }
+
+        let rotation = self.pixel_rotation(x, y, settings);
+        (0..settings.samples_per_pixel as u32)
+            .map(|index| self.sample_position(y, index, rotation))
+            .collect()
+    }
+
+    /// Sample `index` of a pixel in row `y`.
+    fn sample_position(&self, y: usize, index: u32, rotation: [f32; 5]) -> CameraSample {
+        // Each dimension takes the next prime as its base, so they don't move in step
+        let dimension = |base: u32, shift: f32| (filter::halton(index + 1, base) + shift).fract();
+
+        CameraSample {
+            offset: filter::sample_offset(index, (rotation[0], rotation[1])),
+            time: self.shutter.time(y, self.vsize, dimension(5, rotation[2])),
+            lens: (dimension(7, rotation[3]), dimension(11, rotation[4])),
+        }
+    }
+
+    /// How far the Halton sequence is shifted in a pixel, across, down, through the exposure and
+    /// across and down the lens.
+    fn pixel_rotation(&self, x: usize, y: usize, settings: &RenderSettings) -> [f32; 5] {
+        // Seed each pixel separately so the image doesn't depend on the order of pixels
+        let mut rng = Rng::for_pixel(settings.frame_seed(), x, y, self.hsize);
+
+        std::array::from_fn(|_| rng.next_f32())
+    }
+
+    /// Render the beauty image together with its depth, normals, albedo and direct and indirect
+    /// light, all from the same camera rays.
+    pub fn render_aovs(&self, world: &World, settings: &RenderSettings) -> Aovs {
+        let mut layers: [AccumulationBuffer; 6] =
+            std::array::from_fn(|_| AccumulationBuffer::new(self.hsize, self.vsize));
+        let mut object_ids = vec![vec![None; self.hsize]; self.vsize];
+        for (y, row) in object_ids.iter_mut().enumerate() {
+            for (x, id) in row.iter_mut().enumerate() {
+                // Ids can't be averaged, so they come from a single ray through the pixel centre
+                let intersections = world.intersect(&self.ray_for_pixel(x, y));
+                *id = intersections.hit().and_then(|hit| world.object_id(&hit.object));
+
+                for camera_sample in self.camera_samples(x, y, settings) {
+                    let ray = self.ray_for_sample(x, y, &camera_sample);
+                    let samples = Self::aov_samples(world, &ray, settings);
+                    let offset = camera_sample.offset;
+                    for (layer, sample) in layers.iter_mut().zip(samples) {
+                        layer.add_filtered_sample(x, y, offset, sample, settings.filter);
+                    }
+                }
+            }
+        }
+
+        let [mut beauty, depth, normals, albedo, mut direct, mut indirect] =
+            layers.map(|l| l.to_canvas());
+        for layer in [&mut beauty, &mut direct, &mut indirect] {
+            self.expose(layer);
+        }
+        Aovs {
+            beauty,
+            depth,
+            normals,
+            albedo,
+            direct,
+            indirect,
+            object_ids,
+        }
+    }
+
+    /// The beauty, depth, normal, albedo, direct and indirect samples along `ray`, in that order.
+    fn aov_samples(world: &World, ray: &Ray, settings: &RenderSettings) -> [Color; 6] {
+        let intersections = world.intersect(ray);
+        let Some(hit) = intersections.hit() else {
+            let mut samples = [Color::black(); 6];
+            samples[0] = settings.clamp_sample(world.miss_color(ray, settings));
+            return samples;
+        };
+
+        let comps = hit.prepare_computations_biased(ray, &intersections, &settings.shadow_bias);
+        let n = comps.normal_vector;
+        let depth = Color::white() * comps.t;
+        let normal = Color::new(n.x, n.y, n.z);
+        let albedo = comps.object.material().color_at(comps.point, comps.uv);
+        let (direct, indirect) = world.shade_hit_split(comps, settings, settings.max_depth);
+
+        let beauty = settings.clamp_sample(direct + indirect);
+
+        [beauty, depth, normal, albedo, direct, indirect]
     }
 
-    pub fn render(&self, world: &World) -> Canvas {
+    pub fn render_debug(&self, world: &World, channel: DebugChannel) -> Canvas {
         let mut image = Canvas::new(self.hsize, self.vsize);
 
-        for y in 0..self.vsize - 1 {
-            for x in 0..self.hsize - 1 {
+        // Depth and intersection counts are normalised against the whole image, so gather the
+        // raw values first.
+        let mut values = vec![vec![None; self.hsize]; self.vsize];
+        let mut min_value = f32::INFINITY;
+        let mut max_value: f32 = 0.0;
+        for (y, row) in values.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
                 let ray = self.ray_for_pixel(x, y);
-                let color = world.color_at(&ray);
-                image.write_pixel(x, y, &color)
+                *value = Self::debug_value(world, &ray, channel);
+                if let Some(DebugValue::Scalar(v)) = value {
+                    min_value = min_value.min(*v);
+                    max_value = max_value.max(*v);
+                }
+            }
+        }
+
+        for (y, row) in values.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                let color = match value {
+                    None => Color::black(),
+                    Some(DebugValue::Color(c)) => *c,
+                    Some(DebugValue::Scalar(v)) => match channel {
+                        DebugChannel::Depth => {
+                            let range = max_value - min_value;
+                            let normalised = if range > 0.0 { (v - min_value) / range } else { 0.0 };
+                            Color::white() * (1.0 - 0.9 * normalised)
+                        }
+                        _ => Color::from_hsv(240.0 * (1.0 - v / max_value), 1.0, 1.0),
+                    },
+                };
+                image.write_pixel(x, y, &color);
             }
         }
 
-        return image;
+        image
+    }
+
+    fn debug_value(world: &World, ray: &Ray, channel: DebugChannel) -> Option<DebugValue> {
+        let intersections = world.intersect(ray);
+        if channel == DebugChannel::IntersectionCount {
+            let count = intersections.in_range().count();
+            return match count {
+                0 => None,
+                n => Some(DebugValue::Scalar(n as f32)),
+            };
+        }
+
+        let hit = intersections.hit()?;
+        let value = match channel {
+            DebugChannel::Normals => {
+                let n = hit.prepare_computations(ray).normal_vector;
+                DebugValue::Color(Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0) * 0.5)
+            }
+            DebugChannel::Depth => DebugValue::Scalar(hit.t),
+            DebugChannel::ObjectId => {
+                let id = world.object_id(&hit.object).map_or(0, |id| id.0);
+                // Step round the hue wheel by the golden angle so neighbouring ids stand apart.
+                DebugValue::Color(Color::from_hsv(id as f32 * 137.5, 0.65, 1.0))
+            }
+            DebugChannel::ShadowMask => {
+                let comps = hit.prepare_computations(ray);
+                let lights = world.lights();
+                let lit = lights
+                    .iter()
+                    .filter(|light| !world.is_shadowed(light, &comps.over_point))
+                    .count();
+                DebugValue::Color(Color::white() * (lit as f32 / lights.len().max(1) as f32))
+            }
+            DebugChannel::IntersectionCount => unreachable!(),
+        };
+
+        Some(value)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DebugValue {
+    Color(Color),
+    Scalar(f32),
+}
+
+impl Aovs {
+    /// Save the beauty pass as `R`, `G` and `B` in linear light, the depth as `Z` and the object
+    /// ids as `id` in one OpenEXR file. Depth is infinite and the id 0 where nothing is hit; other ids are one
+    /// more than the object's id in the world.
+    pub fn save_exr(&self, file: &mut impl Write) -> Result<(), Error> {
+        self.save_exr_in(file, ColorSpace::Linear)
+    }
+
+    /// Save the AOVs as an OpenEXR image, with the beauty pass converted to `space`.
+    pub fn save_exr_in(&self, file: &mut impl Write, space: ColorSpace) -> Result<(), Error> {
+        let (width, height) = (self.beauty.width(), self.beauty.height());
+        let pixels = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+        let beauty = |channel: fn(Color) -> f32| {
+            pixels()
+                .map(|(x, y)| channel(space.encode(self.beauty.pixel_at(x, y))))
+                .collect()
+        };
+        let depth = pixels()
+            .map(|(x, y)| match self.object_ids[y][x] {
+                Some(_) => self.depth.pixel_at(x, y).r,
+                None => f32::INFINITY,
+            })
+            .collect();
+        let ids = pixels()
+            .map(|(x, y)| self.object_ids[y][x].map_or(0, |id| id.0 as u32 + 1))
+            .collect();
+
+        exr::write(
+            file,
+            width,
+            height,
+            vec![
+                Channel::float("R", beauty(|c| c.r)),
+                Channel::float("G", beauty(|c| c.g)),
+                Channel::float("B", beauty(|c| c.b)),
+                Channel::float("Z", depth),
+                Channel::uint("id", ids),
+            ],
+        )
     }
 }
 
@@ -87,6 +848,16 @@ impl CameraBuilder {
             vsize: 0,
             field_of_view: 0.0,
             transform: Matrix::identity(),
+            lens_shift: 0.0,
+            roll: 0.0,
+            end_transform: None,
+            shutter: Shutter::default(),
+            aperture: 0.0,
+            aperture_shape: Aperture::default(),
+            focal_distance: 1.0,
+            exposure: None,
+            near_clip: 0.0,
+            far_clip: f32::INFINITY,
         }
     }
 
@@ -100,6 +871,20 @@ impl CameraBuilder {
         self
     }
 
+    /// Set both sides of the canvas at once, to one of the usual sizes or from an aspect ratio.
+    pub fn with_resolution(mut self, resolution: Resolution) -> Self {
+        let (hsize, vsize) = resolution.size();
+        self.hsize = hsize;
+        self.vsize = vsize;
+        self
+    }
+
+    /// Make the canvas `vsize` pixels high and as wide as `aspect`, the ratio of width to
+    /// height, says, to the nearest pixel.
+    pub fn with_aspect_ratio(self, aspect: f32, vsize: usize) -> Self {
+        self.with_resolution(Resolution::Aspect(aspect, vsize))
+    }
+
     pub fn with_field_of_view(mut self, field_of_view: f32) -> Self {
         self.field_of_view = field_of_view;
         self
@@ -110,8 +895,121 @@ impl CameraBuilder {
         self
     }
 
+    /// Slide the view sideways without turning the camera, as a shift lens does. A shift of 1.0
+    /// moves the view right by its whole width; negative shifts move it left.
+    pub fn with_lens_shift(mut self, lens_shift: f32) -> Self {
+        self.lens_shift = lens_shift;
+        self
+    }
+
+    /// Turn the camera about its line of sight by `roll` radians, anticlockwise as seen from
+    /// behind it, so the image turns clockwise.
+    pub fn with_roll(mut self, roll: f32) -> Self {
+        self.roll = roll;
+        self
+    }
+
+    /// Move the camera while the shutter is open, from its transform at time 0 to `transform` at
+    /// time 1, blurring whatever the movement sweeps across.
+    pub fn with_end_transform(mut self, transform: Matrix<4>) -> Self {
+        self.end_transform = Some(transform);
+        self
+    }
+
+    /// When the shutter opens and closes, and how long a rolling shutter takes to read out.
+    pub fn with_shutter(mut self, shutter: Shutter) -> Self {
+        self.shutter = shutter;
+        self
+    }
+
+    /// Give the camera a lens of radius `aperture`, so that only things `focal_distance` away
+    /// are sharp. An aperture of 0, the default, is a pinhole that keeps everything sharp.
+    pub fn with_depth_of_field(mut self, aperture: f32, focal_distance: f32) -> Self {
+        self.aperture = aperture;
+        self.focal_distance = focal_distance;
+        self
+    }
+
+    /// Shape the opening in the lens, and so the out-of-focus highlights, as `shape` instead of
+    /// a circle.
+    pub fn with_aperture_shape(mut self, shape: Aperture) -> Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    /// Expose the image as a real camera with these settings would, for scenes lit in physical
+    /// units. Without one the radiance is used as it is.
+    pub fn with_exposure(mut self, exposure: Exposure) -> Self {
+        self.exposure = Some(exposure);
+        self
+    }
+
+    /// Only see things between `near` and `far` from the camera. Whatever is nearer is cut away,
+    /// as if it weren't there, so the camera can look into a room from behind one of its walls;
+    /// whatever is further is left to the background.
+    pub fn with_clipping(mut self, near: f32, far: f32) -> Self {
+        self.near_clip = near;
+        self.far_clip = far;
+        self
+    }
+
+    /// Look at the middle of everything in `world` that has bounds, from in front and a little
+    /// above, standing back far enough to frame it as `fit` says. Set the canvas size first; the
+    /// field of view is kept if it has been set, and otherwise a 60° one is chosen.
+    pub fn framing(mut self, world: &World, fit: FitMode) -> Self {
+        let bounds = world.bounds();
+        if !bounds.is_bounded() {
+            return self;
+        }
+        if self.field_of_view <= 0.0 {
+            self.field_of_view = PI / 3.0;
+        }
+
+        let centre = bounds.min + (bounds.max - bounds.min) * 0.5;
+        let radius = (bounds.max - bounds.min).magnitude() / 2.0;
+
+        // Work out the angle from the middle of the view to its nearer or further edge
+        let half_view = (self.field_of_view / 2.0).tan();
+        let aspect = self.hsize as f32 / self.vsize as f32;
+        let (half_width, half_height) = if aspect >= 1.0 {
+            (half_view, half_view / aspect)
+        } else {
+            (half_view * aspect, half_view)
+        };
+        let half_angle = match fit {
+            FitMode::Fit => half_width.min(half_height).atan(),
+            FitMode::Fill => half_width.max(half_height).atan(),
+        };
+
+        let direction = Vector4::vector(0.0, 0.5, -1.0).normalize();
+        let from = centre + direction * (radius / half_angle.sin());
+        self.transform = Matrix::view_transform(from, centre, Vector4::vector(0.0, 1.0, 0.0));
+        self
+    }
+
     pub fn build(self) -> Camera {
-        Camera::new(self.hsize, self.vsize, self.field_of_view, self.transform)
+        let roll = Matrix::rotation_z(self.roll);
+        let end_inv_transform = self
+            .end_transform
+            .map(|end| (roll * end).try_inverse().unwrap());
+
+        Camera {
+            end_inv_transform,
+            shutter: self.shutter,
+            aperture: self.aperture.max(0.0),
+            aperture_shape: self.aperture_shape,
+            focal_distance: self.focal_distance,
+            exposure: self.exposure,
+            near_clip: self.near_clip.max(0.0),
+            far_clip: self.far_clip,
+            ..Camera::new(
+                self.hsize,
+                self.vsize,
+                self.field_of_view,
+                roll * self.transform,
+                self.lens_shift,
+            )
+        }
     }
 }
 
@@ -127,20 +1025,19 @@ mod tests {
     use rstest::*;
     use spectral::prelude::*;
 
-    use crate::color::Color;
+    use crate::denoise::Denoiser;
+    use crate::light::PointLight;
     use crate::material::MaterialBuilder;
+    use crate::plane::PlaneBuilder;
+    use crate::post::PostProcessBuilder;
+    use crate::settings::AdaptiveSampling;
     use crate::sphere::SphereBuilder;
     use crate::transform::Transform;
     use crate::vector4::Vector4;
     use crate::world::WorldBuilder;
 
     use super::*;
-
-    fn vector_values_are_close(actual: Vector4, expected: Vector4, tolerance: f32) {
-        for row in 0..4 {
-            assert_that!(actual[row]).is_close_to(expected[row], tolerance);
-        }
-    }
+    use crate::assert_approx_eq;
 
     #[test]
     fn constructing_a_camera() {
@@ -181,77 +1078,556 @@ mod tests {
         assert_that!(c.pixel_size).is_equal_to(0.01);
     }
 
+    #[rstest]
+    #[case("720p", (1280, 720))]
+    #[case("1080p", (1920, 1080))]
+    #[case("4K", (3840, 2160))]
+    #[case("square", (1080, 1080))]
+    #[case("16:9@480", (853, 480))]
+    #[case("2.35:1@100", (235, 100))]
+    fn parsing_a_resolution(#[case] text: &str, #[case] expected: (usize, usize)) {
+        let resolution: Resolution = text.parse().unwrap();
+
+        assert_that!(resolution.size()).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("8k")]
+    #[case("16:9")]
+    #[case("16:0@480")]
+    #[case("16:9@0")]
+    #[case("-4:3@480")]
+    fn parsing_a_bad_resolution(#[case] text: &str) {
+        assert_that!(text.parse::<Resolution>().is_err()).is_true();
+    }
+
     #[test]
-    fn constructing_a_ray_through_the_center_of_the_canvas() {
+    fn sizing_the_canvas_by_aspect_ratio() {
         let c = CameraBuilder::new()
-            .with_hsize(201)
-            .with_vsize(101)
+            .with_aspect_ratio(16.0 / 10.0, 125)
             .with_field_of_view(PI / 2.0)
             .build();
 
-        let r = c.ray_for_pixel(100, 50);
-
-        assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
-        vector_values_are_close(r.direction, Vector4::vector(0.0, 0.0, -1.0), 0.0001);
+        assert_that!((c.hsize, c.vsize)).is_equal_to((200, 125));
+        assert_that!(c.pixel_size).is_equal_to(0.01);
     }
 
     #[test]
-    fn constructing_a_ray_through_a_corner_of_the_canvas() {
+    fn sizing_the_canvas_by_preset() {
         let c = CameraBuilder::new()
-            .with_hsize(201)
-            .with_vsize(101)
+            .with_resolution(Resolution::Hd720)
             .with_field_of_view(PI / 2.0)
             .build();
 
-        let r = c.ray_for_pixel(0, 0);
-
-        assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
-        vector_values_are_close(
-            r.direction,
-            Vector4::vector(0.66519, 0.33259, -0.66851),
-            0.0001,
-        );
+        assert_that!((c.hsize, c.vsize)).is_equal_to((1280, 720));
+        assert_that!(c.pixel_size).is_close_to(2.0 / 1280.0, 0.00001);
     }
 
     #[test]
-    fn constructing_a_ray_when_the_camera_is_transformed() {
+    fn a_preview_shrinks_the_canvas_but_keeps_the_view() {
         let c = CameraBuilder::new()
-            .with_hsize(201)
-            .with_vsize(101)
+            .with_hsize(200)
+            .with_vsize(125)
             .with_field_of_view(PI / 2.0)
-            .with_transform(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0))
+            .with_transform(Matrix::rotation_y(PI / 4.0))
             .build();
 
-        let r = c.ray_for_pixel(100, 50);
+        let p = c.preview(40);
 
-        // assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 2.0, -5.0));
-        vector_values_are_close(r.origin, Vector4::point(0.0, 2.0, -5.0), 0.0001);
-        vector_values_are_close(
-            r.direction,
-            Vector4::vector(2.0_f32.sqrt() / 2.0, 0.0, -2.0_f32.sqrt() / 2.0),
+        assert_that!((p.hsize, p.vsize)).is_equal_to((40, 25));
+        assert_that!(p.pixel_size).is_close_to(0.05, 0.0001);
+        assert_approx_eq!(
+            p.ray_for_pixel(0, 0).direction,
+            c.ray_through_pixel(0, 0, 2.5, 2.5).direction,
             0.0001,
         );
     }
 
-    #[fixture]
-    fn default_world() -> World {
-        let s1_material = MaterialBuilder::new()
-            .with_color(Color::new(0.8, 1.0, 0.6))
-            .with_diffuse(0.7)
-            .with_specular(0.2)
+    fn moving_camera(shutter: Shutter) -> Camera {
+        let view_from = |x: f32| {
+            Matrix::view_transform(
+                Vector4::point(x, 0.0, -5.0),
+                Vector4::point(x, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            )
+        };
+        CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(view_from(0.0))
+            .with_end_transform(view_from(2.0))
+            .with_shutter(shutter)
+            .build()
+    }
+
+    #[rstest]
+    #[case((1.0, 0.5))]
+    #[case((0.5, 0.0))]
+    #[case((0.2, 0.9))]
+    fn every_ray_through_the_lens_meets_at_the_focal_distance(#[case] lens: (f32, f32)) {
+        let c = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_depth_of_field(0.5, 4.0)
             .build();
-        let s1 = SphereBuilder::new().with_material(s1_material).build();
+        let sample = CameraSample {
+            offset: (0.5, 0.5),
+            time: 0.0,
+            lens,
+        };
 
-        let s2 = SphereBuilder::new()
-            .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
+        let r = c.ray_for_sample(7, 5, &sample);
+
+        let focus = c.ray_for_pixel(7, 5).position(4.0 / c.ray_for_pixel(7, 5).direction.z.abs());
+        assert_that!(r.origin.z).is_equal_to(0.0);
+        assert_that!(r.origin).is_not_equal_to(Vector4::point(0.0, 0.0, 0.0));
+        assert_approx_eq!(r.position((focus - r.origin).magnitude()), focus, 0.0001);
+    }
+
+    #[rstest]
+    #[case(100.0, 1.0, 1.0, 0.0)]
+    #[case(200.0, 1.0, 1.0, -1.0)]
+    #[case(100.0, 0.5, 1.0, 1.0)]
+    #[case(100.0, 1.0, 2.0, 2.0)]
+    #[case(100.0, 0.01, 16.0, 14.643856)]
+    fn exposure_values(
+        #[case] iso: f32,
+        #[case] shutter_speed: f32,
+        #[case] f_stop: f32,
+        #[case] expected: f32,
+    ) {
+        let exposure = Exposure {
+            iso,
+            shutter_speed,
+            f_stop,
+        };
+
+        assert_that!(exposure.ev100()).is_close_to(expected, 0.0001);
+    }
+
+    #[rstest]
+    fn an_exposure_scales_the_rendered_radiance(default_world: World) {
+        let exposure = Exposure {
+            iso: 100.0,
+            shutter_speed: 0.5,
+            f_stop: 1.0,
+        };
+        let exposed = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, -5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .with_exposure(exposure)
             .build();
+        let settings = RenderSettings::default();
 
-        WorldBuilder::new()
-            .with_object(Rc::new(s1))
-            .with_object(Rc::new(s2))
+        let expected = debug_camera().render(&default_world, &settings).pixel_at(5, 5);
+        let image = exposed.render(&default_world, &settings);
+
+        // EV 1, a stop darker than EV 0, which itself keeps 1/1.2 of the light
+        assert_that!(exposed.exposure_scale()).is_close_to(0.5 / 1.2, 0.00001);
+        assert_approx_eq!(image.pixel_at(5, 5), expected * (0.5 / 1.2), 0.00001);
+        assert_approx_eq!(
+            exposed.render_pixel_with(&default_world, 5, 5, &settings),
+            image.pixel_at(5, 5),
+            0.00001
+        );
+    }
+
+    fn clipped_camera(near: f32, far: f32) -> Camera {
+        CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, -5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .with_clipping(near, far)
             .build()
     }
 
+    #[rstest]
+    fn the_near_clip_cuts_away_what_is_close_to_the_camera(default_world: World) {
+        let camera = clipped_camera(4.2, f32::INFINITY);
+
+        let ray = camera.ray_for_pixel(5, 5);
+        let intersections = default_world.intersect(&ray);
+
+        // Past the front of the outer sphere, to the inner one
+        assert_that!(intersections.hit().map(|hit| hit.t)).is_equal_to(Some(4.5));
+    }
+
+    #[rstest]
+    fn the_far_clip_leaves_distant_things_to_the_background(default_world: World) {
+        let settings = RenderSettings {
+            background: Color::new(0.1, 0.2, 0.3),
+            ..RenderSettings::default()
+        };
+
+        let image = clipped_camera(0.0, 3.0).render(&default_world, &settings);
+
+        assert_that!(image.pixel_at(5, 5)).is_equal_to(Color::new(0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    fn rays_leave_from_the_shape_of_the_aperture() {
+        let c = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_depth_of_field(0.5, 4.0)
+            .with_aperture_shape(Aperture::Polygon {
+                blades: 4,
+                rotation: PI / 4.0,
+            })
+            .build();
+        // Out to the middle of the square's right side, where a circle would reach further
+        let sample = CameraSample {
+            offset: (0.5, 0.5),
+            time: 0.0,
+            lens: (0.99999, 0.5),
+        };
+
+        let r = c.ray_for_sample(5, 5, &sample);
+
+        assert_approx_eq!(r.origin, Vector4::point(0.5 * (PI / 4.0).cos(), 0.0, 0.0), 0.0001);
+    }
+
+    #[rstest]
+    #[case(Shutter::new(0.0, 1.0), 0, 0.5, 0.5)]
+    #[case(Shutter::new(0.25, 0.75), 9, 0.0, 0.25)]
+    #[case(Shutter { open: 0.0, close: 0.5, rolling: 0.5 }, 0, 0.0, 0.0)]
+    #[case(Shutter { open: 0.0, close: 0.5, rolling: 0.5 }, 5, 1.0, 0.75)]
+    fn when_a_row_is_exposed(
+        #[case] shutter: Shutter,
+        #[case] row: usize,
+        #[case] fraction: f32,
+        #[case] expected: f32,
+    ) {
+        assert_that!(shutter.time(row, 10, fraction)).is_close_to(expected, 0.00001);
+    }
+
+    #[test]
+    fn a_moving_camera_takes_rays_from_where_it_is_at_the_time() {
+        let c = moving_camera(Shutter::new(0.0, 1.0));
+
+        let sample = CameraSample {
+            offset: (0.5, 0.5),
+            time: 0.25,
+            lens: (0.5, 0.5),
+        };
+
+        let r = c.ray_for_sample(5, 5, &sample);
+
+        assert_approx_eq!(r.origin, Vector4::point(0.5, 0.0, -5.0), 0.0001);
+        assert_approx_eq!(r.direction, Vector4::vector(0.0, 0.0, 1.0), 0.0001);
+        assert_approx_eq!(c.ray_for_pixel(5, 5).origin, Vector4::point(1.0, 0.0, -5.0), 0.0001);
+    }
+
+    #[rstest]
+    #[case(Shutter::new(0.0, 0.0), 0.0)]
+    #[case(Shutter { open: 0.0, close: 0.0, rolling: 1.0 }, 10.0 / 11.0 * 2.0)]
+    fn a_rolling_shutter_sees_lower_rows_later(#[case] shutter: Shutter, #[case] moved: f32) {
+        let c = moving_camera(shutter);
+
+        let top = c.ray_for_pixel(5, 0).origin;
+        let bottom = c.ray_for_pixel(5, 10).origin;
+
+        assert_that!(bottom.x - top.x).is_close_to(moved, 0.0001);
+    }
+
+    #[rstest]
+    fn a_camera_moving_while_the_shutter_is_open_blurs_the_image(default_world: World) {
+        let settings = RenderSettings {
+            samples_per_pixel: 16,
+            ..RenderSettings::default()
+        };
+        let still = moving_camera(Shutter::new(0.0, 0.0));
+        let moving = moving_camera(Shutter::new(0.0, 1.0));
+
+        let sharp = still.render(&default_world, &settings);
+        let blurred = moving.render(&default_world, &settings);
+
+        // The background to the left of the sphere, which the sphere sweeps across
+        assert_that!(sharp.pixel_at(2, 5)).is_equal_to(Color::black());
+        assert_that!(blurred.pixel_at(2, 5).r).is_greater_than(0.0);
+    }
+
+    #[test]
+    fn orbiting_the_camera_keeps_it_looking_at_the_axis() {
+        let c = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, -5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .build();
+
+        let r = c.orbited(PI / 2.0).ray_for_pixel(5, 5);
+
+        assert_approx_eq!(r.origin, Vector4::point(-5.0, 0.0, 0.0), 0.0001);
+        assert_approx_eq!(r.direction, Vector4::vector(1.0, 0.0, 0.0), 0.0001);
+    }
+
+    #[test]
+    fn a_small_camera_is_its_own_preview() {
+        let c = CameraBuilder::new()
+            .with_hsize(20)
+            .with_vsize(10)
+            .with_field_of_view(PI / 2.0)
+            .build();
+
+        let p = c.preview(40);
+
+        assert_that!((p.hsize, p.vsize)).is_equal_to((20, 10));
+        assert_that!(p.pixel_size).is_equal_to(c.pixel_size);
+    }
+
+    #[test]
+    fn constructing_a_ray_through_the_center_of_the_canvas() {
+        let c = CameraBuilder::new()
+            .with_hsize(201)
+            .with_vsize(101)
+            .with_field_of_view(PI / 2.0)
+            .build();
+
+        let r = c.ray_for_pixel(100, 50);
+
+        assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
+        assert_approx_eq!(r.direction, Vector4::vector(0.0, 0.0, -1.0), 0.0001);
+    }
+
+    #[test]
+    fn constructing_a_ray_through_a_corner_of_the_canvas() {
+        let c = CameraBuilder::new()
+            .with_hsize(201)
+            .with_vsize(101)
+            .with_field_of_view(PI / 2.0)
+            .build();
+
+        let r = c.ray_for_pixel(0, 0);
+
+        assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
+        assert_approx_eq!(
+            r.direction,
+            Vector4::vector(0.66519, 0.33259, -0.66851),
+            0.0001,
+        );
+    }
+
+    #[test]
+    fn a_lens_shift_moves_the_view_without_turning_the_camera() {
+        let c = CameraBuilder::new()
+            .with_hsize(201)
+            .with_vsize(101)
+            .with_field_of_view(PI / 2.0)
+            .with_lens_shift(0.25)
+            .build();
+
+        let r = c.ray_for_pixel(100, 50);
+
+        // The canvas is 2 units wide, so the middle of the image is now half a unit to the right
+        assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
+        assert_approx_eq!(
+            r.direction,
+            Vector4::vector(-0.5, 0.0, -1.0).normalize(),
+            0.0001,
+        );
+    }
+
+    #[test]
+    fn rolling_the_camera_turns_its_view() {
+        let c = CameraBuilder::new()
+            .with_hsize(201)
+            .with_vsize(201)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, 5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .with_roll(PI / 2.0)
+            .build();
+
+        // The top of the image now shows what is to the camera's left, which is +x from here
+        let r = c.ray_for_pixel(100, 0);
+
+        assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 0.0, 5.0));
+        assert_that!(r.direction.x).is_greater_than(0.5);
+        assert_that!(r.direction.y).is_close_to(0.0, 0.0001);
+    }
+
+    #[test]
+    fn reading_back_the_camera_settings() {
+        let transform = Matrix::view_transform(
+            Vector4::point(1.0, 2.0, 3.0),
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+        let c = CameraBuilder::new()
+            .with_hsize(125)
+            .with_vsize(200)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(transform)
+            .build();
+
+        assert_that!((c.hsize(), c.vsize())).is_equal_to((125, 200));
+        assert_that!(c.half_width()).is_close_to(0.625, 0.0001);
+        assert_that!(c.half_height()).is_close_to(1.0, 0.0001);
+        assert_that!(c.pixel_size()).is_close_to(0.01, 0.0001);
+        assert_that!(c.field_of_view()).is_close_to(PI / 2.0, 0.0001);
+        for row in 0..4 {
+            for col in 0..4 {
+                assert_that!(c.transform()[[row, col]])
+                    .is_close_to(transform[[row, col]], 0.0001);
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0)]
+    #[case(0.25, 0.0)]
+    #[case(0.0, PI / 3.0)]
+    fn a_point_seen_through_a_pixel_projects_back_onto_it(
+        #[case] lens_shift: f32,
+        #[case] roll: f32,
+    ) {
+        let c = CameraBuilder::new()
+            .with_hsize(160)
+            .with_vsize(120)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(1.0, 2.0, -5.0),
+                Vector4::point(0.0, 1.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .with_lens_shift(lens_shift)
+            .with_roll(roll)
+            .build();
+        let r = c.ray_for_pixel(30, 100);
+
+        let (x, y) = c.world_to_pixel(r.position(7.0)).unwrap();
+
+        assert_that!(x).is_close_to(30.5, 0.01);
+        assert_that!(y).is_close_to(100.5, 0.01);
+    }
+
+    #[test]
+    fn a_point_behind_the_camera_is_not_on_the_canvas() {
+        let c = CameraBuilder::new()
+            .with_hsize(160)
+            .with_vsize(120)
+            .with_field_of_view(PI / 2.0)
+            .build();
+
+        assert_that!(c.world_to_pixel(Vector4::point(0.0, 0.0, 1.0))).is_none();
+        assert_that!(c.world_to_pixel(Vector4::point(0.0, 0.0, -1.0)))
+            .is_equal_to(Some((80.0, 60.0)));
+    }
+
+    #[test]
+    fn framing_a_world_looks_at_the_middle_of_its_bounds() {
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(Matrix::translation(3.0, 1.0, 0.0))
+                    .build(),
+            ))
+            .with_object(Rc::new(PlaneBuilder::new().build()))
+            .build();
+
+        let c = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .framing(&world, FitMode::Fit)
+            .build();
+
+        let r = c.ray_for_pixel(5, 5);
+        let to_centre = (Vector4::point(3.0, 1.0, 0.0) - r.origin).normalize();
+        assert_approx_eq!(r.direction, to_centre, 0.0001);
+    }
+
+    #[rstest]
+    // Half of the 2:1 image's height, or of its width, which is seen across 90°
+    #[case(FitMode::Fit, 0.5_f32.atan())]
+    #[case(FitMode::Fill, PI / 4.0)]
+    fn framing_stands_back_far_enough_to_fit_the_scene(
+        #[case] fit: FitMode,
+        #[case] half_angle: f32,
+    ) {
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+                    .build(),
+            ))
+            .build();
+
+        let c = CameraBuilder::new()
+            .with_hsize(200)
+            .with_vsize(100)
+            .with_field_of_view(PI / 2.0)
+            .framing(&world, fit)
+            .build();
+
+        // The bounding box of the sphere has a half-diagonal of 2√3
+        let r = c.ray_for_pixel(100, 50);
+        let radius = 2.0 * 3.0_f32.sqrt();
+        let distance = (Vector4::point(0.0, 0.0, 0.0) - r.origin).magnitude();
+        assert_that!(distance).is_close_to(radius / half_angle.sin(), 0.001);
+    }
+
+    #[test]
+    fn framing_an_empty_world_leaves_the_camera_alone() {
+        let world = WorldBuilder::new().build();
+
+        let c = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .framing(&world, FitMode::Fit)
+            .build();
+
+        assert_that!(c.inv_transform).is_equal_to(Matrix::identity());
+    }
+
+    #[test]
+    fn constructing_a_ray_when_the_camera_is_transformed() {
+        let c = CameraBuilder::new()
+            .with_hsize(201)
+            .with_vsize(101)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0))
+            .build();
+
+        let r = c.ray_for_pixel(100, 50);
+
+        // assert_that!(r.origin).is_equal_to(Vector4::point(0.0, 2.0, -5.0));
+        assert_approx_eq!(r.origin, Vector4::point(0.0, 2.0, -5.0), 0.0001);
+        assert_approx_eq!(
+            r.direction,
+            Vector4::vector(2.0_f32.sqrt() / 2.0, 0.0, -2.0_f32.sqrt() / 2.0),
+            0.0001,
+        );
+    }
+
+    #[fixture]
+    fn default_world() -> World {
+        World::default_world()
+    }
+
     #[rstest]
     fn rendering_a_world_with_a_camera(default_world: World) {
         let from = Vector4::point(0.0, 0.0, -5.0);
@@ -264,12 +1640,401 @@ mod tests {
             .with_transform(Matrix::view_transform(from, to, up))
             .build();
 
-        let image = c.render(&default_world);
+        let image = c.render(&default_world, &RenderSettings::default());
 
         let actual = image.pixel_at(5, 5);
         let expected = Color::new(0.38066, 0.47583, 0.2855);
-        assert_that!(actual.r).is_close_to(expected.r, 0.0001);
-        assert_that!(actual.g).is_close_to(expected.g, 0.0001);
-        assert_that!(actual.b).is_close_to(expected.b, 0.0001);
+        assert_approx_eq!(actual, expected, 0.0001);
+    }
+
+    fn debug_camera() -> Camera {
+        CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, -5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .build()
+    }
+
+    #[rstest]
+    fn rendering_normals_as_colors(default_world: World) {
+        let image = debug_camera().render_debug(&default_world, DebugChannel::Normals);
+
+        // The normal facing the camera is (0, 0, -1)
+        assert_that!(image.pixel_at(5, 5)).is_equal_to(Color::new(0.5, 0.5, 0.0));
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn rendering_depth_makes_the_nearest_hit_brightest(default_world: World) {
+        let image = debug_camera().render_debug(&default_world, DebugChannel::Depth);
+
+        let centre = image.pixel_at(5, 5);
+        let edge = image.pixel_at(5, 4);
+        assert_that!(centre).is_equal_to(Color::white());
+        assert_that!(edge.r).is_less_than(centre.r);
+        assert_that!(edge.r).is_greater_than(0.0);
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn rendering_object_ids(default_world: World) {
+        let light = PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+        let front = SphereBuilder::new()
+            .with_transform(Matrix::translation(0.0, 0.0, -3.0) * Matrix::scaling(0.2, 0.2, 0.2))
+            .build();
+        let world = WorldBuilder::from(default_world)
+            .with_light_source(light)
+            .with_object(Rc::new(front))
+            .build();
+
+        let image = debug_camera().render_debug(&world, DebugChannel::ObjectId);
+
+        assert_that!(image.pixel_at(5, 5)).is_equal_to(Color::from_hsv(2.0 * 137.5, 0.65, 1.0));
+        assert_that!(image.pixel_at(5, 4)).is_equal_to(Color::from_hsv(0.0, 0.65, 1.0));
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn rendering_a_shadow_mask(default_world: World) {
+        let lit = debug_camera().render_debug(&default_world, DebugChannel::ShadowMask);
+
+        let light = PointLight::new(Vector4::point(0.0, 0.0, 10.0), Color::white());
+        let world = WorldBuilder::from(default_world)
+            .with_light_source(light)
+            .build();
+        let shadowed = debug_camera().render_debug(&world, DebugChannel::ShadowMask);
+
+        assert_that!(lit.pixel_at(5, 5)).is_equal_to(Color::white());
+        assert_that!(shadowed.pixel_at(5, 5)).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn rendering_intersection_counts_as_a_heatmap(default_world: World) {
+        let image = debug_camera().render_debug(&default_world, DebugChannel::IntersectionCount);
+
+        // The centre ray passes through both spheres, the edge ray only through the outer one
+        assert_that!(image.pixel_at(5, 5)).is_equal_to(Color::new(1.0, 0.0, 0.0));
+        assert_that!(image.pixel_at(5, 4)).is_equal_to(Color::from_hsv(120.0, 1.0, 1.0));
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn rays_that_miss_take_the_background_color(default_world: World) {
+        let settings = RenderSettings {
+            background: Color::new(0.2, 0.3, 0.4),
+            ..RenderSettings::default()
+        };
+
+        let image = debug_camera().render(&default_world, &settings);
+
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(Color::new(0.2, 0.3, 0.4));
+        assert_that!(image.pixel_at(10, 10)).is_equal_to(Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[rstest]
+    fn several_samples_per_pixel_blend_the_edge_of_an_object(default_world: World) {
+        let single = debug_camera().render(&default_world, &RenderSettings::default());
+        let settings = RenderSettings {
+            samples_per_pixel: 16,
+            ..RenderSettings::default()
+        };
+
+        let reference = RenderSettings {
+            samples_per_pixel: 256,
+            ..RenderSettings::default()
+        };
+
+        let image = debug_camera().render(&default_world, &settings);
+
+        // The centre pixel is covered by the sphere, the one above it only partly. The shading
+        // varies across a pixel this large, so its mean is compared with a finely sampled render
+        let centre = image.pixel_at(5, 5);
+        let expected = debug_camera().render(&default_world, &reference).pixel_at(5, 5);
+        assert_that!(centre.r).is_close_to(expected.r, 0.01);
+        let edge = image.pixel_at(5, 4);
+        assert_that!(edge.r).is_less_than(single.pixel_at(5, 4).r);
+        assert_that!(edge.r).is_greater_than(0.0);
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(16)]
+    fn rendering_one_pixel_matches_the_whole_image(
+        default_world: World,
+        #[case] samples_per_pixel: usize,
+    ) {
+        let settings = RenderSettings {
+            samples_per_pixel,
+            ..RenderSettings::default()
+        };
+        let image = debug_camera().render(&default_world, &settings);
+
+        for (x, y) in [(5, 5), (5, 4), (0, 0)] {
+            let pixel = debug_camera().render_pixel_with(&default_world, x, y, &settings);
+
+            assert_that!(pixel).is_equal_to(image.pixel_at(x, y));
+        }
+    }
+
+    #[rstest]
+    fn a_seeded_render_is_the_same_in_any_pixel_order(default_world: World) {
+        let settings = RenderSettings {
+            samples_per_pixel: 4,
+            seed: 7,
+            frame: 3,
+            ..RenderSettings::default()
+        };
+        let camera = debug_camera();
+        let image = camera.render(&default_world, &settings);
+        let bits = |c: Color| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()];
+
+        for y in (0..camera.vsize()).rev() {
+            for x in (0..camera.hsize()).rev() {
+                let pixel = camera.render_pixel_with(&default_world, x, y, &settings);
+                assert_that!(bits(pixel)).is_equal_to(bits(image.pixel_at(x, y)));
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(PixelFilter::Tent)]
+    #[case(PixelFilter::Gaussian)]
+    #[case(PixelFilter::Mitchell)]
+    fn a_filter_softens_edges_but_not_flat_areas(
+        default_world: World,
+        #[case] filter: PixelFilter,
+    ) {
+        let boxed = RenderSettings {
+            samples_per_pixel: 4,
+            ..RenderSettings::default()
+        };
+        let filtered = RenderSettings { filter, ..boxed.clone() };
+        let camera = debug_camera();
+
+        let expected = camera.render(&default_world, &boxed);
+        let image = camera.render(&default_world, &filtered);
+
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(expected.pixel_at(0, 0));
+        assert_that!(image.pixel_at(5, 4)).is_not_equal_to(expected.pixel_at(5, 4));
+        for (x, y) in [(0, 0), (5, 4), (5, 5), (10, 10)] {
+            let pixel = camera.render_pixel_with(&default_world, x, y, &filtered);
+            assert_approx_eq!(pixel, image.pixel_at(x, y), 1e-6);
+        }
+    }
+
+    #[rstest]
+    fn adaptive_sampling_spends_samples_where_the_pixel_is_noisy(default_world: World) {
+        let adaptive = AdaptiveSampling {
+            min_samples: 4,
+            max_samples: 32,
+            threshold: 0.00001,
+        };
+        let settings = RenderSettings {
+            adaptive: Some(adaptive),
+            ..RenderSettings::default()
+        };
+        let camera = debug_camera();
+
+        let background = camera.pixel_samples(&default_world, 0, 0, &settings);
+        let edge = camera.pixel_samples(&default_world, 5, 4, &settings);
+
+        assert_that!(background.len()).is_equal_to(4);
+        assert_that!(edge.len()).is_greater_than(4);
+        assert_that!(edge.len()).is_less_than_or_equal_to(32);
+        let image = camera.render(&default_world, &settings);
+        for (x, y) in [(0, 0), (5, 4), (5, 5)] {
+            let pixel = camera.render_pixel_with(&default_world, x, y, &settings);
+            assert_that!(pixel).is_equal_to(image.pixel_at(x, y));
+        }
+    }
+
+    #[rstest]
+    #[case(0, 0.5, true)]
+    #[case(3, 0.0, true)]
+    #[case(4, 0.0, false)]
+    #[case(4, 0.01, true)]
+    #[case(64, 0.01, false)]
+    fn when_adaptive_sampling_wants_another_sample(
+        #[case] count: usize,
+        #[case] variance: f32,
+        #[case] expected: bool,
+    ) {
+        let adaptive = AdaptiveSampling::default();
+
+        assert_that!(adaptive.needs_more(count, variance)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(1, 0)]
+    #[case(0, 1)]
+    fn the_seed_and_frame_change_the_noise(
+        default_world: World,
+        #[case] seed: u64,
+        #[case] frame: u64,
+    ) {
+        let settings = RenderSettings {
+            samples_per_pixel: 4,
+            ..RenderSettings::default()
+        };
+        let reseeded = RenderSettings {
+            seed,
+            frame,
+            ..settings.clone()
+        };
+
+        // Only the pixels on the edge of the sphere depend on where the samples fall
+        let edge = debug_camera().render_pixel_with(&default_world, 5, 4, &settings);
+        let other = debug_camera().render_pixel_with(&default_world, 5, 4, &reseeded);
+
+        assert_that!(edge).is_not_equal_to(other);
+    }
+
+    #[rstest]
+    fn rendering_one_pixel_with_the_default_settings(default_world: World) {
+        let pixel = debug_camera().render_pixel(&default_world, 5, 5);
+
+        assert_that!(pixel.g).is_close_to(0.47583, 0.0001);
+    }
+
+    #[rstest]
+    fn the_rendered_image_is_post_processed(default_world: World) {
+        let settings = RenderSettings {
+            background: Color::new(0.2, 0.3, 0.4),
+            post: PostProcessBuilder::new().with_exposure(1.0).build(),
+            ..RenderSettings::default()
+        };
+
+        let image = debug_camera().render(&default_world, &settings);
+
+        let actual = image.pixel_at(5, 0);
+        assert_that!(actual.r).is_close_to(0.4, 0.0001);
+        assert_that!(actual.g).is_close_to(0.6, 0.0001);
+        assert_that!(actual.b).is_close_to(0.8, 0.0001);
+    }
+
+    #[rstest]
+    fn denoising_keeps_the_edge_of_an_object(default_world: World) {
+        let plain = debug_camera().render(&default_world, &RenderSettings::default());
+        let settings = RenderSettings {
+            denoise: Some(Denoiser::default()),
+            ..RenderSettings::default()
+        };
+
+        let image = debug_camera().render(&default_world, &settings);
+
+        assert_that!(image.pixel_at(0, 0).r).is_close_to(0.0, 0.0001);
+        assert_that!(image.pixel_at(5, 5).r).is_close_to(plain.pixel_at(5, 5).r, 0.05);
+    }
+
+    #[rstest]
+    fn rendering_aovs(default_world: World) {
+        let aovs = debug_camera().render_aovs(&default_world, &RenderSettings::default());
+
+        let beauty = debug_camera().render(&default_world, &RenderSettings::default());
+        assert_that!(aovs.beauty.pixel_at(5, 5)).is_equal_to(beauty.pixel_at(5, 5));
+        assert_that!(aovs.depth.pixel_at(5, 5).r).is_close_to(4.0, 0.0001);
+        assert_that!(aovs.normals.pixel_at(5, 5)).is_equal_to(Color::new(0.0, 0.0, -1.0));
+        assert_that!(aovs.albedo.pixel_at(5, 5)).is_equal_to(Color::new(0.8, 1.0, 0.6));
+        assert_that!(aovs.indirect.pixel_at(5, 5)).is_equal_to(Color::black());
+        assert_that!(aovs.direct.pixel_at(5, 5)).is_equal_to(beauty.pixel_at(5, 5));
+        assert_that!(aovs.depth.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn aovs_record_the_object_in_each_pixel(default_world: World) {
+        let aovs = debug_camera().render_aovs(&default_world, &RenderSettings::default());
+
+        let outer = default_world.object_id(&default_world.objects()[0]);
+        assert_that!(aovs.object_ids[5][5]).is_equal_to(outer);
+        assert_that!(aovs.object_ids[0][0]).is_equal_to(None);
+    }
+
+    #[rstest]
+    fn saving_aovs_as_exr(default_world: World) {
+        let aovs = debug_camera().render_aovs(&default_world, &RenderSettings::default());
+        let mut file = vec![];
+
+        aovs.save_exr(&mut file).unwrap();
+
+        // The last scanline ends with the id of the bottom right pixel, which misses
+        assert_that!(file[file.len() - 4..].to_vec()).is_equal_to(vec![0, 0, 0, 0]);
+        let names = [&b"R\0"[..], b"G\0", b"B\0", b"Z\0", b"id\0"];
+        for name in names {
+            assert_that!(file.windows(name.len()).any(|w| w == name)).is_true();
+        }
+    }
+
+    #[rstest]
+    fn reflections_are_indirect_light(mut default_world: World) {
+        let mirror = PlaneBuilder::new()
+            .with_material(MaterialBuilder::new().with_reflective(0.5).build())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build();
+        default_world.add_object(Rc::new(mirror));
+        let camera = CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 2.0, -5.0),
+                Vector4::point(0.0, -1.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .build();
+
+        let aovs = camera.render_aovs(&default_world, &RenderSettings::default());
+
+        let mut reflected = 0;
+        for y in 0..11 {
+            for x in 0..11 {
+                let indirect = aovs.indirect.pixel_at(x, y);
+                let sum = aovs.direct.pixel_at(x, y) + indirect;
+                assert_that!(aovs.beauty.pixel_at(x, y).g).is_close_to(sum.g, 0.0001);
+                if indirect.g > 0.0 {
+                    reflected += 1;
+                }
+            }
+        }
+        assert_that!(reflected).is_greater_than(0);
+    }
+
+    #[rstest]
+    fn rays_that_miss_are_transparent(default_world: World) {
+        let image = debug_camera().render(&default_world, &RenderSettings::default());
+
+        assert_that!(image.alpha_at(0, 0)).is_equal_to(0.0);
+        assert_that!(image.alpha_at(5, 5)).is_equal_to(1.0);
+    }
+
+    #[rstest]
+    fn partly_covered_pixels_are_partly_transparent(default_world: World) {
+        let settings = RenderSettings {
+            samples_per_pixel: 16,
+            ..RenderSettings::default()
+        };
+
+        let image = debug_camera().render(&default_world, &settings);
+
+        let edge = image.alpha_at(5, 4);
+        assert_that!(edge).is_greater_than(0.0);
+        assert_that!(edge).is_less_than(1.0);
+        assert_that!(image.alpha_at(5, 5)).is_equal_to(1.0);
+    }
+
+    #[rstest]
+    fn rendering_with_a_debug_integrator(default_world: World) {
+        let settings = RenderSettings {
+            integrator: Integrator::Debug(DebugChannel::Normals),
+            ..RenderSettings::default()
+        };
+
+        let image = debug_camera().render(&default_world, &settings);
+
+        assert_that!(image.pixel_at(5, 5)).is_equal_to(Color::new(0.5, 0.5, 0.0));
     }
 }
@@ -1,17 +1,22 @@
 use std::io::{Error, Write};
 
-use crate::color::Color;
+use crate::color::{Color, ColorSpace};
+use crate::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::png;
 
 const PPM_MAX_LINE_LENGTH: usize = 70;
 
 pub struct Canvas {
-    pixels: Vec<Vec<Color>>
+    pixels: Vec<Vec<Color>>,
+    // How much of each pixel is covered, from 0.0 (transparent) to 1.0 (opaque)
+    alpha: Vec<Vec<f32>>,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
         Self {
-            pixels: vec![vec![Color::black(); width]; height]
+            pixels: vec![vec![Color::black(); width]; height],
+            alpha: vec![vec![1.0; width]; height],
         }
     }
 
@@ -23,6 +28,12 @@ impl Canvas {
         self.pixels.len()
     }
 
+    /// The bytes taken up by the pixels and their coverage.
+    pub fn memory_size(&self) -> usize {
+        let pixels: usize = self.pixels.iter().map(Vec::len).sum();
+        pixels * (std::mem::size_of::<Color>() + std::mem::size_of::<f32>())
+    }
+
     pub fn pixel_at(&self, x: usize, y: usize) -> Color {
         self.pixels[y][x].clone()
     }
@@ -31,7 +42,115 @@ impl Canvas {
         self.pixels[y][x] = color.clone();
     }
 
+    pub fn alpha_at(&self, x: usize, y: usize) -> f32 {
+        self.alpha[y][x]
+    }
+
+    pub fn write_alpha(&mut self, x: usize, y: usize, alpha: f32) {
+        self.alpha[y][x] = alpha;
+    }
+
+    pub fn fill(&mut self, color: &Color) {
+        for row in self.pixels.iter_mut() {
+            row.iter_mut().for_each(|pixel| *pixel = *color);
+        }
+    }
+
+    /// Draw a line between two pixels using Bresenham's algorithm.
+    /// Any part of the line that falls outside the canvas is clipped.
+    pub fn draw_line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: &Color) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.write_pixel_clipped(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draw the outline of a rectangle with its top left corner at (x, y).
+    pub fn draw_rect(&mut self, x: isize, y: isize, width: usize, height: usize, color: &Color) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let right = x + width as isize - 1;
+        let bottom = y + height as isize - 1;
+
+        self.draw_line(x, y, right, y, color);
+        self.draw_line(x, bottom, right, bottom, color);
+        self.draw_line(x, y, x, bottom, color);
+        self.draw_line(right, y, right, bottom, color);
+    }
+
+    /// Draw `text` in a tiny 3x5 pixel font with the top left of its first character at (x, y),
+    /// for labelling images with how they were rendered. Each `\n` starts a new line under the
+    /// first character, and anything that falls outside the canvas is clipped.
+    pub fn draw_text(&mut self, x: isize, y: isize, text: &str, color: &Color) {
+        for (line, characters) in text.lines().enumerate() {
+            let top = y + (line * (GLYPH_HEIGHT + 1)) as isize;
+            for (column, c) in characters.chars().enumerate() {
+                let left = x + (column * (GLYPH_WIDTH + 1)) as isize;
+                for (row, bits) in glyph(c).iter().enumerate() {
+                    for col in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                            let (px, py) = (left + col as isize, top + row as isize);
+                            self.write_pixel_clipped(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy `other` onto this canvas with its top left corner at (x, y), clipping at the edges.
+    pub fn blit(&mut self, other: &Canvas, x: isize, y: isize) {
+        for (row, pixels) in other.pixels.iter().enumerate() {
+            for (col, pixel) in pixels.iter().enumerate() {
+                let (px, py) = (x + col as isize, y + row as isize);
+                if self.contains(px, py) {
+                    self.write_pixel(px as usize, py as usize, pixel);
+                    self.write_alpha(px as usize, py as usize, other.alpha[row][col]);
+                }
+            }
+        }
+    }
+
+    pub fn flip_vertical(&mut self) {
+        self.pixels.reverse();
+        self.alpha.reverse();
+    }
+
+    fn write_pixel_clipped(&mut self, x: isize, y: isize, color: &Color) {
+        if self.contains(x, y) {
+            self.write_pixel(x as usize, y as usize, color);
+        }
+    }
+
+    fn contains(&self, x: isize, y: isize) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width() && (y as usize) < self.height()
+    }
+
     pub fn save(&self, file: &mut impl Write) -> Result<(), Error> {
+        self.save_in(file, ColorSpace::Srgb)
+    }
+
+    /// Save the canvas as a plain PPM, encoded in `space`.
+    pub fn save_in(&self, file: &mut impl Write, space: ColorSpace) -> Result<(), Error> {
         let header = format!("P3\n{width} {height}\n255\n", width=self.width(), height=self.height());
         let _ = file.write(header.as_bytes()).unwrap();
         for row in &self.pixels {
@@ -41,7 +160,8 @@ impl Canvas {
                     file.write(b" ")?;
                     current_length += 1;
                 }
-                let pixel_str = pixel.to_string();
+                let (red, green, blue) = pixel.to_u8_in(space);
+                let pixel_str = format!("{} {} {}", red, green, blue);
                 let pixel_bytes = pixel_str.as_bytes();
                 if current_length + pixel_bytes.len() > PPM_MAX_LINE_LENGTH {
                     file.write(b"\n")?;
@@ -55,6 +175,78 @@ impl Canvas {
 
         Ok(())
     }
+
+    /// Save the canvas as an RGBA PNG, keeping the alpha channel for compositing.
+    pub fn save_png(&self, file: &mut impl Write) -> Result<(), Error> {
+        self.save_png_in(file, ColorSpace::Srgb)
+    }
+
+    /// Save the canvas as an RGBA PNG, encoded in `space`.
+    pub fn save_png_in(&self, file: &mut impl Write, space: ColorSpace) -> Result<(), Error> {
+        let mut rgba = Vec::with_capacity(self.width() * self.height() * 4);
+        for (row, alphas) in self.pixels.iter().zip(&self.alpha) {
+            for (pixel, alpha) in row.iter().zip(alphas) {
+                let (red, green, blue) = pixel.to_u8_in(space);
+                let alpha = (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+                rgba.extend_from_slice(&[red, green, blue, alpha]);
+            }
+        }
+
+        png::write_rgba(file, self.width(), self.height(), &rgba)
+    }
+}
+
+/// Encoded as 8-bit sRGB, dropping the alpha channel.
+#[cfg(feature = "image")]
+impl From<&Canvas> for image::RgbImage {
+    fn from(canvas: &Canvas) -> Self {
+        image::RgbImage::from_fn(canvas.width() as u32, canvas.height() as u32, |x, y| {
+            let (red, green, blue) = canvas.pixel_at(x as usize, y as usize).to_srgb_u8();
+            image::Rgb([red, green, blue])
+        })
+    }
+}
+
+/// Encoded as 8-bit sRGB, with the alpha channel.
+#[cfg(feature = "image")]
+impl From<&Canvas> for image::RgbaImage {
+    fn from(canvas: &Canvas) -> Self {
+        image::RgbaImage::from_fn(canvas.width() as u32, canvas.height() as u32, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            let (red, green, blue) = canvas.pixel_at(x, y).to_srgb_u8();
+            let alpha = (canvas.alpha_at(x, y).clamp(0.0, 1.0) * 255.0).round() as u8;
+            image::Rgba([red, green, blue, alpha])
+        })
+    }
+}
+
+/// Decoded from 8-bit sRGB to linear light, fully opaque.
+#[cfg(feature = "image")]
+impl From<&image::RgbImage> for Canvas {
+    fn from(image: &image::RgbImage) -> Self {
+        let mut canvas = Canvas::new(image.width() as usize, image.height() as usize);
+        for (x, y, image::Rgb([red, green, blue])) in image.enumerate_pixels() {
+            let color = Color::from_srgb_u8(*red, *green, *blue);
+            canvas.write_pixel(x as usize, y as usize, &color);
+        }
+
+        canvas
+    }
+}
+
+/// Decoded from 8-bit sRGB to linear light, keeping the alpha channel.
+#[cfg(feature = "image")]
+impl From<&image::RgbaImage> for Canvas {
+    fn from(image: &image::RgbaImage) -> Self {
+        let mut canvas = Canvas::new(image.width() as usize, image.height() as usize);
+        for (x, y, image::Rgba([red, green, blue, alpha])) in image.enumerate_pixels() {
+            let (x, y) = (x as usize, y as usize);
+            canvas.write_pixel(x, y, &Color::from_srgb_u8(*red, *green, *blue));
+            canvas.write_alpha(x, y, *alpha as f32 / 255.0);
+        }
+
+        canvas
+    }
 }
 
 /* -------------------------------------------------------------------------------------------------
@@ -173,7 +365,7 @@ mod tests {
         assert_that!(buf).is_equal_to(String::from("255 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n"));
         buf.clear();
         let _ = readable.read_line(&mut buf);
-        assert_that!(buf).is_equal_to(String::from("0 0 0 0 0 0 0 128 0 0 0 0 0 0 0\n"));
+        assert_that!(buf).is_equal_to(String::from("0 0 0 0 0 0 0 188 0 0 0 0 0 0 0\n"));
         buf.clear();
         let _ = readable.read_line(&mut buf);
         assert_that!(buf).is_equal_to(String::from("0 0 0 0 0 0 0 0 0 0 0 0 0 0 255\n"));
@@ -202,16 +394,16 @@ mod tests {
             buf.clear();
         }
         let _ = readable.read_line(&mut buf);
-        assert_that!(buf).is_equal_to(String::from("255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 \n"));
+        assert_that!(buf).is_equal_to(String::from("255 231 203 255 231 203 255 231 203 255 231 203 255 231 203 \n"));
         buf.clear();
         let _ = readable.read_line(&mut buf);
-        assert_that!(buf).is_equal_to(String::from("255 204 153 255 204 153 255 204 153 255 204 153 255 204 153\n"));
+        assert_that!(buf).is_equal_to(String::from("255 231 203 255 231 203 255 231 203 255 231 203 255 231 203\n"));
         buf.clear();
         let _ = readable.read_line(&mut buf);
-        assert_that!(buf).is_equal_to(String::from("255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 \n"));
+        assert_that!(buf).is_equal_to(String::from("255 231 203 255 231 203 255 231 203 255 231 203 255 231 203 \n"));
         buf.clear();
         let _ = readable.read_line(&mut buf);
-        assert_that!(buf).is_equal_to(String::from("255 204 153 255 204 153 255 204 153 255 204 153 255 204 153\n"));
+        assert_that!(buf).is_equal_to(String::from("255 231 203 255 231 203 255 231 203 255 231 203 255 231 203\n"));
         buf.clear();
         let res = readable.read_line(&mut buf);
         assert_that!(res).is_ok().is_equal_to(0);
@@ -232,4 +424,251 @@ mod tests {
 
         assert_that!(file.last().unwrap()).is_equal_to(&10u8);
     }
+
+    #[test]
+    fn filling_a_canvas() {
+        let mut canvas = Canvas::new(3, 2);
+        let red = Color::new(1.0, 0.0, 0.0);
+
+        canvas.fill(&red);
+
+        for x in 0..3 {
+            for y in 0..2 {
+                assert_that!(canvas.pixel_at(x, y)).is_equal_to(red)
+            }
+        }
+    }
+
+    #[test]
+    fn drawing_a_horizontal_line() {
+        let mut canvas = Canvas::new(5, 3);
+
+        canvas.draw_line(1, 1, 3, 1, &Color::white());
+
+        assert_that!(canvas.pixel_at(0, 1)).is_equal_to(Color::black());
+        assert_that!(canvas.pixel_at(1, 1)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(2, 1)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(3, 1)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(4, 1)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn drawing_a_diagonal_line_in_either_direction() {
+        let mut canvas = Canvas::new(4, 4);
+        let mut reversed = Canvas::new(4, 4);
+
+        canvas.draw_line(0, 0, 3, 3, &Color::white());
+        reversed.draw_line(3, 3, 0, 0, &Color::white());
+
+        for i in 0..4 {
+            assert_that!(canvas.pixel_at(i, i)).is_equal_to(Color::white());
+            assert_that!(reversed.pixel_at(i, i)).is_equal_to(Color::white());
+        }
+        assert_that!(canvas.pixel_at(1, 0)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn lines_outside_the_canvas_are_clipped() {
+        let mut canvas = Canvas::new(3, 3);
+
+        canvas.draw_line(-2, 1, 5, 1, &Color::white());
+
+        for x in 0..3 {
+            assert_that!(canvas.pixel_at(x, 1)).is_equal_to(Color::white());
+        }
+    }
+
+    #[test]
+    fn drawing_a_rectangle_draws_its_outline() {
+        let mut canvas = Canvas::new(5, 5);
+
+        canvas.draw_rect(1, 1, 3, 3, &Color::white());
+
+        assert_that!(canvas.pixel_at(1, 1)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(3, 1)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(1, 3)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(3, 3)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(2, 2)).is_equal_to(Color::black());
+        assert_that!(canvas.pixel_at(0, 0)).is_equal_to(Color::black());
+        assert_that!(canvas.pixel_at(4, 4)).is_equal_to(Color::black());
+    }
+
+    /// The canvas as rows of `#` for lit pixels and `.` for dark ones.
+    fn picture(canvas: &Canvas) -> Vec<String> {
+        (0..canvas.height())
+            .map(|y| {
+                (0..canvas.width())
+                    .map(|x| {
+                        if canvas.pixel_at(x, y) == Color::black() {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drawing_text() {
+        let mut canvas = Canvas::new(13, 7);
+
+        canvas.draw_text(1, 1, "t=1", &Color::white());
+
+        assert_that!(picture(&canvas)).is_equal_to(
+            [
+                ".............",
+                ".###......#..",
+                "..#..###.##..",
+                "..#.......#..",
+                "..#..###..#..",
+                "..#......###.",
+                ".............",
+            ]
+            .map(String::from)
+            .to_vec(),
+        );
+    }
+
+    #[test]
+    fn text_starts_a_new_line_at_each_newline() {
+        let mut canvas = Canvas::new(3, 11);
+
+        canvas.draw_text(0, 0, "-\n-", &Color::white());
+
+        assert_that!(picture(&canvas)[2].as_str()).is_equal_to("###");
+        assert_that!(picture(&canvas)[8].as_str()).is_equal_to("###");
+    }
+
+    #[test]
+    fn text_outside_the_canvas_is_clipped() {
+        let mut canvas = Canvas::new(2, 2);
+
+        canvas.draw_text(-2, -3, "8", &Color::white());
+
+        assert_that!(picture(&canvas))
+            .is_equal_to(vec![String::from("#."), String::from("#.")]);
+    }
+
+    #[test]
+    fn blitting_copies_another_canvas_into_place() {
+        let mut canvas = Canvas::new(4, 4);
+        let mut other = Canvas::new(2, 2);
+        let red = Color::new(1.0, 0.0, 0.0);
+        other.fill(&red);
+
+        canvas.blit(&other, 1, 2);
+
+        assert_that!(canvas.pixel_at(1, 2)).is_equal_to(red);
+        assert_that!(canvas.pixel_at(2, 3)).is_equal_to(red);
+        assert_that!(canvas.pixel_at(0, 2)).is_equal_to(Color::black());
+        assert_that!(canvas.pixel_at(1, 1)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn blitting_is_clipped_at_the_edges() {
+        let mut canvas = Canvas::new(2, 2);
+        let mut other = Canvas::new(2, 2);
+        other.fill(&Color::white());
+
+        canvas.blit(&other, -1, 1);
+
+        assert_that!(canvas.pixel_at(0, 1)).is_equal_to(Color::white());
+        assert_that!(canvas.pixel_at(1, 1)).is_equal_to(Color::black());
+        assert_that!(canvas.pixel_at(0, 0)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn a_new_canvas_is_opaque() {
+        let canvas = Canvas::new(3, 2);
+
+        assert_that!(canvas.alpha_at(2, 1)).is_equal_to(1.0);
+    }
+
+    #[test]
+    fn blitting_copies_the_alpha_channel() {
+        let mut canvas = Canvas::new(4, 4);
+        let mut other = Canvas::new(2, 2);
+        other.write_alpha(1, 1, 0.25);
+
+        canvas.blit(&other, 1, 1);
+
+        assert_that!(canvas.alpha_at(2, 2)).is_equal_to(0.25);
+        assert_that!(canvas.alpha_at(1, 1)).is_equal_to(1.0);
+    }
+
+    #[test]
+    fn saving_a_png_keeps_the_alpha_channel() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, &Color::white());
+        canvas.write_alpha(1, 0, 0.0);
+        let mut file = vec![];
+
+        canvas.save_png(&mut file).unwrap();
+
+        // The scanline follows the PNG signature, three chunk headers and the zlib block header
+        let scanline = 8 + 8 + 13 + 4 + 8 + 2 + 5;
+        assert_that!(file[scanline..scanline + 9].to_vec())
+            .is_equal_to(vec![0, 255, 255, 255, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn saving_a_png_in_another_colour_space() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.write_pixel(0, 0, &Color::new(0.5, 0.5, 0.5));
+        let (mut srgb, mut linear) = (vec![], vec![]);
+
+        canvas.save_png(&mut srgb).unwrap();
+        canvas.save_png_in(&mut linear, ColorSpace::Linear).unwrap();
+
+        let scanline = 8 + 8 + 13 + 4 + 8 + 2 + 5;
+        assert_that!(srgb[scanline + 1]).is_equal_to(188);
+        assert_that!(linear[scanline + 1]).is_equal_to(128);
+    }
+
+    #[test]
+    fn flipping_a_canvas_vertically() {
+        let mut canvas = Canvas::new(2, 3);
+        let red = Color::new(1.0, 0.0, 0.0);
+        canvas.write_pixel(1, 0, &red);
+
+        canvas.flip_vertical();
+
+        assert_that!(canvas.pixel_at(1, 2)).is_equal_to(red);
+        assert_that!(canvas.pixel_at(1, 0)).is_equal_to(Color::black());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn converting_to_and_from_an_rgb_image() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, &Color::new(1.0, 0.0, 0.0));
+        c.write_pixel(2, 1, &Color::new(0.0, 0.5, 1.0));
+
+        let image = image::RgbImage::from(&c);
+
+        assert_that!(image.dimensions()).is_equal_to((3, 2));
+        assert_that!(image.get_pixel(0, 0).0).is_equal_to([255, 0, 0]);
+        assert_that!(image.get_pixel(2, 1).0).is_equal_to([0, 188, 255]);
+        let back = Canvas::from(&image);
+        let pixel = back.pixel_at(2, 1);
+        assert_that!((pixel.r, pixel.b)).is_equal_to((0.0, 1.0));
+        assert_that!((pixel.g - 0.5).abs() < 0.01).is_equal_to(true);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn converting_to_and_from_an_rgba_image_keeps_alpha() {
+        let mut c = Canvas::new(2, 1);
+        c.write_alpha(1, 0, 0.0);
+
+        let image = image::RgbaImage::from(&c);
+
+        assert_that!(image.get_pixel(0, 0).0).is_equal_to([0, 0, 0, 255]);
+        assert_that!(image.get_pixel(1, 0).0).is_equal_to([0, 0, 0, 0]);
+        let back = Canvas::from(&image);
+        assert_that!(back.alpha_at(1, 0)).is_equal_to(0.0);
+        assert_that!(back.alpha_at(0, 0)).is_equal_to(1.0);
+    }
 }
@@ -0,0 +1,134 @@
+use std::rc::Rc;
+
+use crate::consts::EPSILON;
+use crate::plane::PlaneBuilder;
+use crate::ray::Ray;
+use crate::shape::Shape;
+use crate::vector4::Vector4;
+
+/// A plane that cuts away everything on the side its normal faces, for cutaway and section views.
+///
+/// When capped, the cut faces of closed objects are filled in with the object's own material, so
+/// solids look solid where they are sliced open instead of showing their insides.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipPlane {
+    point: Vector4,
+    normal: Vector4,
+    capped: bool,
+}
+
+impl ClipPlane {
+    /// A plane through `point` that cuts away everything `normal` points towards.
+    pub fn new(point: Vector4, normal: Vector4) -> Self {
+        assert!(point.is_point(), "Point must be a point");
+        assert!(
+            normal.is_vector() && normal.magnitude() > EPSILON,
+            "Normal must be a non-zero vector"
+        );
+
+        Self {
+            point,
+            normal: normal.normalize(),
+            capped: false,
+        }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+
+    pub fn point(&self) -> Vector4 {
+        self.point
+    }
+
+    pub fn normal(&self) -> Vector4 {
+        self.normal
+    }
+
+    pub fn capped(&self) -> bool {
+        self.capped
+    }
+
+    /// Is `point` in the part of space that is cut away?
+    pub fn clips(&self, point: Vector4) -> bool {
+        (point - self.point).dot(&self.normal) > 0.0
+    }
+
+    /// How far along `ray` it crosses the plane, if it isn't parallel to it.
+    pub fn crossing(&self, ray: &Ray) -> Option<f32> {
+        let denominator = ray.direction.dot(&self.normal);
+        if denominator.abs() < EPSILON {
+            return None;
+        }
+
+        Some((self.point - ray.origin).dot(&self.normal) / denominator)
+    }
+
+    /// The cap where the plane cuts through `object`, made of the object's material.
+    pub(crate) fn cap(&self, object: &Rc<dyn Shape>) -> Rc<dyn Shape> {
+        Rc::new(
+            PlaneBuilder::from_point_normal(self.point, self.normal)
+                .with_material(object.material().clone())
+                .build(),
+        )
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[fixture]
+    fn plane() -> ClipPlane {
+        ClipPlane::new(
+            Vector4::point(0.0, 0.0, 1.0),
+            Vector4::vector(0.0, 0.0, -2.0),
+        )
+    }
+
+    #[rstest]
+    fn the_normal_is_normalized(plane: ClipPlane) {
+        assert_that!(plane.normal()).is_equal_to(Vector4::vector(0.0, 0.0, -1.0));
+        assert_that!(plane.capped()).is_false();
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 0.0, 0.0), true)]
+    #[case(Vector4::point(5.0, -3.0, 0.5), true)]
+    #[case(Vector4::point(0.0, 0.0, 2.0), false)]
+    fn points_in_front_of_the_plane_are_clipped(
+        plane: ClipPlane,
+        #[case] point: Vector4,
+        #[case] expected: bool,
+    ) {
+        assert_that!(plane.clips(point)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    fn a_ray_crosses_the_plane(plane: ClipPlane) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 2.0),
+        );
+
+        assert_that!(plane.crossing(&r)).is_equal_to(Some(3.0));
+    }
+
+    #[rstest]
+    fn a_ray_parallel_to_the_plane_never_crosses_it(plane: ClipPlane) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        );
+
+        assert_that!(plane.crossing(&r)).is_none();
+    }
+}
@@ -1,5 +1,30 @@
 use std::fmt::{Formatter, Display, Error};
-use std::ops::{Add, Sub, Mul};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Sub};
+
+// Rec. 709 primaries (and white point) to ACES AP1 primaries, adapted from D65 to D60 (Bradford)
+const REC709_TO_ACESCG: [[f32; 3]; 3] = [
+    [0.613_097_4, 0.339_523_2, 0.047_379_45],
+    [0.070_194_86, 0.916_355_2, 0.013_450_01],
+    [0.020_615_6, 0.109_569_86, 0.869_814_5],
+];
+
+/// The colour spaces that images can be saved in.
+///
+/// Colours are worked out in linear light with the Rec. 709 primaries that sRGB also uses, and
+/// converted to the chosen space on the way out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorSpace {
+    /// The sRGB curve, for viewing on ordinary displays.
+    #[default]
+    Srgb,
+    /// The Rec. 709 camera curve, for video.
+    Rec709,
+    /// No curve at all, for compositing in linear light.
+    Linear,
+    /// The linear ACES working space, with its wider primaries.
+    AcesCg,
+}
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color {
@@ -20,6 +45,159 @@ impl Color {
     pub fn white() -> Self {
         Self::new(1.0, 1.0, 1.0)
     }
+
+    /// Create a colour from 8-bit channel values, mapping 0..=255 onto 0.0..=1.0.
+    pub fn from_u8(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    }
+
+    /// Create a linear colour from 8-bit sRGB-encoded channel values.
+    pub fn from_srgb_u8(r: u8, g: u8, b: u8) -> Self {
+        Self::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+    }
+
+    /// Encode the (linear) colour as 8-bit sRGB channel values, clamping to the displayable range.
+    pub fn to_srgb_u8(self) -> (u8, u8, u8) {
+        self.to_u8_in(ColorSpace::Srgb)
+    }
+
+    /// Encode the (linear) colour as 8-bit channel values in `space`, clamping to 0.0..=1.0.
+    pub fn to_u8_in(self, space: ColorSpace) -> (u8, u8, u8) {
+        let encoded = space.encode(self);
+        let quantise = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        (quantise(encoded.r), quantise(encoded.g), quantise(encoded.b))
+    }
+
+    /// Parse a colour from a hex string of the form `#rrggbb`.
+    pub fn from_hex(hex: &str) -> Result<Self, ParseColorError> {
+        let digits = hex.strip_prefix('#').ok_or(ParseColorError)?;
+        // `from_str_radix` would take a sign, so check the digits first
+        if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseColorError);
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| ParseColorError);
+
+        Ok(Self::from_u8(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// Create a colour from hue (in degrees), saturation and value.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as usize {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+
+        Self::new(r + m, g + m, b + m)
+    }
+
+    /// The colour of a black body glowing at `kelvin`, scaled to a luminance of 1 so it only sets
+    /// the tint of a light. Candlelight is about 1900K, a household bulb 2700K, noon daylight
+    /// 5500K and an overcast sky 6500K or more. Temperatures are kept to 1667K..=25000K, the
+    /// range of Kang et al.'s fit to the Planckian locus that this uses.
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        let t = kelvin.clamp(1667.0, 25000.0) as f64;
+        let x = if t <= 4000.0 {
+            -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+        } else {
+            -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+        };
+        let y = if t <= 2222.0 {
+            -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+        } else if t <= 4000.0 {
+            -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+        } else {
+            3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+        };
+
+        // CIE XYZ with Y = 1, then into linear Rec. 709
+        let (cx, cy, cz) = (x / y, 1.0, (1.0 - x - y) / y);
+        let r = 3.2404542 * cx - 1.5371385 * cy - 0.4985314 * cz;
+        let g = -0.9692660 * cx + 1.8760108 * cy + 0.0415560 * cz;
+        let b = 0.0556434 * cx - 0.2040259 * cy + 1.0572252 * cz;
+        let color = Self::new(r as f32, g as f32, b as f32).clamp(0.0, f32::MAX);
+
+        color * (1.0 / color.luminance())
+    }
+
+    /// Linearly interpolate between `self` (at `t = 0`) and `other` (at `t = 1`).
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
+
+    /// The relative luminance of the colour, using the Rec. 709 weights.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn clamp(&self, min: f32, max: f32) -> Self {
+        Self::new(
+            self.r.clamp(min, max),
+            self.g.clamp(min, max),
+            self.b.clamp(min, max),
+        )
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let encoded = value as f32 / 255.0;
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn linear_to_rec709(linear: f32) -> f32 {
+    if linear < 0.018 {
+        linear * 4.5
+    } else {
+        1.099 * linear.powf(0.45) - 0.099
+    }
+}
+
+impl ColorSpace {
+    /// Convert a linear Rec. 709 colour into this space. Values outside 0.0..=1.0 are kept.
+    pub fn encode(self, color: Color) -> Color {
+        let curve = |f: fn(f32) -> f32| Color::new(f(color.r), f(color.g), f(color.b));
+        match self {
+            ColorSpace::Srgb => curve(linear_to_srgb),
+            ColorSpace::Rec709 => curve(linear_to_rec709),
+            ColorSpace::Linear => color,
+            ColorSpace::AcesCg => {
+                let m = REC709_TO_ACESCG;
+                let row = |i: usize| m[i][0] * color.r + m[i][1] * color.g + m[i][2] * color.b;
+                Color::new(row(0), row(1), row(2))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseColorError;
+
+impl std::error::Error for ParseColorError {}
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "colour must be given as a hex string of the form `#rrggbb`")
+    }
 }
 
 impl Add for Color {
@@ -30,6 +208,18 @@ impl Add for Color {
     }
 }
 
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for Color {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Color::black(), |acc, c| acc + c)
+    }
+}
+
 impl Sub for Color {
     type Output = Color;
 
@@ -54,6 +244,14 @@ impl Mul<f32> for Color {
     }
 }
 
+impl Div<f32> for Color {
+    type Output = Color;
+
+    fn div(self, divisor: f32) -> Self::Output {
+        Color::new(self.r / divisor, self.g / divisor, self.b / divisor)
+    }
+}
+
 impl Display for Color {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         let red = (self.r.clamp(0.0, 1.0) * 255.0).round() as u8;
@@ -63,6 +261,23 @@ impl Display for Color {
     }
 }
 
+/// As the array `[r, g, b]`, in linear light.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.r, self.g, self.b].serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [r, g, b] = <[f32; 3]>::deserialize(deserializer)?;
+
+        Ok(Self::new(r, g, b))
+    }
+}
+
 /* -------------------------------------------------------------------------------------------------
 Tests
 ------------------------------------------------------------------------------------------------- */
@@ -71,7 +286,9 @@ Tests
 mod tests {
     use spectral::assert_that;
     use spectral::numeric::FloatAssertions;
-    use crate::color::Color;
+    use spectral::prelude::*;
+    use crate::assert_approx_eq;
+    use crate::color::{Color, ColorSpace, ParseColorError};
 
     #[test]
     fn adding_colours() {
@@ -81,9 +298,7 @@ mod tests {
 
         let result = c1 + c2;
 
-        assert_that!(result.r).is_close_to(expected.r, 0.0001_f32);
-        assert_that!(result.g).is_close_to(expected.g, 0.0001_f32);
-        assert_that!(result.b).is_close_to(expected.b, 0.0001_f32);
+        assert_approx_eq!(result, expected, 0.0001);
     }
 
     #[test]
@@ -94,9 +309,7 @@ mod tests {
 
         let result = c1 - c2;
 
-        assert_that!(result.r).is_close_to(expected.r, 0.0001_f32);
-        assert_that!(result.g).is_close_to(expected.g, 0.0001_f32);
-        assert_that!(result.b).is_close_to(expected.b, 0.0001_f32);
+        assert_approx_eq!(result, expected, 0.0001);
     }
 
     #[test]
@@ -115,9 +328,7 @@ mod tests {
 
         let result = c1 * c2;
 
-        assert_that!(result.r).is_close_to(expected.r, 0.0001_f32);
-        assert_that!(result.g).is_close_to(expected.g, 0.0001_f32);
-        assert_that!(result.b).is_close_to(expected.b, 0.0001_f32);
+        assert_approx_eq!(result, expected, 0.0001);
     }
 
     #[test]
@@ -133,4 +344,220 @@ mod tests {
 
         assert_that(&c1.to_string()).is_equal_to(String::from("255 0 128"));
     }
+
+    #[test]
+    fn dividing_colours() {
+        let c = Color::new(0.4, 0.6, 0.8);
+
+        assert_that!(c / 2.0).is_equal_to(Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn add_assigning_colours() {
+        let mut c = Color::new(0.9, 0.6, 0.75);
+
+        c += Color::new(0.7, 0.1, 0.25);
+
+        assert_approx_eq!(c, Color::new(1.6, 0.7, 1.0), 0.0001);
+    }
+
+    #[test]
+    fn summing_colours() {
+        let colors = vec![
+            Color::new(0.1, 0.2, 0.3),
+            Color::new(0.2, 0.3, 0.4),
+            Color::new(0.3, 0.4, 0.5),
+        ];
+
+        let result: Color = colors.into_iter().sum();
+
+        assert_approx_eq!(result, Color::new(0.6, 0.9, 1.2), 0.0001);
+    }
+
+    #[test]
+    fn summing_no_colours_is_black() {
+        let result: Color = Vec::<Color>::new().into_iter().sum();
+
+        assert_that!(result).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn interpolating_between_colours() {
+        let c1 = Color::new(0.0, 0.5, 1.0);
+        let c2 = Color::new(1.0, 0.5, 0.0);
+
+        assert_approx_eq!(c1.lerp(&c2, 0.0), c1, 0.0001);
+        assert_approx_eq!(c1.lerp(&c2, 0.25), Color::new(0.25, 0.5, 0.75), 0.0001);
+        assert_approx_eq!(c1.lerp(&c2, 1.0), c2, 0.0001);
+    }
+
+    #[test]
+    fn the_luminance_of_white_is_one() {
+        assert_that!(Color::white().luminance()).is_close_to(1.0, 0.0001_f32);
+    }
+
+    #[test]
+    fn the_luminance_weights_green_most_heavily() {
+        let red = Color::new(1.0, 0.0, 0.0).luminance();
+        let green = Color::new(0.0, 1.0, 0.0).luminance();
+        let blue = Color::new(0.0, 0.0, 1.0).luminance();
+
+        assert_that!(green).is_greater_than(red);
+        assert_that!(red).is_greater_than(blue);
+    }
+
+    #[test]
+    fn clamping_colours() {
+        let c = Color::new(1.5, -0.5, 0.5);
+
+        assert_that!(c.clamp(0.0, 1.0)).is_equal_to(Color::new(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn creating_colours_from_u8() {
+        let c = Color::from_u8(255, 0, 51);
+
+        assert_approx_eq!(c, Color::new(1.0, 0.0, 0.2), 0.0001);
+    }
+
+    #[test]
+    fn parsing_colours_from_hex() {
+        let c = Color::from_hex("#ff0033");
+
+        assert_that!(c).is_ok();
+        assert_approx_eq!(c.unwrap(), Color::new(1.0, 0.0, 0.2), 0.0001);
+    }
+
+    #[test]
+    fn parsing_colours_from_hex_accepts_upper_case() {
+        let c = Color::from_hex("#FF8000").unwrap();
+
+        assert_that!(c).is_equal_to(Color::from_u8(255, 128, 0));
+    }
+
+    #[test]
+    fn parsing_invalid_hex_colours_is_an_error() {
+        assert_that!(Color::from_hex("ff0033")).is_err_containing(ParseColorError);
+        assert_that!(Color::from_hex("#ff003")).is_err_containing(ParseColorError);
+        assert_that!(Color::from_hex("#ff00zz")).is_err_containing(ParseColorError);
+        assert_that!(Color::from_hex("#ff00é")).is_err_containing(ParseColorError);
+        assert_that!(Color::from_hex("#+f+f+f")).is_err_containing(ParseColorError);
+    }
+
+    #[test]
+    fn creating_colours_from_hsv() {
+        assert_approx_eq!(Color::from_hsv(0.0, 1.0, 1.0), Color::new(1.0, 0.0, 0.0), 0.0001);
+        assert_approx_eq!(Color::from_hsv(120.0, 1.0, 1.0), Color::new(0.0, 1.0, 0.0), 0.0001);
+        assert_approx_eq!(Color::from_hsv(240.0, 1.0, 1.0), Color::new(0.0, 0.0, 1.0), 0.0001);
+        assert_approx_eq!(Color::from_hsv(60.0, 1.0, 0.5), Color::new(0.5, 0.5, 0.0), 0.0001);
+        assert_approx_eq!(Color::from_hsv(360.0, 1.0, 1.0), Color::new(1.0, 0.0, 0.0), 0.0001);
+    }
+
+    #[test]
+    fn hsv_colours_with_no_saturation_are_grey() {
+        assert_approx_eq!(Color::from_hsv(200.0, 0.0, 0.4), Color::new(0.4, 0.4, 0.4), 0.0001);
+    }
+
+    #[test]
+    fn warm_colour_temperatures_are_orange() {
+        let c = Color::from_kelvin(2700.0);
+
+        assert_that!(c.r).is_greater_than(c.g);
+        assert_that!(c.g).is_greater_than(c.b);
+    }
+
+    #[test]
+    fn cool_colour_temperatures_are_blue() {
+        let c = Color::from_kelvin(10000.0);
+
+        assert_that!(c.b).is_greater_than(c.r);
+    }
+
+    #[test]
+    fn daylight_is_nearly_white() {
+        assert_approx_eq!(Color::from_kelvin(6500.0), Color::white(), 0.05);
+    }
+
+    #[test]
+    fn colour_temperatures_have_unit_luminance() {
+        for kelvin in [1000.0, 1900.0, 2700.0, 5500.0, 6500.0, 10000.0, 40000.0] {
+            assert_that!(Color::from_kelvin(kelvin).luminance()).is_close_to(1.0, 0.0001);
+        }
+    }
+
+    #[test]
+    fn encoding_colours_as_srgb() {
+        assert_that!(Color::black().to_srgb_u8()).is_equal_to((0, 0, 0));
+        assert_that!(Color::white().to_srgb_u8()).is_equal_to((255, 255, 255));
+        assert_that!(Color::new(0.5, 0.2, 0.001).to_srgb_u8()).is_equal_to((188, 124, 3));
+    }
+
+    #[test]
+    fn encoding_colours_as_srgb_clamps_out_of_range_values() {
+        assert_that!(Color::new(1.5, -0.5, 0.0).to_srgb_u8()).is_equal_to((255, 0, 0));
+    }
+
+    #[test]
+    fn decoding_srgb_colours() {
+        assert_approx_eq!(
+            Color::from_srgb_u8(188, 124, 0),
+            Color::new(0.5029, 0.2016, 0.0),
+            0.0001
+        );
+        assert_approx_eq!(Color::from_srgb_u8(5, 255, 0), Color::new(0.0015, 1.0, 0.0), 0.0001);
+    }
+
+    #[test]
+    fn srgb_round_trips_every_value() {
+        for value in 0..=255 {
+            let c = Color::from_srgb_u8(value, value, value);
+
+            assert_that!(c.to_srgb_u8()).is_equal_to((value, value, value));
+        }
+    }
+
+    #[test]
+    fn encoding_colours_in_other_spaces() {
+        let grey = Color::new(0.5, 0.5, 0.5);
+
+        assert_that!(grey.to_u8_in(ColorSpace::Srgb)).is_equal_to(grey.to_srgb_u8());
+        assert_that!(grey.to_u8_in(ColorSpace::Linear)).is_equal_to((128, 128, 128));
+        assert_that!(grey.to_u8_in(ColorSpace::Rec709)).is_equal_to((180, 180, 180));
+    }
+
+    #[test]
+    fn the_rec709_curve_is_linear_near_black() {
+        let encoded = ColorSpace::Rec709.encode(Color::new(0.01, 0.016, 1.0));
+
+        assert_approx_eq!(encoded, Color::new(0.045, 0.072, 1.0), 0.0001);
+    }
+
+    #[test]
+    fn acescg_keeps_white_white_and_narrows_pure_colours() {
+        assert_approx_eq!(ColorSpace::AcesCg.encode(Color::white()), Color::white(), 0.0001);
+        assert_approx_eq!(
+            ColorSpace::AcesCg.encode(Color::new(1.0, 0.0, 0.0)),
+            Color::new(0.6131, 0.0702, 0.0206),
+            0.0001,
+        );
+    }
+
+    #[test]
+    fn encoding_keeps_values_out_of_range() {
+        assert_that!(ColorSpace::Linear.encode(Color::new(2.0, -1.0, 0.0)))
+            .is_equal_to(Color::new(2.0, -1.0, 0.0));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_from_an_array() {
+        use serde::de::value::{Error, SeqDeserializer};
+        use serde::Deserialize;
+
+        let c = Color::deserialize(SeqDeserializer::<_, Error>::new(
+            vec![0.5_f32, 0.25, 1.0].into_iter(),
+        ));
+
+        assert_that!(c.unwrap()).is_equal_to(Color::new(0.5, 0.25, 1.0));
+    }
 }
@@ -1 +1,33 @@
 pub const EPSILON: f32 = 1.0e-05_f32;
+
+/// Are `a` and `b` the same to within `EPSILON`? Beyond 1 the allowance grows with the size of
+/// the numbers, as f32 can't hold large values to five places.
+pub fn nearly_equal(a: f32, b: f32) -> bool {
+    (a - b).abs() < EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+// How many times a ray may be reflected or refracted before its contribution is dropped.
+pub const MAX_RECURSION_DEPTH: usize = 5;
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(1.0, 1.0 + EPSILON / 2.0, true)]
+    #[case(1.0, 1.0 + EPSILON * 2.0, false)]
+    #[case(0.0, -EPSILON / 2.0, true)]
+    #[case(100_000.0, 100_000.5, true)]
+    #[case(100_000.0, 100_002.0, false)]
+    fn comparing_numbers(#[case] a: f32, #[case] b: f32, #[case] expected: bool) {
+        assert_that!(nearly_equal(a, b)).is_equal_to(expected);
+        assert_that!(nearly_equal(b, a)).is_equal_to(expected);
+    }
+}
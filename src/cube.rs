@@ -0,0 +1,286 @@
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// An axis-aligned cube spanning -1 to 1 on every axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cube {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+}
+
+pub struct CubeBuilder {
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+}
+
+impl Shape for Cube {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Vector4::point(-1.0, -1.0, -1.0),
+            Vector4::point(1.0, 1.0, 1.0),
+        )
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let (xtmin, xtmax) = check_axis(ray.origin.x, ray.direction.x);
+        let (ytmin, ytmax) = check_axis(ray.origin.y, ray.direction.y);
+        let (ztmin, ztmax) = check_axis(ray.origin.z, ray.direction.z);
+
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+
+        if tmin > tmax {
+            return Vec::default();
+        }
+
+        vec![tmin, tmax]
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        let x = object_point.x.abs();
+        let y = object_point.y.abs();
+        let z = object_point.z.abs();
+
+        if x >= y && x >= z {
+            Vector4::vector(object_point.x, 0.0, 0.0)
+        } else if y >= z {
+            Vector4::vector(0.0, object_point.y, 0.0)
+        } else {
+            Vector4::vector(0.0, 0.0, object_point.z)
+        }
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        let clamped = Vector4::point(
+            object_point.x.clamp(-1.0, 1.0),
+            object_point.y.clamp(-1.0, 1.0),
+            object_point.z.clamp(-1.0, 1.0),
+        );
+        if clamped != object_point {
+            return clamped;
+        }
+
+        // Inside the cube, so push out through the nearest face
+        let mut closest = object_point;
+        let axis = (0..3)
+            .max_by(|&a, &b| object_point[a].abs().total_cmp(&object_point[b].abs()))
+            .unwrap();
+        closest[axis] = if object_point[axis] < 0.0 { -1.0 } else { 1.0 };
+
+        closest
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+/// Find where a ray crosses the pair of planes at -1 and 1 on one axis.
+fn check_axis(origin: f32, direction: f32) -> (f32, f32) {
+    let tmin_numerator = -1.0 - origin;
+    let tmax_numerator = 1.0 - origin;
+
+    let (tmin, tmax) = if direction.abs() >= EPSILON {
+        (tmin_numerator / direction, tmax_numerator / direction)
+    } else {
+        (
+            tmin_numerator * f32::INFINITY,
+            tmax_numerator * f32::INFINITY,
+        )
+    };
+
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+impl CubeBuilder {
+    pub fn new() -> Self {
+        Self {
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    pub fn build(self) -> impl Shape {
+        Cube {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Vector4::point(5.0, 0.5, 0.0), Vector4::vector(-1.0, 0.0, 0.0), 4.0, 6.0)] // +x
+    #[case(Vector4::point(-5.0, 0.5, 0.0), Vector4::vector(1.0, 0.0, 0.0), 4.0, 6.0)] // -x
+    #[case(Vector4::point(0.5, 5.0, 0.0), Vector4::vector(0.0, -1.0, 0.0), 4.0, 6.0)] // +y
+    #[case(Vector4::point(0.5, -5.0, 0.0), Vector4::vector(0.0, 1.0, 0.0), 4.0, 6.0)] // -y
+    #[case(Vector4::point(0.5, 0.0, 5.0), Vector4::vector(0.0, 0.0, -1.0), 4.0, 6.0)] // +z
+    #[case(Vector4::point(0.5, 0.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 4.0, 6.0)] // -z
+    #[case(Vector4::point(0.0, 0.5, 0.0), Vector4::vector(0.0, 0.0, 1.0), -1.0, 1.0)] // inside
+    fn a_ray_intersects_a_cube(
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+        #[case] t1: f32,
+        #[case] t2: f32,
+    ) {
+        let c = CubeBuilder::new().build();
+        let r = Ray::new(origin, direction);
+
+        let xs = c.local_intersect(&r);
+
+        assert_that!(xs).has_length(2);
+        assert_that!(xs[0]).is_equal_to(t1);
+        assert_that!(xs[1]).is_equal_to(t2);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(-2.0, 0.0, 0.0), Vector4::vector(0.2673, 0.5345, 0.8018))]
+    #[case(Vector4::point(0.0, -2.0, 0.0), Vector4::vector(0.8018, 0.2673, 0.5345))]
+    #[case(Vector4::point(0.0, 0.0, -2.0), Vector4::vector(0.5345, 0.8018, 0.2673))]
+    #[case(Vector4::point(2.0, 0.0, 2.0), Vector4::vector(0.0, 0.0, -1.0))]
+    #[case(Vector4::point(0.0, 2.0, 2.0), Vector4::vector(0.0, -1.0, 0.0))]
+    #[case(Vector4::point(2.0, 2.0, 0.0), Vector4::vector(-1.0, 0.0, 0.0))]
+    fn a_ray_misses_a_cube(#[case] origin: Vector4, #[case] direction: Vector4) {
+        let c = CubeBuilder::new().build();
+        let r = Ray::new(origin, direction);
+
+        let xs = c.local_intersect(&r);
+
+        assert_that!(xs).is_empty();
+    }
+
+    #[rstest]
+    #[case(Vector4::point(1.0, 0.5, -0.8), Vector4::vector(1.0, 0.0, 0.0))]
+    #[case(Vector4::point(-1.0, -0.2, 0.9), Vector4::vector(-1.0, 0.0, 0.0))]
+    #[case(Vector4::point(-0.4, 1.0, -0.1), Vector4::vector(0.0, 1.0, 0.0))]
+    #[case(Vector4::point(0.3, -1.0, -0.7), Vector4::vector(0.0, -1.0, 0.0))]
+    #[case(Vector4::point(-0.6, 0.3, 1.0), Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(0.4, 0.4, -1.0), Vector4::vector(0.0, 0.0, -1.0))]
+    #[case(Vector4::point(1.0, 1.0, 1.0), Vector4::vector(1.0, 0.0, 0.0))]
+    #[case(Vector4::point(-1.0, -1.0, -1.0), Vector4::vector(-1.0, 0.0, 0.0))]
+    fn the_normal_on_the_surface_of_a_cube(#[case] point: Vector4, #[case] expected: Vector4) {
+        let c = CubeBuilder::new().build();
+
+        assert_that!(c.local_normal_at(point)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn a_cube_has_a_bounding_box() {
+        let c = CubeBuilder::new().build();
+
+        let b = c.bounds();
+
+        assert_that!(b.min).is_equal_to(Vector4::point(-1.0, -1.0, -1.0));
+        assert_that!(b.max).is_equal_to(Vector4::point(1.0, 1.0, 1.0));
+    }
+
+    #[rstest]
+    #[case(Vector4::point(2.0, 2.0, 2.0), Vector4::point(1.0, 1.0, 1.0))]
+    #[case(Vector4::point(0.5, 3.0, 0.0), Vector4::point(0.5, 1.0, 0.0))]
+    #[case(Vector4::point(0.5, 0.9, 0.0), Vector4::point(0.5, 1.0, 0.0))]
+    #[case(Vector4::point(-0.8, 0.1, 0.2), Vector4::point(-1.0, 0.1, 0.2))]
+    fn the_closest_point_on_a_cube(#[case] point: Vector4, #[case] expected: Vector4) {
+        let c = CubeBuilder::new().build();
+
+        assert_that!(c.local_closest_point(point)).is_equal_to(expected);
+    }
+}
@@ -0,0 +1,512 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// The most times the curve is halved into straight pieces: 1024 of them.
+const MAX_DEPTH: u32 = 10;
+
+/// A tube swept along a cubic Bézier curve, its radius running evenly from one end to the other:
+/// a strand of hair, a blade of grass or a wire, which would take a great many triangles as a
+/// mesh.
+///
+/// The curve is cut into straight pieces, short enough that the tube strays from the curve by no
+/// more than a twentieth of its radius, and rays are tested against a tree of boxes round them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+    segments: Vec<Segment>,
+    // Note: the boxes round the segments are precomputed as an optimisation. They form a binary
+    // tree in heap order, with the children of box `i` at `2i + 1` and `2i + 2`, and the box
+    // round segment `j` at `segments.len() - 1 + j`.
+    boxes: Vec<BoundingBox>,
+}
+
+pub struct CurveBuilder {
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+    control: [Vector4; 4],
+    radii: (f32, f32),
+}
+
+/// A straight piece of the curve: a capsule between two points on it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Segment {
+    start: Vector4,
+    end: Vector4,
+    radius: f32,
+}
+
+impl Segment {
+    fn bounds(&self) -> BoundingBox {
+        let reach = Vector4::vector(self.radius, self.radius, self.radius);
+        let mut bounds = BoundingBox::empty();
+        for p in [self.start, self.end] {
+            bounds.add_point(p - reach);
+            bounds.add_point(p + reach);
+        }
+
+        bounds
+    }
+
+    /// The point on the line from `start` to `end` closest to `point`.
+    fn closest_on_axis(&self, point: Vector4) -> Vector4 {
+        let axis = self.end - self.start;
+        let length2 = axis.dot(&axis);
+        if length2 < EPSILON * EPSILON {
+            return self.start;
+        }
+
+        self.start + axis * ((point - self.start).dot(&axis) / length2).clamp(0.0, 1.0)
+    }
+
+    /// How far `point` is from the surface of the capsule, negative inside.
+    fn distance(&self, point: Vector4) -> f32 {
+        (point - self.closest_on_axis(point)).magnitude() - self.radius
+    }
+
+    /// Where the ray crosses the surface of the capsule: through its sides between the two
+    /// points, and through the balls round them beyond.
+    fn intersect(&self, ray: &Ray) -> Vec<f32> {
+        let axis = self.end - self.start;
+        let length2 = axis.dot(&axis);
+        let to_origin = ray.origin - self.start;
+        let along = |t: f32| (to_origin + ray.direction * t).dot(&axis);
+        let mut xs = Vec::with_capacity(2);
+
+        // The sides, as an infinite cylinder with the component along the axis taken out
+        let axis_direction = axis.dot(&ray.direction);
+        let axis_origin = axis.dot(&to_origin);
+        let a = length2 * ray.direction.dot(&ray.direction) - axis_direction.powi(2);
+        let b = length2 * ray.direction.dot(&to_origin) - axis_origin * axis_direction;
+        let c = length2 * (to_origin.dot(&to_origin) - self.radius.powi(2)) - axis_origin.powi(2);
+        let discriminant = b * b - a * c;
+        if a.abs() >= EPSILON && discriminant >= 0.0 {
+            for t in [
+                (-b - discriminant.sqrt()) / a,
+                (-b + discriminant.sqrt()) / a,
+            ] {
+                if (0.0..length2).contains(&along(t)) {
+                    xs.push(t);
+                }
+            }
+        }
+
+        // The balls at each end, each only beyond its end of the sides
+        let ends: &[(Vector4, bool)] = if length2 < EPSILON * EPSILON {
+            &[(self.start, true)]
+        } else {
+            &[(self.start, false), (self.end, true)]
+        };
+        for &(centre, is_end) in ends {
+            let to_origin = ray.origin - centre;
+            let a = ray.direction.dot(&ray.direction);
+            let b = ray.direction.dot(&to_origin);
+            let c = to_origin.dot(&to_origin) - self.radius.powi(2);
+            let discriminant = b * b - a * c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            for t in [
+                (-b - discriminant.sqrt()) / a,
+                (-b + discriminant.sqrt()) / a,
+            ] {
+                let beyond = if is_end {
+                    along(t) >= length2
+                } else {
+                    along(t) < 0.0
+                };
+                if beyond || length2 < EPSILON * EPSILON {
+                    xs.push(t);
+                }
+            }
+        }
+
+        xs
+    }
+}
+
+impl Curve {
+    /// The segment whose surface is closest to `object_point`.
+    fn nearest_segment(&self, object_point: Vector4) -> &Segment {
+        self.segments
+            .iter()
+            .min_by(|a, b| {
+                a.distance(object_point)
+                    .abs()
+                    .total_cmp(&b.distance(object_point).abs())
+            })
+            .unwrap()
+    }
+
+    /// Is the crossing at `point` of segment `index` one that its neighbours hide or see to? A
+    /// crossing inside either neighbour is hidden, and one on the surface of the segment before
+    /// is left to it, so that a ray through a joint only crosses there once.
+    fn claimed_by_neighbour(&self, index: usize, point: Vector4) -> bool {
+        let before = index
+            .checked_sub(1)
+            .is_some_and(|i| self.segments[i].distance(point) < EPSILON);
+        let after = self
+            .segments
+            .get(index + 1)
+            .is_some_and(|segment| segment.distance(point) < -EPSILON);
+
+        before || after
+    }
+}
+
+impl Shape for Curve {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.boxes[0]
+    }
+
+    fn memory_size(&self, _counted: &mut HashSet<*const ()>) -> usize {
+        mem::size_of_val(self)
+            + self.segments.capacity() * mem::size_of::<Segment>()
+            + self.boxes.capacity() * mem::size_of::<BoundingBox>()
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let first_segment = self.segments.len() - 1;
+        let mut xs = Vec::new();
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if self.boxes[node].span(ray).is_none() {
+                continue;
+            }
+            if node < first_segment {
+                stack.push(2 * node + 1);
+                stack.push(2 * node + 2);
+                continue;
+            }
+
+            // The capsules overlap where they meet, so only keep the crossings of their outsides
+            let index = node - first_segment;
+            xs.extend(
+                self.segments[index]
+                    .intersect(ray)
+                    .into_iter()
+                    .filter(|&t| !self.claimed_by_neighbour(index, ray.position(t))),
+            );
+        }
+        xs.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        xs
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        let segment = self.nearest_segment(object_point);
+
+        (object_point - segment.closest_on_axis(object_point)).normalize()
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        let segment = self.nearest_segment(object_point);
+        let on_axis = segment.closest_on_axis(object_point);
+        let offset = object_point - on_axis;
+        let outwards = if offset.magnitude() < EPSILON {
+            // On the axis itself, any way out is as close as any other
+            (segment.end - segment.start)
+                .normalize()
+                .orthonormal_basis()
+                .0
+        } else {
+            offset.normalize()
+        };
+
+        on_axis + outwards * segment.radius
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl CurveBuilder {
+    /// A curve from `p0` to `p3`, pulled towards `p1` and `p2` on the way, with a radius of 0.1.
+    pub fn new(p0: Vector4, p1: Vector4, p2: Vector4, p3: Vector4) -> Self {
+        Self {
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+            control: [p0, p1, p2, p3],
+            radii: (0.1, 0.1),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radii = (radius, radius);
+
+        self
+    }
+
+    /// A radius of `start` at `p0`, tapering, or swelling, evenly to `end` at `p3`.
+    pub fn with_radii(mut self, start: f32, end: f32) -> Self {
+        self.radii = (start, end);
+
+        self
+    }
+
+    pub fn build(self) -> Curve {
+        let depth = self.depth();
+        let count = 1 << depth;
+        let point_at = |t: f32| {
+            let s = 1.0 - t;
+            let [p0, p1, p2, p3] = self.control;
+            p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+        };
+        let radius_at = |t: f32| self.radii.0 + (self.radii.1 - self.radii.0) * t;
+
+        let segments: Vec<Segment> = (0..count)
+            .map(|i| {
+                let (t0, t1) = (i as f32 / count as f32, (i + 1) as f32 / count as f32);
+                Segment {
+                    start: point_at(t0),
+                    end: point_at(t1),
+                    radius: radius_at((t0 + t1) / 2.0),
+                }
+            })
+            .collect();
+
+        let mut boxes = vec![BoundingBox::empty(); 2 * count - 1];
+        for (i, segment) in segments.iter().enumerate() {
+            boxes[count - 1 + i] = segment.bounds();
+        }
+        for node in (0..count - 1).rev() {
+            let mut bounds = boxes[2 * node + 1];
+            bounds.merge(&boxes[2 * node + 2]);
+            boxes[node] = bounds;
+        }
+
+        Curve {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+            segments,
+            boxes,
+        }
+    }
+
+    /// How many times to halve the curve so that its straight pieces stray from the curve, and
+    /// their radii from the taper, by no more than a twentieth of the radius. The bend bound is
+    /// the one pbrt uses, from the curve's second differences.
+    fn depth(&self) -> u32 {
+        let tolerance = self.radii.0.max(self.radii.1) / 20.0;
+        if tolerance <= 0.0 {
+            return MAX_DEPTH;
+        }
+
+        let [p0, p1, p2, p3] = self.control;
+        let bend = (p0 - p1 * 2.0 + p2)
+            .magnitude()
+            .max((p1 - p2 * 2.0 + p3).magnitude());
+        let for_bend = ((6.0 * bend) / (8.0 * tolerance)).log2() / 2.0;
+        let for_taper = ((self.radii.1 - self.radii.0).abs() / (2.0 * tolerance)).log2();
+
+        for_bend.max(for_taper).ceil().clamp(0.0, MAX_DEPTH as f32) as u32
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    /// A U in the xy plane, 4 wide and about 3 tall, opening upwards.
+    #[fixture]
+    fn u_shape() -> Curve {
+        CurveBuilder::new(
+            Vector4::point(-2.0, 4.0, 0.0),
+            Vector4::point(-2.0, -2.0, 0.0),
+            Vector4::point(2.0, -2.0, 0.0),
+            Vector4::point(2.0, 4.0, 0.0),
+        )
+        .with_radius(0.25)
+        .build()
+    }
+
+    #[test]
+    fn a_straight_curve_is_a_single_capsule() {
+        let curve = CurveBuilder::new(
+            Vector4::point(-2.0, 0.0, 0.0),
+            Vector4::point(-2.0 / 3.0, 0.0, 0.0),
+            Vector4::point(2.0 / 3.0, 0.0, 0.0),
+            Vector4::point(2.0, 0.0, 0.0),
+        )
+        .with_radius(0.5)
+        .build();
+        let across = Ray::new(
+            Vector4::point(0.0, 5.0, 0.0),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+        let along = Ray::new(
+            Vector4::point(-5.0, 0.0, 0.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        );
+
+        assert_that!(curve.segments.len()).is_equal_to(1);
+        assert_that!(curve.local_intersect(&across)).is_equal_to(vec![4.5, 5.5]);
+        assert_that!(curve.local_intersect(&along)).is_equal_to(vec![2.5, 7.5]);
+    }
+
+    #[rstest]
+    fn a_bent_curve_is_cut_into_pieces(u_shape: Curve) {
+        assert_that!(u_shape.segments.len()).is_greater_than(8);
+        assert_that!(u_shape.bounds().min.x).is_close_to(-2.0 - 0.25, 0.0001);
+        assert_that!(u_shape.bounds().max.y).is_close_to(4.0 + 0.25, 0.0001);
+    }
+
+    #[rstest]
+    fn a_ray_across_a_curve_meets_the_tube_round_it(u_shape: Curve) {
+        // The bottom of the U, at t = 0.5, is at (0, -0.5, 0)
+        let r = Ray::new(
+            Vector4::point(0.0, -0.5, 5.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+        );
+
+        let xs = u_shape.local_intersect(&r);
+
+        assert_that!(xs.len()).is_equal_to(2);
+        assert_that!(xs[0]).is_close_to(4.75, 0.01);
+        assert_that!(u_shape.local_normal_at(r.position(xs[0])))
+            .is_equal_to(Vector4::vector(0.0, 0.0, 1.0));
+    }
+
+    #[rstest]
+    fn a_ray_through_both_arms_of_a_curve_only_meets_their_outsides(u_shape: Curve) {
+        let r = Ray::new(
+            Vector4::point(-5.0, 2.0, 0.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        );
+
+        assert_that!(u_shape.local_intersect(&r).len()).is_equal_to(4);
+    }
+
+    #[rstest]
+    fn a_ray_inside_the_bend_of_a_curve_misses_it(u_shape: Curve) {
+        let r = Ray::new(
+            Vector4::point(0.0, 2.0, 5.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+        );
+
+        assert_that!(u_shape.local_intersect(&r)).is_empty();
+    }
+
+    #[rstest]
+    #[case(-2.0, 0.2)]
+    #[case(0.0, 0.4)]
+    #[case(1.0, 0.5)]
+    fn a_curve_can_taper(#[case] x: f32, #[case] radius: f32) {
+        let curve = CurveBuilder::new(
+            Vector4::point(-2.0, 0.0, 0.0),
+            Vector4::point(-2.0 / 3.0, 0.0, 0.0),
+            Vector4::point(2.0 / 3.0, 0.0, 0.0),
+            Vector4::point(2.0, 0.0, 0.0),
+        )
+        .with_radii(0.2, 0.6)
+        .build();
+        let r = Ray::new(Vector4::point(x, 5.0, 0.0), Vector4::vector(0.0, -1.0, 0.0));
+
+        let xs = curve.local_intersect(&r);
+
+        // To within a twentieth of the largest radius
+        assert_that!(xs[0]).is_close_to(5.0 - radius, 0.03);
+    }
+
+    #[rstest]
+    fn the_closest_point_on_a_curve_is_on_its_surface(u_shape: Curve) {
+        let point = u_shape.local_closest_point(Vector4::point(0.0, -3.0, 0.0));
+
+        assert_that!(point).is_equal_to(Vector4::point(0.0, -0.75, 0.0));
+    }
+}
@@ -0,0 +1,412 @@
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// A cylinder of radius 1 around the y axis, optionally truncated and capped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cylinder {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+    minimum: f32,
+    maximum: f32,
+    closed: bool,
+}
+
+pub struct CylinderBuilder {
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+    minimum: f32,
+    maximum: f32,
+    closed: bool,
+}
+
+impl Cylinder {
+    /// Does the ray at `t` fall within the radius of a cap?
+    fn check_cap(ray: &Ray, t: f32) -> bool {
+        let x = ray.origin.x + t * ray.direction.x;
+        let z = ray.origin.z + t * ray.direction.z;
+
+        x.powi(2) + z.powi(2) <= 1.0 + EPSILON
+    }
+
+    fn intersect_caps(&self, ray: &Ray, xs: &mut Vec<f32>) {
+        if !self.closed || ray.direction.y.abs() < EPSILON {
+            return;
+        }
+
+        for y in [self.minimum, self.maximum] {
+            let t = (y - ray.origin.y) / ray.direction.y;
+            if Self::check_cap(ray, t) {
+                xs.push(t);
+            }
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Vector4::point(-1.0, self.minimum, -1.0),
+            Vector4::point(1.0, self.maximum, 1.0),
+        )
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let mut xs = Vec::default();
+
+        let a = ray.direction.x.powi(2) + ray.direction.z.powi(2);
+        // A ray parallel to the y axis can only hit the caps
+        if a.abs() >= EPSILON {
+            let b = 2.0 * ray.origin.x * ray.direction.x + 2.0 * ray.origin.z * ray.direction.z;
+            let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - 1.0;
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return xs;
+            }
+
+            let root_disc = discriminant.sqrt();
+            let mut t0 = (-b - root_disc) / (2.0 * a);
+            let mut t1 = (-b + root_disc) / (2.0 * a);
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            for t in [t0, t1] {
+                let y = ray.origin.y + t * ray.direction.y;
+                if self.minimum < y && y < self.maximum {
+                    xs.push(t);
+                }
+            }
+        }
+
+        self.intersect_caps(ray, &mut xs);
+        xs
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        let distance = object_point.x.powi(2) + object_point.z.powi(2);
+
+        if distance < 1.0 && object_point.y >= self.maximum - EPSILON {
+            Vector4::vector(0.0, 1.0, 0.0)
+        } else if distance < 1.0 && object_point.y <= self.minimum + EPSILON {
+            Vector4::vector(0.0, -1.0, 0.0)
+        } else {
+            Vector4::vector(object_point.x, 0.0, object_point.z)
+        }
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        let radius = (object_point.x.powi(2) + object_point.z.powi(2)).sqrt();
+        let y = object_point.y.clamp(self.minimum, self.maximum);
+        let (dx, dz) = if radius < EPSILON {
+            (1.0, 0.0)
+        } else {
+            (object_point.x / radius, object_point.z / radius)
+        };
+        let on_side = Vector4::point(dx, y, dz);
+
+        if !self.closed {
+            return on_side;
+        }
+
+        let nearest_cap = if object_point.y - self.minimum < self.maximum - object_point.y {
+            self.minimum
+        } else {
+            self.maximum
+        };
+        let cap_radius = radius.min(1.0);
+        let on_cap = Vector4::point(dx * cap_radius, nearest_cap, dz * cap_radius);
+
+        if (on_cap - object_point).magnitude() < (on_side - object_point).magnitude() {
+            on_cap
+        } else {
+            on_side
+        }
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl CylinderBuilder {
+    pub fn new() -> Self {
+        Self {
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+            minimum: f32::NEG_INFINITY,
+            maximum: f32::INFINITY,
+            closed: false,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    /// Truncate the cylinder below this y value (exclusive, in object space).
+    pub fn with_minimum(mut self, minimum: f32) -> Self {
+        self.minimum = minimum;
+
+        self
+    }
+
+    /// Truncate the cylinder above this y value (exclusive, in object space).
+    pub fn with_maximum(mut self, maximum: f32) -> Self {
+        self.maximum = maximum;
+
+        self
+    }
+
+    /// Cap the ends of a truncated cylinder.
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+
+        self
+    }
+
+    pub fn build(self) -> impl Shape {
+        Cylinder {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+            minimum: self.minimum,
+            maximum: self.maximum,
+            closed: self.closed,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Vector4::point(1.0, 0.0, 0.0), Vector4::vector(0.0, 1.0, 0.0))]
+    #[case(Vector4::point(0.0, 0.0, 0.0), Vector4::vector(0.0, 1.0, 0.0))]
+    #[case(Vector4::point(0.0, 0.0, -5.0), Vector4::vector(1.0, 1.0, 1.0))]
+    fn a_ray_misses_a_cylinder(#[case] origin: Vector4, #[case] direction: Vector4) {
+        let cyl = CylinderBuilder::new().build();
+        let r = Ray::new(origin, direction.normalize());
+
+        let xs = cyl.local_intersect(&r);
+
+        assert_that!(xs).is_empty();
+    }
+
+    #[rstest]
+    #[case(Vector4::point(1.0, 0.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 5.0, 5.0)]
+    #[case(Vector4::point(0.0, 0.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 4.0, 6.0)]
+    #[case(Vector4::point(0.5, 0.0, -5.0), Vector4::vector(0.1, 1.0, 1.0), 6.80798, 7.08872)]
+    fn a_ray_strikes_a_cylinder(
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+        #[case] t0: f32,
+        #[case] t1: f32,
+    ) {
+        let cyl = CylinderBuilder::new().build();
+        let r = Ray::new(origin, direction.normalize());
+
+        let xs = cyl.local_intersect(&r);
+
+        assert_that!(xs).has_length(2);
+        assert_that!(xs[0]).is_close_to(t0, 0.0001);
+        assert_that!(xs[1]).is_close_to(t1, 0.0001);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(1.0, 0.0, 0.0), Vector4::vector(1.0, 0.0, 0.0))]
+    #[case(Vector4::point(0.0, 5.0, -1.0), Vector4::vector(0.0, 0.0, -1.0))]
+    #[case(Vector4::point(0.0, -2.0, 1.0), Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(-1.0, 1.0, 0.0), Vector4::vector(-1.0, 0.0, 0.0))]
+    fn normal_vector_on_a_cylinder(#[case] point: Vector4, #[case] expected: Vector4) {
+        let cyl = CylinderBuilder::new().build();
+
+        assert_that!(cyl.local_normal_at(point)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 1.5, 0.0), Vector4::vector(0.1, 1.0, 0.0), 0)]
+    #[case(Vector4::point(0.0, 3.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 0)]
+    #[case(Vector4::point(0.0, 0.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 0)]
+    #[case(Vector4::point(0.0, 2.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 0)]
+    #[case(Vector4::point(0.0, 1.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 0)]
+    #[case(Vector4::point(0.0, 1.5, -2.0), Vector4::vector(0.0, 0.0, 1.0), 2)]
+    fn intersecting_a_constrained_cylinder(
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+        #[case] count: usize,
+    ) {
+        let cyl = CylinderBuilder::new()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .build();
+        let r = Ray::new(origin, direction.normalize());
+
+        let xs = cyl.local_intersect(&r);
+
+        assert_that!(xs).has_length(count);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 3.0, 0.0), Vector4::vector(0.0, -1.0, 0.0), 2)]
+    #[case(Vector4::point(0.0, 3.0, -2.0), Vector4::vector(0.0, -1.0, 2.0), 2)]
+    #[case(Vector4::point(0.0, 4.0, -2.0), Vector4::vector(0.0, -1.0, 1.0), 2)] // corner case
+    #[case(Vector4::point(0.0, 0.0, -2.0), Vector4::vector(0.0, 1.0, 2.0), 2)]
+    #[case(Vector4::point(0.0, -1.0, -2.0), Vector4::vector(0.0, 1.0, 1.0), 2)] // corner case
+    fn intersecting_the_caps_of_a_closed_cylinder(
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+        #[case] count: usize,
+    ) {
+        let cyl = CylinderBuilder::new()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .build();
+        let r = Ray::new(origin, direction.normalize());
+
+        let xs = cyl.local_intersect(&r);
+
+        assert_that!(xs).has_length(count);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 1.0, 0.0), Vector4::vector(0.0, -1.0, 0.0))]
+    #[case(Vector4::point(0.5, 1.0, 0.0), Vector4::vector(0.0, -1.0, 0.0))]
+    #[case(Vector4::point(0.0, 1.0, 0.5), Vector4::vector(0.0, -1.0, 0.0))]
+    #[case(Vector4::point(0.0, 2.0, 0.0), Vector4::vector(0.0, 1.0, 0.0))]
+    #[case(Vector4::point(0.5, 2.0, 0.0), Vector4::vector(0.0, 1.0, 0.0))]
+    #[case(Vector4::point(0.0, 2.0, 0.5), Vector4::vector(0.0, 1.0, 0.0))]
+    fn the_normal_vector_on_a_cylinders_end_caps(
+        #[case] point: Vector4,
+        #[case] expected: Vector4,
+    ) {
+        let cyl = CylinderBuilder::new()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .build();
+
+        assert_that!(cyl.local_normal_at(point)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn a_bounded_cylinder_has_a_bounding_box() {
+        let cyl = CylinderBuilder::new()
+            .with_minimum(-5.0)
+            .with_maximum(3.0)
+            .build();
+
+        let b = cyl.bounds();
+
+        assert_that!(b.min).is_equal_to(Vector4::point(-1.0, -5.0, -1.0));
+        assert_that!(b.max).is_equal_to(Vector4::point(1.0, 3.0, 1.0));
+    }
+
+    #[rstest]
+    #[case(false, Vector4::point(3.0, 5.0, 0.0), Vector4::point(1.0, 2.0, 0.0))]
+    #[case(false, Vector4::point(0.0, 1.8, 0.2), Vector4::point(0.0, 1.8, 1.0))]
+    #[case(true, Vector4::point(0.0, 1.8, 0.2), Vector4::point(0.0, 2.0, 0.2))]
+    #[case(true, Vector4::point(0.9, 1.5, 0.0), Vector4::point(1.0, 1.5, 0.0))]
+    #[case(true, Vector4::point(0.5, -3.0, 0.0), Vector4::point(0.5, 0.0, 0.0))]
+    fn the_closest_point_on_a_cylinder(
+        #[case] closed: bool,
+        #[case] point: Vector4,
+        #[case] expected: Vector4,
+    ) {
+        let cyl = CylinderBuilder::new()
+            .with_minimum(0.0)
+            .with_maximum(2.0)
+            .with_closed(closed)
+            .build();
+
+        assert_that!(cyl.local_closest_point(point)).is_equal_to(expected);
+    }
+}
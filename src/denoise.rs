@@ -0,0 +1,197 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+// The B3 spline, spread further apart on each pass of the à-trous ("with holes") filter
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// An edge-aware à-trous filter for smoothing out sampling noise.
+///
+/// Each pixel is blurred with its neighbours, but neighbours that differ much in colour, normal
+/// or depth count for less, so the edges of objects and shadows stay sharp. The larger a sigma,
+/// the bigger the difference it lets through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Denoiser {
+    /// Each pass doubles the spacing of the kernel, so the filter reaches 2^(passes + 1) pixels.
+    pub passes: usize,
+    pub color_sigma: f32,
+    pub normal_sigma: f32,
+    pub depth_sigma: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            passes: 4,
+            color_sigma: 0.5,
+            normal_sigma: 0.1,
+            depth_sigma: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Filter `image`, using `normals` and `depth` (such as the debug views of the same scene, at
+    /// the same size) to find the edges.
+    pub fn apply(&self, image: &Canvas, normals: &Canvas, depth: &Canvas) -> Canvas {
+        let mut current = Self::copy(image);
+        for pass in 0..self.passes {
+            // Halve the colour tolerance each pass as the noise is smoothed away
+            let color_sigma = self.color_sigma / (1 << pass) as f32;
+            current = self.pass(&current, normals, depth, 1 << pass, color_sigma);
+        }
+
+        current
+    }
+
+    fn pass(
+        &self,
+        image: &Canvas,
+        normals: &Canvas,
+        depth: &Canvas,
+        step: isize,
+        color_sigma: f32,
+    ) -> Canvas {
+        let (width, height) = (image.width() as isize, image.height() as isize);
+        let mut filtered = Canvas::new(image.width(), image.height());
+        for y in 0..height {
+            for x in 0..width {
+                let (px, py) = (x as usize, y as usize);
+                let color = image.pixel_at(px, py);
+                let normal = normals.pixel_at(px, py);
+                let distance = depth.pixel_at(px, py);
+
+                let mut sum = Color::black();
+                let mut total_weight = 0.0;
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx = x + (i as isize - 2) * step;
+                        let qy = y + (j as isize - 2) * step;
+                        if qx < 0 || qy < 0 || qx >= width || qy >= height {
+                            continue;
+                        }
+                        let (qx, qy) = (qx as usize, qy as usize);
+                        let neighbour = image.pixel_at(qx, qy);
+
+                        let weight = kx
+                            * ky
+                            * Self::edge_stop(color, neighbour, color_sigma)
+                            * Self::edge_stop(normal, normals.pixel_at(qx, qy), self.normal_sigma)
+                            * Self::edge_stop(distance, depth.pixel_at(qx, qy), self.depth_sigma);
+                        sum += neighbour * weight;
+                        total_weight += weight;
+                    }
+                }
+
+                // The pixel itself always has some weight, so this never divides by zero
+                filtered.write_pixel(px, py, &(sum / total_weight));
+            }
+        }
+
+        filtered
+    }
+
+    /// How much a neighbour counts, from 1.0 when it is the same as the pixel towards 0.0.
+    fn edge_stop(a: Color, b: Color, sigma: f32) -> f32 {
+        let difference = a - b;
+        let distance2 = difference.r.powi(2) + difference.g.powi(2) + difference.b.powi(2);
+
+        (-distance2 / (sigma * sigma).max(f32::EPSILON)).exp()
+    }
+
+    fn copy(image: &Canvas) -> Canvas {
+        let mut copy = Canvas::new(image.width(), image.height());
+        copy.blit(image, 0, 0);
+
+        copy
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::rng::Rng;
+
+    use super::*;
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        canvas.fill(&color);
+
+        canvas
+    }
+
+    fn noisy(width: usize, height: usize, color: Color) -> Canvas {
+        let mut rng = Rng::new(7);
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let noise = (rng.next_f32() - 0.5) * 0.4;
+                canvas.write_pixel(x, y, &(color + Color::white() * noise));
+            }
+        }
+
+        canvas
+    }
+
+    /// A canvas with `left` on its left half and `right` on its right half.
+    fn split(width: usize, height: usize, left: Color, right: Color) -> Canvas {
+        let mut canvas = filled(width, height, left);
+        canvas.blit(&filled(width / 2, height, right), (width / 2) as isize, 0);
+
+        canvas
+    }
+
+    fn mean_error(image: &Canvas, expected: Color) -> f32 {
+        let mut error = 0.0;
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                error += (image.pixel_at(x, y).r - expected.r).abs();
+            }
+        }
+
+        error / (image.width() * image.height()) as f32
+    }
+
+    #[test]
+    fn denoising_a_flat_image_reduces_the_noise() {
+        let grey = Color::white() * 0.5;
+        let image = noisy(16, 16, grey);
+        let normals = filled(16, 16, Color::new(0.5, 0.5, 0.0));
+        let depth = filled(16, 16, Color::white());
+
+        let denoised = Denoiser::default().apply(&image, &normals, &depth);
+
+        assert_that!(mean_error(&denoised, grey)).is_less_than(mean_error(&image, grey) / 3.0);
+    }
+
+    #[test]
+    fn denoising_keeps_edges_between_surfaces() {
+        let image = split(16, 16, Color::black(), Color::white());
+        let normals = split(16, 16, Color::new(0.5, 0.5, 0.0), Color::new(1.0, 0.5, 0.5));
+        let depth = filled(16, 16, Color::white());
+
+        let denoised = Denoiser::default().apply(&image, &normals, &depth);
+
+        assert_that!(denoised.pixel_at(7, 8).r).is_less_than(0.01);
+        assert_that!(denoised.pixel_at(8, 8).r).is_greater_than(0.99);
+    }
+
+    #[test]
+    fn no_passes_leaves_the_image_alone() {
+        let image = noisy(4, 4, Color::white() * 0.5);
+        let guide = filled(4, 4, Color::white());
+        let denoiser = Denoiser {
+            passes: 0,
+            ..Denoiser::default()
+        };
+
+        let denoised = denoiser.apply(&image, &guide, &guide);
+
+        assert_that!(denoised.pixel_at(1, 2)).is_equal_to(image.pixel_at(1, 2));
+    }
+}
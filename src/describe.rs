@@ -0,0 +1,160 @@
+use std::rc::Rc;
+
+use crate::shape::Shape;
+use crate::transform::Transform;
+use crate::world::World;
+
+/// A plain text account of everything in the world, one thing to a line: the lights, then the
+/// objects with their transforms taken apart and their materials, and the contents of groups
+/// indented under them.
+pub fn describe(world: &World) -> String {
+    let mut lines = Vec::new();
+    for (index, light) in world.lights().iter().enumerate() {
+        let p = light.position;
+        lines.push(format!(
+            "light {}: position ({:.2}, {:.2}, {:.2}), intensity {}",
+            index, p.x, p.y, p.z, light.intensity
+        ));
+    }
+    for object in world.objects() {
+        describe_shape(object, 0, &mut lines);
+    }
+
+    lines.join("\n")
+}
+
+fn describe_shape(shape: &Rc<dyn Shape>, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    let name = shape.name().unwrap_or("(unnamed)");
+    let transform = shape.transformation().decompose();
+    let children = shape.children();
+    // Empty groups are the only shapes with no bounds at all
+    if !children.is_empty() || shape.bounds().is_empty() {
+        lines.push(format!("{}group {}: {}", indent, name, transform));
+        for child in children {
+            describe_shape(child, depth + 1, lines);
+        }
+    } else {
+        lines.push(format!("{}{}: {}", indent, name, transform));
+        lines.push(format!("{}  material {}", indent, shape.material()));
+    }
+}
+
+/// The lines of the two worlds' descriptions that differ, with `-` in front of those only in
+/// `before` and `+` in front of those only in `after`, in the order they come.
+pub fn diff(before: &World, after: &World) -> Vec<String> {
+    let (before, after) = (describe(before), describe(after));
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+
+    // The length of the longest common run of lines from each point onwards
+    let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            changes.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::group::GroupBuilder;
+    use crate::matrix::Matrix;
+    use crate::sphere::SphereBuilder;
+
+    #[fixture]
+    fn world() -> World {
+        let mut world = World::default_world();
+        world.add_null("pivot");
+        world.add_object(Rc::new(
+            GroupBuilder::new()
+                .with_name("pair")
+                .with_child(Rc::new(SphereBuilder::new().with_name("ball").build()))
+                .build(),
+        ));
+
+        world
+    }
+
+    #[rstest]
+    fn describing_a_world(world: World) {
+        let description = describe(&world);
+        let lines: Vec<&str> = description.lines().collect();
+
+        assert_that!(lines.len()).is_equal_to(9);
+        assert_that!(lines[0])
+            .is_equal_to("light 0: position (-10.00, 10.00, -10.00), intensity 255 255 255");
+        assert_that!(lines[1]).starts_with("(unnamed): translate (0.00, 0.00, 0.00)");
+        assert_that!(lines[2]).starts_with("  material (SolidPattern 204 255 153)");
+        assert_that!(lines[3]).ends_with("scale (0.50, 0.50, 0.50)");
+        assert_that!(lines[5]).starts_with("group pivot: ");
+        assert_that!(lines[6]).starts_with("group pair: ");
+        assert_that!(lines[7]).starts_with("  ball: ");
+        assert_that!(lines[8]).starts_with("    material ");
+    }
+
+    #[rstest]
+    fn the_same_world_has_no_differences(world: World) {
+        assert_that!(diff(&world, &world).is_empty()).is_true();
+    }
+
+    #[rstest]
+    fn differences_show_what_changed(world: World) {
+        let mut moved = World::default_world();
+        moved.add_null("pivot");
+        moved.add_object(Rc::new(
+            GroupBuilder::new()
+                .with_name("pair")
+                .with_child(Rc::new(
+                    SphereBuilder::new()
+                        .with_name("ball")
+                        .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+                        .build(),
+                ))
+                .build(),
+        ));
+
+        let changes = diff(&world, &moved);
+
+        assert_that!(changes.len()).is_equal_to(2);
+        assert_that!(changes[0]).starts_with("-   ball: translate (0.00, 0.00, 0.00)");
+        assert_that!(changes[1]).starts_with("+   ball: translate (0.00, 1.00, 0.00)");
+    }
+
+    #[rstest]
+    fn differences_show_what_was_added(world: World) {
+        let changes = diff(&World::default_world(), &world);
+
+        assert_that!(changes[0]).starts_with("+ group pivot");
+        assert_that!(changes.iter().all(|c| c.starts_with("+ "))).is_true();
+    }
+}
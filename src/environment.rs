@@ -0,0 +1,494 @@
+use std::f32::consts::PI;
+use std::fmt::Debug;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::pattern::{Pattern, TexturePattern};
+use crate::vector4::Vector4;
+
+/// Light arriving from infinitely far away, seen behind everything in the scene and lighting it
+/// from all around.
+pub trait Environment: Debug {
+    /// The colour seen looking along `direction`, which need not be normalised.
+    fn color_in_direction(&self, direction: Vector4) -> Color;
+
+    /// The bytes of image data the environment holds, for environments made from images.
+    fn texture_memory(&self) -> usize {
+        0
+    }
+}
+
+/// Which face of a cube map a direction points at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CubeFace {
+    Right,
+    Left,
+    Up,
+    Down,
+    Front,
+    Back,
+}
+
+/// An environment made of six square images on the faces of a cube around the scene, each as
+/// seen from the centre with +y up (+z up for the bottom face, -z up for the top).
+#[derive(Debug)]
+pub struct CubeMap {
+    // Note: in the order of `CubeFace`
+    faces: [TexturePattern; 6],
+}
+
+/// An environment made of one image covering every direction, longitude across and latitude up,
+/// with +z in the middle of the image.
+#[derive(Debug)]
+pub struct EquirectangularMap {
+    image: TexturePattern,
+}
+
+impl CubeFace {
+    pub fn of(direction: Vector4) -> Self {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let largest = x.abs().max(y.abs()).max(z.abs());
+        if largest == x {
+            CubeFace::Right
+        } else if largest == -x {
+            CubeFace::Left
+        } else if largest == y {
+            CubeFace::Up
+        } else if largest == -y {
+            CubeFace::Down
+        } else if largest == z {
+            CubeFace::Front
+        } else {
+            CubeFace::Back
+        }
+    }
+}
+
+impl CubeMap {
+    /// Faces in the order right (+x), left, up (+y), down, front (+z), back.
+    pub fn new(faces: [Canvas; 6]) -> Self {
+        Self {
+            faces: faces.map(TexturePattern::new),
+        }
+    }
+
+    /// The face `direction` points at and where on it, each of u and v in [0, 1].
+    pub fn face_uv(direction: Vector4) -> (CubeFace, f32, f32) {
+        let face = CubeFace::of(direction);
+        // Project onto the face, so the other coordinates are in [-1, 1]
+        let largest = direction
+            .x
+            .abs()
+            .max(direction.y.abs())
+            .max(direction.z.abs());
+        let (x, y, z) = (
+            direction.x / largest,
+            direction.y / largest,
+            direction.z / largest,
+        );
+        let (u, v) = match face {
+            CubeFace::Right => (1.0 - z, y + 1.0),
+            CubeFace::Left => (z + 1.0, y + 1.0),
+            CubeFace::Up => (x + 1.0, 1.0 - z),
+            CubeFace::Down => (x + 1.0, z + 1.0),
+            CubeFace::Front => (x + 1.0, y + 1.0),
+            CubeFace::Back => (1.0 - x, y + 1.0),
+        };
+
+        (face, u / 2.0, v / 2.0)
+    }
+
+    /// The direction that points at `(u, v)` on `face`, undoing `face_uv`. It reaches 1 along
+    /// the face's axis, so isn't normalised.
+    pub fn direction(face: CubeFace, u: f32, v: f32) -> Vector4 {
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        match face {
+            CubeFace::Right => Vector4::vector(1.0, b, -a),
+            CubeFace::Left => Vector4::vector(-1.0, b, a),
+            CubeFace::Up => Vector4::vector(a, 1.0, -b),
+            CubeFace::Down => Vector4::vector(a, -1.0, b),
+            CubeFace::Front => Vector4::vector(a, b, 1.0),
+            CubeFace::Back => Vector4::vector(-a, b, -1.0),
+        }
+    }
+}
+
+impl Environment for CubeMap {
+    fn color_in_direction(&self, direction: Vector4) -> Color {
+        let (face, u, v) = Self::face_uv(direction);
+        // Keep the far edges on the face rather than wrapping back to the near ones
+        let below_one = 1.0 - f32::EPSILON;
+
+        self.faces[face as usize].color_at_uv(u.min(below_one), v.min(below_one))
+    }
+
+    fn texture_memory(&self) -> usize {
+        self.faces.iter().map(Pattern::texture_memory).sum()
+    }
+}
+
+impl EquirectangularMap {
+    pub fn new(image: Canvas) -> Self {
+        Self {
+            image: TexturePattern::new(image),
+        }
+    }
+}
+
+impl Environment for EquirectangularMap {
+    fn color_in_direction(&self, direction: Vector4) -> Color {
+        let d = direction.normalize();
+        let u = 0.5 + d.x.atan2(d.z) / (2.0 * PI);
+        let v = 0.5 + d.y.clamp(-1.0, 1.0).asin() / PI;
+
+        self.image.color_at_uv(u, v.min(1.0 - f32::EPSILON))
+    }
+
+    fn texture_memory(&self) -> usize {
+        self.image.texture_memory()
+    }
+}
+
+/// A clear daytime sky after Preetham, Shirley and Smits' analytic model, with a disc for the sun.
+///
+/// Brightness is relative to the sky straight overhead, which comes out at `intensity`. Below the
+/// horizon the sky is continued from the horizon.
+#[derive(Clone, Debug)]
+pub struct Sky {
+    sun_direction: Vector4,
+    intensity: f32,
+    sun_radius: f32,
+    sun_intensity: f32,
+    // Note: the rest is worked out from the settings above as an optimisation.
+    zenith: (f32, f32, f32), // Y, x and y at the zenith
+    perez: [[f32; 5]; 3],    // A to E for each of Y, x and y
+    perez_zenith: [f32; 3],  // the Perez function at the zenith for each of Y, x and y
+}
+
+pub struct SkyBuilder {
+    sun_direction: Vector4,
+    turbidity: f32,
+    intensity: f32,
+    sun_radius: f32,
+    sun_intensity: f32,
+}
+
+impl Sky {
+    fn perez(coefficients: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = *coefficients;
+        (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    fn sky_color(&self, direction: Vector4) -> Color {
+        // Keep just above the horizon, where the model breaks down
+        let cos_theta = direction.y.max(0.001);
+        let gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            let zenith = [self.zenith.0, self.zenith.1, self.zenith.2][i];
+            zenith * Self::perez(&self.perez[i], cos_theta, gamma) / self.perez_zenith[i]
+        });
+        let luminance = luminance / self.zenith.0 * self.intensity;
+
+        // From xyY to CIE XYZ, then to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        Color::new(
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+        )
+    }
+}
+
+impl Environment for Sky {
+    fn color_in_direction(&self, direction: Vector4) -> Color {
+        let direction = direction.normalize();
+        let sky = self.sky_color(direction);
+        let sun_angle = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+        if sun_angle < self.sun_radius {
+            return sky + Color::white() * self.sun_intensity;
+        }
+
+        sky
+    }
+}
+
+impl SkyBuilder {
+    pub fn new() -> Self {
+        Self {
+            sun_direction: Vector4::vector(0.0, 1.0, 0.0),
+            turbidity: 3.0,
+            intensity: 1.0,
+            sun_radius: 0.02,
+            sun_intensity: 20.0,
+        }
+    }
+
+    /// The direction towards the sun. The model only covers a sun above the horizon, so a lower
+    /// sun is treated as being on it.
+    pub fn with_sun_direction(mut self, sun_direction: Vector4) -> Self {
+        self.sun_direction = sun_direction;
+
+        self
+    }
+
+    /// How hazy the air is, from 2 (very clear) to about 10 (hazy).
+    pub fn with_turbidity(mut self, turbidity: f32) -> Self {
+        self.turbidity = turbidity;
+
+        self
+    }
+
+    /// The brightness of the sky straight overhead.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+
+        self
+    }
+
+    /// The angular radius of the sun's disc, in radians, and how bright it is.
+    pub fn with_sun(mut self, radius: f32, intensity: f32) -> Self {
+        self.sun_radius = radius;
+        self.sun_intensity = intensity;
+
+        self
+    }
+
+    pub fn build(self) -> Sky {
+        let mut sun_direction = self.sun_direction.normalize();
+        sun_direction.y = sun_direction.y.max(0.0);
+        let sun_direction = sun_direction.normalize();
+        let t = self.turbidity;
+        let theta_s = sun_direction.y.clamp(-1.0, 1.0).acos();
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |c: [[f32; 4]; 3]| {
+            let cubic = |k: [f32; 4]| {
+                k[0] * theta_s.powi(3) + k[1] * theta_s.powi(2) + k[2] * theta_s + k[3]
+            };
+            t * t * cubic(c[0]) + t * cubic(c[1]) + cubic(c[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let perez_zenith = perez.map(|coefficients| Sky::perez(&coefficients, 1.0, theta_s));
+
+        Sky {
+            sun_direction,
+            intensity: self.intensity,
+            sun_radius: self.sun_radius,
+            sun_intensity: self.sun_intensity,
+            zenith: (zenith_luminance, zenith_x, zenith_y),
+            perez,
+            perez_zenith,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn filled(color: Color) -> Canvas {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.fill(&color);
+        canvas
+    }
+
+    #[rstest]
+    #[case(Vector4::vector(-1.0, 0.5, -0.25), CubeFace::Left)]
+    #[case(Vector4::vector(1.1, -0.75, 0.8), CubeFace::Right)]
+    #[case(Vector4::vector(0.1, 0.6, 0.9), CubeFace::Front)]
+    #[case(Vector4::vector(-0.7, 0.0, -2.0), CubeFace::Back)]
+    #[case(Vector4::vector(0.5, 1.0, 0.9), CubeFace::Up)]
+    #[case(Vector4::vector(-0.2, -1.3, 1.1), CubeFace::Down)]
+    fn the_face_of_a_cube_a_direction_points_at(
+        #[case] direction: Vector4,
+        #[case] expected: CubeFace,
+    ) {
+        assert_that!(CubeFace::of(direction)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(Vector4::vector(-0.5, 0.5, 1.0), (0.25, 0.75))]
+    #[case(Vector4::vector(0.5, -0.5, 1.0), (0.75, 0.25))]
+    #[case(Vector4::vector(0.5, 0.5, -1.0), (0.25, 0.75))]
+    #[case(Vector4::vector(-1.0, 0.5, -0.5), (0.25, 0.75))]
+    #[case(Vector4::vector(1.0, 0.5, 0.5), (0.25, 0.75))]
+    #[case(Vector4::vector(-0.5, 1.0, -0.5), (0.25, 0.75))]
+    #[case(Vector4::vector(-0.5, -1.0, 0.5), (0.25, 0.75))]
+    fn where_a_direction_points_on_a_face(
+        #[case] direction: Vector4,
+        #[case] expected: (f32, f32),
+    ) {
+        let (_, u, v) = CubeMap::face_uv(direction);
+
+        assert_that!((u, v)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(Vector4::vector(1.0, 0.5, 0.5))]
+    #[case(Vector4::vector(-1.0, -0.2, 0.7))]
+    #[case(Vector4::vector(0.3, 1.0, -0.5))]
+    #[case(Vector4::vector(-0.5, -1.0, 0.5))]
+    #[case(Vector4::vector(0.1, 0.9, 1.0))]
+    #[case(Vector4::vector(0.6, -0.4, -1.0))]
+    fn the_direction_to_a_point_on_a_face(#[case] direction: Vector4) {
+        let (face, u, v) = CubeMap::face_uv(direction);
+
+        assert_approx_eq!(CubeMap::direction(face, u, v), direction, 0.0001);
+    }
+
+    #[test]
+    fn a_cube_map_shows_the_face_in_each_direction() {
+        let colors = [
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 0.0),
+            Color::new(0.0, 1.0, 1.0),
+            Color::new(1.0, 0.0, 1.0),
+        ];
+        let map = CubeMap::new(colors.map(filled));
+
+        assert_that!(map.color_in_direction(Vector4::vector(2.0, 0.0, 0.0))).is_equal_to(colors[0]);
+        assert_that!(map.color_in_direction(Vector4::vector(0.0, -1.0, 0.0)))
+            .is_equal_to(colors[3]);
+        assert_that!(map.color_in_direction(Vector4::vector(0.0, 0.0, -1.0)))
+            .is_equal_to(colors[5]);
+        // Exactly on an edge still finds a face
+        assert_that!(map.color_in_direction(Vector4::vector(1.0, 1.0, 1.0))).is_equal_to(colors[0]);
+    }
+
+    #[test]
+    fn an_equirectangular_map_puts_latitude_up_the_image() {
+        // White on the top row, black on the bottom
+        let mut image = Canvas::new(4, 2);
+        for x in 0..4 {
+            image.write_pixel(x, 0, &Color::white());
+        }
+        let map = EquirectangularMap::new(image);
+
+        assert_that!(map.color_in_direction(Vector4::vector(0.0, 1.0, 0.0)))
+            .is_equal_to(Color::white());
+        assert_that!(map.color_in_direction(Vector4::vector(1.0, 0.2, -1.0)))
+            .is_equal_to(Color::white());
+        assert_that!(map.color_in_direction(Vector4::vector(0.0, -1.0, 0.0)))
+            .is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn an_equirectangular_map_puts_longitude_across_the_image() {
+        let mut image = Canvas::new(4, 1);
+        image.write_pixel(2, 0, &Color::white());
+        let map = EquirectangularMap::new(image);
+
+        // +z looks at the middle of the image, +x a quarter turn to the right
+        assert_that!(map.color_in_direction(Vector4::vector(0.1, 0.0, 1.0)))
+            .is_equal_to(Color::white());
+        assert_that!(map.color_in_direction(Vector4::vector(-0.1, 0.0, 1.0)))
+            .is_equal_to(Color::black());
+        assert_that!(map.color_in_direction(Vector4::vector(1.0, 0.0, -0.1)))
+            .is_equal_to(Color::black());
+    }
+
+    fn sky(sun_direction: Vector4, turbidity: f32) -> Sky {
+        SkyBuilder::new()
+            .with_sun_direction(sun_direction)
+            .with_turbidity(turbidity)
+            .build()
+    }
+
+    #[test]
+    fn the_sky_overhead_is_blue() {
+        let s = sky(Vector4::vector(1.0, 1.0, 0.0), 3.0);
+
+        let c = s.color_in_direction(Vector4::vector(0.0, 1.0, 0.0));
+
+        assert_that!(c.b).is_greater_than(c.g);
+        assert_that!(c.g).is_greater_than(c.r);
+        assert_that!(0.299 * c.r + 0.587 * c.g + 0.114 * c.b).is_close_to(1.0, 0.05);
+    }
+
+    #[test]
+    fn the_sky_is_brighter_near_the_sun() {
+        let s = sky(Vector4::vector(1.0, 0.5, 0.0), 3.0);
+
+        let near = s.color_in_direction(Vector4::vector(1.0, 0.6, 0.2));
+        let far = s.color_in_direction(Vector4::vector(-1.0, 0.6, -0.2));
+
+        assert_that!(near.r + near.g + near.b).is_greater_than(far.r + far.g + far.b);
+    }
+
+    #[test]
+    fn looking_at_the_sun_sees_its_disc() {
+        let s = sky(Vector4::vector(0.0, 1.0, 1.0), 3.0);
+
+        let sun = s.color_in_direction(Vector4::vector(0.0, 2.0, 2.0));
+        let beside = s.color_in_direction(Vector4::vector(0.0, 1.0, 1.1));
+
+        assert_that!(sun.r - beside.r).is_greater_than(19.0);
+    }
+
+    #[test]
+    fn a_hazier_sky_is_less_blue() {
+        let up = Vector4::vector(0.0, 0.5, 1.0);
+        let clear = sky(Vector4::vector(1.0, 1.0, 0.0), 2.0).color_in_direction(up);
+        let hazy = sky(Vector4::vector(1.0, 1.0, 0.0), 8.0).color_in_direction(up);
+
+        assert_that!(hazy.b / hazy.r).is_less_than(clear.b / clear.r);
+    }
+
+    #[rstest]
+    #[case(Vector4::vector(0.0, -1.0, 0.0))]
+    #[case(Vector4::vector(1.0, 0.0, 0.0))]
+    fn the_sky_is_finite_at_and_below_the_horizon(#[case] direction: Vector4) {
+        let c = sky(Vector4::vector(0.0, 0.2, 1.0), 3.0).color_in_direction(direction);
+
+        for channel in [c.r, c.g, c.b] {
+            assert_that!(channel.is_finite()).is_true();
+            assert_that!(channel).is_greater_than_or_equal_to(0.0);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::{Error, Write};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+// Single-part scanline image, no flags set
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+/// The values of one channel of an image, a row at a time from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Samples {
+    Uint(Vec<u32>),
+    Float(Vec<f32>),
+}
+
+/// A named layer of an OpenEXR image, such as `R` or `Z`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    pub samples: Samples,
+}
+
+impl Samples {
+    fn len(&self) -> usize {
+        match self {
+            Samples::Uint(values) => values.len(),
+            Samples::Float(values) => values.len(),
+        }
+    }
+
+    fn pixel_type(&self) -> i32 {
+        match self {
+            Samples::Uint(_) => 0,
+            Samples::Float(_) => 2,
+        }
+    }
+
+    fn write_row(&self, row: usize, width: usize, out: &mut Vec<u8>) {
+        let range = row * width..(row + 1) * width;
+        match self {
+            Samples::Uint(values) => values[range]
+                .iter()
+                .for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+            Samples::Float(values) => values[range]
+                .iter()
+                .for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+        }
+    }
+}
+
+impl Channel {
+    pub fn float(name: &str, values: Vec<f32>) -> Self {
+        Self {
+            name: name.to_string(),
+            samples: Samples::Float(values),
+        }
+    }
+
+    pub fn uint(name: &str, values: Vec<u32>) -> Self {
+        Self {
+            name: name.to_string(),
+            samples: Samples::Uint(values),
+        }
+    }
+}
+
+/// Write `channels` as an uncompressed, single-part OpenEXR image with full 32-bit samples.
+pub fn write(
+    file: &mut impl Write,
+    width: usize,
+    height: usize,
+    mut channels: Vec<Channel>,
+) -> Result<(), Error> {
+    for channel in &channels {
+        assert_eq!(
+            channel.samples.len(),
+            width * height,
+            "Channel {} doesn't match the image size",
+            channel.name
+        );
+    }
+    // Readers expect the channels in alphabetical order
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut header = vec![];
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+
+    let mut channel_list = vec![];
+    for channel in &channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&channel.samples.pixel_type().to_le_bytes());
+        // Not perceptually linear, three reserved bytes, then x and y sampling
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1_i32.to_le_bytes());
+        channel_list.extend_from_slice(&1_i32.to_le_bytes());
+    }
+    channel_list.push(0);
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let window = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<_>>();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Each scanline is its own block, found through a table of offsets from the start of the file
+    let row_size = width * 4 * channels.len();
+    let block_size = 8 + row_size;
+    let first_block = header.len() + 8 * height;
+    for y in 0..height {
+        header.extend_from_slice(&((first_block + y * block_size) as u64).to_le_bytes());
+    }
+    file.write_all(&header)?;
+
+    let mut block = Vec::with_capacity(block_size);
+    for y in 0..height {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&(row_size as i32).to_le_bytes());
+        for channel in &channels {
+            channel.samples.write_row(y, width, &mut block);
+        }
+        file.write_all(&block)?;
+    }
+
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::*;
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    }
+
+    fn read_u64(bytes: &[u8], at: usize) -> u64 {
+        let mut value = [0; 8];
+        value.copy_from_slice(&bytes[at..at + 8]);
+        u64::from_le_bytes(value)
+    }
+
+    fn read_f32(bytes: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    #[test]
+    fn an_exr_starts_with_the_magic_number_and_version() {
+        let mut file = vec![];
+
+        write(&mut file, 1, 1, vec![Channel::float("Z", vec![1.0])]).unwrap();
+
+        assert_that!(file[..8].to_vec()).is_equal_to(vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn channels_are_listed_in_alphabetical_order() {
+        let mut file = vec![];
+        let channels = vec![
+            Channel::float("Z", vec![0.0]),
+            Channel::uint("id", vec![0]),
+            Channel::float("R", vec![0.0]),
+        ];
+
+        write(&mut file, 1, 1, channels).unwrap();
+
+        let r = find(&file, b"R\0").unwrap();
+        let z = find(&file, b"Z\0").unwrap();
+        let id = find(&file, b"id\0").unwrap();
+        assert_that!(r).is_less_than(z);
+        assert_that!(z).is_less_than(id);
+    }
+
+    #[test]
+    fn the_offset_table_points_at_each_scanline() {
+        let mut file = vec![];
+        let depth = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+
+        write(&mut file, 3, 2, vec![Channel::float("Z", depth)]).unwrap();
+
+        // Two blocks of 8 bytes of header and 12 of samples finish the file
+        let table = file.len() - 2 * 20 - 16;
+        let second = read_u64(&file, table + 8) as usize;
+        assert_that!(read_u64(&file, table) as usize).is_equal_to(table + 16);
+        assert_that!(file[second..second + 4].to_vec()).is_equal_to(vec![1, 0, 0, 0]);
+        assert_that!(read_f32(&file, second + 8)).is_equal_to(4.0);
+        assert_that!(read_f32(&file, second + 16)).is_equal_to(6.0);
+    }
+}
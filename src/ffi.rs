@@ -0,0 +1,270 @@
+//! A C interface for embedding the renderer in viewers written in other languages. Scenes are
+//! opaque handles; images are written into buffers the caller owns.
+//!
+//! A scene must only be used from one thread at a time, but `rt_progress` may be called from
+//! any thread while it renders. A panic can't unwind into the caller, so reading and rendering
+//! catch them and report failure instead.
+
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::camera::{Camera, CameraBuilder, FitMode};
+use crate::registry::SceneRegistry;
+use crate::scene;
+use crate::settings::RenderSettings;
+use crate::world::World;
+
+/// A world and the camera to render it with.
+pub struct RtScene {
+    world: World,
+    camera: Camera,
+    /// How far through its latest render the scene is, as the bits of an `f32` from 0 to 1.
+    progress: AtomicU32,
+}
+
+impl RtScene {
+    fn new(world: World, camera: Camera) -> *mut RtScene {
+        Box::into_raw(Box::new(RtScene {
+            world,
+            camera,
+            progress: AtomicU32::new(0),
+        }))
+    }
+}
+
+/// The default world, framed by a camera `width` by `height` pixels, or null if either is 0.
+/// Free it with `rt_scene_free`.
+#[no_mangle]
+pub extern "C" fn rt_scene_default(width: usize, height: usize) -> *mut RtScene {
+    if width == 0 || height == 0 {
+        return std::ptr::null_mut();
+    }
+    let world = World::default_world();
+    let camera = CameraBuilder::new()
+        .with_hsize(width)
+        .with_vsize(height)
+        .framing(&world, FitMode::Fit)
+        .build();
+
+    RtScene::new(world, camera)
+}
+
+/// The scene described by the JSON text `json`, in the form `scene::from_json` reads, or null if
+/// it can't be read. Free it with `rt_scene_free`.
+///
+/// # Safety
+/// `json` must be null or point to a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_from_json(json: *const c_char) -> *mut RtScene {
+    if json.is_null() {
+        return std::ptr::null_mut();
+    }
+    let Ok(text) = CStr::from_ptr(json).to_str() else {
+        return std::ptr::null_mut();
+    };
+    let scene = panic::catch_unwind(|| {
+        let (world, camera) = scene::from_json(text, &SceneRegistry::builtin()).ok()?;
+        Some(RtScene::new(world, camera.build()))
+    });
+    match scene {
+        Ok(Some(scene)) => scene,
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Free a scene made by `rt_scene_default` or `rt_scene_from_json`. Null is ignored.
+///
+/// # Safety
+/// `scene` must be null or a scene from this interface that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_free(scene: *mut RtScene) {
+    if !scene.is_null() {
+        drop(Box::from_raw(scene));
+    }
+}
+
+/// The width of the scene's image in pixels, or 0 for a null scene.
+///
+/// # Safety
+/// `scene` must be null or a live scene.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_width(scene: *const RtScene) -> usize {
+    scene.as_ref().map_or(0, |scene| scene.camera.hsize())
+}
+
+/// The height of the scene's image in pixels, or 0 for a null scene.
+///
+/// # Safety
+/// `scene` must be null or a live scene.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_height(scene: *const RtScene) -> usize {
+    scene.as_ref().map_or(0, |scene| scene.camera.vsize())
+}
+
+/// Render the scene into `buffer` as 8-bit sRGB RGBA, row by row from the top left, with alpha
+/// for how much of each pixel the scene covers. Returns 0 on success, or -1 if the scene is null,
+/// the buffer is too small for the scene's camera, or rendering fails.
+///
+/// # Safety
+/// `scene` must be null or a live scene, and `buffer` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rt_render_rgba(scene: *const RtScene, buffer: *mut u8, len: usize) -> i32 {
+    let Some(scene) = scene.as_ref() else {
+        return -1;
+    };
+    let (width, height) = (scene.camera.hsize(), scene.camera.vsize());
+    let Some(size) = width.checked_mul(height).and_then(|n| n.checked_mul(4)) else {
+        return -1;
+    };
+    if buffer.is_null() || len < size {
+        return -1;
+    }
+    let pixels = std::slice::from_raw_parts_mut(buffer, size);
+
+    match panic::catch_unwind(AssertUnwindSafe(|| render_rgba(scene, pixels))) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// How far the scene's render in progress has got, from 0 to 1, or 0 for a null scene.
+///
+/// # Safety
+/// `scene` must be null or a live scene.
+#[no_mangle]
+pub unsafe extern "C" fn rt_progress(scene: *const RtScene) -> f32 {
+    match scene.as_ref() {
+        Some(scene) => f32::from_bits(scene.progress.load(Ordering::Relaxed)),
+        None => 0.0,
+    }
+}
+
+fn render_rgba(scene: &RtScene, pixels: &mut [u8]) {
+    let settings = RenderSettings::default();
+    let (width, height) = (scene.camera.hsize(), scene.camera.vsize());
+    scene.progress.store(0.0_f32.to_bits(), Ordering::Relaxed);
+    for (y, row) in pixels.chunks_exact_mut(width * 4).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let ray = scene.camera.ray_for_pixel(x, y);
+            let (color, hit) = scene.world.sample(&ray, &settings);
+            let (r, g, b) = color.to_srgb_u8();
+            pixel.copy_from_slice(&[r, g, b, if hit { 255 } else { 0 }]);
+        }
+        scene.progress.store(
+            ((y + 1) as f32 / height as f32).to_bits(),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn rendering_into_a_buffer() {
+        let scene = rt_scene_default(8, 6);
+        let mut buffer = vec![0_u8; 8 * 6 * 4];
+
+        let result = unsafe { rt_render_rgba(scene, buffer.as_mut_ptr(), buffer.len()) };
+
+        assert_that!(result).is_equal_to(0);
+        assert_that!(unsafe { rt_progress(scene) }).is_equal_to(1.0);
+        // The framed sphere fills the middle and leaves the corners clear
+        let middle = (3 * 8 + 4) * 4;
+        assert_that!(buffer[middle + 3]).is_equal_to(255);
+        assert_that!(buffer[3]).is_equal_to(0);
+        unsafe { rt_scene_free(scene) };
+    }
+
+    #[test]
+    fn a_buffer_too_small_is_refused() {
+        let scene = rt_scene_default(8, 6);
+        let mut buffer = vec![0_u8; 8 * 6 * 4 - 1];
+
+        let result = unsafe { rt_render_rgba(scene, buffer.as_mut_ptr(), buffer.len()) };
+
+        assert_that!(result).is_equal_to(-1);
+        unsafe { rt_scene_free(scene) };
+    }
+
+    #[test]
+    fn each_scene_keeps_its_own_progress() {
+        let (first, second) = (rt_scene_default(4, 3), rt_scene_default(4, 3));
+        let mut buffer = vec![0_u8; 4 * 3 * 4];
+
+        unsafe { rt_render_rgba(first, buffer.as_mut_ptr(), buffer.len()) };
+
+        assert_that!(unsafe { rt_progress(first) }).is_equal_to(1.0);
+        assert_that!(unsafe { rt_progress(second) }).is_equal_to(0.0);
+        unsafe {
+            rt_scene_free(first);
+            rt_scene_free(second);
+        }
+    }
+
+    #[test]
+    fn a_scene_from_json() {
+        let json = CString::new(
+            r#"{ "camera": { "width": 5, "height": 4 }, "objects": [ { "type": "sphere" } ] }"#,
+        )
+        .unwrap();
+        let scene = unsafe { rt_scene_from_json(json.as_ptr()) };
+        let mut buffer = vec![0_u8; 5 * 4 * 4];
+
+        assert_that!(unsafe { (rt_scene_width(scene), rt_scene_height(scene)) })
+            .is_equal_to((5, 4));
+
+        let result = unsafe { rt_render_rgba(scene, buffer.as_mut_ptr(), buffer.len()) };
+
+        assert_that!(result).is_equal_to(0);
+        let middle = (2 * 5 + 2) * 4;
+        assert_that!(buffer[middle + 3]).is_equal_to(255);
+        unsafe { rt_scene_free(scene) };
+    }
+
+    #[test]
+    fn a_camera_too_big_to_have_a_buffer_is_refused() {
+        let json = CString::new(r#"{ "camera": { "width": 1e19, "height": 1e19 } }"#).unwrap();
+        let scene = unsafe { rt_scene_from_json(json.as_ptr()) };
+        let mut buffer = vec![0_u8; 4];
+
+        let result = unsafe { rt_render_rgba(scene, buffer.as_mut_ptr(), usize::MAX) };
+
+        assert_that!(result).is_equal_to(-1);
+        unsafe { rt_scene_free(scene) };
+    }
+
+    #[test]
+    fn json_that_isnt_a_scene_is_refused() {
+        let json = CString::new(r#"{ "objects": [ { "type": "torus" } ] }"#).unwrap();
+        let flat = CString::new(
+            r#"{ "objects": [ { "type": "sphere", "transform": [["scale", 0, 1, 1]] } ] }"#,
+        )
+        .unwrap();
+
+        assert_that!(unsafe { rt_scene_from_json(json.as_ptr()) }.is_null()).is_true();
+        assert_that!(unsafe { rt_scene_from_json(flat.as_ptr()) }.is_null()).is_true();
+        assert_that!(unsafe { rt_scene_from_json(std::ptr::null()) }.is_null()).is_true();
+    }
+
+    #[test]
+    fn null_scenes_are_refused() {
+        let mut buffer = vec![0_u8; 4];
+
+        assert_that!(rt_scene_default(0, 10).is_null()).is_true();
+        let result = unsafe { rt_render_rgba(std::ptr::null(), buffer.as_mut_ptr(), 4) };
+        assert_that!(result).is_equal_to(-1);
+        assert_that!(unsafe { rt_progress(std::ptr::null()) }).is_equal_to(0.0);
+        unsafe { rt_scene_free(std::ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,155 @@
+//! Where the samples for a pixel go, and how they are weighted into the pixels around them.
+//!
+//! Samples are placed on a Halton sequence, which covers a pixel more evenly than independent
+//! random points, shifted by a random amount for each pixel so neighbouring pixels don't share a
+//! pattern. Each sample then counts towards every pixel within the filter's radius, weighted by
+//! its distance from the pixel's centre, so edges come out smoother and sharper than a plain
+//! average of the samples in each pixel gives.
+
+/// How samples are weighted into pixels, by their offset from the pixel centre in pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PixelFilter {
+    /// Every sample in a pixel counts equally, and only towards that pixel.
+    #[default]
+    Box,
+    /// Weights fall off linearly to nothing a pixel away.
+    Tent,
+    /// A Gaussian bell, cut off 1.5 pixels away. Smooth, but slightly soft.
+    Gaussian,
+    /// Mitchell and Netravali's cubic, with B = C = 1/3, reaching 2 pixels away. Sharper than the
+    /// Gaussian; its small negative lobes can ring faintly at hard edges.
+    Mitchell,
+}
+
+impl PixelFilter {
+    /// How far from a pixel's centre, in pixels along each axis, a sample still counts towards it.
+    pub fn radius(&self) -> f32 {
+        match self {
+            PixelFilter::Box => 0.5,
+            PixelFilter::Tent => 1.0,
+            PixelFilter::Gaussian => 1.5,
+            PixelFilter::Mitchell => 2.0,
+        }
+    }
+
+    /// How many pixels away, along each axis, a sample taken somewhere in a pixel can still count.
+    pub fn reach(&self) -> usize {
+        (self.radius() + 0.5).floor() as usize
+    }
+
+    /// The weight of a sample `(dx, dy)` pixels from a pixel's centre.
+    pub fn weight(&self, dx: f32, dy: f32) -> f32 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, d: f32) -> f32 {
+        let d = d.abs();
+        if d > self.radius() {
+            return 0.0;
+        }
+
+        match self {
+            PixelFilter::Box => 1.0,
+            PixelFilter::Tent => 1.0 - d,
+            PixelFilter::Gaussian => {
+                // Shifted down so it reaches zero at the radius instead of stopping abruptly
+                let alpha = 2.0;
+                (-alpha * d * d).exp() - (-alpha * self.radius().powi(2)).exp()
+            }
+            PixelFilter::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let value = if d < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * d.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * d.powi(2)
+                        + (6.0 - 2.0 * b)
+                } else {
+                    (-b - 6.0 * c) * d.powi(3)
+                        + (6.0 * b + 30.0 * c) * d.powi(2)
+                        + (-12.0 * b - 48.0 * c) * d
+                        + (8.0 * b + 24.0 * c)
+                };
+                value / 6.0
+            }
+        }
+    }
+}
+
+/// The `index`th number of the van der Corput sequence in `base`, which spreads out evenly over
+/// [0, 1) however many of its numbers are taken.
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * fraction;
+        index /= base;
+        fraction /= base as f32;
+    }
+
+    result
+}
+
+/// Where sample `index` falls in a pixel, as fractions of the way across and down, with the
+/// whole pattern shifted by `rotation` and wrapped back into the pixel.
+pub fn sample_offset(index: u32, rotation: (f32, f32)) -> (f32, f32) {
+    let wrap = |v: f32| v - v.floor();
+
+    (
+        wrap(halton(index + 1, 2) + rotation.0),
+        wrap(halton(index + 1, 3) + rotation.1),
+    )
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(PixelFilter::Box, 0.4, 1.0)]
+    #[case(PixelFilter::Box, 0.6, 0.0)]
+    #[case(PixelFilter::Tent, 0.25, 0.75)]
+    #[case(PixelFilter::Tent, 1.5, 0.0)]
+    #[case(PixelFilter::Mitchell, 0.0, 8.0 / 9.0)]
+    #[case(PixelFilter::Mitchell, 1.0, 1.0 / 18.0)]
+    #[case(PixelFilter::Mitchell, 2.0, 0.0)]
+    #[case(PixelFilter::Gaussian, 1.5, 0.0)]
+    fn filter_weights_along_one_axis(
+        #[case] filter: PixelFilter,
+        #[case] d: f32,
+        #[case] expected: f32,
+    ) {
+        assert_that!(filter.weight_1d(d)).is_close_to(expected, 0.0001);
+        assert_that!(filter.weight_1d(-d)).is_close_to(expected, 0.0001);
+    }
+
+    #[test]
+    fn the_mitchell_filter_dips_below_zero() {
+        assert_that!(PixelFilter::Mitchell.weight(1.5, 0.0)).is_less_than(0.0);
+    }
+
+    #[rstest]
+    #[case(1, 2, 0.5)]
+    #[case(2, 2, 0.25)]
+    #[case(3, 2, 0.75)]
+    #[case(1, 3, 1.0 / 3.0)]
+    #[case(5, 3, 7.0 / 9.0)]
+    fn the_halton_sequence(#[case] index: u32, #[case] base: u32, #[case] expected: f32) {
+        assert_that!(halton(index, base)).is_close_to(expected, 0.00001);
+    }
+
+    #[test]
+    fn sample_offsets_stay_in_the_pixel() {
+        for index in 0..64 {
+            let (dx, dy) = sample_offset(index, (0.7, 0.9));
+
+            assert_that!((0.0..1.0).contains(&dx)).is_true();
+            assert_that!((0.0..1.0).contains(&dy)).is_true();
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// The shortest step taken while marching along a ray, so that thin gaps still get looked at.
+const MIN_STEP: f32 = 1.0e-3;
+/// How many steps a ray may take through the bounds before giving up.
+const MAX_STEPS: usize = 1024;
+/// How many times a crossing is halved once it has been found.
+const REFINEMENTS: usize = 24;
+
+/// An implicit surface given by a closure from points to their signed distance from the surface,
+/// negative inside, for trying out a shape without writing a whole new shape type.
+///
+/// The closure need not be an exact distance, but it must never be more than the real distance,
+/// or rays will step over parts of the surface. Rays are only marched through the bounds, which
+/// must enclose the whole surface.
+#[derive(Clone)]
+pub struct FnShape {
+    distance: Rc<dyn Fn(Vector4) -> f32>,
+    bounds: BoundingBox,
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+}
+
+pub struct FnShapeBuilder {
+    distance: Rc<dyn Fn(Vector4) -> f32>,
+    bounds: BoundingBox,
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+}
+
+impl FnShape {
+    fn distance(&self, object_point: Vector4) -> f32 {
+        (self.distance)(object_point)
+    }
+
+    /// The gradient of the distance at `object_point`, by central differences.
+    fn gradient(&self, object_point: Vector4) -> Vector4 {
+        let h = MIN_STEP;
+        let along = |offset: Vector4| {
+            (self.distance(object_point + offset) - self.distance(object_point - offset))
+                / (2.0 * h)
+        };
+
+        Vector4::vector(
+            along(Vector4::vector(h, 0.0, 0.0)),
+            along(Vector4::vector(0.0, h, 0.0)),
+            along(Vector4::vector(0.0, 0.0, h)),
+        )
+    }
+}
+
+impl Shape for FnShape {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let (start, end) = match self.bounds.span(ray) {
+            Some(span) => span,
+            None => return Vec::default(),
+        };
+        let speed = ray.direction.magnitude();
+        let at = |t: f32| self.distance(ray.position(t));
+
+        // March along the ray, stepping no further than the surface could be, and look for the
+        // distance changing sign
+        let mut xs = Vec::new();
+        let (mut t, mut d) = (start, at(start));
+        for _ in 0..MAX_STEPS {
+            if t >= end {
+                break;
+            }
+            let next_t = (t + d.abs().max(MIN_STEP) / speed).min(end);
+            let next_d = at(next_t);
+            if (d < 0.0) != (next_d < 0.0) {
+                let (mut lo, mut hi) = (t, next_t);
+                for _ in 0..REFINEMENTS {
+                    let mid = (lo + hi) / 2.0;
+                    if (at(mid) < 0.0) == (d < 0.0) {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                xs.push((lo + hi) / 2.0);
+            }
+            t = next_t;
+            d = next_d;
+        }
+
+        xs
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        self.gradient(object_point)
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        // Slide down the gradient by the distance, a few times over for inexact distances
+        let mut point = object_point;
+        for _ in 0..8 {
+            let d = self.distance(point);
+            let gradient = self.gradient(point);
+            if d.abs() < EPSILON || gradient.magnitude() < EPSILON {
+                break;
+            }
+            point = point - gradient.normalize() * d;
+        }
+
+        point
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl Debug for FnShape {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FnShape")
+            .field("bounds", &self.bounds)
+            .field("inv_transform", &self.inv_transform)
+            .field("material", &self.material)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl FnShapeBuilder {
+    /// A shape whose surface is where `distance` is zero, within the cube from -1 to 1 unless
+    /// given other bounds.
+    pub fn new(distance: impl Fn(Vector4) -> f32 + 'static) -> Self {
+        Self {
+            distance: Rc::new(distance),
+            bounds: BoundingBox::new(
+                Vector4::point(-1.0, -1.0, -1.0),
+                Vector4::point(1.0, 1.0, 1.0),
+            ),
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+        }
+    }
+
+    /// A capsule: a cylinder of `radius` along the y axis from `-half_height` to `half_height`,
+    /// capped at each end with a hemisphere. Unlike `shapes::compound::capsule`, which overlaps
+    /// primitives, it is one closed surface, so it refracts properly when made of glass.
+    pub fn capsule(radius: f32, half_height: f32) -> Self {
+        Self::new(move |p| {
+            let y = p.y - p.y.clamp(-half_height, half_height);
+            (p.x * p.x + y * y + p.z * p.z).sqrt() - radius
+        })
+        .with_bounds(BoundingBox::new(
+            Vector4::point(-radius, -half_height - radius, -radius),
+            Vector4::point(radius, half_height + radius, radius),
+        ))
+    }
+
+    /// A box reaching out `half_size`, a vector, from the origin along each axis, with its edges
+    /// and corners rounded off to `radius`. A single surface, as the capsule is, in place of
+    /// the slabs, cylinders and spheres of `shapes::compound::rounded_box`.
+    pub fn rounded_box(half_size: Vector4, radius: f32) -> Self {
+        let radius = radius.clamp(0.0, half_size.x.min(half_size.y).min(half_size.z));
+        Self::new(move |p| {
+            let q = [
+                p.x.abs() - half_size.x + radius,
+                p.y.abs() - half_size.y + radius,
+                p.z.abs() - half_size.z + radius,
+            ];
+            let outside = q.iter().map(|v| v.max(0.0).powi(2)).sum::<f32>().sqrt();
+            let inside = q[0].max(q[1]).max(q[2]).min(0.0);
+            outside + inside - radius
+        })
+        .with_bounds(BoundingBox::new(
+            Vector4::point(-half_size.x, -half_size.y, -half_size.z),
+            Vector4::point(half_size.x, half_size.y, half_size.z),
+        ))
+    }
+
+    /// A cylinder of `radius` along the y axis from `-half_height` to `half_height`, closed at
+    /// both ends, with the rims where its sides meet its ends rounded off to `edge_radius`.
+    pub fn rounded_cylinder(radius: f32, half_height: f32, edge_radius: f32) -> Self {
+        let edge_radius = edge_radius.clamp(0.0, radius.min(half_height));
+        Self::new(move |p| {
+            let across = (p.x * p.x + p.z * p.z).sqrt() - radius + edge_radius;
+            let along = p.y.abs() - half_height + edge_radius;
+            let outside = (across.max(0.0).powi(2) + along.max(0.0).powi(2)).sqrt();
+            outside + across.max(along).min(0.0) - edge_radius
+        })
+        .with_bounds(BoundingBox::new(
+            Vector4::point(-radius, -half_height, -radius),
+            Vector4::point(radius, half_height, radius),
+        ))
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    /// The box in object space that the whole surface lies within.
+    pub fn with_bounds(mut self, bounds: BoundingBox) -> Self {
+        assert!(bounds.is_bounded(), "The bounds must be finite");
+        self.bounds = bounds;
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    pub fn build(self) -> impl Shape {
+        FnShape {
+            distance: self.distance,
+            bounds: self.bounds,
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::*;
+    use crate::assert_approx_eq;
+    use crate::transform::Transform;
+
+    /// A sphere of radius 1 at the origin, by its exact distance.
+    #[fixture]
+    fn ball() -> impl Shape {
+        FnShapeBuilder::new(|p| (p.x * p.x + p.y * p.y + p.z * p.z).sqrt() - 1.0).build()
+    }
+
+    #[rstest]
+    fn a_ray_passes_through_the_surface_twice(ball: impl Shape) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = ball.local_intersect(&r);
+
+        assert_that!(xs.len()).is_equal_to(2);
+        assert_that!(xs[0]).is_close_to(4.0, 0.0001);
+        assert_that!(xs[1]).is_close_to(6.0, 0.0001);
+    }
+
+    #[rstest]
+    fn a_ray_from_inside_finds_the_way_out(ball: impl Shape) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 2.0, 0.0),
+        );
+
+        let xs = ball.local_intersect(&r);
+
+        assert_that!(xs.len()).is_equal_to(2);
+        assert_that!(xs[0]).is_close_to(-0.5, 0.0001);
+        assert_that!(xs[1]).is_close_to(0.5, 0.0001);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 2.0, -5.0), Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(0.0, 0.0, -5.0), Vector4::vector(0.0, 1.0, 0.0))]
+    fn a_ray_misses_the_surface(
+        ball: impl Shape,
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+    ) {
+        assert_that!(ball
+            .local_intersect(&Ray::new(origin, direction))
+            .is_empty())
+        .is_true();
+    }
+
+    #[rstest]
+    fn the_normal_is_the_gradient(ball: impl Shape) {
+        let n = ball
+            .local_normal_at(Vector4::point(0.0, 0.0, -1.0))
+            .normalize();
+
+        assert_that!(n).is_equal_to(Vector4::vector(0.0, 0.0, -1.0));
+    }
+
+    #[rstest]
+    fn the_closest_point_is_on_the_surface(ball: impl Shape) {
+        let p = ball.local_closest_point(Vector4::point(0.0, 3.0, 0.0));
+
+        assert_that!(p).is_equal_to(Vector4::point(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn a_transformed_closure_shape() {
+        let s = FnShapeBuilder::new(|p| p.y)
+            .with_bounds(BoundingBox::new(
+                Vector4::point(-1.0, -1.0, -1.0),
+                Vector4::point(1.0, 1.0, 1.0),
+            ))
+            .with_transform(Matrix::translation(0.0, 2.0, 0.0))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 5.0, 0.0),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+
+        let xs = s.intersect(&r);
+
+        assert_that!(xs.len()).is_equal_to(1);
+        assert_that!(xs[0]).is_close_to(3.0, 0.0001);
+        assert_that!(s.bounds().max.y).is_equal_to(1.0);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 5.0, 0.0), Vector4::vector(0.0, -1.0, 0.0), 3.5)]
+    #[case(Vector4::point(0.0, 1.0, -5.0), Vector4::vector(0.0, 0.0, 1.0), 4.5)]
+    #[case(Vector4::point(0.0, 1.25, -5.0), Vector4::vector(0.0, 0.0, 1.0), 4.566987)]
+    fn intersecting_a_capsule(#[case] origin: Vector4, #[case] direction: Vector4, #[case] t: f32) {
+        let capsule = FnShapeBuilder::capsule(0.5, 1.0).build();
+
+        let xs = capsule.intersect(&Ray::new(origin, direction));
+
+        assert_that!(xs[0]).is_close_to(t, 0.0001);
+    }
+
+    #[test]
+    fn the_ends_of_a_capsule_are_round() {
+        let capsule = FnShapeBuilder::capsule(0.5, 1.0).build();
+
+        let n = capsule.normal_at(&Vector4::point(
+            0.0,
+            1.0 + 0.5 * FRAC_1_SQRT_2,
+            0.5 * FRAC_1_SQRT_2,
+        ));
+
+        assert_approx_eq!(n, Vector4::vector(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2), 0.001);
+    }
+
+    #[rstest]
+    // Straight at a face, and near the top, where the edge is rounded back
+    #[case(Vector4::point(5.0, 0.0, 0.0), 3.0)]
+    #[case(Vector4::point(5.0, 0.9, 0.0), 3.05)]
+    fn intersecting_a_rounded_box(#[case] origin: Vector4, #[case] t: f32) {
+        let rounded = FnShapeBuilder::rounded_box(Vector4::vector(2.0, 1.0, 1.0), 0.25).build();
+
+        let xs = rounded.intersect(&Ray::new(origin, Vector4::vector(-1.0, 0.0, 0.0)));
+
+        assert_that!(xs[0]).is_close_to(t, 0.0001);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 5.0, 0.0), Vector4::vector(0.0, -1.0, 0.0), 3.0)]
+    #[case(Vector4::point(5.0, 0.0, 0.0), Vector4::vector(-1.0, 0.0, 0.0), 4.0)]
+    #[case(Vector4::point(5.0, 1.92, 0.0), Vector4::vector(-1.0, 0.0, 0.0), 4.04)]
+    fn intersecting_a_rounded_cylinder(
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+        #[case] t: f32,
+    ) {
+        let rounded = FnShapeBuilder::rounded_cylinder(1.0, 2.0, 0.2).build();
+
+        let xs = rounded.intersect(&Ray::new(origin, direction));
+
+        assert_that!(xs[0]).is_close_to(t, 0.0001);
+    }
+}
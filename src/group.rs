@@ -0,0 +1,456 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// A collection of shapes that are transformed together.
+///
+/// The group's transform is baked into its children when the group is built, so each child can
+/// be intersected and shaded on its own without reference back to the group.
+#[derive(Clone, Debug)]
+pub struct Group {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+    children: Vec<Rc<dyn Shape>>,
+}
+
+pub struct GroupBuilder {
+    transform: Matrix<4>,
+    name: Option<String>,
+    user_data: UserData,
+    children: Vec<Rc<dyn Shape>>,
+}
+
+impl Group {
+    /// Wireframe boxes showing the bounds of this group and of every group nested inside it, to
+    /// check how a scene is subdivided. Add the result to the world alongside the group.
+    pub fn bounding_boxes_as_wireframe(&self, radius: f32, material: &Material) -> Group {
+        let mut wireframes = GroupBuilder::new();
+        let mut pending: Vec<&dyn Shape> = vec![self];
+
+        while let Some(node) = pending.pop() {
+            if node.children().is_empty() {
+                continue;
+            }
+            let wireframe = node.parent_space_bounds().wireframe(radius, material);
+            if !wireframe.children().is_empty() {
+                wireframes = wireframes.with_child(Rc::new(wireframe));
+            }
+            pending.extend(node.children().iter().map(|child| child.as_ref()));
+        }
+
+        wireframes.build()
+    }
+}
+
+impl Shape for Group {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            children: self
+                .children
+                .iter()
+                .map(|child| child.transformed(transform))
+                .collect(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            children: self
+                .children
+                .iter()
+                .map(|child| child.with_material(material))
+                .collect(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.parent_space_bounds().transform(&self.inv_transform)
+    }
+
+    fn parent_space_bounds(&self) -> BoundingBox {
+        // The children already include the group's transform
+        let mut bounds = BoundingBox::empty();
+        for child in self.children.iter() {
+            bounds.merge(&child.parent_space_bounds());
+        }
+
+        bounds
+    }
+
+    fn children(&self) -> &[Rc<dyn Shape>] {
+        &self.children
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.children.iter().map(|child| child.triangle_count()).sum()
+    }
+
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        // Each child is behind a pointer in the list, with the counts of its `Rc` beside it
+        let list = self.children.capacity() * mem::size_of::<Rc<dyn Shape>>();
+        let children: usize = self
+            .children
+            .iter()
+            .map(|child| child.memory_size(counted) + 2 * mem::size_of::<usize>())
+            .sum();
+
+        mem::size_of_val(self) + list + children
+    }
+
+    fn flattened(&self) -> Option<Rc<dyn Shape>> {
+        // The children already include every group's transform, so they can be lifted out as is.
+        // Empty groups have nothing to lift, so they drop out.
+        let mut leaves = vec![];
+        for child in self.children.iter() {
+            match child.flattened() {
+                Some(group) => leaves.extend(group.children().iter().cloned()),
+                None => leaves.push(Rc::clone(child)),
+            }
+        }
+
+        Some(Rc::new(Self {
+            children: leaves,
+            ..self.clone()
+        }))
+    }
+
+    fn intersect(&self, ray: &Ray) -> Vec<f32> {
+        let mut xs: Vec<f32> = self
+            .children
+            .iter()
+            .flat_map(|c| c.intersect(ray))
+            .collect();
+        xs.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        xs
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        // The children already include the group's transform, so undo it
+        self.intersect(&ray.transform(&self.transformation()))
+    }
+
+    fn closest_point(&self, world_point: &Vector4) -> Vector4 {
+        // An empty group has no surface, so everything is infinitely far from it
+        let nowhere = Vector4::point(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+
+        self.children
+            .iter()
+            .map(|child| child.closest_point(world_point))
+            .min_by(|a, b| {
+                (*a - *world_point)
+                    .magnitude()
+                    .total_cmp(&(*b - *world_point).magnitude())
+            })
+            .unwrap_or(nowhere)
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        // The children already include the group's transform, so undo it and redo it after
+        *self.inv_transform() * self.closest_point(&(self.transformation() * object_point))
+    }
+
+    fn local_normal_at(&self, _object_point: Vector4) -> Vector4 {
+        panic!("Groups have no surface of their own, so no normal")
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl GroupBuilder {
+    pub fn new() -> Self {
+        Self {
+            transform: Matrix::identity(),
+            name: None,
+            user_data: UserData::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_child(mut self, child: Rc<dyn Shape>) -> Self {
+        self.children.push(child);
+
+        self
+    }
+
+    pub fn build(self) -> Group {
+        let transform = self.transform;
+
+        Group {
+            inv_transform: transform.try_inverse().unwrap(),
+            material: MaterialBuilder::new().build(),
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+            children: self
+                .children
+                .iter()
+                .map(|child| child.transformed(&transform))
+                .collect(),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use spectral::prelude::*;
+
+    use crate::cube::CubeBuilder;
+    use crate::plane::PlaneBuilder;
+    use crate::sphere::SphereBuilder;
+    use crate::transform::Transform;
+
+    use super::*;
+
+    #[test]
+    fn creating_a_new_group() {
+        let g = GroupBuilder::new().build();
+
+        assert_that!(g.transformation()).is_equal_to(Matrix::identity());
+        assert_that!(g.children().len()).is_equal_to(0);
+    }
+
+    #[test]
+    fn adding_a_child_to_a_group() {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+
+        let g = GroupBuilder::new().with_child(Rc::clone(&s)).build();
+
+        assert_that!(g.children().len()).is_equal_to(1);
+        assert_that!(g.children()[0]).is_equal_to(&s);
+    }
+
+    #[test]
+    fn intersecting_a_ray_with_an_empty_group() {
+        let g = GroupBuilder::new().build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        assert_that!(g.intersect(&r)).is_empty();
+    }
+
+    #[test]
+    fn intersecting_a_ray_with_a_nonempty_group() {
+        let s1 = SphereBuilder::new().build();
+        let s2 = SphereBuilder::new()
+            .with_transform(Matrix::translation(0.0, 0.0, -3.0))
+            .build();
+        let s3 = SphereBuilder::new()
+            .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+            .build();
+        let g = GroupBuilder::new()
+            .with_child(Rc::new(s1))
+            .with_child(Rc::new(s2))
+            .with_child(Rc::new(s3))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = g.intersect(&r);
+
+        assert_that!(xs).is_equal_to(vec![1.0, 3.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn intersecting_a_transformed_group() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+            .build();
+        let g = GroupBuilder::new()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .with_child(Rc::new(s))
+            .build();
+        let r = Ray::new(
+            Vector4::point(10.0, 0.0, -10.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = g.intersect(&r);
+
+        assert_that!(xs).has_length(2);
+    }
+
+    #[test]
+    fn the_group_transform_is_applied_to_its_children() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+            .build();
+        let g = GroupBuilder::new()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .with_child(Rc::new(s))
+            .build();
+
+        assert_that!(g.children()[0].transformation())
+            .is_equal_to(Matrix::scaling(2.0, 2.0, 2.0) * Matrix::translation(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn finding_the_normal_on_a_child_object() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+            .build();
+        let g2 = GroupBuilder::new()
+            .with_transform(Matrix::scaling(1.0, 2.0, 3.0))
+            .with_child(Rc::new(s))
+            .build();
+        let g1 = GroupBuilder::new()
+            .with_transform(Matrix::rotation_y(PI / 2.0))
+            .with_child(Rc::new(g2))
+            .build();
+        let child = &g1.children()[0].children()[0];
+
+        let n = child.normal_at(&Vector4::point(1.7321, 1.1547, -5.5774));
+
+        assert_that!(n.x).is_close_to(0.2857, 0.0001);
+        assert_that!(n.y).is_close_to(0.4286, 0.0001);
+        assert_that!(n.z).is_close_to(-0.8571, 0.0001);
+    }
+
+    #[test]
+    fn a_group_has_a_bounding_box_that_contains_its_children() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(2.0, 5.0, -3.0) * Matrix::scaling(2.0, 2.0, 2.0))
+            .build();
+        let c = CubeBuilder::new()
+            .with_transform(Matrix::translation(-4.0, -1.0, 4.0))
+            .build();
+        let g = GroupBuilder::new()
+            .with_child(Rc::new(s))
+            .with_child(Rc::new(c))
+            .build();
+
+        let b = g.bounds();
+
+        assert_that!(b.min).is_equal_to(Vector4::point(-5.0, -2.0, -5.0));
+        assert_that!(b.max).is_equal_to(Vector4::point(4.0, 7.0, 5.0));
+    }
+
+    #[test]
+    fn a_wireframe_is_drawn_for_every_nested_group() {
+        let inner = GroupBuilder::new()
+            .with_child(Rc::new(SphereBuilder::new().build()))
+            .build();
+        let outer = GroupBuilder::new()
+            .with_child(Rc::new(inner))
+            .with_child(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+                    .build(),
+            ))
+            .build();
+
+        let wireframes = outer.bounding_boxes_as_wireframe(0.01, &MaterialBuilder::new().build());
+
+        assert_that!(wireframes.children().len()).is_equal_to(2);
+        assert_that!(wireframes.bounds().min).is_equal_to(Vector4::point(-1.01, -1.01, -1.01));
+        assert_that!(wireframes.bounds().max).is_equal_to(Vector4::point(6.01, 1.01, 1.01));
+    }
+
+    #[test]
+    fn unbounded_groups_have_no_wireframe() {
+        let g = GroupBuilder::new()
+            .with_child(Rc::new(PlaneBuilder::new().build()))
+            .build();
+
+        let wireframes = g.bounding_boxes_as_wireframe(0.01, &MaterialBuilder::new().build());
+
+        assert_that!(wireframes.children().len()).is_equal_to(0);
+    }
+
+    #[test]
+    fn the_closest_point_on_a_group_is_on_its_nearest_child() {
+        let g = GroupBuilder::new()
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .with_child(Rc::new(SphereBuilder::new().build()))
+            .with_child(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(Matrix::translation(5.0, 0.0, 0.0))
+                    .build(),
+            ))
+            .build();
+
+        assert_that!(g.closest_point(&Vector4::point(8.0, 1.0, 0.0)))
+            .is_equal_to(Vector4::point(6.0, 1.0, 0.0));
+        assert_that!(g.distance_to(&Vector4::point(0.0, 4.0, 0.0))).is_close_to(2.0, 0.0001);
+    }
+
+    #[test]
+    fn everything_is_infinitely_far_from_an_empty_group() {
+        let g = GroupBuilder::new().build();
+
+        assert_that!(g.distance_to(&Vector4::point(0.0, 0.0, 0.0))).is_equal_to(f32::INFINITY);
+    }
+}
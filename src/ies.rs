@@ -0,0 +1,328 @@
+use std::fmt::{Display, Error, Formatter};
+use std::path::Path;
+
+use crate::vector4::Vector4;
+
+/// The angular spread of a real light fixture, measured by its manufacturer and published as an
+/// IES (IESNA LM-63) photometric file.
+///
+/// Only type C photometry, the kind used for architectural fixtures, is supported. Its vertical
+/// angles run from 0° straight down to 180° straight up, and its horizontal angles go round the
+/// fixture from 0° along its length.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IesProfile {
+    vertical_angles: Vec<f32>,
+    horizontal_angles: Vec<f32>,
+    /// One row of candela values, one for each vertical angle, for each horizontal angle.
+    candela: Vec<Vec<f32>>,
+    peak: f32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseIesError(&'static str);
+
+impl std::error::Error for ParseIesError {}
+
+impl Display for ParseIesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "invalid IES file: {}", self.0)
+    }
+}
+
+impl IesProfile {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        Self::parse(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseIesError> {
+        // Keywords come first, one per line, and end with the TILT line
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find_map(|line| line.strip_prefix("TILT="))
+            .ok_or(ParseIesError("no TILT line"))?;
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f32>());
+        let mut next = || match numbers.next() {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(_)) => Err(ParseIesError("expected a number")),
+            None => Err(ParseIesError("the file ends too soon")),
+        };
+        // Counts must be whole numbers, and small enough that the data could follow them
+        let count = |value: f32| {
+            if value.fract() == 0.0 && (0.0..=text.len() as f32).contains(&value) {
+                Ok(value as usize)
+            } else {
+                Err(ParseIesError("expected a count"))
+            }
+        };
+
+        // Lamp tilt only matters for fixtures mounted at an angle, so the table is skipped
+        if tilt.trim() == "INCLUDE" {
+            next()?;
+            let pairs = count(next()?)?;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = count(next()?)?;
+        let horizontal_count = count(next()?)?;
+        if next()? != 1.0 {
+            return Err(ParseIesError("only type C photometry is supported"));
+        }
+        // Units, the luminous opening's size, ballast factor, a reserved value and input watts
+        for _ in 0..7 {
+            next()?;
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(ParseIesError("there must be at least one angle each way"));
+        }
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| next().map(|c| c * multiplier))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let ascending = |angles: &[f32]| angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !ascending(&vertical_angles) || !ascending(&horizontal_angles) {
+            return Err(ParseIesError("the angles must be in increasing order"));
+        }
+        let finite = |values: &[f32]| values.iter().all(|v| v.is_finite());
+        if !finite(&vertical_angles)
+            || !finite(&horizontal_angles)
+            || !candela.iter().all(|row| finite(row))
+        {
+            return Err(ParseIesError(
+                "the angles and candela values must be finite",
+            ));
+        }
+
+        Ok(Self::new(vertical_angles, horizontal_angles, candela))
+    }
+
+    fn new(vertical_angles: Vec<f32>, horizontal_angles: Vec<f32>, candela: Vec<Vec<f32>>) -> Self {
+        let peak = candela
+            .iter()
+            .flatten()
+            .fold(0.0_f32, |peak, &c| peak.max(c));
+
+        Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+            peak,
+        }
+    }
+
+    /// The brightest candela value in the profile.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    /// How bright the fixture is in `direction`, in the fixture's own space where it points down
+    /// -y and its length runs along x, from 0.0 to 1.0 at its brightest.
+    pub fn relative_intensity(&self, direction: Vector4) -> f32 {
+        if self.peak <= 0.0 {
+            return 0.0;
+        }
+
+        let direction = direction.normalize();
+        let vertical = (-direction.y).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = direction
+            .z
+            .atan2(direction.x)
+            .to_degrees()
+            .rem_euclid(360.0);
+
+        self.candela_at(vertical, self.fold_horizontal(horizontal)) / self.peak
+    }
+
+    /// Map a horizontal angle onto the range the file covers, using the symmetry that range
+    /// implies.
+    fn fold_horizontal(&self, angle: f32) -> f32 {
+        let last = *self.horizontal_angles.last().unwrap();
+        if last == 0.0 {
+            // The same all the way round
+            0.0
+        } else if last <= 90.0 {
+            // Each quadrant mirrors the first
+            let half = if angle > 180.0 { 360.0 - angle } else { angle };
+            if half > 90.0 {
+                180.0 - half
+            } else {
+                half
+            }
+        } else if last <= 180.0 {
+            // Each side mirrors the other
+            if angle > 180.0 {
+                360.0 - angle
+            } else {
+                angle
+            }
+        } else {
+            angle
+        }
+    }
+
+    fn candela_at(&self, vertical: f32, horizontal: f32) -> f32 {
+        let (h0, h1, ht) = Self::bracket(&self.horizontal_angles, horizontal);
+        let (v0, v1, vt) = match Self::bracket_within(&self.vertical_angles, vertical) {
+            Some(bracket) => bracket,
+            // No light is given off outside the measured angles
+            None => return 0.0,
+        };
+        let row = |h: usize| {
+            let values = &self.candela[h];
+            values[v0] + (values[v1] - values[v0]) * vt
+        };
+
+        row(h0) + (row(h1) - row(h0)) * ht
+    }
+
+    /// The indices either side of `angle` and how far it is between them, clamping to the ends.
+    fn bracket(angles: &[f32], angle: f32) -> (usize, usize, f32) {
+        let last = angles.len() - 1;
+        if angle <= angles[0] {
+            return (0, 0, 0.0);
+        }
+        if angle >= angles[last] {
+            return (last, last, 0.0);
+        }
+
+        let upper = angles.iter().position(|&a| a > angle).unwrap();
+        let (a0, a1) = (angles[upper - 1], angles[upper]);
+        (upper - 1, upper, (angle - a0) / (a1 - a0))
+    }
+
+    fn bracket_within(angles: &[f32], angle: f32) -> Option<(usize, usize, f32)> {
+        if angle < angles[0] || angle > angles[angles.len() - 1] {
+            return None;
+        }
+
+        Some(Self::bracket(angles, angle))
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::testing::mutations;
+
+    /// A downlight that is brightest straight down and gives off nothing above the horizontal.
+    const DOWNLIGHT: &str = "IESNA:LM-63-2002
+[TEST] a made up downlight
+[MANUFAC] nobody
+TILT=NONE
+1 1000 2.0 3 1 1 2 0.1 0.1 0.0
+1.0 1.0 20
+0 45 90
+0
+500 250 0
+";
+
+    #[fixture]
+    fn downlight() -> IesProfile {
+        IesProfile::parse(DOWNLIGHT).unwrap()
+    }
+
+    #[rstest]
+    fn parsing_a_profile(downlight: IesProfile) {
+        assert_that!(downlight.vertical_angles).is_equal_to(vec![0.0, 45.0, 90.0]);
+        assert_that!(downlight.horizontal_angles).is_equal_to(vec![0.0]);
+        assert_that!(downlight.candela).is_equal_to(vec![vec![1000.0, 500.0, 0.0]]);
+        assert_that!(downlight.peak()).is_equal_to(1000.0);
+    }
+
+    #[rstest]
+    #[case(Vector4::vector(0.0, -1.0, 0.0), 1.0)]
+    #[case(Vector4::vector(1.0, -1.0, 0.0), 0.5)]
+    #[case(Vector4::vector(0.0, -1.0, -1.0), 0.5)]
+    #[case(Vector4::vector(1.0, 0.0, 0.0), 0.0)]
+    #[case(Vector4::vector(0.0, 1.0, 0.0), 0.0)]
+    fn the_intensity_follows_the_profile(
+        downlight: IesProfile,
+        #[case] direction: Vector4,
+        #[case] expected: f32,
+    ) {
+        assert_that!(downlight.relative_intensity(direction)).is_close_to(expected, 0.0001);
+    }
+
+    #[rstest]
+    fn the_intensity_is_interpolated_between_angles(downlight: IesProfile) {
+        let direction = Vector4::vector(22.5_f32.to_radians().tan(), -1.0, 0.0);
+
+        assert_that!(downlight.relative_intensity(direction)).is_close_to(0.75, 0.0001);
+    }
+
+    #[rstest]
+    #[case(30.0, 30.0)]
+    #[case(120.0, 60.0)]
+    #[case(200.0, 20.0)]
+    #[case(330.0, 30.0)]
+    fn a_quadrant_profile_is_mirrored_all_the_way_round(#[case] angle: f32, #[case] expected: f32) {
+        let profile = IesProfile::new(vec![0.0], vec![0.0, 90.0], vec![vec![1.0], vec![1.0]]);
+
+        assert_that!(profile.fold_horizontal(angle)).is_close_to(expected, 0.0001);
+    }
+
+    #[test]
+    fn a_tilt_table_is_skipped() {
+        let text = DOWNLIGHT.replace("TILT=NONE", "TILT=INCLUDE\n1\n2\n0 90\n1.0 0.5");
+
+        assert_that!(IesProfile::parse(&text)).is_equal_to(IesProfile::parse(DOWNLIGHT));
+    }
+
+    #[rstest]
+    #[case("1 1000 1 3 1 1 2 0 0 0")]
+    #[case("TILT=NONE\n1 1000 1 3 1 1 2 0 0 0 1 1 20 0 45 90 0 500 250")]
+    #[case("TILT=NONE\n1 1000 1 3 1 2 2 0 0 0 1 1 20 0 45 90 0 500 250 0")]
+    #[case("TILT=NONE\n1 1000 1 3 1 1 2 0 0 0 1 1 20 0 90 45 0 500 250 0")]
+    #[case("TILT=NONE\n1 1000 1 3 1 1 2 0 0 0 1 1 20 0 45 x 0 500 250 0")]
+    fn parsing_a_bad_profile_fails(#[case] text: &str) {
+        assert_that!(IesProfile::parse(text)).is_err();
+    }
+
+    #[rstest]
+    #[case("TILT=NONE\n1 1000 1 1e30 1 1 2 0 0 0 1 1 20 0")]
+    #[case("TILT=NONE\n1 1000 1 2.5 1 1 2 0 0 0 1 1 20 0 90 0 1 1")]
+    #[case("TILT=INCLUDE\n1 -3\n1 1000 1 1 1 1 2 0 0 0 1 1 20 0 0 1")]
+    #[case("TILT=NONE\n1 1000 1 2 1 1 2 0 0 0 1 1 20 0 inf 0 1 1")]
+    #[case("TILT=NONE\n1 1000 NaN 2 1 1 2 0 0 0 1 1 20 0 90 0 1 1")]
+    fn parsing_an_impossible_profile_fails(#[case] text: &str) {
+        assert_that!(IesProfile::parse(text)).is_err();
+    }
+
+    #[test]
+    fn parsing_a_corrupted_profile_never_panics() {
+        for text in mutations(DOWNLIGHT, 2000) {
+            if let Ok(profile) = IesProfile::parse(&text) {
+                let _ = profile.relative_intensity(Vector4::vector(0.3, -1.0, 0.2));
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::light::PointLight;
+use crate::material::Material;
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// A shape placed in the scene under its own transform, and optionally its own material, without
+/// copying it. Many instances can share one shape, however large.
+///
+/// Unlike a group, an instance doesn't bake its transform into the shape, so it is intersected
+/// and shaded as a single object. Lighting is worked out in the instance's own space, so patterns
+/// move with the instance; this is exact for rotations, translations and uniform scales.
+#[derive(Clone, Debug)]
+pub struct Instance {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Option<Material>,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+    shape: Rc<dyn Shape>,
+}
+
+pub struct InstanceBuilder {
+    transform: Matrix<4>,
+    material: Option<Material>,
+    name: Option<String>,
+    user_data: UserData,
+    shape: Rc<dyn Shape>,
+}
+
+impl Instance {
+    /// The shared shape being instanced.
+    pub fn shape(&self) -> &Rc<dyn Shape> {
+        &self.shape
+    }
+
+    /// The shape, among those grouped under `shape`, whose surface `point` lies on.
+    fn leaf_at<'a>(shape: &'a Rc<dyn Shape>, point: &Vector4) -> &'a Rc<dyn Shape> {
+        match shape
+            .children()
+            .iter()
+            .min_by(|a, b| a.distance_to(point).total_cmp(&b.distance_to(point)))
+        {
+            Some(child) => Self::leaf_at(child, point),
+            None => shape,
+        }
+    }
+}
+
+impl Shape for Instance {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        self.material
+            .as_ref()
+            .unwrap_or_else(|| self.shape.material())
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: Some(material.clone()),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.shape.parent_space_bounds()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.shape.triangle_count()
+    }
+
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        let shape = if counted.insert(Rc::as_ptr(&self.shape) as *const ()) {
+            self.shape.memory_size(counted) + 2 * mem::size_of::<usize>()
+        } else {
+            0
+        };
+
+        mem::size_of_val(self) + shape
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        self.shape.intersect(ray)
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        Self::leaf_at(&self.shape, &object_point).normal_at(&object_point)
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        self.shape.closest_point(&object_point)
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        // Any profile is applied here, in world space, so the object's transform doesn't skew it
+        let object_light = PointLight::new(
+            self.inv_transform * light.position,
+            light.intensity_towards(point),
+        );
+        let object_point = self.inv_transform * point;
+        let object_eye = (self.inv_transform * eye_vector).normalize();
+        let mut object_normal = self.transformation().transpose() * normal_vector;
+        object_normal.w = 0.0;
+        let object_normal = object_normal.normalize();
+
+        match &self.material {
+            Some(material) => material.lighting(
+                &object_light,
+                object_point,
+                object_eye,
+                object_normal,
+                in_shadow,
+            ),
+            None => Self::leaf_at(&self.shape, &object_point).lighting(
+                &object_light,
+                object_point,
+                object_eye,
+                object_normal,
+                in_shadow,
+            ),
+        }
+    }
+}
+
+impl InstanceBuilder {
+    pub fn new(shape: Rc<dyn Shape>) -> Self {
+        Self {
+            transform: Matrix::identity(),
+            material: None,
+            name: None,
+            user_data: UserData::new(),
+            shape,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    /// Use `material` for the whole instance in place of the shape's own materials.
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+
+        self
+    }
+
+    pub fn build(self) -> Instance {
+        Instance {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+            shape: self.shape,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use crate::group::GroupBuilder;
+    use crate::material::MaterialBuilder;
+    use crate::sphere::SphereBuilder;
+    use crate::transform::Transform;
+    use crate::world::WorldBuilder;
+
+    use super::*;
+    use crate::assert_approx_eq;
+
+    #[fixture]
+    fn sphere() -> Rc<dyn Shape> {
+        Rc::new(SphereBuilder::new().build())
+    }
+
+    #[rstest]
+    fn instances_share_their_shape(sphere: Rc<dyn Shape>) {
+        let instances: Vec<Instance> = (0..3)
+            .map(|i| {
+                InstanceBuilder::new(Rc::clone(&sphere))
+                    .with_transform(Matrix::translation(3.0 * i as f32, 0.0, 0.0))
+                    .build()
+            })
+            .collect();
+
+        assert_that!(Rc::strong_count(&sphere)).is_equal_to(4);
+        for instance in instances.iter() {
+            assert_that!(Rc::ptr_eq(instance.shape(), &sphere)).is_true();
+        }
+    }
+
+    #[rstest]
+    fn intersecting_an_instance_applies_its_transform(sphere: Rc<dyn Shape>) {
+        let instance = InstanceBuilder::new(sphere)
+            .with_transform(Matrix::translation(5.0, 0.0, 0.0) * Matrix::scaling(2.0, 2.0, 2.0))
+            .build();
+        let r = Ray::new(
+            Vector4::point(5.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        assert_that!(instance.intersect(&r)).is_equal_to(vec![3.0, 7.0]);
+    }
+
+    #[rstest]
+    fn the_normal_on_an_instance_of_a_group_comes_from_the_child_hit(sphere: Rc<dyn Shape>) {
+        let group: Rc<dyn Shape> = Rc::new(
+            GroupBuilder::new()
+                .with_child(Rc::clone(&sphere))
+                .with_child(Rc::new(
+                    SphereBuilder::new()
+                        .with_transform(Matrix::translation(3.0, 0.0, 0.0))
+                        .build(),
+                ))
+                .build(),
+        );
+        let instance = InstanceBuilder::new(group)
+            .with_transform(Matrix::translation(0.0, 0.0, 10.0))
+            .build();
+
+        let n = instance.normal_at(&Vector4::point(4.0, 0.0, 10.0));
+
+        assert_that!(n).is_equal_to(Vector4::vector(1.0, 0.0, 0.0));
+    }
+
+    #[rstest]
+    fn the_bounds_of_an_instance_include_the_transform_of_its_shape(sphere: Rc<dyn Shape>) {
+        let group: Rc<dyn Shape> = Rc::new(
+            GroupBuilder::new()
+                .with_transform(Matrix::translation(1.0, 0.0, 0.0))
+                .with_child(sphere)
+                .build(),
+        );
+        let instance = InstanceBuilder::new(group)
+            .with_transform(Matrix::translation(0.0, 5.0, 0.0))
+            .build();
+
+        let bounds = instance.parent_space_bounds();
+
+        assert_that!(bounds.min).is_equal_to(Vector4::point(0.0, 4.0, -1.0));
+        assert_that!(bounds.max).is_equal_to(Vector4::point(2.0, 6.0, 1.0));
+    }
+
+    #[rstest]
+    fn an_instance_can_override_the_material(sphere: Rc<dyn Shape>) {
+        let plain = InstanceBuilder::new(Rc::clone(&sphere)).build();
+        let material = MaterialBuilder::new().with_ambient(1.0).build();
+        let overridden = InstanceBuilder::new(sphere.clone())
+            .with_material(material.clone())
+            .build();
+
+        assert_that!(plain.material()).is_equal_to(sphere.material());
+        assert_that!(overridden.material()).is_equal_to(&material);
+    }
+
+    #[rstest]
+    fn lighting_an_instance_matches_lighting_the_shape_in_place(sphere: Rc<dyn Shape>) {
+        let transform = Matrix::translation(0.0, 0.0, 10.0) * Matrix::rotation_y(1.0);
+        let instance = InstanceBuilder::new(sphere)
+            .with_transform(transform)
+            .build();
+        let in_place = SphereBuilder::new().with_transform(transform).build();
+        let light = PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+        let point = Vector4::point(0.0, 0.0, 9.0);
+        let eye = Vector4::vector(0.0, 0.0, -1.0);
+
+        let expected = in_place.lighting(&light, point, eye, in_place.normal_at(&point), false);
+        let actual = instance.lighting(&light, point, eye, instance.normal_at(&point), false);
+
+        assert_approx_eq!(actual, expected, 0.0001);
+    }
+
+    #[rstest]
+    fn hits_on_an_instance_refer_to_the_instance(sphere: Rc<dyn Shape>) {
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(
+                InstanceBuilder::new(Rc::clone(&sphere))
+                    .with_name("left")
+                    .with_transform(Matrix::translation(-2.0, 0.0, 0.0))
+                    .build(),
+            ))
+            .with_object(Rc::new(
+                InstanceBuilder::new(sphere)
+                    .with_name("right")
+                    .with_transform(Matrix::translation(2.0, 0.0, 0.0))
+                    .build(),
+            ))
+            .build();
+        let r = Ray::new(
+            Vector4::point(2.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = world.intersect(&r);
+
+        assert_that!(xs.hit().unwrap().object.name()).is_equal_to(Some("right"));
+    }
+}
@@ -3,8 +3,9 @@ use std::ops::Index;
 use std::rc::Rc;
 
 use crate::consts::EPSILON;
+use crate::material::Material;
 use crate::ray::Ray;
-use crate::shape::Shape;
+use crate::shape::{Shape, UserData};
 use crate::vector4::Vector4;
 
 #[derive(Clone, Debug)]
@@ -19,9 +20,23 @@ impl PartialEq for Intersection {
     }
 }
 
-#[derive(Debug)]
 pub struct Intersections {
     intersections: Vec<Intersection>,
+    /// The part of the ray that counts for hits: from `t_min` up to, but not including, `t_max`.
+    t_min: f32,
+    t_max: f32,
+}
+
+/// Each intersection as its `t` and the name of the shape, which is usually all that matters
+/// when looking at a list of them; the shapes themselves print at great length.
+impl std::fmt::Debug for Intersections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut list = f.debug_list();
+        for i in &self.intersections {
+            list.entry(&format_args!("{:.3} {}", i.t, i.object.name().unwrap_or("(unnamed)")));
+        }
+        list.finish()
+    }
 }
 
 pub struct Computations {
@@ -29,9 +44,44 @@ pub struct Computations {
     pub object: Rc<dyn Shape>,
     pub point: Vector4,
     pub over_point: Vector4,
+    pub under_point: Vector4,
     pub eye_vector: Vector4,
     pub normal_vector: Vector4,
+    pub uv: Option<(f32, f32)>, // texture coordinates, for shapes that have them
+    pub uv_derivatives: Option<(Vector4, Vector4)>, // dp/du and dp/dv, for shapes with a uv
+    pub user_data: Option<Rc<UserData>>, // the tags on the shape hit, if it has any
+    pub reflect_vector: Vector4,
     pub inside: bool,
+    pub n1: f32, // refractive index of the material the ray is leaving
+    pub n2: f32, // refractive index of the material the ray is entering
+    pub dispersion: Option<[(f32, f32); 3]>, // n1 and n2 for red, green and blue, if they differ
+}
+
+/// How far hit points are pushed off a surface so that rays leaving it don't hit it again
+/// ("acne"). The offset shrinks with the size of the object, so small objects don't leak light,
+/// and grows with the distance along the ray, as that is where f32 precision runs out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceBias {
+    pub absolute: f32, // offset for an unscaled object near the origin
+    pub relative: f32, // fraction of the distance from the origin or along the ray
+}
+
+impl Default for SurfaceBias {
+    fn default() -> Self {
+        Self {
+            absolute: EPSILON,
+            relative: 1.0e-6,
+        }
+    }
+}
+
+impl SurfaceBias {
+    pub fn offset(&self, object: &dyn Shape, point: Vector4, t: f32) -> f32 {
+        let object_scale = object.inv_transform().determinant().abs().cbrt().recip();
+        let distance = point.x.abs().max(point.y.abs()).max(point.z.abs()).max(t.abs());
+
+        (self.absolute * object_scale).max(self.relative * distance)
+    }
 }
 
 impl Intersection {
@@ -39,7 +89,28 @@ impl Intersection {
         Self { t, object }
     }
 
+    pub fn object_name(&self) -> Option<&str> {
+        self.object.name()
+    }
+
+    /// Prepare the computations for this intersection as if it were the only one along the ray.
     pub fn prepare_computations(&self, ray: &Ray) -> Computations {
+        self.prepare_computations_with(ray, &Intersections::from(vec![self.clone()]))
+    }
+
+    /// Prepare the computations for this intersection, using all the intersections along the ray
+    /// to work out which materials the ray is passing between.
+    pub fn prepare_computations_with(&self, ray: &Ray, xs: &Intersections) -> Computations {
+        self.prepare_computations_biased(ray, xs, &SurfaceBias::default())
+    }
+
+    /// As `prepare_computations_with`, offsetting the over and under points by `bias`.
+    pub fn prepare_computations_biased(
+        &self,
+        ray: &Ray,
+        xs: &Intersections,
+        bias: &SurfaceBias,
+    ) -> Computations {
         let point = ray.position(self.t);
         let eye_vector = -ray.direction;
 
@@ -50,17 +121,89 @@ impl Intersection {
             normal_vector = -normal_vector;
         }
 
-        let over_point = point + normal_vector * EPSILON;
+        let offset = bias.offset(self.object.as_ref(), point, self.t);
+        let over_point = point + normal_vector * offset;
+        let under_point = point - normal_vector * offset;
+        let reflect_vector = ray.direction.reflect(&normal_vector);
+        let (leaving, entering) = self.media(xs);
+        let index_of = |medium: Option<&Material>| medium.map_or(1.0, |m| m.refractive_index());
+        let indices_of = |medium: Option<&Material>| medium.and_then(|m| m.dispersion());
+        let (n1, n2) = (index_of(leaving), index_of(entering));
+        let dispersion = match (indices_of(leaving), indices_of(entering)) {
+            (None, None) => None,
+            (from, to) => {
+                let (from, to) = (from.unwrap_or([n1; 3]), to.unwrap_or([n2; 3]));
+                Some([0, 1, 2].map(|channel| (from[channel], to[channel])))
+            }
+        };
 
         Computations {
             t: self.t,
             object: Rc::clone(&self.object),
             point,
             over_point,
+            under_point,
             eye_vector,
             normal_vector,
+            uv: self.object.uv_at(&point),
+            uv_derivatives: self.object.uv_derivatives_at(&point),
+            user_data: self.object.user_data().cloned(),
+            reflect_vector,
             inside,
+            n1,
+            n2,
+            dispersion,
+        }
+    }
+
+    /// The materials the ray is leaving and entering at this intersection, or `None` for empty
+    /// space.
+    fn media<'a>(&self, xs: &'a Intersections) -> (Option<&'a Material>, Option<&'a Material>) {
+        let mut containers: Vec<&Rc<dyn Shape>> = Vec::new();
+        let mut leaving = None;
+        for i in xs.iter() {
+            let is_hit = i.t == self.t && Rc::ptr_eq(&i.object, &self.object);
+            if is_hit {
+                leaving = containers.last().map(|o| o.material());
+            }
+
+            match containers.iter().position(|o| Rc::ptr_eq(o, &i.object)) {
+                Some(index) => {
+                    containers.remove(index);
+                }
+                None => containers.push(&i.object),
+            }
+
+            if is_hit {
+                return (leaving, containers.last().map(|o| o.material()));
+            }
         }
+
+        (leaving, containers.last().map(|o| o.material()))
+    }
+}
+
+impl Computations {
+    pub fn object_name(&self) -> Option<&str> {
+        self.object.name()
+    }
+
+    /// Schlick's approximation of the Fresnel reflectance at the hit.
+    pub fn schlick(&self) -> f32 {
+        let mut cos = self.eye_vector.dot(&self.normal_vector);
+
+        // Total internal reflection can only happen when leaving a denser material
+        if self.n1 > self.n2 {
+            let n = self.n1 / self.n2;
+            let sin2_t = n.powi(2) * (1.0 - cos.powi(2));
+            if sin2_t > 1.0 {
+                return 1.0;
+            }
+            cos = (1.0 - sin2_t).sqrt();
+        }
+
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
 }
 
@@ -75,18 +218,46 @@ impl<'a> Intersections {
     }
 
     pub fn push(&mut self, intersection: Intersection) {
-        self.intersections.push(intersection);
-        self.sort();
+        // Insert in order rather than re-sorting, so the list is always sorted
+        let index = self
+            .intersections
+            .partition_point(|i| i.t <= intersection.t);
+        self.intersections.insert(index, intersection);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Intersection> {
+        self.intersections.iter()
+    }
+
+    /// Only count hits from `t_min` up to `t_max`, as for intersections along a ray limited to
+    /// that range. Intersections outside it are kept, as they still say which shapes the ray
+    /// is inside.
+    pub fn with_range(mut self, t_min: f32, t_max: f32) -> Self {
+        self.t_min = t_min;
+        self.t_max = t_max;
+        self
+    }
+
+    /// The intersections in the range that counts for hits, nearest first.
+    pub fn in_range(&self) -> impl Iterator<Item = &Intersection> {
+        let (t_min, t_max) = (self.t_min, self.t_max);
+        self.iter().filter(move |i| t_min <= i.t && i.t < t_max)
     }
 
     pub fn hit(&self) -> Option<&Intersection> {
-        for i in &self.intersections {
-            if i.t >= 0.0 {
-                return Some(i);
-            }
-        }
+        self.hit_sorted()
+    }
 
-        None
+    /// The first intersection in the range that counts, which unless it is set is every
+    /// non-negative t. The list is kept sorted, so this is a binary search rather than a scan.
+    pub fn hit_sorted(&self) -> Option<&Intersection> {
+        let index = self.intersections.partition_point(|i| i.t < self.t_min);
+        self.intersections.get(index).filter(|i| i.t < self.t_max)
+    }
+
+    /// Is there a hit in front of the ray before `t_max`? This is all a shadow ray needs to know.
+    pub fn any_hit_before(&self, t_max: f32) -> bool {
+        self.hit_sorted().is_some_and(|i| i.t < t_max)
     }
 }
 
@@ -102,10 +273,23 @@ impl<'a> Default for Intersections {
     fn default() -> Self {
         Self {
             intersections: Vec::new(),
+            t_min: 0.0,
+            t_max: f32::INFINITY,
         }
     }
 }
 
+impl From<Vec<Intersection>> for Intersections {
+    fn from(intersections: Vec<Intersection>) -> Self {
+        let mut xs = Self {
+            intersections,
+            ..Self::default()
+        };
+        xs.sort();
+        xs
+    }
+}
+
 impl<'a> IntoIterator for Intersections {
     type Item = Intersection;
     type IntoIter = std::vec::IntoIter<Intersection>;
@@ -121,9 +305,12 @@ Tests
 
 #[cfg(test)]
 mod tests {
+    use rstest::*;
     use spectral::prelude::*;
 
+    use crate::material::MaterialBuilder;
     use crate::matrix::Matrix;
+    use crate::plane::PlaneBuilder;
     use crate::ray::Ray;
     use crate::sphere::SphereBuilder;
     use crate::transform::Transform;
@@ -216,6 +403,90 @@ mod tests {
         assert_that!(i).is_some().is_equal_to(&i4);
     }
 
+    #[test]
+    fn pushing_keeps_intersections_sorted() {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let mut xs = Intersections::default();
+
+        for t in [5.0, -3.0, 7.0, 2.0, 5.0] {
+            xs.push(Intersection::new(t, Rc::clone(&s)));
+        }
+
+        let ts: Vec<f32> = xs.iter().map(|i| i.t).collect();
+        assert_that!(ts).is_equal_to(vec![-3.0, 2.0, 5.0, 5.0, 7.0]);
+    }
+
+    #[rstest]
+    #[case(vec![-3.0, 2.0, 5.0], Some(2.0))]
+    #[case(vec![0.0, 2.0], Some(0.0))]
+    #[case(vec![-2.0, -1.0], None)]
+    #[case(vec![], None)]
+    fn the_sorted_hit_is_the_first_nonnegative_intersection(
+        #[case] ts: Vec<f32>,
+        #[case] expected: Option<f32>,
+    ) {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let xs = Intersections::from(
+            ts.into_iter()
+                .map(|t| Intersection::new(t, Rc::clone(&s)))
+                .collect::<Vec<_>>(),
+        );
+
+        assert_that!(xs.hit_sorted().map(|i| i.t)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(2.5, true)]
+    #[case(2.0, false)]
+    #[case(1.0, false)]
+    fn checking_for_a_hit_before_a_distance(#[case] t_max: f32, #[case] expected: bool) {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let xs = Intersections::from(vec![
+            Intersection::new(-1.0, Rc::clone(&s)),
+            Intersection::new(2.0, Rc::clone(&s)),
+        ]);
+
+        assert_that!(xs.any_hit_before(t_max)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(0.0, f32::INFINITY, Some(1.0))]
+    #[case(1.5, f32::INFINITY, Some(3.0))]
+    #[case(-2.0, f32::INFINITY, Some(-1.0))]
+    #[case(1.5, 3.0, None)]
+    #[case(4.0, 9.0, None)]
+    fn the_hit_is_the_first_intersection_in_range(
+        #[case] t_min: f32,
+        #[case] t_max: f32,
+        #[case] expected: Option<f32>,
+    ) {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let xs = Intersections::from(vec![
+            Intersection::new(-1.0, Rc::clone(&s)),
+            Intersection::new(1.0, Rc::clone(&s)),
+            Intersection::new(3.0, Rc::clone(&s)),
+        ])
+        .with_range(t_min, t_max);
+
+        assert_that!(xs.hit().map(|i| i.t)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn listing_the_intersections_in_range() {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let xs = Intersections::from(vec![
+            Intersection::new(-1.0, Rc::clone(&s)),
+            Intersection::new(1.0, Rc::clone(&s)),
+            Intersection::new(3.0, Rc::clone(&s)),
+        ])
+        .with_range(0.0, 3.0);
+
+        let ts: Vec<f32> = xs.in_range().map(|i| i.t).collect();
+
+        assert_that!(ts).is_equal_to(vec![1.0]);
+        assert_that!(xs.len()).is_equal_to(3);
+    }
+
     #[test]
     fn precomputing_the_state_of_an_intersection() {
         let r = Ray::new(
@@ -284,4 +555,230 @@ mod tests {
         assert_that!(comps.over_point.z).is_less_than(-EPSILON / 2.0);
         assert_that!(comps.point.z).is_greater_than(comps.over_point.z);
     }
+
+    #[rstest]
+    #[case(Matrix::identity(), Vector4::point(0.0, 0.0, -1.0), 5.0, EPSILON)]
+    #[case(Matrix::scaling(0.001, 0.001, 0.001), Vector4::point(0.0, 0.0, 0.0), 0.0, 1.0e-8)]
+    #[case(Matrix::scaling(8.0, 8.0, 8.0), Vector4::point(0.0, 0.0, 0.0), 0.0, 8.0 * EPSILON)]
+    #[case(Matrix::identity(), Vector4::point(0.0, 1.0e5, 0.0), 10.0, 0.1)]
+    #[case(Matrix::identity(), Vector4::point(0.0, 0.0, 0.0), 1.0e5, 0.1)]
+    fn the_surface_offset_scales_with_the_object_and_distance(
+        #[case] transform: Matrix<4>,
+        #[case] point: Vector4,
+        #[case] t: f32,
+        #[case] expected: f32,
+    ) {
+        let shape = SphereBuilder::new().with_transform(transform).build();
+
+        let offset = SurfaceBias::default().offset(&shape, point, t);
+
+        assert_that!(offset).is_close_to(expected, expected * 0.001);
+    }
+
+    #[test]
+    fn the_over_and_under_points_use_the_given_bias() {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let shape: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let i = Intersection::new(4.0, shape);
+        let bias = SurfaceBias {
+            absolute: 0.25,
+            relative: 0.0,
+        };
+
+        let comps = i.prepare_computations_biased(&r, &Intersections::from(vec![i.clone()]), &bias);
+
+        assert_that!(comps.over_point).is_equal_to(Vector4::point(0.0, 0.0, -1.25));
+        assert_that!(comps.under_point).is_equal_to(Vector4::point(0.0, 0.0, -0.75));
+    }
+
+    #[test]
+    fn an_intersection_exposes_the_name_of_its_object() {
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().with_name("ball").build());
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let i = Intersection::new(4.0, s);
+        let comps = i.prepare_computations(&r);
+
+        assert_that!(i.object_name()).is_some().is_equal_to("ball");
+        assert_that!(comps.object_name()).is_some().is_equal_to("ball");
+    }
+
+    #[test]
+    fn precomputing_the_reflection_vector() {
+        let shape: Rc<dyn Shape> = Rc::new(PlaneBuilder::new().build());
+        let r = Ray::new(
+            Vector4::point(0.0, 1.0, -1.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f32.sqrt(), shape);
+
+        let comps = i.prepare_computations(&r);
+
+        assert_that!(comps.reflect_vector)
+            .is_equal_to(Vector4::vector(0.0, 2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0));
+    }
+
+    fn glass_sphere(transform: Matrix<4>, refractive_index: f32) -> Rc<dyn Shape> {
+        Rc::new(
+            SphereBuilder::new()
+                .with_transform(transform)
+                .with_material(
+                    MaterialBuilder::new()
+                        .with_transparency(1.0)
+                        .with_refractive_index(refractive_index)
+                        .build(),
+                )
+                .build(),
+        )
+    }
+
+    #[rstest]
+    #[case(0, 1.0, 1.5)]
+    #[case(1, 1.5, 2.0)]
+    #[case(2, 2.0, 2.5)]
+    #[case(3, 2.5, 2.5)]
+    #[case(4, 2.5, 1.5)]
+    #[case(5, 1.5, 1.0)]
+    fn finding_n1_and_n2_at_various_intersections(
+        #[case] index: usize,
+        #[case] n1: f32,
+        #[case] n2: f32,
+    ) {
+        let a = glass_sphere(Matrix::scaling(2.0, 2.0, 2.0), 1.5);
+        let b = glass_sphere(Matrix::translation(0.0, 0.0, -0.25), 2.0);
+        let c = glass_sphere(Matrix::translation(0.0, 0.0, 0.25), 2.5);
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -4.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = Intersections::from(vec![
+            Intersection::new(2.0, Rc::clone(&a)),
+            Intersection::new(2.75, Rc::clone(&b)),
+            Intersection::new(3.25, Rc::clone(&c)),
+            Intersection::new(4.75, b),
+            Intersection::new(5.25, c),
+            Intersection::new(6.0, a),
+        ]);
+
+        let comps = xs[index].prepare_computations_with(&r, &xs);
+
+        assert_that!(comps.n1).is_equal_to(n1);
+        assert_that!(comps.n2).is_equal_to(n2);
+    }
+
+    #[test]
+    fn finding_the_refractive_indices_of_each_channel() {
+        let prism: Rc<dyn Shape> = Rc::new(
+            SphereBuilder::new()
+                .with_material(
+                    MaterialBuilder::new()
+                        .with_transparency(1.0)
+                        .with_refractive_index(1.5)
+                        .with_dispersion([1.48, 1.5, 1.52])
+                        .build(),
+                )
+                .build(),
+        );
+        let plain = glass_sphere(Matrix::scaling(2.0, 2.0, 2.0), 1.3);
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -4.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = Intersections::from(vec![
+            Intersection::new(2.0, Rc::clone(&plain)),
+            Intersection::new(3.0, Rc::clone(&prism)),
+            Intersection::new(5.0, prism),
+            Intersection::new(6.0, plain),
+        ]);
+
+        let entering = xs[1].prepare_computations_with(&r, &xs);
+        let leaving = xs[2].prepare_computations_with(&r, &xs);
+
+        assert_that!(entering.dispersion)
+            .is_equal_to(Some([(1.3, 1.48), (1.3, 1.5), (1.3, 1.52)]));
+        assert_that!(leaving.dispersion).is_equal_to(Some([(1.48, 1.3), (1.5, 1.3), (1.52, 1.3)]));
+        assert_that!(xs[0].prepare_computations_with(&r, &xs).dispersion).is_none();
+    }
+
+    #[test]
+    fn the_under_point_is_offset_below_the_surface() {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let shape = glass_sphere(Matrix::translation(0.0, 0.0, 1.0), 1.5);
+        let i = Intersection::new(5.0, shape);
+        let xs = Intersections::from(vec![i.clone()]);
+
+        let comps = i.prepare_computations_with(&r, &xs);
+
+        assert_that!(comps.under_point.z).is_greater_than(EPSILON / 2.0);
+        assert_that!(comps.point.z).is_less_than(comps.under_point.z);
+    }
+
+    #[test]
+    fn the_schlick_approximation_under_total_internal_reflection() {
+        let shape = glass_sphere(Matrix::identity(), 1.5);
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 2.0_f32.sqrt() / 2.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+        let xs = Intersections::from(vec![
+            Intersection::new(-2.0_f32.sqrt() / 2.0, Rc::clone(&shape)),
+            Intersection::new(2.0_f32.sqrt() / 2.0, shape),
+        ]);
+
+        let comps = xs[1].prepare_computations_with(&r, &xs);
+
+        assert_that!(comps.schlick()).is_equal_to(1.0);
+    }
+
+    #[test]
+    fn the_schlick_approximation_with_a_perpendicular_viewing_angle() {
+        let shape = glass_sphere(Matrix::identity(), 1.5);
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+        let xs = Intersections::from(vec![
+            Intersection::new(-1.0, Rc::clone(&shape)),
+            Intersection::new(1.0, shape),
+        ]);
+
+        let comps = xs[1].prepare_computations_with(&r, &xs);
+
+        assert_that!(comps.schlick()).is_close_to(0.04, 0.0001);
+    }
+
+    #[test]
+    fn the_schlick_approximation_with_small_angle_and_n2_greater_than_n1() {
+        let shape = glass_sphere(Matrix::identity(), 1.5);
+        let r = Ray::new(
+            Vector4::point(0.0, 0.99, -2.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = Intersections::from(vec![Intersection::new(1.8589, shape)]);
+
+        let comps = xs[0].prepare_computations_with(&r, &xs);
+
+        assert_that!(comps.schlick()).is_close_to(0.48873, 0.0001);
+    }
+
+    #[test]
+    fn debugging_intersections_lists_t_and_the_shape_name() {
+        let named: Rc<dyn Shape> = Rc::new(SphereBuilder::new().with_name("ball").build());
+        let unnamed: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let xs = Intersections::from(vec![
+            Intersection::new(1.5, Rc::clone(&named)),
+            Intersection::new(-2.0, Rc::clone(&unnamed)),
+        ]);
+
+        assert_that!(format!("{:?}", xs).as_str()).is_equal_to("[-2.000 (unnamed), 1.500 ball]");
+    }
 }
@@ -0,0 +1,837 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::info_span;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::triangle::{Triangle, TriangleBuilder};
+use crate::vector4::Vector4;
+
+/// How many triangles go in a chunk unless the builder is told otherwise.
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+/// The bytes a face takes in a page file: three corners of three little-endian `f32`s.
+const FACE_BYTES: usize = 36;
+
+/// A triangle mesh too big to keep whole as shapes, such as a scanned model with millions of
+/// faces. Only its points and the corners of each face are kept; the triangles are built, with a
+/// tree of boxes round them, a chunk at a time when a ray first reaches that chunk. Chunks that
+/// no ray reaches cost almost nothing.
+///
+/// The faces are put in chunks of nearby faces, so a ray only builds the chunks it passes near.
+/// The builder can cap how many chunks are kept built at once, dropping the one used longest ago
+/// to make room. That bounds the memory taken by triangles and their boxes.
+///
+/// Built with `build`, every point and face stays in memory. Built with `build_paged`, the
+/// corners of the faces are written to a file a chunk at a time and read back when a chunk is
+/// built, so that only the box round each chunk stays in memory. Rendering is slower, as chunks
+/// are read from disk each time they are built again, but scenes of meshes that together don't
+/// fit in memory can still render, as long as they are loaded one mesh at a time.
+///
+/// The triangles are flat, and shaded with the mesh's material.
+#[derive(Clone, Debug)]
+pub struct LazyMesh {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+    // Note: shared with transformed copies, so they don't build the same chunks twice
+    data: Rc<MeshData>,
+    // The corners of the faces crossed by the last ray through the mesh, so that the normal
+    // where one of them was hit can be found without searching the mesh for it
+    hits: RefCell<Vec<[Vector4; 3]>>,
+}
+
+pub struct LazyMeshBuilder {
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+    points: Vec<Vector4>,
+    faces: Vec<[usize; 3]>,
+    chunk_size: usize,
+    max_built_chunks: usize,
+}
+
+#[derive(Debug)]
+struct MeshData {
+    faces: Faces,
+    chunks: Vec<Chunk>,
+    bounds: BoundingBox,
+    max_built_chunks: usize,
+    built_chunks: Cell<usize>,
+    // Counts up on every use of a chunk, to find the one used longest ago
+    clock: Cell<u64>,
+}
+
+/// Where the corners of the faces are kept. Either way the faces are in chunk order, so a chunk
+/// is a run of them.
+#[derive(Debug)]
+enum Faces {
+    /// In memory, as indices into the points.
+    Resident {
+        points: Vec<Vector4>,
+        faces: Vec<[u32; 3]>,
+    },
+    /// In a file, read a chunk at a time.
+    Paged(PageFile),
+}
+
+/// The file the corners of a paged mesh's faces are kept in, removed when the mesh is dropped.
+#[derive(Debug)]
+struct PageFile {
+    path: PathBuf,
+    file: RefCell<File>,
+}
+
+#[derive(Debug)]
+struct Chunk {
+    bounds: BoundingBox,
+    /// The faces of the chunk, as where they start among the mesh's faces and how many there are.
+    first_face: usize,
+    len: usize,
+    tree: RefCell<Option<Rc<ChunkTree>>>,
+    last_used: Cell<u64>,
+}
+
+/// The triangles of a chunk, and a binary tree of boxes round them in heap order, with the
+/// children of box `i` at `2i + 1` and `2i + 2`, and the box round triangle `j` at
+/// `triangles.len() - 1 + j`.
+#[derive(Debug)]
+struct ChunkTree {
+    triangles: Vec<Triangle>,
+    boxes: Vec<BoundingBox>,
+}
+
+impl LazyMesh {
+    /// How many chunks the faces were put in.
+    pub fn chunks(&self) -> usize {
+        self.data.chunks.len()
+    }
+
+    /// How many chunks have their triangles built just now.
+    pub fn built_chunks(&self) -> usize {
+        self.data.built_chunks.get()
+    }
+}
+
+impl MeshData {
+    /// The corners of each face in `chunk`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk is paged out and its file can't be read, as when it has been removed
+    /// while the mesh was in use.
+    fn corners(&self, chunk: &Chunk) -> Vec<[Vector4; 3]> {
+        let range = chunk.first_face..chunk.first_face + chunk.len;
+        match &self.faces {
+            Faces::Resident { points, faces } => faces[range]
+                .iter()
+                .map(|face| face.map(|corner| points[corner as usize]))
+                .collect(),
+            Faces::Paged(page) => page
+                .read(range.start, range.len())
+                .unwrap_or_else(|e| panic!("The mesh's page file couldn't be read: {}", e)),
+        }
+    }
+
+    /// Roughly how many bytes the mesh takes once as many chunks are built as may be at once,
+    /// which is what rendering it comes to.
+    fn memory_size(&self) -> usize {
+        let faces = match &self.faces {
+            Faces::Resident { points, faces } => {
+                points.capacity() * mem::size_of::<Vector4>()
+                    + faces.capacity() * mem::size_of::<[u32; 3]>()
+            }
+            // On disk until they are read into the trees
+            Faces::Paged(_) => 0,
+        };
+        let chunks = self.chunks.len() * mem::size_of::<Chunk>();
+
+        // A built chunk has a triangle and two boxes in its tree for each face
+        let mut lens: Vec<usize> = self.chunks.iter().map(|chunk| chunk.len).collect();
+        lens.sort_unstable_by(|a, b| b.cmp(a));
+        let built: usize = lens.iter().take(self.max_built_chunks).sum();
+        let trees = built * (mem::size_of::<Triangle>() + 2 * mem::size_of::<BoundingBox>());
+
+        mem::size_of::<Self>() + faces + chunks + trees
+    }
+
+    /// The triangles of chunk `index`, built if they aren't already.
+    fn tree(&self, index: usize) -> Rc<ChunkTree> {
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+        let chunk = &self.chunks[index];
+        chunk.last_used.set(now);
+        if let Some(tree) = chunk.tree.borrow().as_ref() {
+            return Rc::clone(tree);
+        }
+
+        let tree = {
+            let _span = info_span!("mesh.chunk", index = index, faces = chunk.len).entered();
+            Rc::new(ChunkTree::new(&self.corners(chunk)))
+        };
+        *chunk.tree.borrow_mut() = Some(Rc::clone(&tree));
+        self.built_chunks.set(self.built_chunks.get() + 1);
+        if self.built_chunks.get() > self.max_built_chunks {
+            let oldest = self
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.tree.borrow().is_some())
+                .min_by_key(|chunk| chunk.last_used.get());
+            if let Some(oldest) = oldest {
+                *oldest.tree.borrow_mut() = None;
+                self.built_chunks.set(self.built_chunks.get() - 1);
+            }
+        }
+
+        tree
+    }
+
+    /// The triangle whose surface is closest to `point`, looking in the nearest chunks first.
+    fn nearest_triangle(&self, point: Vector4) -> Option<(Rc<ChunkTree>, usize)> {
+        let mut order: Vec<(f32, usize)> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| (distance_to_box(&chunk.bounds, point), index))
+            .collect();
+        order.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut nearest: Option<(f32, Rc<ChunkTree>, usize)> = None;
+        for (distance, index) in order {
+            if nearest.as_ref().is_some_and(|n| n.0 <= distance) {
+                break;
+            }
+            let tree = self.tree(index);
+            if let Some((d, triangle)) = tree.nearest(point) {
+                if nearest.as_ref().is_none_or(|n| d < n.0) {
+                    nearest = Some((d, tree, triangle));
+                }
+            }
+        }
+
+        nearest.map(|(_, tree, triangle)| (tree, triangle))
+    }
+}
+
+impl PageFile {
+    /// Write the corners of `faces` to a new file at `path`, in place of anything there.
+    fn write(path: &Path, faces: impl Iterator<Item = [Vector4; 3]>) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        // Made before writing, so that the file is removed if writing it fails
+        let page = Self {
+            path: path.to_path_buf(),
+            file: RefCell::new(file),
+        };
+
+        {
+            let file = page.file.borrow();
+            let mut writer = BufWriter::new(&*file);
+            for corners in faces {
+                for corner in corners {
+                    for value in [corner.x, corner.y, corner.z] {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                }
+            }
+            writer.flush()?;
+        }
+
+        Ok(page)
+    }
+
+    /// The corners of `count` faces, starting from face `first`.
+    fn read(&self, first: usize, count: usize) -> std::io::Result<Vec<[Vector4; 3]>> {
+        let mut bytes = vec![0; count * FACE_BYTES];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start((first * FACE_BYTES) as u64))?;
+        file.read_exact(&mut bytes)?;
+
+        let value = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        Ok(bytes
+            .chunks_exact(FACE_BYTES)
+            .map(|face| {
+                [0, 12, 24].map(|at| {
+                    let corner = &face[at..at + 12];
+                    Vector4::point(value(corner), value(&corner[4..]), value(&corner[8..]))
+                })
+            })
+            .collect())
+    }
+}
+
+impl Drop for PageFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl ChunkTree {
+    fn new(corners: &[[Vector4; 3]]) -> Self {
+        let triangles: Vec<Triangle> = corners
+            .iter()
+            .map(|&[a, b, c]| TriangleBuilder::new(a, b, c).build())
+            .collect();
+
+        let count = triangles.len();
+        let mut boxes = vec![BoundingBox::empty(); 2 * count - 1];
+        for (i, triangle) in triangles.iter().enumerate() {
+            boxes[count - 1 + i] = triangle.bounds();
+        }
+        for node in (0..count - 1).rev() {
+            let mut bounds = boxes[2 * node + 1];
+            bounds.merge(&boxes[2 * node + 2]);
+            boxes[node] = bounds;
+        }
+
+        Self { triangles, boxes }
+    }
+
+    /// Add where `ray` crosses each triangle to `hits`, along with which triangle it is.
+    fn intersect(&self, ray: &Ray, hits: &mut Vec<(f32, usize)>) {
+        let first_triangle = self.triangles.len() - 1;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if self.boxes[node].span(ray).is_none() {
+                continue;
+            }
+            if node < first_triangle {
+                stack.push(2 * node + 1);
+                stack.push(2 * node + 2);
+            } else {
+                let index = node - first_triangle;
+                let xs = self.triangles[index].local_intersect(ray);
+                hits.extend(xs.into_iter().map(|t| (t, index)));
+            }
+        }
+    }
+
+    /// How far `point` is from the nearest triangle, and which triangle that is.
+    fn nearest(&self, point: Vector4) -> Option<(f32, usize)> {
+        let first_triangle = self.triangles.len() - 1;
+        let mut nearest: Option<(f32, usize)> = None;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if nearest.is_some_and(|(d, _)| d <= distance_to_box(&self.boxes[node], point)) {
+                continue;
+            }
+            if node < first_triangle {
+                stack.push(2 * node + 1);
+                stack.push(2 * node + 2);
+                continue;
+            }
+
+            let index = node - first_triangle;
+            let d = (self.triangles[index].local_closest_point(point) - point).magnitude();
+            if nearest.is_none_or(|(best, _)| d < best) {
+                nearest = Some((d, index));
+            }
+        }
+
+        nearest
+    }
+}
+
+/// How far `point` is from the nearest part of `bounds`, or 0 if it is inside.
+fn distance_to_box(bounds: &BoundingBox, point: Vector4) -> f32 {
+    let (min, max) = (bounds.min, bounds.max);
+    let clamped = Vector4::point(
+        point.x.clamp(min.x, max.x),
+        point.y.clamp(min.y, max.y),
+        point.z.clamp(min.z, max.z),
+    );
+
+    (clamped - point).magnitude()
+}
+
+impl Shape for LazyMesh {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.data.bounds
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.data.chunks.iter().map(|chunk| chunk.len).sum()
+    }
+
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        let hits = self.hits.borrow().capacity() * mem::size_of::<[Vector4; 3]>();
+        let data = if counted.insert(Rc::as_ptr(&self.data) as *const ()) {
+            self.data.memory_size() + 2 * mem::size_of::<usize>()
+        } else {
+            0
+        };
+
+        mem::size_of_val(self) + hits + data
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let mut faces = self.hits.borrow_mut();
+        faces.clear();
+        let mut xs = Vec::new();
+        let mut hits = Vec::new();
+        for (index, chunk) in self.data.chunks.iter().enumerate() {
+            if chunk.bounds.span(ray).is_some() {
+                let tree = self.data.tree(index);
+                tree.intersect(ray, &mut hits);
+                for (t, triangle) in hits.drain(..) {
+                    xs.push(t);
+                    faces.push(tree.triangles[triangle].vertices());
+                }
+            }
+        }
+        xs.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        xs
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        // Hits are shaded straight after they are found, so the face should be among the last
+        // ray's. Allow for rounding in the hit point, which grows with the size of the mesh.
+        let extent = self.data.bounds.max - self.data.bounds.min;
+        let tolerance = EPSILON * extent.x.max(extent.y).max(extent.z).max(1.0);
+        let hit = self
+            .hits
+            .borrow()
+            .iter()
+            .map(|&[a, b, c]| {
+                let triangle = TriangleBuilder::new(a, b, c).build();
+                let distance =
+                    (triangle.local_closest_point(object_point) - object_point).magnitude();
+                (distance, triangle)
+            })
+            .filter(|(distance, _)| *distance < tolerance)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, triangle)) = hit {
+            return triangle.local_normal_at(object_point);
+        }
+
+        // Otherwise the point wasn't just hit, as when the mesh is shared by several instances
+        // and another was intersected since, so look for the face
+        match self.data.nearest_triangle(object_point) {
+            Some((tree, index)) => tree.triangles[index].local_normal_at(object_point),
+            None => panic!("An empty mesh has no surface, so no normal"),
+        }
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        match self.data.nearest_triangle(object_point) {
+            Some((tree, index)) => tree.triangles[index].local_closest_point(object_point),
+            // An empty mesh has no surface, so everything is infinitely far from it
+            None => Vector4::point(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl LazyMeshBuilder {
+    /// A mesh of triangles between `points`, each face giving the indices of its corners in the
+    /// order that `Triangle` takes its corners.
+    pub fn new(points: Vec<Vector4>, faces: Vec<[usize; 3]>) -> Self {
+        assert!(
+            faces.iter().flatten().all(|&i| i < points.len()),
+            "A face refers to a point that isn't there"
+        );
+        assert!(
+            points.len() <= u32::MAX as usize,
+            "The mesh has more points than can be indexed"
+        );
+
+        Self {
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+            points,
+            faces,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_built_chunks: usize::MAX,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    /// How many triangles to put in each chunk.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+
+        self
+    }
+
+    /// The most chunks to keep built at once. By default every chunk a ray reaches stays built.
+    pub fn with_max_built_chunks(mut self, max_built_chunks: usize) -> Self {
+        self.max_built_chunks = max_built_chunks.max(1);
+
+        self
+    }
+
+    pub fn build(mut self) -> LazyMesh {
+        let (bounds, faces, chunks) = self.chunks();
+        let points = mem::take(&mut self.points);
+
+        self.finish(Faces::Resident { points, faces }, bounds, chunks)
+    }
+
+    /// Build the mesh with the corners of its faces written to a file at `path`, in place of
+    /// anything there, and read back a chunk at a time as rays reach them, so that they don't
+    /// take up memory. The file is removed when the mesh is dropped.
+    pub fn build_paged(self, path: impl AsRef<Path>) -> std::io::Result<LazyMesh> {
+        let (bounds, faces, chunks) = self.chunks();
+        let corners = faces
+            .iter()
+            .map(|face| face.map(|corner| self.points[corner as usize]));
+        let page = PageFile::write(path.as_ref(), corners)?;
+
+        Ok(self.finish(Faces::Paged(page), bounds, chunks))
+    }
+
+    /// The bounds of the mesh, and its faces in chunk order with the chunks they make.
+    fn chunks(&self) -> (BoundingBox, Vec<[u32; 3]>, Vec<Chunk>) {
+        let points = &self.points;
+        let mut bounds = BoundingBox::empty();
+        for &point in points {
+            bounds.add_point(point);
+        }
+
+        // Faces with no area can never be hit, so leave them out. Then sort the rest along a
+        // curve that keeps nearby faces together, so each run of them makes a compact chunk.
+        let mut faces: Vec<(u32, [u32; 3])> = self
+            .faces
+            .iter()
+            .filter(|&&[a, b, c]| {
+                let normal = (points[c] - points[a]).cross_product(&(points[b] - points[a]));
+                normal.magnitude() > 0.0
+            })
+            .map(|&[a, b, c]| {
+                let centroid = (points[a] + points[b] + points[c]) / 3.0;
+                (
+                    morton_code(&bounds, centroid),
+                    [a as u32, b as u32, c as u32],
+                )
+            })
+            .collect();
+        faces.sort_unstable_by_key(|&(code, _)| code);
+
+        let chunks = faces
+            .chunks(self.chunk_size)
+            .enumerate()
+            .map(|(index, run)| {
+                let mut chunk_bounds = BoundingBox::empty();
+                for &(_, face) in run {
+                    for corner in face {
+                        chunk_bounds.add_point(points[corner as usize]);
+                    }
+                }
+                Chunk {
+                    bounds: chunk_bounds,
+                    first_face: index * self.chunk_size,
+                    len: run.len(),
+                    tree: RefCell::new(None),
+                    last_used: Cell::new(0),
+                }
+            })
+            .collect();
+
+        (
+            bounds,
+            faces.into_iter().map(|(_, face)| face).collect(),
+            chunks,
+        )
+    }
+
+    fn finish(self, faces: Faces, bounds: BoundingBox, chunks: Vec<Chunk>) -> LazyMesh {
+        LazyMesh {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+            data: Rc::new(MeshData {
+                faces,
+                chunks,
+                bounds,
+                max_built_chunks: self.max_built_chunks,
+                built_chunks: Cell::new(0),
+                clock: Cell::new(0),
+            }),
+            hits: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+/// Where `point` falls along a Z-order curve through `bounds`, ten bits to an axis. Points close
+/// together along the curve are close together in space.
+fn morton_code(bounds: &BoundingBox, point: Vector4) -> u32 {
+    let cell = |value: f32, min: f32, max: f32| {
+        let fraction = if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.0
+        };
+        ((fraction * 1024.0) as u32).min(1023)
+    };
+    let (min, max) = (bounds.min, bounds.max);
+    let cells = [
+        cell(point.x, min.x, max.x),
+        cell(point.y, min.y, max.y),
+        cell(point.z, min.z, max.z),
+    ];
+
+    (0..10).fold(0, |code, bit| {
+        cells.iter().enumerate().fold(code, |code, (axis, &c)| {
+            code | (((c >> bit) & 1) << (3 * bit + axis))
+        })
+    })
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::transform::Transform;
+
+    /// A floor of `n` by `n` unit squares, each cut into two triangles facing up, from the
+    /// origin along +x and +z.
+    fn floor_mesh(n: usize) -> LazyMeshBuilder {
+        let index = |i: usize, j: usize| j * (n + 1) + i;
+        let points = (0..=n)
+            .flat_map(|j| (0..=n).map(move |i| Vector4::point(i as f32, 0.0, j as f32)))
+            .collect();
+        let mut faces = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                faces.push([index(i, j), index(i + 1, j + 1), index(i, j + 1)]);
+                faces.push([index(i, j), index(i + 1, j), index(i + 1, j + 1)]);
+            }
+        }
+
+        LazyMeshBuilder::new(points, faces)
+    }
+
+    #[fixture]
+    fn floor() -> LazyMesh {
+        floor_mesh(8).with_chunk_size(8).build()
+    }
+
+    fn down_from(x: f32, z: f32) -> Ray {
+        Ray::new(Vector4::point(x, 5.0, z), Vector4::vector(0.0, -1.0, 0.0))
+    }
+
+    #[rstest]
+    fn the_faces_are_put_in_chunks(floor: LazyMesh) {
+        assert_that!(floor.chunks()).is_equal_to(16);
+        assert_that!(floor.built_chunks()).is_equal_to(0);
+        assert_that!(floor.bounds()).is_equal_to(BoundingBox::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::point(8.0, 0.0, 8.0),
+        ));
+    }
+
+    #[rstest]
+    fn a_ray_only_builds_the_chunks_it_reaches(floor: LazyMesh) {
+        let xs = floor.intersect(&down_from(2.3, 5.6));
+
+        assert_that!(xs).is_equal_to(vec![5.0]);
+        assert_that!(floor.built_chunks()).is_equal_to(1);
+    }
+
+    #[rstest]
+    fn a_ray_that_misses_the_mesh_builds_nothing(floor: LazyMesh) {
+        assert_that!(floor.intersect(&down_from(9.5, 2.5))).is_empty();
+        assert_that!(floor.built_chunks()).is_equal_to(0);
+    }
+
+    #[rstest]
+    fn the_normal_is_that_of_the_nearest_triangle(floor: LazyMesh) {
+        let normal = floor.normal_at(&Vector4::point(6.2, 0.0, 1.7));
+
+        assert_that!(normal).is_equal_to(Vector4::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn the_normal_where_a_ray_hit_is_found_without_building_more_chunks() {
+        let floor = floor_mesh(8)
+            .with_chunk_size(8)
+            .with_max_built_chunks(1)
+            .build();
+        let ray = down_from(6.2, 1.7);
+
+        let t = floor.intersect(&ray)[0];
+        let normal = floor.normal_at(&ray.position(t));
+
+        assert_that!(normal).is_equal_to(Vector4::vector(0.0, 1.0, 0.0));
+        assert_that!(floor.built_chunks()).is_equal_to(1);
+        assert_that!(floor.data.clock.get()).is_equal_to(1);
+    }
+
+    #[test]
+    fn the_memory_a_mesh_will_take_allows_for_the_chunks_it_may_build() {
+        let capped = floor_mesh(8)
+            .with_chunk_size(8)
+            .with_max_built_chunks(2)
+            .build();
+        let uncapped = floor_mesh(8).with_chunk_size(8).build();
+        let tree = 8 * (mem::size_of::<Triangle>() + 2 * mem::size_of::<BoundingBox>());
+
+        let capped_size = capped.memory_size(&mut HashSet::new());
+        let uncapped_size = uncapped.memory_size(&mut HashSet::new());
+
+        assert_that!(uncapped.triangle_count()).is_equal_to(128);
+        assert_that!(uncapped_size - capped_size).is_equal_to(14 * tree);
+    }
+
+    #[test]
+    fn chunks_used_longest_ago_are_dropped_to_stay_within_the_limit() {
+        let floor = floor_mesh(8)
+            .with_chunk_size(8)
+            .with_max_built_chunks(2)
+            .build();
+
+        for (x, z) in [(0.3, 0.6), (7.3, 7.6), (0.3, 7.6), (7.3, 0.6)] {
+            assert_that!(floor.intersect(&down_from(x, z))).is_equal_to(vec![5.0]);
+        }
+
+        assert_that!(floor.built_chunks()).is_equal_to(2);
+    }
+
+    #[test]
+    fn a_transformed_mesh_hits_where_it_has_been_moved() {
+        let floor = floor_mesh(4)
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .build();
+
+        assert_that!(floor.intersect(&down_from(1.3, 2.6))).is_equal_to(vec![4.0]);
+    }
+
+    #[test]
+    fn faces_with_no_area_are_left_out() {
+        let points = vec![
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::point(1.0, 0.0, 0.0),
+            Vector4::point(2.0, 0.0, 0.0),
+            Vector4::point(0.0, 0.0, 1.0),
+        ];
+        let mesh = LazyMeshBuilder::new(points, vec![[0, 1, 2], [0, 3, 1]]).build();
+
+        assert_that!(mesh.chunks()).is_equal_to(1);
+        assert_that!(mesh.intersect(&down_from(0.2, 0.3))).is_equal_to(vec![5.0]);
+    }
+
+    #[test]
+    fn a_paged_mesh_is_read_back_from_its_file() {
+        let path = std::env::temp_dir().join(format!("lazy-mesh-{}.page", std::process::id()));
+        let paged = floor_mesh(8)
+            .with_chunk_size(8)
+            .with_max_built_chunks(1)
+            .build_paged(&path)
+            .unwrap();
+        let resident = floor_mesh(8).with_chunk_size(8).build();
+
+        // Each ray reaches a chunk the last one didn't, so every chunk is read again
+        for (x, z) in [(0.3, 0.6), (7.3, 7.6), (0.3, 0.6), (6.2, 1.7)] {
+            let ray = down_from(x, z);
+            let xs = paged.intersect(&ray);
+            assert_that!(xs).is_equal_to(resident.intersect(&ray));
+            assert_that!(paged.normal_at(&ray.position(xs[0])))
+                .is_equal_to(resident.normal_at(&ray.position(xs[0])));
+        }
+        assert_that!(paged.built_chunks()).is_equal_to(1);
+        assert_that!(paged.triangle_count()).is_equal_to(128);
+        assert_that!(paged.memory_size(&mut HashSet::new()))
+            .is_less_than(resident.memory_size(&mut HashSet::new()));
+        assert_that!(std::fs::metadata(&path).unwrap().len()).is_equal_to(128 * 36);
+
+        drop(paged);
+
+        assert_that!(path.exists()).is_false();
+    }
+}
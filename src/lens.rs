@@ -0,0 +1,220 @@
+//! Where on the lens a camera ray starts from, for depth of field. The shape of the aperture is
+//! the shape out-of-focus highlights take, so a polygonal one gives the hexagons of a real lens
+//! with six blades.
+
+use std::f32::consts::{FRAC_PI_4, PI};
+
+use crate::canvas::Canvas;
+
+/// The shape of the opening in the lens, fitting inside a circle of radius 1.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Aperture {
+    /// A perfect circle.
+    #[default]
+    Circle,
+    /// A regular polygon, as formed by `blades` straight diaphragm blades, turned `rotation`
+    /// radians anticlockwise from a corner pointing along +x.
+    Polygon { blades: u32, rotation: f32 },
+    /// Any shape, drawn as an image: brighter pixels let through more light.
+    Mask(ApertureMask),
+}
+
+/// An image of an aperture, prepared for picking points on it in proportion to its brightness.
+/// The image covers the square around the lens, with its top edge at the top of the lens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // Note: the running total of the pixels' luminance, scaled to end at 1
+    cdf: Vec<f32>,
+}
+
+impl ApertureMask {
+    /// The mask drawn in `image`, or `None` if the image is black and so lets no light through.
+    pub fn new(image: &Canvas) -> Option<Self> {
+        let mut total = 0.0;
+        let mut cdf = Vec::with_capacity(image.width() * image.height());
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                total += image.pixel_at(x, y).luminance().max(0.0);
+                cdf.push(total);
+            }
+        }
+        if total <= 0.0 {
+            return None;
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        Some(Self {
+            width: image.width(),
+            height: image.height(),
+            cdf,
+        })
+    }
+
+    fn sample(&self, u: f32, v: f32) -> (f32, f32) {
+        // Pick a pixel with `u`, and reuse where `u` fell within the pixel's share to go across it
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .min(self.cdf.len() - 1);
+        let below = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+        let share = self.cdf[index] - below;
+        let across = if share > 0.0 {
+            ((u - below) / share).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        let (px, py) = (index % self.width, index / self.width);
+
+        (
+            2.0 * (px as f32 + across) / self.width as f32 - 1.0,
+            1.0 - 2.0 * (py as f32 + v) / self.height as f32,
+        )
+    }
+}
+
+impl Aperture {
+    /// The point on the aperture for a sample `(u, v)` in the unit square. Samples spread evenly
+    /// over the square come out spread evenly over the opening.
+    pub fn sample(&self, u: f32, v: f32) -> (f32, f32) {
+        match self {
+            Aperture::Circle => sample_disc(u, v),
+            Aperture::Polygon { blades, rotation } => sample_polygon(*blades, *rotation, u, v),
+            Aperture::Mask(mask) => mask.sample(u, v),
+        }
+    }
+}
+
+/// The point on a lens of radius 1 for a sample `(u, v)` in the unit square, by Shirley and
+/// Chiu's concentric mapping. It keeps samples that are spread evenly over the square spread
+/// evenly over the disc, which a mapping to radius and angle doesn't.
+pub fn sample_disc(u: f32, v: f32) -> (f32, f32) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, 2.0 * FRAC_PI_4 - FRAC_PI_4 * (a / b))
+    };
+
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+/// The point on a regular polygon with `sides` corners on the unit circle for a sample `(u, v)`.
+/// `u` picks one of the triangles between the middle and each side, and what is left of it
+/// together with `v` a point in that triangle.
+fn sample_polygon(sides: u32, rotation: f32, u: f32, v: f32) -> (f32, f32) {
+    if sides < 3 {
+        return sample_disc(u, v);
+    }
+
+    let scaled = u * sides as f32;
+    let side = (scaled.floor() as u32).min(sides - 1);
+    let (along, out) = (v, (scaled - side as f32).sqrt());
+    let corner = |i: u32| {
+        let angle = rotation + 2.0 * PI * i as f32 / sides as f32;
+        (angle.cos(), angle.sin())
+    };
+    let (a, b) = (corner(side), corner(side + 1));
+
+    (
+        out * (a.0 + (b.0 - a.0) * along),
+        out * (a.1 + (b.1 - a.1) * along),
+    )
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case((0.5, 0.5), (0.0, 0.0))]
+    #[case((1.0, 0.5), (1.0, 0.0))]
+    #[case((0.5, 0.0), (0.0, -1.0))]
+    #[case((0.25, 0.5), (-0.5, 0.0))]
+    fn mapping_samples_onto_the_lens(#[case] uv: (f32, f32), #[case] expected: (f32, f32)) {
+        let (x, y) = sample_disc(uv.0, uv.1);
+
+        assert_that!(x).is_close_to(expected.0, 0.0001);
+        assert_that!(y).is_close_to(expected.1, 0.0001);
+    }
+
+    #[test]
+    fn samples_land_inside_the_lens() {
+        for i in 0..10 {
+            for j in 0..10 {
+                let (x, y) = sample_disc(i as f32 / 9.0, j as f32 / 9.0);
+
+                assert_that!(x * x + y * y).is_less_than_or_equal_to(1.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn a_hexagonal_aperture_stays_inside_its_sides() {
+        let aperture = Aperture::Polygon {
+            blades: 6,
+            rotation: 0.0,
+        };
+        // The flat sides of a hexagon with corners on the unit circle are this far from the middle
+        let apothem = (PI / 6.0).cos();
+
+        for i in 0..20 {
+            for j in 0..20 {
+                let (x, y) = aperture.sample(i as f32 / 20.0, j as f32 / 19.0);
+
+                assert_that!(y.abs()).is_less_than_or_equal_to(apothem + 0.0001);
+                assert_that!(x * x + y * y).is_less_than_or_equal_to(1.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn turning_the_blades_turns_the_polygon() {
+        let corner = |rotation: f32| {
+            Aperture::Polygon {
+                blades: 5,
+                rotation,
+            }
+            // All the way out along the first side's triangle, at its first corner
+            .sample(0.19999, 0.0)
+        };
+
+        let (x, y) = corner(PI / 2.0);
+
+        assert_that!(corner(0.0).0).is_close_to(1.0, 0.0001);
+        assert_that!(x).is_close_to(0.0, 0.0001);
+        assert_that!(y).is_close_to(1.0, 0.0001);
+    }
+
+    #[test]
+    fn a_mask_only_lets_light_through_where_it_is_bright() {
+        // A 2x2 image lit only in its top right pixel
+        let mut image = Canvas::new(2, 2);
+        image.write_pixel(1, 0, &crate::color::Color::white());
+        let aperture = Aperture::Mask(ApertureMask::new(&image).unwrap());
+
+        for (u, v) in [(0.0, 0.0), (0.3, 0.9), (0.99, 0.5)] {
+            let (x, y) = aperture.sample(u, v);
+
+            assert_that!((0.0..=1.0).contains(&x)).is_true();
+            assert_that!((0.0..=1.0).contains(&y)).is_true();
+        }
+    }
+
+    #[test]
+    fn a_black_mask_is_refused() {
+        assert_that!(ApertureMask::new(&Canvas::new(4, 4))).is_none();
+    }
+}
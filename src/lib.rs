@@ -0,0 +1,69 @@
+//! A ray tracer: shapes and groups, materials and patterns, lights and cameras, and the extras
+//! built on them.
+//!
+//! Scenes are put together with the builders in each module, such as `SphereBuilder` and
+//! `WorldBuilder`, and rendered with `Camera::render`. The `ray-tracer` binary is a thin command
+//! line front end over the same API.
+
+pub mod accumulation;
+pub mod animation;
+pub mod approx;
+pub mod bounds;
+pub mod camera;
+pub mod canvas;
+pub mod clip;
+pub mod color;
+pub mod consts;
+pub mod cube;
+pub mod curve;
+pub mod cylinder;
+pub mod denoise;
+pub mod describe;
+pub mod disc;
+pub mod environment;
+pub mod exr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+mod font;
+pub mod fn_shape;
+pub mod group;
+pub mod ies;
+pub mod instance;
+pub mod intersection;
+pub mod lazy_mesh;
+pub mod lens;
+pub mod light;
+pub mod material;
+pub mod matrix;
+pub mod mipmap;
+pub mod noise;
+pub mod pattern;
+pub mod plane;
+pub mod ply;
+mod png;
+pub mod post;
+pub mod probe;
+pub mod progressive;
+pub mod quad;
+pub mod ray;
+pub mod registry;
+pub mod rng;
+pub mod scene;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod settings;
+pub mod shape;
+pub mod shapes;
+pub mod sphere;
+pub mod stereo;
+#[cfg(test)]
+mod testing;
+pub mod timing;
+pub mod trace;
+pub mod transform;
+pub mod triangle;
+pub mod vector4;
+pub mod video;
+pub mod watch;
+pub mod world;
@@ -1,17 +1,102 @@
+use std::rc::Rc;
+
 use crate::color::Color;
+use crate::ies::IesProfile;
+use crate::lens;
+use crate::matrix::Matrix;
 use crate::vector4::Vector4;
 
+/// Every light group at once: the groups of a light that hasn't been put in any in particular,
+/// and the links of an object that any light may illuminate.
+pub const ALL_LIGHT_GROUPS: u32 = u32::MAX;
+
 #[derive(Debug, PartialEq)]
 pub struct PointLight {
     pub position: Vector4,
     pub intensity: Color,
+    /// A bit for each group the light belongs to. It only lights, and only casts shadows from,
+    /// objects linked to at least one of them.
+    pub groups: u32,
+    /// Shapes the light the way a real fixture does. `intensity` is the light at its brightest.
+    pub profile: Option<Rc<IesProfile>>,
+    /// A rotation that aims the fixture, which points straight down before it is rotated.
+    pub orientation: Matrix<4>,
+    /// How big the light is when working out shadows. Shadows are sharp when it is 0, and their
+    /// edges soften as it grows, as if the light were a ball this size.
+    pub radius: f32,
+    /// How many points across the light are checked for shadows when it has a radius. More give
+    /// smoother shadows, for more work.
+    pub shadow_samples: usize,
 }
 
+/// How many points across a light with a radius are checked for shadows, unless told otherwise.
+const DEFAULT_SHADOW_SAMPLES: usize = 16;
+
 impl PointLight {
     pub fn new(position: Vector4, intensity: Color) -> Self {
         Self {
             position,
             intensity,
+            groups: ALL_LIGHT_GROUPS,
+            profile: None,
+            orientation: Matrix::identity(),
+            radius: 0.0,
+            shadow_samples: DEFAULT_SHADOW_SAMPLES,
+        }
+    }
+
+    pub fn with_groups(mut self, groups: u32) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: Rc<IesProfile>) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_orientation(mut self, orientation: Matrix<4>) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_shadow_samples(mut self, samples: usize) -> Self {
+        self.shadow_samples = samples;
+        self
+    }
+
+    /// A point on the light as seen from `from`, for a sample `(u, v)` in the unit square. The
+    /// points lie on a disc of the light's radius facing `from`, which is what a ball looks like.
+    pub fn sample_position(&self, from: Vector4, u: f32, v: f32) -> Vector4 {
+        if self.radius <= 0.0 {
+            return self.position;
+        }
+
+        let (across, up) = (from - self.position).normalize().orthonormal_basis();
+        let (x, y) = lens::sample_disc(u, v);
+        self.position + across * (x * self.radius) + up * (y * self.radius)
+    }
+
+    /// Give the light the colour of a black body at `kelvin`, keeping its brightness.
+    pub fn with_temperature(mut self, kelvin: f32) -> Self {
+        self.intensity = Color::from_kelvin(kelvin) * self.intensity.luminance();
+        self
+    }
+
+    /// The intensity of the light shining towards `point`.
+    pub fn intensity_towards(&self, point: Vector4) -> Color {
+        match &self.profile {
+            Some(profile) => {
+                // The orientation is a rotation, so its transpose undoes it
+                let direction = self.orientation.transpose() * (point - self.position);
+                self.intensity * profile.relative_intensity(direction)
+            }
+            None => self.intensity,
         }
     }
 }
@@ -21,6 +106,11 @@ impl Default for PointLight {
         Self {
             position: Vector4::point(-10.0, 10.0, -10.0),
             intensity: Color::white(),
+            groups: ALL_LIGHT_GROUPS,
+            profile: None,
+            orientation: Matrix::identity(),
+            radius: 0.0,
+            shadow_samples: DEFAULT_SHADOW_SAMPLES,
         }
     }
 }
@@ -31,11 +121,23 @@ Tests
 
 #[cfg(test)]
 mod tests {
-    use spectral::assert_that;
+    use spectral::prelude::*;
+
+    use std::f32::consts::PI;
+
+    use rstest::*;
 
     use super::*;
+    use crate::transform::Transform;
     use crate::vector4::Vector4;
 
+    /// A profile that only gives off light straight down.
+    fn spot() -> Rc<IesProfile> {
+        let text = "TILT=NONE\n1 1000 1 3 1 1 2 0 0 0 1 1 20\n0 10 20\n0\n100 100 0\n";
+
+        Rc::new(IesProfile::parse(text).unwrap())
+    }
+
     #[test]
     fn a_point_light_has_a_position_and_intensity() {
         let intensity = Color::new(1.0, 1.0, 1.0);
@@ -45,5 +147,80 @@ mod tests {
 
         assert_that!(light.position).is_equal_to(position);
         assert_that!(light.intensity).is_equal_to(intensity);
+        assert_that!(light.groups).is_equal_to(ALL_LIGHT_GROUPS);
+    }
+
+    #[test]
+    fn a_point_light_can_be_put_in_groups() {
+        let light = PointLight::default().with_groups(0b101);
+
+        assert_that!(light.groups).is_equal_to(0b101);
+    }
+
+    #[test]
+    fn a_light_given_a_temperature_keeps_its_brightness() {
+        let light = PointLight::new(Vector4::point(0.0, 0.0, 0.0), Color::new(2.0, 2.0, 2.0))
+            .with_temperature(3200.0);
+
+        assert_that!(light.intensity.luminance()).is_close_to(2.0, 0.0001);
+        assert_that!(light.intensity.r).is_greater_than(light.intensity.b);
+    }
+
+    #[test]
+    fn a_light_without_a_radius_is_sampled_at_its_position() {
+        let light = PointLight::default();
+
+        assert_that!(light.sample_position(Vector4::point(0.0, 0.0, 0.0), 0.9, 0.2))
+            .is_equal_to(light.position);
+    }
+
+    #[rstest]
+    #[case(0.5, 0.5, 0.0)]
+    #[case(0.99999, 0.5, 2.0)]
+    #[case(0.3, 0.8, 1.2)]
+    fn a_light_with_a_radius_is_sampled_on_a_disc_facing_the_point(
+        #[case] u: f32,
+        #[case] v: f32,
+        #[case] distance: f32,
+    ) {
+        let light =
+            PointLight::new(Vector4::point(0.0, 10.0, 0.0), Color::white()).with_radius(2.0);
+
+        let sample = light.sample_position(Vector4::point(0.0, 0.0, 0.0), u, v);
+
+        assert_that!(sample.y).is_close_to(10.0, 0.0001);
+        assert_that!((sample - light.position).magnitude()).is_close_to(distance, 0.0001);
+    }
+
+    #[test]
+    fn a_light_without_a_profile_is_the_same_in_every_direction() {
+        let light = PointLight::default();
+
+        assert_that!(light.intensity_towards(Vector4::point(5.0, 3.0, 1.0)))
+            .is_equal_to(Color::white());
+    }
+
+    #[test]
+    fn a_profile_shapes_the_light() {
+        let light =
+            PointLight::new(Vector4::point(0.0, 5.0, 0.0), Color::white()).with_profile(spot());
+
+        assert_that!(light.intensity_towards(Vector4::point(0.0, 0.0, 0.0)))
+            .is_equal_to(Color::white());
+        assert_that!(light.intensity_towards(Vector4::point(5.0, 0.0, 0.0)))
+            .is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn the_orientation_aims_the_profile() {
+        let light = PointLight::new(Vector4::point(0.0, 5.0, 0.0), Color::white())
+            .with_profile(spot())
+            .with_orientation(Matrix::rotation_z(PI / 2.0));
+
+        // Turned a quarter of the way round, the fixture points along +x
+        assert_that!(light.intensity_towards(Vector4::point(5.0, 5.0, 0.0)))
+            .is_equal_to(Color::white());
+        assert_that!(light.intensity_towards(Vector4::point(0.0, 0.0, 0.0)))
+            .is_equal_to(Color::black());
     }
 }
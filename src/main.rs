@@ -1,35 +1,561 @@
 use std::f32::consts::PI;
 use std::fs::File;
+use std::io::BufWriter;
+use std::panic;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::camera::CameraBuilder;
-use crate::color::Color;
-use crate::material::MaterialBuilder;
-use crate::matrix::Matrix;
-use crate::plane::PlaneBuilder;
-use crate::sphere::SphereBuilder;
-use crate::transform::Transform;
-use crate::vector4::Vector4;
-use crate::world::WorldBuilder;
-
-mod camera;
-mod canvas;
-mod color;
-mod consts;
-mod intersection;
-mod light;
-mod material;
-mod matrix;
-mod pattern;
-mod plane;
-mod ray;
-mod shape;
-mod sphere;
-mod transform;
-mod vector4;
-mod world;
+use ray_tracer::animation::{CameraAnimation, Easing, Track};
+use ray_tracer::camera::{CameraBuilder, Exposure, Resolution};
+use ray_tracer::canvas::Canvas;
+use ray_tracer::color::Color;
+use ray_tracer::filter::PixelFilter;
+use ray_tracer::intersection::SurfaceBias;
+use ray_tracer::lens::Aperture;
+use ray_tracer::material::MaterialBuilder;
+use ray_tracer::matrix::Matrix;
+use ray_tracer::plane::PlaneBuilder;
+use ray_tracer::post::PostProcessBuilder;
+use ray_tracer::progressive::ProgressiveRender;
+use ray_tracer::registry::SceneRegistry;
+use ray_tracer::settings::{AdaptiveSampling, OutlierRejection, RenderSettings};
+use ray_tracer::sphere::SphereBuilder;
+use ray_tracer::stereo::{AnaglyphMethod, StereoCameraBuilder};
+use ray_tracer::transform::Transform;
+use ray_tracer::vector4::Vector4;
+use ray_tracer::video::{FfmpegEncoder, Y4mWriter};
+use ray_tracer::watch::FileWatcher;
+use ray_tracer::world::{World, WorldBuilder};
+use ray_tracer::{describe, info_span, scene, trace};
 
 fn main() -> Result<(), std::io::Error> {
+    let command = std::env::args().nth(1);
+    // `--scene` reads the scene from a JSON file in place of the one built in below. `check`,
+    // `describe` and `watch` take the file after them instead, as `diff` takes its two.
+    let scene_file = option("--scene").or_else(|| {
+        let file = std::env::args().nth(2).filter(|arg| !arg.starts_with('-'));
+        file.filter(|_| matches!(command.as_deref(), Some("check" | "describe" | "watch")))
+    });
+
+    // `check scene.json` looks the scene over for problems without rendering it, exiting with 1
+    // if there are any
+    if command.as_deref() == Some("check") {
+        let Some(file) = &scene_file else {
+            eprintln!("check takes a scene file");
+            std::process::exit(2);
+        };
+        let text = std::fs::read_to_string(file).unwrap_or_else(|e| {
+            eprintln!("{}: {}", file, e);
+            std::process::exit(2);
+        });
+        let problems = match scene::from_json(&text, &SceneRegistry::builtin()) {
+            Ok((world, _)) => {
+                println!("{}", world.summary());
+                println!("{}", world.stats());
+                world.check().iter().map(ToString::to_string).collect()
+            }
+            // A scene that can't be read, such as one with a transform that can't be inverted,
+            // has that as its problem
+            Err(e) => vec![e.to_string()],
+        };
+        for problem in &problems {
+            eprintln!("problem: {}", problem);
+        }
+        if !problems.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let (mut world, camera) = {
+        let _span = info_span!("scene.build").entered();
+        match &scene_file {
+            Some(file) => read_scene(file).unwrap_or_else(|e| {
+                eprintln!("{}: {}", file, e);
+                std::process::exit(2);
+            }),
+            None => scene(),
+        }
+    };
+
+    // `describe scene.json` lists what is in the scene
+    if command.as_deref() == Some("describe") {
+        if scene_file.is_none() {
+            eprintln!("describe takes a scene file");
+            std::process::exit(2);
+        }
+        println!("{}", describe::describe(&world));
+        return Ok(());
+    }
+    // `diff before.json after.json` lists what changed between two scene files, and exits with
+    // 1 if anything did
+    if command.as_deref() == Some("diff") {
+        let (Some(before), Some(after)) = (std::env::args().nth(2), std::env::args().nth(3)) else {
+            eprintln!("diff takes two scene files");
+            std::process::exit(2);
+        };
+        let [before, after] = [before, after].map(|file| match read_scene(&file) {
+            Ok((world, _)) => world,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                std::process::exit(2);
+            }
+        });
+        let changes = describe::diff(&before, &after);
+        for line in &changes {
+            println!("{}", line);
+        }
+        std::process::exit(if changes.is_empty() { 0 } else { 1 });
+    }
+    // `serve` answers render requests over HTTP, on `--port` or 8080. It only listens on this
+    // machine unless `--host` gives another address, such as 0.0.0.0 for every network.
+    #[cfg(feature = "server")]
+    if command.as_deref() == Some("serve") {
+        let host = option("--host").unwrap_or_else(|| "127.0.0.1".to_string());
+        let port = option("--port").unwrap_or_else(|| "8080".to_string());
+        return ray_tracer::server::RenderServer::new().serve(format!("{}:{}", host, port));
+    }
+
+    // Say how big the scene is before starting on it, as a large one can take a long time or
+    // more memory than there is
+    eprintln!("{}", world.stats());
+
+    // `--resolution` sizes the image by name, such as 1080p, or by ratio and height, as 16:9@480
+    let camera = match option("--resolution") {
+        Some(text) => match text.parse::<Resolution>() {
+            Ok(resolution) => camera.with_resolution(resolution),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        None => camera,
+    };
+
+    // `--depth-of-field aperture,focus` gives the camera a lens of that radius focused that far
+    // away, and `--blades n` makes its opening an n-sided polygon, so highlights that are out of
+    // focus take that shape
+    let camera = match option("--depth-of-field") {
+        Some(text) => {
+            let values: Vec<f32> = text.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+            let [aperture, focus] = values[..] else {
+                eprintln!("--depth-of-field takes aperture,focus");
+                std::process::exit(2);
+            };
+            camera.with_depth_of_field(aperture, focus)
+        }
+        None => camera,
+    };
+    // `--exposure iso,shutter,f-stop` exposes the image as a camera with those settings would,
+    // such as 100,1/125,16, for scenes lit in physical units
+    let camera = match option("--exposure") {
+        Some(text) => {
+            let seconds = |v: &str| match v.split_once('/') {
+                Some((a, b)) => Some(a.trim().parse::<f32>().ok()? / b.trim().parse::<f32>().ok()?),
+                None => v.trim().parse().ok(),
+            };
+            let values: Vec<f32> = text.split(',').filter_map(seconds).collect();
+            let [iso, shutter_speed, f_stop] = values[..] else {
+                eprintln!("--exposure takes iso,shutter,f-stop, such as 100,1/125,16");
+                std::process::exit(2);
+            };
+            camera.with_exposure(Exposure {
+                iso,
+                shutter_speed,
+                f_stop,
+            })
+        }
+        None => camera,
+    };
+    // `--clip near[,far]` only shows what is between those distances from the camera, for looking
+    // into a room through one of its walls
+    let camera = match option("--clip") {
+        Some(text) => {
+            let values: Vec<f32> = text.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+            let (near, far) = match values[..] {
+                [near] => (near, f32::INFINITY),
+                [near, far] if near < far => (near, far),
+                _ => {
+                    eprintln!("--clip takes near[,far], with near less than far");
+                    std::process::exit(2);
+                }
+            };
+            camera.with_clipping(near, far)
+        }
+        None => camera,
+    };
+    let camera = match option("--blades").map(|text| text.parse::<u32>()) {
+        Some(Ok(blades)) if blades >= 3 => camera.with_aperture_shape(Aperture::Polygon {
+            blades,
+            rotation: 0.0,
+        }),
+        Some(_) => {
+            eprintln!("--blades takes a whole number, at least 3");
+            std::process::exit(2);
+        }
+        None => camera,
+    }
+    .build();
+
+    // `--shadow-bias absolute[,relative]` sets how far secondary rays start from a surface, for
+    // scenes that show acne or leak light at the default
+    let mut settings = RenderSettings::default();
+    if let Some(text) = option("--shadow-bias") {
+        let mut values = text.split(',').map(|v| v.trim().parse::<f32>());
+        let bias = match (values.next(), values.next(), values.next()) {
+            (Some(Ok(absolute)), None, None) => Some(SurfaceBias {
+                absolute,
+                ..settings.shadow_bias
+            }),
+            (Some(Ok(absolute)), Some(Ok(relative)), None) => {
+                Some(SurfaceBias { absolute, relative })
+            }
+            _ => None,
+        };
+        let Some(bias) = bias.filter(|b| b.absolute >= 0.0 && b.relative >= 0.0) else {
+            eprintln!("--shadow-bias takes an absolute offset, and optionally a relative one");
+            std::process::exit(2);
+        };
+        settings.shadow_bias = bias;
+    }
+
+    // `--seed` picks the noise in sampled renders; the same seed always gives the same image
+    if let Some(text) = option("--seed") {
+        let Ok(seed) = text.parse() else {
+            eprintln!("--seed takes a whole number");
+            std::process::exit(2);
+        };
+        settings.seed = seed;
+    }
+
+    // `--samples` sets the camera rays per pixel, which antialiasing, motion blur and depth of
+    // field all need more than one of
+    if let Some(text) = option("--samples") {
+        let Some(samples) = text.parse().ok().filter(|&n: &usize| n >= 1) else {
+            eprintln!("--samples takes a whole number, at least 1");
+            std::process::exit(2);
+        };
+        settings.samples_per_pixel = samples;
+    }
+
+    // `--filter` picks how samples are weighted into pixels: box, tent, gaussian or mitchell
+    if let Some(text) = option("--filter") {
+        settings.filter = match text.to_ascii_lowercase().as_str() {
+            "box" => PixelFilter::Box,
+            "tent" => PixelFilter::Tent,
+            "gaussian" => PixelFilter::Gaussian,
+            "mitchell" => PixelFilter::Mitchell,
+            _ => {
+                eprintln!("--filter takes box, tent, gaussian or mitchell");
+                std::process::exit(2);
+            }
+        };
+    }
+
+    // `--adaptive min,max` takes between min and max samples in each pixel, more where it is
+    // noisy
+    if let Some(text) = option("--adaptive") {
+        let bounds = text
+            .split_once(',')
+            .and_then(|(min, max)| Some((min.trim().parse().ok()?, max.trim().parse().ok()?)))
+            .filter(|&(min, max): &(usize, usize)| min >= 1 && min <= max);
+        let Some((min_samples, max_samples)) = bounds else {
+            eprintln!("--adaptive takes min,max samples per pixel, with min at least 1");
+            std::process::exit(2);
+        };
+        settings.adaptive = Some(AdaptiveSampling {
+            min_samples,
+            max_samples,
+            ..AdaptiveSampling::default()
+        });
+    }
+
+    // `--clamp` sets the brightest a camera sample may be, or turns clamping off with `none`, and
+    // `--reject-outliers` pulls down samples far brighter than the rest of their pixel
+    if let Some(text) = option("--clamp") {
+        settings.max_sample_radiance = match text.parse::<f32>() {
+            Ok(max) if max > 0.0 => Some(max),
+            _ if text == "none" => None,
+            _ => {
+                eprintln!("--clamp takes a radiance above zero, or none");
+                std::process::exit(2);
+            }
+        };
+    }
+    if std::env::args().any(|arg| arg == "--reject-outliers") {
+        settings.outlier_rejection = Some(OutlierRejection::default());
+    }
+
+    // `--exposure-ev`, `--white-balance`, `--contrast`, `--saturation` and `--vignette` adjust
+    // the finished image, in that order: by stops brighter or darker, so that the colour given
+    // as r,g,b or a temperature such as 3200K comes out white, by contrast and saturation where
+    // 1 leaves the image alone, and by how much to darken the corners from 0 to 1
+    let number = |name: &str, valid: fn(f32) -> bool, what: &str| {
+        option(name).map(|text| match text.parse::<f32>() {
+            Ok(value) if valid(value) => value,
+            _ => {
+                eprintln!("{} takes {}", name, what);
+                std::process::exit(2);
+            }
+        })
+    };
+    let mut post = PostProcessBuilder::new();
+    if let Some(stops) = number("--exposure-ev", f32::is_finite, "a number of stops") {
+        post = post.with_exposure(stops);
+    }
+    if let Some(text) = option("--white-balance") {
+        let white = match text.strip_suffix(['K', 'k']) {
+            Some(kelvin) => kelvin
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&k: &f32| k > 0.0)
+                .map(Color::from_kelvin),
+            None => {
+                let values: Vec<f32> =
+                    text.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+                match values[..] {
+                    [r, g, b] if r > 0.0 && g > 0.0 && b > 0.0 => Some(Color::new(r, g, b)),
+                    _ => None,
+                }
+            }
+        };
+        let Some(white) = white else {
+            eprintln!("--white-balance takes r,g,b above zero, or a temperature such as 3200K");
+            std::process::exit(2);
+        };
+        post = post.with_white_balance(white);
+    }
+    if let Some(contrast) = number("--contrast", |c| c > 0.0, "a number above zero") {
+        post = post.with_contrast(contrast);
+    }
+    if let Some(saturation) = number("--saturation", |s| s >= 0.0, "a number, at least zero") {
+        post = post.with_saturation(saturation);
+    }
+    if let Some(strength) = number("--vignette", |v| (0.0..=1.0).contains(&v), "0 to 1") {
+        post = post.with_vignette(strength);
+    }
+    settings.post = post.build();
+
+    // `--pixel x,y` renders just that pixel, and with `--trace` prints every ray, intersection
+    // and light that went into it, for chasing shading bugs
+    if let Some(text) = option("--pixel") {
+        let pixel = text
+            .split_once(',')
+            .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+            .filter(|&(x, y)| x < camera.hsize() && y < camera.vsize());
+        let Some((x, y)) = pixel else {
+            eprintln!(
+                "--pixel takes x,y with x below {} and y below {}",
+                camera.hsize(),
+                camera.vsize()
+            );
+            std::process::exit(2);
+        };
+        if std::env::args().any(|arg| arg == "--trace") {
+            print!("{}", trace::trace_pixel(&world, &camera, x, y, &settings));
+        }
+        println!("{}", camera.render_pixel_with(&world, x, y, &settings));
+        return Ok(());
+    }
+
+    let path = "/tmp/scene.ppm";
+    // `--stamp` writes the samples per pixel and the time taken onto renders and animation
+    // frames, for comparing them side by side
+    let stamping = std::env::args().any(|arg| arg == "--stamp");
+
+    // `watch scene.json` renders the scene again every time the file is saved, at preview size
+    // with the longer side `--largest` pixels, 400 by default, writing it to `-o` as a `.png` or
+    // `.ppm`. It carries on through scenes that can't be read, waiting for the next save.
+    if command.as_deref() == Some("watch") {
+        let Some(file) = scene_file else {
+            eprintln!("watch takes a scene file to watch");
+            std::process::exit(2);
+        };
+        let largest = option("--largest").and_then(|n| n.parse().ok()).unwrap_or(400);
+        let output = PathBuf::from(option("-o").unwrap_or_else(|| path.to_string()));
+        let mut watcher = FileWatcher::new(&file);
+        let mut scene = Ok((world, camera.preview(largest)));
+        loop {
+            match scene {
+                Ok((world, camera)) => {
+                    let _span = info_span!("watch.render", file = %file).entered();
+                    let canvas = camera.render(&world, &settings);
+                    let mut out = BufWriter::new(File::create(&output)?);
+                    if output.extension().is_some_and(|e| e == "png") {
+                        canvas.save_png(&mut out)?;
+                    } else {
+                        canvas.save(&mut out)?;
+                    }
+                    eprintln!("rendered {} to {}", file, output.display());
+                }
+                Err(e) => eprintln!("{}: {}", file, e),
+            }
+            watcher.wait(Duration::from_millis(250))?;
+            // A scene saved half edited mustn't stop the watch, even if reading it panics
+            let read = panic::catch_unwind(|| read_scene(&file));
+            scene = read
+                .unwrap_or_else(|_| Err("the scene couldn't be read".into()))
+                .map(|(world, camera)| {
+                    let camera = camera.build().preview(largest);
+                    (world, camera)
+                });
+        }
+    }
+
+    // `animate` renders a turntable, the camera going once around the scene, into the video
+    // file `-o` names: `.y4m` is written directly and anything else is encoded by ffmpeg.
+    // `--frames` and `--fps` set its length, 48 frames at 24 a second by default.
+    // `--focus-pull aperture,near,far` instead keeps the camera still and racks its focus from
+    // `near` to `far` over the animation, with a lens of radius `aperture`.
+    if command.as_deref() == Some("animate") {
+        let focus_pull = option("--focus-pull").map(|text| {
+            let values: Vec<f32> = text.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+            let [aperture, near, far] = values[..] else {
+                eprintln!("--focus-pull takes aperture,near,far");
+                std::process::exit(2);
+            };
+            CameraAnimation::new()
+                .with_aperture(Track::new().with_key(0.0, aperture, Easing::Linear))
+                .with_focal_distance(
+                    Track::new()
+                        .with_key(0.0, near, Easing::Linear)
+                        .with_key(1.0, far, Easing::EaseInOut),
+                )
+        });
+        let frames: usize = option("--frames").and_then(|f| f.parse().ok()).unwrap_or(48);
+        let fps: u32 = option("--fps").and_then(|f| f.parse().ok()).unwrap_or(24);
+        let output = PathBuf::from(option("-o").unwrap_or_else(|| "/tmp/scene.y4m".to_string()));
+        let (width, height) = (camera.hsize(), camera.vsize());
+        let write_frames = |write: &mut dyn FnMut(&Canvas) -> std::io::Result<()>| {
+            for frame in 0..frames {
+                let _span = info_span!("animate.frame", frame = frame).entered();
+                let angle = 2.0 * PI * frame as f32 / frames as f32;
+                let settings = RenderSettings {
+                    frame: frame as u64,
+                    ..settings.clone()
+                };
+                let camera = match &focus_pull {
+                    Some(animation) => {
+                        animation.camera_at(&camera, frame as f32 / (frames - 1).max(1) as f32)
+                    }
+                    None => camera.orbited(angle),
+                };
+                let start = Instant::now();
+                let mut canvas = camera.render(&world, &settings);
+                if stamping {
+                    stamp(&mut canvas, &settings, start.elapsed());
+                }
+                write(&canvas)?;
+            }
+            Ok::<(), std::io::Error>(())
+        };
+        if output.extension().is_some_and(|e| e == "y4m") {
+            let file = BufWriter::new(File::create(&output)?);
+            let mut video = Y4mWriter::new(file, width, height, fps);
+            write_frames(&mut |canvas| video.write_frame(canvas))?;
+            video.finish()?;
+        } else {
+            let mut video = FfmpegEncoder::start(&output, width, height, fps)?;
+            write_frames(&mut |canvas| video.write_frame(canvas))?;
+            video.finish()?;
+        }
+        return Ok(());
+    }
+
+    // `preview` refines the image a sample at a time, writing it out after every frame so a
+    // viewer that reloads the file shows it sharpening. `--frames` sets how many, 16 by default.
+    if command.as_deref() == Some("preview") {
+        let frames = option("--frames").and_then(|f| f.parse().ok()).unwrap_or(16);
+        // `--probes` looks reflections up in a probe captured at the middle of the scene
+        if std::env::args().any(|arg| arg == "--probes") {
+            let bounds = world.bounds();
+            let centre = bounds.min + (bounds.max - bounds.min) * 0.5;
+            world.capture_probes(&[centre], 32, &settings);
+            settings.reflection_probes = true;
+        }
+        let mut preview = ProgressiveRender::new(camera);
+        for _ in 0..frames {
+            let _span = info_span!("preview.frame", frame = preview.frames()).entered();
+            preview.render_frame(&world, &settings);
+            preview.image(&settings).save(&mut File::create(path)?)?;
+        }
+        return Ok(());
+    }
+
+    // `--anaglyph color|gray|dubois` renders an eye either side of the camera and merges them
+    // into one image for red/cyan glasses, with the eyes' views meeting `--convergence` units
+    // in front of the camera, 5 by default
+    let start = Instant::now();
+    let mut canvas = match option("--anaglyph") {
+        Some(text) => {
+            let method = match text.to_ascii_lowercase().as_str() {
+                "color" | "colour" => AnaglyphMethod::Color,
+                "gray" | "grey" => AnaglyphMethod::Gray,
+                "dubois" => AnaglyphMethod::Dubois,
+                _ => {
+                    eprintln!("--anaglyph takes color, gray or dubois");
+                    std::process::exit(2);
+                }
+            };
+            let convergence = match option("--convergence").map(|text| text.parse::<f32>()) {
+                Some(Ok(distance)) if distance > 0.0 => distance,
+                Some(_) => {
+                    eprintln!("--convergence takes a distance above zero");
+                    std::process::exit(2);
+                }
+                None => 5.0,
+            };
+            let stereo = StereoCameraBuilder::new()
+                .with_hsize(camera.hsize())
+                .with_vsize(camera.vsize())
+                .with_field_of_view(camera.field_of_view())
+                .with_transform(camera.transform())
+                .with_convergence(convergence)
+                .build();
+            stereo.render_anaglyph(&world, &settings, method)
+        }
+        None => camera.render(&world, &settings),
+    };
+    if stamping {
+        stamp(&mut canvas, &settings, start.elapsed());
+    }
+
+    let _span = info_span!("output", path = %path).entered();
+    let mut file = File::create(path).unwrap();
+    canvas.save(&mut file)?;
+
+    Ok(())
+}
+
+/// Write the samples per pixel and the time the render took into the top left of `canvas`, in
+/// white with a dark shadow so that it shows up on any background.
+fn stamp(canvas: &mut Canvas, settings: &RenderSettings, elapsed: Duration) {
+    let samples = match &settings.adaptive {
+        Some(adaptive) => format!("{}-{}", adaptive.min_samples, adaptive.max_samples),
+        None => settings.samples_per_pixel.to_string(),
+    };
+    let text = format!("spp={} t={:.1}s", samples, elapsed.as_secs_f32());
+    canvas.draw_text(3, 3, &text, &Color::black());
+    canvas.draw_text(2, 2, &text, &Color::white());
+}
+
+/// The scene in the JSON file at `path`, made with the built-in shapes and patterns.
+fn read_scene(path: &str) -> Result<(World, CameraBuilder), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+
+    Ok(scene::from_json(&text, &SceneRegistry::builtin())?)
+}
+
+/// The value following `name` on the command line, if it is there.
+fn option(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+fn scene() -> (World, CameraBuilder) {
     let floor_material = MaterialBuilder::new()
         .with_color(Color::new(1.0, 0.9, 0.9))
         .with_specular(0.0)
@@ -45,10 +571,12 @@ fn main() -> Result<(), std::io::Error> {
         .with_material(floor_material.clone())
         .build();
 
-    let rear_wall = PlaneBuilder::new()
-        .with_transform(Matrix::translation(0.0, 0.0, 3.0) * Matrix::rotation_x(PI / 2.0))
-        .with_material(wall_material.clone())
-        .build();
+    let rear_wall = PlaneBuilder::from_point_normal(
+        Vector4::point(0.0, 0.0, 3.0),
+        Vector4::vector(0.0, 0.0, -1.0),
+    )
+    .with_material(wall_material.clone())
+    .build();
 
     let middle_material = MaterialBuilder::new()
         .with_color(Color::new(0.1, 1.0, 0.5))
@@ -96,13 +624,7 @@ fn main() -> Result<(), std::io::Error> {
             Vector4::point(0.0, 1.5, -5.0),
             Vector4::point(0.0, 1.0, 0.0),
             Vector4::vector(0.0, 1.0, 0.0),
-        ))
-        .build();
+        ));
 
-    let canvas = camera.render(&world);
-
-    let mut file = File::create("/tmp/scene.ppm").unwrap();
-    canvas.save(&mut file)?;
-
-    Ok(())
+    (world, camera)
 }
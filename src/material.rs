@@ -1,3 +1,5 @@
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
 use crate::color::Color;
@@ -5,6 +7,20 @@ use crate::light::PointLight;
 use crate::pattern::{Pattern, SolidPattern};
 use crate::vector4::Vector4;
 
+/// How the highlight on a surface falls off away from the mirror direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SpecularModel {
+    /// The classic model: the reflected light direction against the eye.
+    #[default]
+    Phong,
+    /// The half-way vector against the normal. Broader highlights than Phong for the same
+    /// shininess, and no hard cut-off at grazing angles.
+    BlinnPhong,
+    /// A microfacet model with a roughness taken from the shininess. Highlights have a bright core
+    /// and long tail, like most real surfaces.
+    Ggx,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Material {
     pattern: Rc<dyn Pattern>,
@@ -12,6 +28,13 @@ pub struct Material {
     diffuse: f32,
     specular: f32,
     shininess: f32,
+    reflective: f32,
+    transparency: f32,
+    refractive_index: f32,
+    dispersion: Option<[f32; 3]>,
+    specular_model: SpecularModel,
+    double_sided: bool,
+    casts_shadow: bool,
 }
 
 pub struct MaterialBuilder {
@@ -20,9 +43,57 @@ pub struct MaterialBuilder {
     diffuse: f32,
     specular: f32,
     shininess: f32,
+    reflective: f32,
+    transparency: f32,
+    refractive_index: f32,
+    dispersion: Option<[f32; 3]>,
+    specular_model: SpecularModel,
+    double_sided: bool,
+    casts_shadow: bool,
 }
 
 impl Material {
+    pub fn reflective(&self) -> f32 {
+        self.reflective
+    }
+
+    pub fn transparency(&self) -> f32 {
+        self.transparency
+    }
+
+    pub fn refractive_index(&self) -> f32 {
+        self.refractive_index
+    }
+
+    /// Separate refractive indices for red, green and blue light, if the material splits them.
+    pub fn dispersion(&self) -> Option<[f32; 3]> {
+        self.dispersion
+    }
+
+    pub fn specular_model(&self) -> SpecularModel {
+        self.specular_model
+    }
+
+    /// Whether both faces of the surface can be seen. Rays reaching a single-sided surface from
+    /// behind pass straight through it.
+    pub fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+
+    /// Whether the surface blocks light from reaching what is behind it.
+    pub fn casts_shadow(&self) -> bool {
+        self.casts_shadow
+    }
+
+    pub fn pattern(&self) -> &Rc<dyn Pattern> {
+        &self.pattern
+    }
+
+    /// The colour of the material's pattern at a point with texture coordinates `uv`, if any.
+    pub(crate) fn color_at(&self, point: Vector4, uv: Option<(f32, f32)>) -> Color {
+        self.pattern.color_at_surface(point, uv)
+    }
+
     pub(crate) fn lighting(
         &self,
         light: &PointLight,
@@ -31,7 +102,22 @@ impl Material {
         normal_vector: Vector4,
         in_shadow: bool,
     ) -> Color {
-        let effective_color = self.pattern.color_at_point(point) * light.intensity;
+        let color = self.pattern.color_at_point(point);
+        self.lighting_with_color(color, light, point, eye_vector, normal_vector, in_shadow)
+    }
+
+    /// Light the surface as if `color` were the colour of its pattern at `point`.
+    pub(crate) fn lighting_with_color(
+        &self,
+        color: Color,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        let intensity = light.intensity_towards(point);
+        let effective_color = color * intensity;
 
         let ambient = effective_color * self.ambient;
 
@@ -48,16 +134,80 @@ impl Material {
         if light_dot_normal >= 0.0 {
             diffuse = effective_color * self.diffuse * light_dot_normal;
 
-            let reflect_vector = (-light_vector).reflect(&normal_vector);
-            let reflect_dot_eye = reflect_vector.dot(&eye_vector);
-            if reflect_dot_eye > 0.0 {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity * self.specular * factor;
-            }
+            let factor = self.specular_factor(light_vector, eye_vector, normal_vector);
+            specular = intensity * self.specular * factor;
         }
 
         ambient + diffuse + specular
     }
+
+    /// How much of the light is reflected towards the eye as highlight.
+    fn specular_factor(&self, light_vector: Vector4, eye_vector: Vector4, normal: Vector4) -> f32 {
+        match self.specular_model {
+            SpecularModel::Phong => {
+                let reflect_vector = (-light_vector).reflect(&normal);
+                let reflect_dot_eye = reflect_vector.dot(&eye_vector);
+                if reflect_dot_eye > 0.0 {
+                    reflect_dot_eye.powf(self.shininess)
+                } else {
+                    0.0
+                }
+            }
+            SpecularModel::BlinnPhong => {
+                let halfway = (light_vector + eye_vector).normalize();
+                halfway.dot(&normal).max(0.0).powf(self.shininess)
+            }
+            SpecularModel::Ggx => {
+                let light_dot_normal = light_vector.dot(&normal);
+                let eye_dot_normal = eye_vector.dot(&normal);
+                if eye_dot_normal <= 0.0 {
+                    return 0.0;
+                }
+                // The roughness whose highlight best matches a Blinn-Phong one of this shininess
+                let alpha2 = 2.0 / (self.shininess + 2.0);
+                let halfway = (light_vector + eye_vector).normalize();
+                let halfway_dot_normal = halfway.dot(&normal).max(0.0);
+
+                let d = halfway_dot_normal.powi(2) * (alpha2 - 1.0) + 1.0;
+                let distribution = alpha2 / (PI * d * d);
+                let masking =
+                    |cos: f32| 2.0 * cos / (cos + (alpha2 + (1.0 - alpha2) * cos * cos).sqrt());
+                let geometry = masking(light_dot_normal) * masking(eye_dot_normal);
+
+                // The light_dot_normal of the rendering equation cancels with the denominator's
+                distribution * geometry / (4.0 * eye_dot_normal)
+            }
+        }
+    }
+}
+
+impl Display for Material {
+    /// The pattern and lighting, leaving out the reflection and refraction it doesn't have.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ambient {}, diffuse {}, specular {}, shininess {}",
+            self.pattern, self.ambient, self.diffuse, self.specular, self.shininess
+        )?;
+        if self.reflective > 0.0 {
+            write!(f, ", reflective {}", self.reflective)?;
+        }
+        if self.transparency > 0.0 {
+            write!(
+                f,
+                ", transparency {}, refractive index {}",
+                self.transparency, self.refractive_index
+            )?;
+        }
+        if !self.double_sided {
+            write!(f, ", single-sided")?;
+        }
+        if !self.casts_shadow {
+            write!(f, ", casts no shadow")?;
+        }
+
+        Ok(())
+    }
 }
 
 impl MaterialBuilder {
@@ -68,6 +218,13 @@ impl MaterialBuilder {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            dispersion: None,
+            specular_model: SpecularModel::default(),
+            double_sided: true,
+            casts_shadow: true,
         }
     }
 
@@ -84,6 +241,13 @@ impl MaterialBuilder {
         self
     }
 
+    /// Use a pattern that is already shared, or whose type is only known at run time.
+    pub fn with_shared_pattern(mut self, pattern: Rc<dyn Pattern>) -> Self {
+        self.pattern = Some(pattern);
+
+        self
+    }
+
     pub fn with_ambient(mut self, ambient: f32) -> Self {
         self.ambient = ambient;
 
@@ -108,6 +272,55 @@ impl MaterialBuilder {
         self
     }
 
+    pub fn with_reflective(mut self, reflective: f32) -> Self {
+        self.reflective = reflective;
+
+        self
+    }
+
+    pub fn with_transparency(mut self, transparency: f32) -> Self {
+        self.transparency = transparency;
+
+        self
+    }
+
+    pub fn with_refractive_index(mut self, refractive_index: f32) -> Self {
+        self.refractive_index = refractive_index;
+
+        self
+    }
+
+    /// Refract red, green and blue light by different amounts, splitting white light into a
+    /// rainbow. `presets::dispersion` works these out from the usual optical figures.
+    pub fn with_dispersion(mut self, refractive_indices: [f32; 3]) -> Self {
+        self.dispersion = Some(refractive_indices);
+
+        self
+    }
+
+    pub fn with_specular_model(mut self, specular_model: SpecularModel) -> Self {
+        self.specular_model = specular_model;
+
+        self
+    }
+
+    /// Single-sided surfaces are culled when seen from behind, which is quicker for closed meshes
+    /// and open geometry meant to be seen from one side. Thin things like leaves and paper should
+    /// stay double-sided so that both faces are shaded.
+    pub fn with_double_sided(mut self, double_sided: bool) -> Self {
+        self.double_sided = double_sided;
+
+        self
+    }
+
+    /// Shapes that cast no shadow are left out of shadow rays altogether, which is quicker and
+    /// suits things like glass and light fittings that would otherwise darken what they light.
+    pub fn with_casts_shadow(mut self, casts_shadow: bool) -> Self {
+        self.casts_shadow = casts_shadow;
+
+        self
+    }
+
     pub fn build(self) -> Material {
         let pattern = match self.pattern {
             Some(p) => p,
@@ -119,10 +332,116 @@ impl MaterialBuilder {
             diffuse: self.diffuse,
             specular: self.specular,
             shininess: self.shininess,
+            reflective: self.reflective,
+            transparency: self.transparency,
+            refractive_index: self.refractive_index,
+            dispersion: self.dispersion,
+            specular_model: self.specular_model,
+            double_sided: self.double_sided,
+            casts_shadow: self.casts_shadow,
         }
     }
 }
 
+/// Ready-made materials, tuned to look believable without hand-adjusting every parameter.
+pub mod presets {
+    use crate::color::Color;
+    use crate::material::{Material, MaterialBuilder};
+
+    pub const VACUUM: f32 = 1.0;
+    pub const AIR: f32 = 1.00029;
+    pub const WATER: f32 = 1.333;
+    pub const GLASS: f32 = 1.52;
+    pub const DIAMOND: f32 = 2.417;
+
+    // Abbe numbers: the lower the number, the more a material spreads out colours
+    pub const WATER_ABBE: f32 = 55.7;
+    pub const GLASS_ABBE: f32 = 64.2;
+    pub const FLINT_GLASS_ABBE: f32 = 36.4;
+    pub const DIAMOND_ABBE: f32 = 55.3;
+
+    /// Clear glass: almost all light is transmitted or reflected, with a tight highlight.
+    pub fn glass() -> Material {
+        transparent(GLASS)
+    }
+
+    pub fn water() -> Material {
+        transparent(WATER)
+    }
+
+    pub fn diamond() -> Material {
+        transparent(DIAMOND)
+    }
+
+    pub fn mirror() -> Material {
+        MaterialBuilder::new()
+            .with_color(Color::black())
+            .with_ambient(0.0)
+            .with_diffuse(0.0)
+            .with_specular(1.0)
+            .with_shininess(300.0)
+            .with_reflective(1.0)
+            .build()
+    }
+
+    /// A purely diffuse surface with no highlight.
+    pub fn matte(color: Color) -> Material {
+        MaterialBuilder::new()
+            .with_color(color)
+            .with_diffuse(0.9)
+            .with_specular(0.0)
+            .build()
+    }
+
+    /// A diffuse surface with a soft highlight.
+    pub fn plastic(color: Color) -> Material {
+        MaterialBuilder::new()
+            .with_color(color)
+            .with_diffuse(0.8)
+            .with_specular(0.5)
+            .with_shininess(50.0)
+            .build()
+    }
+
+    /// A metallic surface. `roughness` runs from 0.0 (polished) to 1.0 (brushed): rougher metal
+    /// reflects less and has a broader, dimmer highlight.
+    pub fn metal(color: Color, roughness: f32) -> Material {
+        let smoothness = 1.0 - roughness.clamp(0.0, 1.0);
+        MaterialBuilder::new()
+            .with_color(color)
+            .with_ambient(0.1)
+            .with_diffuse(0.3 + 0.3 * roughness.clamp(0.0, 1.0))
+            .with_specular(0.4 + 0.6 * smoothness)
+            .with_shininess(10.0 + 290.0 * smoothness.powi(2))
+            .with_reflective(0.8 * smoothness)
+            .build()
+    }
+
+    /// Refractive indices for red, green and blue light in a material with the given refractive
+    /// index (for yellow light) and Abbe number, following Cauchy's equation.
+    pub fn dispersion(refractive_index: f32, abbe_number: f32) -> [f32; 3] {
+        // Wavelengths in micrometres: the Fraunhofer F, C and d lines, then red, green and blue
+        let (f, c, d) = (0.4861, 0.6563, 0.5876);
+        let b = (refractive_index - 1.0) / abbe_number / (1.0 / (f * f) - 1.0 / (c * c));
+        let a = refractive_index - b / (d * d);
+
+        [0.65, 0.55, 0.45].map(|wavelength: f32| a + b / (wavelength * wavelength))
+    }
+
+    fn transparent(refractive_index: f32) -> Material {
+        MaterialBuilder::new()
+            .with_color(Color::black())
+            .with_ambient(0.0)
+            .with_diffuse(0.1)
+            .with_specular(1.0)
+            .with_shininess(300.0)
+            .with_reflective(1.0)
+            .with_transparency(1.0)
+            .with_refractive_index(refractive_index)
+            .build()
+    }
+}
+
 /* -------------------------------------------------------------------------------------------------
 Tests
 ------------------------------------------------------------------------------------------------- */
@@ -135,6 +454,7 @@ mod tests {
     use crate::pattern::StripePattern;
 
     use super::*;
+    use crate::assert_approx_eq;
 
     #[fixture]
     fn default_material() -> Material {
@@ -153,6 +473,27 @@ mod tests {
         assert_that!(default_material.diffuse).is_equal_to(0.9);
         assert_that!(default_material.specular).is_equal_to(0.9);
         assert_that!(default_material.shininess).is_equal_to(200.0);
+        assert_that!(default_material.reflective).is_equal_to(0.0);
+        assert_that!(default_material.transparency).is_equal_to(0.0);
+        assert_that!(default_material.refractive_index).is_equal_to(1.0);
+        assert_that!(default_material.double_sided).is_true();
+        assert_that!(default_material.casts_shadow).is_true();
+    }
+
+    #[rstest]
+    fn displaying_a_material(default_material: Material) {
+        let glass = MaterialBuilder::new()
+            .with_transparency(1.0)
+            .with_refractive_index(1.5)
+            .with_double_sided(false)
+            .build();
+
+        assert_that!(default_material.to_string().as_str()).is_equal_to(
+            "(SolidPattern 255 255 255) ambient 0.1, diffuse 0.9, specular 0.9, shininess 200",
+        );
+        assert_that!(glass.to_string().as_str()).ends_with(
+            "shininess 200, transparency 1, refractive index 1.5, single-sided",
+        );
     }
 
     #[rstest]
@@ -198,9 +539,7 @@ mod tests {
             default_material.lighting(&light, default_position, eye_vector, normal_vector, false);
 
         let expected = Color::new(0.7364, 0.7364, 0.7364);
-        assert_that!(result.r).is_close_to(expected.r, 0.0001);
-        assert_that!(result.g).is_close_to(expected.g, 0.0001);
-        assert_that!(result.b).is_close_to(expected.b, 0.0001);
+        assert_approx_eq!(result, expected, 0.0001);
     }
 
     #[rstest]
@@ -216,9 +555,7 @@ mod tests {
             default_material.lighting(&light, default_position, eye_vector, normal_vector, false);
 
         let expected = Color::new(1.6364, 1.6364, 1.6364);
-        assert_that!(result.r).is_close_to(expected.r, 0.0001);
-        assert_that!(result.g).is_close_to(expected.g, 0.0001);
-        assert_that!(result.b).is_close_to(expected.b, 0.0001);
+        assert_approx_eq!(result, expected, 0.0001);
     }
 
     #[rstest]
@@ -262,4 +599,139 @@ mod tests {
             .with_pattern(Box::new(p))
             .build();
     }
+
+    #[test]
+    fn the_glass_preset_is_transparent_and_refractive() {
+        let m = presets::glass();
+
+        assert_that!(m.transparency).is_equal_to(1.0);
+        assert_that!(m.refractive_index).is_equal_to(presets::GLASS);
+        assert_that!(m.reflective).is_greater_than(0.0);
+    }
+
+    #[test]
+    fn the_mirror_preset_is_fully_reflective() {
+        let m = presets::mirror();
+
+        assert_that!(m.reflective).is_equal_to(1.0);
+        assert_that!(m.transparency).is_equal_to(0.0);
+        assert_that!(m.diffuse).is_equal_to(0.0);
+    }
+
+    #[rstest]
+    fn the_matte_preset_has_no_highlight(default_position: Vector4) {
+        let m = presets::matte(Color::new(1.0, 0.0, 0.0));
+        let eye_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let normal_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Vector4::point(0.0, 0.0, -10.0), Color::white());
+
+        let result = m.lighting(&light, default_position, eye_vector, normal_vector, false);
+
+        assert_that!(result.r).is_close_to(1.0, 0.0001);
+        assert_that!(result.g).is_close_to(0.0, 0.0001);
+        assert_that!(m.specular).is_equal_to(0.0);
+    }
+
+    #[test]
+    fn rougher_metal_is_less_reflective_and_has_a_broader_highlight() {
+        let polished = presets::metal(Color::white(), 0.0);
+        let brushed = presets::metal(Color::white(), 1.0);
+
+        assert_that!(polished.reflective).is_greater_than(brushed.reflective);
+        assert_that!(polished.shininess).is_greater_than(brushed.shininess);
+        assert_that!(brushed.reflective).is_equal_to(0.0);
+    }
+
+    #[test]
+    fn dispersion_bends_blue_more_than_red() {
+        let [red, green, blue] = presets::dispersion(presets::GLASS, presets::GLASS_ABBE);
+
+        assert_that!(red).is_less_than(green);
+        assert_that!(green).is_less_than(blue);
+        assert_that!(green).is_close_to(presets::GLASS, 0.002);
+    }
+
+    #[test]
+    fn a_lower_abbe_number_spreads_the_colours_further() {
+        let [glass_red, _, glass_blue] = presets::dispersion(1.6, presets::GLASS_ABBE);
+        let [flint_red, _, flint_blue] = presets::dispersion(1.6, presets::FLINT_GLASS_ABBE);
+
+        assert_that!(flint_blue - flint_red).is_greater_than(glass_blue - glass_red);
+    }
+
+    #[rstest]
+    #[case(SpecularModel::Phong)]
+    #[case(SpecularModel::BlinnPhong)]
+    fn the_highlight_peaks_in_the_mirror_direction(
+        #[case] specular_model: SpecularModel,
+        default_position: Vector4,
+    ) {
+        let m = MaterialBuilder::new()
+            .with_specular_model(specular_model)
+            .build();
+        let eye_vector = Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, -2.0_f32.sqrt() / 2.0);
+        let normal_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Vector4::point(0.0, 10.0, -10.0), Color::white());
+
+        let result = m.lighting(&light, default_position, eye_vector, normal_vector, false);
+
+        assert_that!(result.r).is_close_to(1.6364, 0.0001);
+    }
+
+    #[rstest]
+    fn blinn_phong_has_a_broader_highlight_than_phong(default_position: Vector4) {
+        let phong = MaterialBuilder::new().with_shininess(10.0).build();
+        let blinn_phong = MaterialBuilder::new()
+            .with_shininess(10.0)
+            .with_specular_model(SpecularModel::BlinnPhong)
+            .build();
+        let eye_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let normal_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Vector4::point(0.0, 10.0, -10.0), Color::white());
+
+        let phong_result =
+            phong.lighting(&light, default_position, eye_vector, normal_vector, false);
+        let blinn_phong_result =
+            blinn_phong.lighting(&light, default_position, eye_vector, normal_vector, false);
+
+        assert_that!(blinn_phong_result.r).is_greater_than(phong_result.r + 0.1);
+    }
+
+    #[rstest]
+    fn a_ggx_highlight_falls_off_away_from_the_mirror_direction(default_position: Vector4) {
+        let m = MaterialBuilder::new()
+            .with_ambient(0.0)
+            .with_diffuse(0.0)
+            .with_shininess(50.0)
+            .with_specular_model(SpecularModel::Ggx)
+            .build();
+        let normal_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Vector4::point(0.0, 10.0, -10.0), Color::white());
+        let highlight = |eye_vector: Vector4| {
+            m.lighting(&light, default_position, eye_vector.normalize(), normal_vector, false)
+                .r
+        };
+
+        let mirror = highlight(Vector4::vector(0.0, -1.0, -1.0));
+        let near = highlight(Vector4::vector(0.0, -0.8, -1.0));
+        let far = highlight(Vector4::vector(0.0, 0.5, -1.0));
+
+        assert_that!(mirror).is_greater_than(near);
+        assert_that!(near).is_greater_than(far);
+        assert_that!(far).is_greater_than(0.0);
+    }
+
+    #[rstest]
+    fn a_ggx_highlight_is_not_lit_from_behind(default_position: Vector4) {
+        let m = MaterialBuilder::new()
+            .with_specular_model(SpecularModel::Ggx)
+            .build();
+        let eye_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let normal_vector = Vector4::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Vector4::point(0.0, 0.0, 10.0), Color::white());
+
+        let result = m.lighting(&light, default_position, eye_vector, normal_vector, false);
+
+        assert_that!(result).is_equal_to(Color::new(0.1, 0.1, 0.1));
+    }
 }
@@ -1,6 +1,6 @@
 use std::error::Error;
 
-use crate::consts::EPSILON;
+use crate::consts::nearly_equal;
 use crate::vector4::Vector4;
 
 #[derive(Debug)]
@@ -78,7 +78,7 @@ impl<const L: usize> PartialEq for Matrix<L> {
     fn eq(&self, other: &Self) -> bool {
         for row in 0..self.len() {
             for col in 0..self.len() {
-                if (self[[row, col]] - other[[row, col]]).abs() > EPSILON {
+                if !nearly_equal(self[[row, col]], other[[row, col]]) {
                     return false;
                 }
             }
@@ -297,6 +297,81 @@ fn submatrix<const L: usize, const M: usize>(
     new_matrix
 }
 
+// glam stores matrices a column at a time, so they go across transposed
+/// One row to a line, with the columns lined up on their decimal points. The precision, 3 places
+/// unless given, applies to each entry.
+impl<const L: usize> std::fmt::Display for Matrix<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let places = f.precision().unwrap_or(3);
+        // Adding zero turns -0.0 into 0.0, which reads better
+        let cells: Vec<Vec<String>> = self
+            ._inner
+            .iter()
+            .map(|row| row.iter().map(|v| format!("{:.*}", places, v + 0.0)).collect())
+            .collect();
+        let width = cells.iter().flatten().map(String::len).max().unwrap_or(0);
+        for (i, row) in cells.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let padded: Vec<String> = row.iter().map(|c| format!("{:>1$}", c, width)).collect();
+            write!(f, "| {} |", padded.join("  "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Matrix<4>> for glam::Mat4 {
+    fn from(m: Matrix<4>) -> Self {
+        glam::Mat4::from_cols_array_2d(&m.transpose()._inner)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Mat4> for Matrix<4> {
+    fn from(m: glam::Mat4) -> Self {
+        Matrix::from(m.to_cols_array_2d()).transpose()
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Matrix<4>> for nalgebra::Matrix4<f32> {
+    fn from(m: Matrix<4>) -> Self {
+        nalgebra::Matrix4::from_fn(|row, col| m[[row, col]])
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Matrix4<f32>> for Matrix<4> {
+    fn from(m: nalgebra::Matrix4<f32>) -> Self {
+        Matrix::from(std::array::from_fn(|row| std::array::from_fn(|col| m[(row, col)])))
+    }
+}
+
+/// As an array of rows, so transforms read in scene files the way they are written on paper.
+#[cfg(feature = "serde")]
+impl<const L: usize> serde::Serialize for Matrix<L> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        // serde only implements arrays up to a fixed size, so write the rows out by hand
+        let mut rows = serializer.serialize_seq(Some(L))?;
+        for row in &self._inner {
+            rows.serialize_element(&row[..])?;
+        }
+        rows.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Matrix<4> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from(<[[f32; 4]; 4]>::deserialize(deserializer)?))
+    }
+}
+
 /* -------------------------------------------------------------------------------------------------
 Tests
 ------------------------------------------------------------------------------------------------- */
@@ -309,6 +384,8 @@ mod tests {
 
     use super::*;
     use crate::vector4::Vector4;
+    use crate::approx::ApproxEq;
+    use crate::testing::for_all;
 
     #[test]
     fn matrices_constructed_from_rows() {
@@ -592,4 +669,163 @@ mod tests {
 
         assert_that!(prod_x_inv).is_equal_to(a);
     }
+
+    #[test]
+    fn a_matrix_times_its_inverse_is_the_identity() {
+        for_all(|m: &Matrix<4>| {
+            let inverse = m.try_inverse().unwrap();
+            (*m * inverse).approx_eq(&Matrix::identity(), 0.001)
+                && (inverse * *m).approx_eq(&Matrix::identity(), 0.001)
+        });
+    }
+
+    #[test]
+    fn transposing_twice_gives_the_same_matrix() {
+        for_all(|m: &Matrix<4>| m.transpose().transpose().approx_eq(m, 0.0))
+    }
+
+    #[test]
+    fn the_inverse_of_a_product_is_the_product_of_the_inverses_reversed() {
+        for_all(|(a, b): &(Matrix<4>, Matrix<4>)| {
+            let product_inverse = (*a * *b).try_inverse().unwrap();
+            let reversed = b.try_inverse().unwrap() * a.try_inverse().unwrap();
+            product_inverse.approx_eq(&reversed, 0.001)
+        });
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn converting_to_and_from_glam() {
+        use crate::transform::Transform;
+
+        let m = Matrix::translation(1.0, 2.0, 3.0) * Matrix::rotation_y(0.5);
+        let p = Vector4::point(-1.0, 0.5, 2.0);
+
+        let g = glam::Mat4::from(m);
+
+        let moved: Vector4 = (g * glam::Vec4::from(p)).into();
+        assert_that!(moved).is_equal_to(m * p);
+        assert_that!(g.w_axis).is_equal_to(glam::Vec4::new(1.0, 2.0, 3.0, 1.0));
+        assert_that!(Matrix::from(g)).is_equal_to(m);
+    }
+
+    /// Times multiplying chains of matrices and transforming points with our matrices and with
+    /// glam's, to see what switching would gain. Run it in release mode with
+    /// `cargo test --release --features glam -- --ignored --nocapture comparing_speed`.
+    #[cfg(feature = "glam")]
+    #[test]
+    #[ignore]
+    fn comparing_speed_with_glam() {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        use crate::transform::Transform;
+
+        const ROUNDS: usize = 1_000_000;
+        let m = Matrix::rotation_x(0.1) * Matrix::scaling(1.0, 1.001, 1.0);
+        let p = Vector4::point(1.0, 2.0, 3.0);
+        let (gm, gp) = (glam::Mat4::from(m), glam::Vec4::from(p));
+
+        let time = |name: &str, work: &mut dyn FnMut()| {
+            let start = Instant::now();
+            work();
+            let each = start.elapsed().as_nanos() as f64 / ROUNDS as f64;
+            println!("{:<28} {:>8.2} ns each", name, each);
+        };
+        time("Matrix * Matrix", &mut || {
+            let mut acc = Matrix::identity();
+            for _ in 0..ROUNDS {
+                acc = black_box(acc) * m;
+            }
+            black_box(acc);
+        });
+        time("glam::Mat4 * glam::Mat4", &mut || {
+            let mut acc = glam::Mat4::IDENTITY;
+            for _ in 0..ROUNDS {
+                acc = black_box(acc) * gm;
+            }
+            black_box(acc);
+        });
+        time("Matrix * Vector4", &mut || {
+            let mut acc = p;
+            for _ in 0..ROUNDS {
+                acc = m * black_box(acc);
+            }
+            black_box(acc);
+        });
+        time("glam::Mat4 * glam::Vec4", &mut || {
+            let mut acc = gp;
+            for _ in 0..ROUNDS {
+                acc = gm * black_box(acc);
+            }
+            black_box(acc);
+        });
+        time("Matrix::try_inverse", &mut || {
+            for _ in 0..ROUNDS {
+                black_box(black_box(m).try_inverse());
+            }
+        });
+        time("glam::Mat4::inverse", &mut || {
+            for _ in 0..ROUNDS {
+                black_box(black_box(gm).inverse());
+            }
+        });
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn converting_to_and_from_nalgebra() {
+        use crate::transform::Transform;
+
+        let m = Matrix::translation(1.0, 2.0, 3.0) * Matrix::rotation_y(0.5);
+        let p = Vector4::point(-1.0, 0.5, 2.0);
+
+        let n = nalgebra::Matrix4::from(m);
+
+        // The same transform, with rows and columns the same way round
+        let moved: Vector4 = (n * nalgebra::Vector4::from(p)).into();
+        assert_that!(moved).is_equal_to(m * p);
+        assert_that!(n[(0, 3)]).is_equal_to(1.0);
+        assert_that!(Matrix::from(n)).is_equal_to(m);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_from_rows() {
+        use serde::de::value::Error;
+        use serde::de::IntoDeserializer;
+        use serde::Deserialize;
+
+        use crate::transform::Transform;
+
+        let rows = vec![
+            vec![1.0_f32, 0.0, 0.0, 5.0],
+            vec![0.0, 1.0, 0.0, -3.0],
+            vec![0.0, 0.0, 1.0, 2.0],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ];
+        let deserializer = IntoDeserializer::<Error>::into_deserializer(rows);
+
+        let m = Matrix::<4>::deserialize(deserializer).unwrap();
+
+        assert_that!(m).is_equal_to(Matrix::translation(5.0, -3.0, 2.0));
+    }
+
+    #[test]
+    fn displaying_a_matrix_lines_up_the_columns() {
+        let m = Matrix::from([[1.0, -0.0, 12.5], [-3.25, 100.0, 0.0], [0.0, 0.0, 1.0]]);
+
+        assert_that!(m.to_string().as_str()).is_equal_to(
+            "|   1.000    0.000   12.500 |\n\
+             |  -3.250  100.000    0.000 |\n\
+             |   0.000    0.000    1.000 |",
+        );
+    }
+
+    #[test]
+    fn displaying_a_matrix_to_a_given_precision() {
+        let m = Matrix::from([[0.5, 1.0], [-1.0, 0.25]]);
+
+        assert_that!(format!("{:.1}", m).as_str()).is_equal_to("|  0.5   1.0 |\n| -1.0   0.2 |");
+    }
 }
@@ -0,0 +1,202 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// A texture stored at its full size and at every halving of it down to a single texel, so that
+/// it can be looked up at the detail that suits how much of it a pixel covers.
+///
+/// Each level is box filtered from the one before. Lookups repeat the texture outside [0, 1) and
+/// put (0, 0) at the bottom left of the image.
+pub struct MipMap {
+    levels: Vec<Canvas>,
+}
+
+impl MipMap {
+    pub fn new(texture: Canvas) -> Self {
+        let mut levels = vec![texture];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width() <= 1 && last.height() <= 1 {
+                break;
+            }
+            let next = Self::downsample(last);
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    pub fn levels(&self) -> &[Canvas] {
+        &self.levels
+    }
+
+    /// The bytes taken up by every level.
+    pub fn memory_size(&self) -> usize {
+        self.levels.iter().map(Canvas::memory_size).sum()
+    }
+
+    /// The full-size texture.
+    pub fn base(&self) -> &Canvas {
+        &self.levels[0]
+    }
+
+    /// The nearest texel of the full-size texture, a plain box filter for when there is nothing
+    /// to say how much of the texture a pixel covers.
+    pub fn nearest(&self, u: f32, v: f32) -> Color {
+        let texture = self.base();
+        let (width, height) = (texture.width(), texture.height());
+        let x = (u.rem_euclid(1.0) * width as f32) as usize;
+        let y = ((1.0 - v.rem_euclid(1.0)) * height as f32) as usize;
+
+        texture.pixel_at(x.min(width - 1), y.min(height - 1))
+    }
+
+    /// Blend the four texels of `level` around `(u, v)`.
+    pub fn bilinear(&self, level: usize, u: f32, v: f32) -> Color {
+        let texture = &self.levels[level.min(self.levels.len() - 1)];
+        let (width, height) = (texture.width() as isize, texture.height() as isize);
+        // Texel centres sit half a texel in from their edges
+        let x = u * width as f32 - 0.5;
+        let y = (1.0 - v) * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let texel = |i: isize, j: isize| {
+            texture.pixel_at(
+                (x0 as isize + i).rem_euclid(width) as usize,
+                (y0 as isize + j).rem_euclid(height) as usize,
+            )
+        };
+
+        let top = texel(0, 0).lerp(&texel(1, 0), tx);
+        let bottom = texel(0, 1).lerp(&texel(1, 1), tx);
+        top.lerp(&bottom, ty)
+    }
+
+    /// Blend the two levels either side of the one whose texels are `footprint` wide, where
+    /// `footprint` is how far across the texture a pixel reaches, as a fraction of its width.
+    pub fn trilinear(&self, u: f32, v: f32, footprint: f32) -> Color {
+        let base = self.base();
+        let texels = footprint * base.width().max(base.height()) as f32;
+        let level = texels.max(1.0).log2().min((self.levels.len() - 1) as f32);
+        let lower = level.floor() as usize;
+        let t = level - lower as f32;
+        if t == 0.0 {
+            return self.bilinear(lower, u, v);
+        }
+
+        self.bilinear(lower, u, v)
+            .lerp(&self.bilinear(lower + 1, u, v), t)
+    }
+
+    /// Halve the texture in each direction that is more than one texel, averaging the texels
+    /// that each new one covers.
+    fn downsample(texture: &Canvas) -> Canvas {
+        let (width, height) = (texture.width(), texture.height());
+        let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut smaller = Canvas::new(new_width, new_height);
+        for y in 0..new_height {
+            for x in 0..new_width {
+                // Odd sizes leave some new texels covering three old ones
+                let (x0, x1) = (x * width / new_width, (x + 1) * width / new_width);
+                let (y0, y1) = (y * height / new_height, (y + 1) * height / new_height);
+                let mut sum = Color::black();
+                for j in y0..y1 {
+                    for i in x0..x1 {
+                        sum += texture.pixel_at(i, j);
+                    }
+                }
+                smaller.write_pixel(x, y, &(sum / ((x1 - x0) * (y1 - y0)) as f32));
+            }
+        }
+
+        smaller
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    /// A black and white checkerboard of single texels.
+    fn checkerboard(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if (x + y) % 2 == 0 {
+                    canvas.write_pixel(x, y, &Color::white());
+                }
+            }
+        }
+
+        canvas
+    }
+
+    fn assert_grey(color: Color, expected: f32) {
+        assert_that!(color.r).is_close_to(expected, 0.0001);
+        assert_that!(color.g).is_close_to(expected, 0.0001);
+        assert_that!(color.b).is_close_to(expected, 0.0001);
+    }
+
+    #[rstest]
+    #[case(8, 8, vec![(8, 8), (4, 4), (2, 2), (1, 1)])]
+    #[case(8, 2, vec![(8, 2), (4, 1), (2, 1), (1, 1)])]
+    #[case(5, 3, vec![(5, 3), (2, 1), (1, 1)])]
+    fn levels_halve_down_to_a_single_texel(
+        #[case] width: usize,
+        #[case] height: usize,
+        #[case] expected: Vec<(usize, usize)>,
+    ) {
+        let mipmap = MipMap::new(Canvas::new(width, height));
+
+        let sizes: Vec<(usize, usize)> = mipmap
+            .levels()
+            .iter()
+            .map(|level| (level.width(), level.height()))
+            .collect();
+        assert_that!(sizes).is_equal_to(expected);
+    }
+
+    #[test]
+    fn each_level_averages_the_one_before() {
+        let mipmap = MipMap::new(checkerboard(4, 4));
+
+        assert_grey(mipmap.levels()[1].pixel_at(1, 0), 0.5);
+        assert_grey(mipmap.levels()[2].pixel_at(0, 0), 0.5);
+    }
+
+    #[test]
+    fn the_box_filter_fallback_picks_the_nearest_texel() {
+        let mipmap = MipMap::new(checkerboard(2, 2));
+
+        // (0, 0) is the bottom left, texel (0, 1), which is black
+        assert_that!(mipmap.nearest(0.25, 0.25)).is_equal_to(Color::black());
+        assert_that!(mipmap.nearest(0.75, 0.25)).is_equal_to(Color::white());
+    }
+
+    #[test]
+    fn bilinear_lookups_blend_between_texel_centres() {
+        let mipmap = MipMap::new(checkerboard(2, 2));
+
+        assert_grey(mipmap.bilinear(0, 0.25, 0.75), 1.0);
+        assert_grey(mipmap.bilinear(0, 0.5, 0.75), 0.5);
+        assert_grey(mipmap.bilinear(0, 0.5, 0.5), 0.5);
+    }
+
+    #[rstest]
+    #[case(1.0 / 64.0, 1.0)]
+    #[case(1.0 / 4.0, 0.5)]
+    #[case(1.0, 0.5)]
+    fn trilinear_lookups_blur_with_the_footprint(#[case] footprint: f32, #[case] expected: f32) {
+        let mipmap = MipMap::new(checkerboard(8, 8));
+        // The centre of the top left texel, which is white
+        let (u, v) = (1.0 / 16.0, 15.0 / 16.0);
+
+        assert_grey(mipmap.trilinear(u, v, footprint), expected);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::rng::Rng;
+use crate::vector4::Vector4;
+
+/// Smooth, random-looking functions of space that are the same every time for the same seed, for
+/// procedural patterns and anything else that wants some natural-looking variation.
+///
+/// Both kinds of noise are built on a lattice of whole-number coordinates. Perlin noise has a
+/// random gradient at each lattice point, and value noise a random value; fractal sums of either
+/// add finer and finer detail.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Noise {
+    seed: u64,
+    // A shuffle of 0..256, repeated so that lookups can run past the end without wrapping
+    permutation: Vec<u8>,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut shuffled: Vec<u8> = (0..=255).collect();
+        for i in (1..shuffled.len()).rev() {
+            let j = (rng.next_u32() as usize) % (i + 1);
+            shuffled.swap(i, j);
+        }
+
+        Self {
+            seed,
+            permutation: [shuffled.as_slice(), shuffled.as_slice()].concat(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Ken Perlin's improved gradient noise, roughly in [-1, 1] and zero at every lattice point.
+    pub fn perlin(&self, point: Vector4) -> f32 {
+        let cell = |v: f32| (v.floor() as i64).rem_euclid(256) as usize;
+        let (x, y, z) = (cell(point.x), cell(point.y), cell(point.z));
+        let (fx, fy, fz) = (
+            point.x - point.x.floor(),
+            point.y - point.y.floor(),
+            point.z - point.z.floor(),
+        );
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+
+        let p = |i: usize| self.permutation[i] as usize;
+        let a = p(x) + y;
+        let (aa, ab) = (p(a) + z, p(a + 1) + z);
+        let b = p(x + 1) + y;
+        let (ba, bb) = (p(b) + z, p(b + 1) + z);
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p(aa), fx, fy, fz), grad(p(ba), fx - 1.0, fy, fz)),
+                lerp(
+                    u,
+                    grad(p(ab), fx, fy - 1.0, fz),
+                    grad(p(bb), fx - 1.0, fy - 1.0, fz),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p(aa + 1), fx, fy, fz - 1.0),
+                    grad(p(ba + 1), fx - 1.0, fy, fz - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p(ab + 1), fx, fy - 1.0, fz - 1.0),
+                    grad(p(bb + 1), fx - 1.0, fy - 1.0, fz - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Value noise, in [0, 1]: random values at the lattice points, blended smoothly between
+    /// them. Blobbier and cheaper than Perlin noise.
+    pub fn value(&self, point: Vector4) -> f32 {
+        let cell = |v: f32| (v.floor() as i64).rem_euclid(256) as usize;
+        let (x, y, z) = (cell(point.x), cell(point.y), cell(point.z));
+        let (u, v, w) = (
+            fade(point.x - point.x.floor()),
+            fade(point.y - point.y.floor()),
+            fade(point.z - point.z.floor()),
+        );
+
+        let p = |i: usize| self.permutation[i] as usize;
+        let corner = |i: usize, j: usize, k: usize| p(p(p(x + i) + y + j) + z + k) as f32 / 255.0;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Fractal Brownian motion: Perlin noise summed over `octaves`, each twice the frequency and
+    /// half the strength of the one before. Roughly in [-2, 2], like rolling hills.
+    pub fn fbm(&self, point: Vector4, octaves: usize) -> f32 {
+        self.octaves(point, octaves, |n| n)
+    }
+
+    /// Like `fbm`, but with every octave folded to be positive. Gives the creased, billowing look
+    /// of marble veins and wood grain; 0.0 and up, and below 2.0.
+    pub fn turbulence(&self, point: Vector4, octaves: usize) -> f32 {
+        self.octaves(point, octaves, f32::abs)
+    }
+
+    fn octaves(&self, point: Vector4, octaves: usize, shape: impl Fn(f32) -> f32) -> f32 {
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves {
+            let scaled = Vector4::point(
+                point.x * frequency,
+                point.y * frequency,
+                point.z * frequency,
+            );
+            total += shape(self.perlin(scaled)) / frequency;
+            frequency *= 2.0;
+        }
+
+        total
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// The dot product of the offset with one of twelve gradients, picked by the hash.
+fn grad(hash: usize, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    /// Points spread through a few cells of the lattice.
+    fn samples() -> impl Iterator<Item = Vector4> {
+        (0..500).map(|i| {
+            let i = i as f32;
+            Vector4::point(i * 0.137 - 20.0, i * 0.071, -i * 0.053)
+        })
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 0.0, 0.0))]
+    #[case(Vector4::point(3.0, -7.0, 12.0))]
+    #[case(Vector4::point(-300.0, 1.0, 256.0))]
+    fn perlin_noise_is_zero_on_the_lattice(#[case] point: Vector4) {
+        assert_that!(Noise::new(0).perlin(point)).is_equal_to(0.0);
+    }
+
+    #[test]
+    fn perlin_noise_stays_in_range() {
+        let noise = Noise::new(1);
+
+        for point in samples() {
+            let value = noise.perlin(point);
+            assert_that!(value).is_greater_than_or_equal_to(-1.0);
+            assert_that!(value).is_less_than_or_equal_to(1.0);
+        }
+    }
+
+    #[test]
+    fn noise_changes_smoothly() {
+        let noise = Noise::new(2);
+        let step = 0.001;
+
+        for point in samples() {
+            let nearby = Vector4::point(point.x + step, point.y, point.z);
+            assert_that!((noise.perlin(point) - noise.perlin(nearby)).abs()).is_less_than(0.01);
+            assert_that!((noise.value(point) - noise.value(nearby)).abs()).is_less_than(0.01);
+        }
+    }
+
+    #[test]
+    fn value_noise_stays_in_range() {
+        let noise = Noise::new(1);
+
+        for point in samples() {
+            let value = noise.value(point);
+            assert_that!(value).is_greater_than_or_equal_to(0.0);
+            assert_that!(value).is_less_than_or_equal_to(1.0);
+        }
+    }
+
+    #[test]
+    fn value_noise_varies_across_the_lattice() {
+        let noise = Noise::new(4);
+
+        let values: Vec<f32> = (0..10)
+            .map(|i| noise.value(Vector4::point(i as f32, 0.0, 0.0)))
+            .collect();
+
+        assert_that!(values.iter().any(|&v| v != values[0])).is_true();
+    }
+
+    #[test]
+    fn one_octave_of_fbm_is_perlin_noise() {
+        let noise = Noise::new(7);
+        let point = Vector4::point(0.3, 1.7, -2.2);
+
+        assert_that!(noise.fbm(point, 1)).is_equal_to(noise.perlin(point));
+        assert_that!(noise.turbulence(point, 1)).is_equal_to(noise.perlin(point).abs());
+    }
+
+    #[test]
+    fn fbm_adds_finer_octaves() {
+        let noise = Noise::new(7);
+        let point = Vector4::point(0.3, 1.7, -2.2);
+        let finer = Vector4::point(0.6, 3.4, -4.4);
+
+        let expected = noise.perlin(point) + noise.perlin(finer) / 2.0;
+        assert_that!(noise.fbm(point, 2)).is_close_to(expected, 0.0001);
+    }
+
+    #[test]
+    fn the_seed_picks_the_noise() {
+        let point = Vector4::point(0.3, 1.7, -2.2);
+
+        assert_that!(Noise::new(5).seed()).is_equal_to(5);
+
+        assert_that!(Noise::new(5).perlin(point)).is_equal_to(Noise::new(5).perlin(point));
+        assert_that!(Noise::new(5).perlin(point)).is_not_equal_to(Noise::new(6).perlin(point));
+        assert_that!(Noise::new(5).value(point)).is_not_equal_to(Noise::new(6).value(point));
+    }
+
+    #[test]
+    fn turbulence_is_never_negative() {
+        let noise = Noise::new(3);
+
+        for point in samples() {
+            let value = noise.turbulence(point, 4);
+            assert_that!(value).is_greater_than_or_equal_to(0.0);
+            assert_that!(value).is_less_than(2.0);
+        }
+    }
+}
@@ -1,11 +1,27 @@
+use std::f32::consts::PI;
 use std::fmt;
 use std::fmt::{Debug, Display};
+use std::rc::Rc;
 
+use crate::canvas::Canvas;
 use crate::color::Color;
+use crate::mipmap::MipMap;
+use crate::noise::Noise;
 use crate::vector4::Vector4;
 
 pub trait Pattern: Debug + Display {
     fn color_at_point(&self, point: Vector4) -> Color;
+
+    /// The colour at a point on a surface that has texture coordinates `uv`, for shapes such as
+    /// mesh triangles that carry them. Patterns that don't use them just look at the point.
+    fn color_at_surface(&self, point: Vector4, _uv: Option<(f32, f32)>) -> Color {
+        self.color_at_point(point)
+    }
+
+    /// The bytes of image data the pattern holds, for patterns made from images.
+    fn texture_memory(&self) -> usize {
+        0
+    }
 }
 
 impl PartialEq for &dyn Pattern {
@@ -60,13 +76,461 @@ impl Display for StripePattern {
     }
 }
 
+/// A pattern made from a closure from points to colours, for trying out an idea without writing a
+/// whole new pattern type.
+///
+/// Closures can't be compared, so patterns are told apart by their names; give each a different
+/// one if that matters.
+#[derive(Clone)]
+pub struct FnPattern {
+    color_at: Rc<dyn Fn(Vector4) -> Color>,
+    name: String,
+}
+
+impl FnPattern {
+    pub fn new(color_at: impl Fn(Vector4) -> Color + 'static) -> Self {
+        Self {
+            color_at: Rc::new(color_at),
+            name: String::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+impl Pattern for FnPattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        (self.color_at)(point)
+    }
+}
+
+impl Debug for FnPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Display for FnPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(FnPattern {})", self.name)
+    }
+}
+
+/// An image wrapped over a surface by its texture coordinates, with (0, 0) at the bottom left of
+/// the image. Surfaces without texture coordinates get the image projected down the y axis.
+pub struct TexturePattern {
+    texture: MipMap,
+}
+
+impl TexturePattern {
+    pub fn new(texture: Canvas) -> Self {
+        Self {
+            texture: MipMap::new(texture),
+        }
+    }
+
+    /// The nearest texel to `(u, v)`, repeating the image outside [0, 1).
+    pub fn color_at_uv(&self, u: f32, v: f32) -> Color {
+        self.texture.nearest(u, v)
+    }
+
+    /// The texture around `(u, v)` filtered to match a pixel that reaches `footprint` across the
+    /// texture, as a fraction of its width, so that distant textures don't shimmer.
+    pub fn color_at_uv_filtered(&self, u: f32, v: f32, footprint: f32) -> Color {
+        self.texture.trilinear(u, v, footprint)
+    }
+}
+
+impl Pattern for TexturePattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        self.color_at_uv(point.x, point.z)
+    }
+
+    fn color_at_surface(&self, point: Vector4, uv: Option<(f32, f32)>) -> Color {
+        match uv {
+            Some((u, v)) => self.color_at_uv(u, v),
+            None => self.color_at_point(point),
+        }
+    }
+
+    fn texture_memory(&self) -> usize {
+        self.texture.memory_size()
+    }
+}
+
+impl Debug for TexturePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Display for TexturePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "(TexturePattern {}x{})",
+            self.texture.base().width(),
+            self.texture.base().height()
+        )
+    }
+}
+
+/// How many octaves of noise the procedural patterns add up for their turbulence.
+const OCTAVES: usize = 6;
+
+/// Concentric growth rings around the y axis, pushed out of true by noise like real grain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WoodPattern {
+    light: Color,
+    dark: Color,
+    scale: f32,
+    turbulence: f32,
+    noise: Noise,
+}
+
+impl WoodPattern {
+    pub fn new(light: Color, dark: Color) -> Self {
+        Self {
+            light,
+            dark,
+            scale: 4.0,
+            turbulence: 0.2,
+            noise: Noise::new(0),
+        }
+    }
+
+    /// How many rings there are per unit of distance from the axis.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// How far the rings wander, in rings. 0.0 gives perfect circles.
+    pub fn with_turbulence(mut self, turbulence: f32) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.noise = Noise::new(seed);
+        self
+    }
+}
+
+impl Pattern for WoodPattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        let radius = (point.x * point.x + point.z * point.z).sqrt() * self.scale;
+        let rings = radius + self.turbulence * self.noise.turbulence(point, OCTAVES);
+
+        self.light.lerp(&self.dark, rings.rem_euclid(1.0))
+    }
+}
+
+impl Display for WoodPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "(WoodPattern {}, {}, {}, {})",
+            self.light, self.dark, self.scale, self.turbulence
+        )
+    }
+}
+
+/// Veins of one colour through another, running across the x axis and twisted by turbulence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarblePattern {
+    base: Color,
+    vein: Color,
+    scale: f32,
+    turbulence: f32,
+    noise: Noise,
+}
+
+impl MarblePattern {
+    pub fn new(base: Color, vein: Color) -> Self {
+        Self {
+            base,
+            vein,
+            scale: 1.0,
+            turbulence: 5.0,
+            noise: Noise::new(0),
+        }
+    }
+
+    /// How many veins there are per unit along the x axis.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// How much the veins twist. 0.0 gives straight, even bands.
+    pub fn with_turbulence(mut self, turbulence: f32) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.noise = Noise::new(seed);
+        self
+    }
+}
+
+impl Pattern for MarblePattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        let phase = point.x * self.scale + self.turbulence * self.noise.turbulence(point, OCTAVES);
+        // Sharpen the bands into thin veins
+        let vein = (1.0 - (phase * PI).sin().abs()).powi(4);
+
+        self.base.lerp(&self.vein, vein)
+    }
+}
+
+impl Display for MarblePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "(MarblePattern {}, {}, {}, {})",
+            self.base, self.vein, self.scale, self.turbulence
+        )
+    }
+}
+
+/// Fine, random speckles of two colours, like polished stone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GranitePattern {
+    color1: Color,
+    color2: Color,
+    scale: f32,
+    turbulence: f32,
+    noise: Noise,
+}
+
+impl GranitePattern {
+    pub fn new(color1: Color, color2: Color) -> Self {
+        Self {
+            color1,
+            color2,
+            scale: 10.0,
+            turbulence: 1.0,
+            noise: Noise::new(0),
+        }
+    }
+
+    /// How many speckles there are per unit.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// How sharply the speckles stand out from each other.
+    pub fn with_turbulence(mut self, turbulence: f32) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.noise = Noise::new(seed);
+        self
+    }
+}
+
+impl Pattern for GranitePattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        let scaled = Vector4::point(
+            point.x * self.scale,
+            point.y * self.scale,
+            point.z * self.scale,
+        );
+        let speckle = self.noise.turbulence(scaled, OCTAVES);
+
+        self.color1
+            .lerp(&self.color2, (speckle * 2.0 * self.turbulence).min(1.0))
+    }
+}
+
+impl Display for GranitePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "(GranitePattern {}, {}, {}, {})",
+            self.color1, self.color2, self.scale, self.turbulence
+        )
+    }
+}
+
+/// Where a `PalettePattern` gets the value it looks up in its palette.
+#[derive(Clone, Debug)]
+pub enum ScalarField {
+    /// Fractal noise with the given number of features per unit, mapped from [-1, 1] to [0, 1].
+    Noise(Noise, f32),
+    /// The luminance of another pattern.
+    Luminance(Rc<dyn Pattern>),
+}
+
+impl ScalarField {
+    pub fn value_at(&self, point: Vector4) -> f32 {
+        match self {
+            ScalarField::Noise(noise, scale) => {
+                let scaled = Vector4::point(point.x * scale, point.y * scale, point.z * scale);
+                (noise.fbm(scaled, OCTAVES) + 1.0) / 2.0
+            }
+            ScalarField::Luminance(pattern) => pattern.color_at_point(point).luminance(),
+        }
+    }
+}
+
+impl Display for ScalarField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalarField::Noise(noise, scale) => write!(f, "Noise {} {}", noise.seed(), scale),
+            ScalarField::Luminance(pattern) => write!(f, "Luminance {}", pattern),
+        }
+    }
+}
+
+/// A value from a scalar field looked up in a gradient of colours, like the colour ramps of a
+/// terrain map or a heatmap.
+///
+/// The gradient is a list of stops, each a value and the colour there; values between two stops
+/// blend their colours, and values beyond the ends take the colour of the nearest end.
+#[derive(Clone, Debug)]
+pub struct PalettePattern {
+    field: ScalarField,
+    stops: Vec<(f32, Color)>,
+}
+
+impl PalettePattern {
+    pub fn new(field: ScalarField, mut stops: Vec<(f32, Color)>) -> Self {
+        assert!(!stops.is_empty(), "The gradient must have at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Self { field, stops }
+    }
+
+    /// The colour of the gradient at `value`.
+    pub fn color_at_value(&self, value: f32) -> Color {
+        let upper = match self.stops.iter().position(|&(stop, _)| stop > value) {
+            Some(0) => return self.stops[0].1,
+            Some(upper) => upper,
+            None => return self.stops[self.stops.len() - 1].1,
+        };
+        let ((v0, c0), (v1, c1)) = (self.stops[upper - 1], self.stops[upper]);
+
+        c0.lerp(&c1, (value - v0) / (v1 - v0))
+    }
+}
+
+impl Pattern for PalettePattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        self.color_at_value(self.field.value_at(point))
+    }
+}
+
+impl Display for PalettePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(PalettePattern {}", self.field)?;
+        for (value, color) in &self.stops {
+            write!(f, ", {} {}", value, color)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Which fractal a `FractalPattern` draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fractal {
+    /// Each point is the constant added on every iteration, starting from zero.
+    Mandelbrot,
+    /// Each point is where the iterations start, adding the same constant `(re, im)` every time.
+    Julia(f32, f32),
+}
+
+/// The Mandelbrot set or a Julia set, drawn across the xz plane with x as the real axis and z as
+/// the imaginary one. Points that escape are coloured by how many iterations that took, running
+/// through the palette and starting over at its end; points in the set are the inside colour.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FractalPattern {
+    fractal: Fractal,
+    palette: Vec<Color>,
+    inside: Color,
+    max_iterations: usize,
+}
+
+impl FractalPattern {
+    pub fn new(fractal: Fractal, palette: Vec<Color>) -> Self {
+        assert!(!palette.is_empty(), "The palette must have at least one colour");
+
+        Self {
+            fractal,
+            palette,
+            inside: Color::black(),
+            max_iterations: 64,
+        }
+    }
+
+    pub fn with_inside(mut self, inside: Color) -> Self {
+        self.inside = inside;
+        self
+    }
+
+    /// How many iterations a point may take to escape before it is taken to be in the set.
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// How many iterations it takes to get further than 2 from the origin, if it ever does.
+    fn escape_time(&self, re: f32, im: f32) -> Option<usize> {
+        let ((mut zr, mut zi), (cr, ci)) = match self.fractal {
+            Fractal::Mandelbrot => ((0.0, 0.0), (re, im)),
+            Fractal::Julia(cr, ci) => ((re, im), (cr, ci)),
+        };
+
+        for i in 0..self.max_iterations {
+            if zr * zr + zi * zi > 4.0 {
+                return Some(i);
+            }
+            let next = zr * zr - zi * zi + cr;
+            zi = 2.0 * zr * zi + ci;
+            zr = next;
+        }
+
+        None
+    }
+}
+
+impl Pattern for FractalPattern {
+    fn color_at_point(&self, point: Vector4) -> Color {
+        match self.escape_time(point.x, point.z) {
+            Some(i) => self.palette[i % self.palette.len()],
+            None => self.inside,
+        }
+    }
+}
+
+impl Display for FractalPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "(FractalPattern {:?}, {}, {}, {})",
+            self.fractal,
+            self.palette.len(),
+            self.inside,
+            self.max_iterations
+        )
+    }
+}
+
 /* -------------------------------------------------------------------------------------------------
 Tests
 ------------------------------------------------------------------------------------------------- */
 
 #[cfg(test)]
 mod tests {
-    use spectral::assert_that;
+    use spectral::prelude::*;
 
     use super::*;
 
@@ -119,4 +583,232 @@ mod tests {
         assert_that!(p.color_at_point(Vector4::point(-1.0, 0.0, 0.0))).is_equal_to(Color::black());
         assert_that!(p.color_at_point(Vector4::point(-1.1, 0.0, 0.0))).is_equal_to(Color::white());
     }
+
+    fn checker_texture() -> TexturePattern {
+        // Red and green on the bottom row, blue and white on the top
+        let mut texture = Canvas::new(2, 2);
+        texture.write_pixel(0, 1, &Color::new(1.0, 0.0, 0.0));
+        texture.write_pixel(1, 1, &Color::new(0.0, 1.0, 0.0));
+        texture.write_pixel(0, 0, &Color::new(0.0, 0.0, 1.0));
+        texture.write_pixel(1, 0, &Color::white());
+
+        TexturePattern::new(texture)
+    }
+
+    #[test]
+    fn a_filtered_texture_lookup_blurs_with_the_footprint() {
+        let p = checker_texture();
+
+        let sharp = p.color_at_uv_filtered(0.25, 0.75, 0.01);
+        let blurred = p.color_at_uv_filtered(0.25, 0.75, 1.0);
+
+        assert_that!(sharp).is_equal_to(Color::new(0.0, 0.0, 1.0));
+        assert_that!(blurred).is_equal_to(Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn a_texture_is_looked_up_by_uv() {
+        let p = checker_texture();
+
+        assert_that!(p.color_at_uv(0.25, 0.25)).is_equal_to(Color::new(1.0, 0.0, 0.0));
+        assert_that!(p.color_at_uv(0.75, 0.25)).is_equal_to(Color::new(0.0, 1.0, 0.0));
+        assert_that!(p.color_at_uv(0.25, 0.75)).is_equal_to(Color::new(0.0, 0.0, 1.0));
+        assert_that!(p.color_at_uv(0.75, 0.75)).is_equal_to(Color::white());
+        assert_that!(p.color_at_uv(1.25, -0.75)).is_equal_to(Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn a_texture_uses_the_surface_uv_when_there_is_one() {
+        let p = checker_texture();
+        let point = Vector4::point(0.75, 0.0, 0.75);
+
+        assert_that!(p.color_at_surface(point, Some((0.25, 0.25))))
+            .is_equal_to(Color::new(1.0, 0.0, 0.0));
+        assert_that!(p.color_at_surface(point, None)).is_equal_to(Color::white());
+    }
+
+    #[test]
+    fn wood_without_turbulence_has_even_rings_around_the_y_axis() {
+        let (light, dark) = (Color::white(), Color::black());
+        let p = WoodPattern::new(light, dark)
+            .with_scale(1.0)
+            .with_turbulence(0.0);
+
+        assert_that!(p.color_at_point(Vector4::point(0.0, 0.0, 0.0))).is_equal_to(light);
+        assert_that!(p.color_at_point(Vector4::point(0.5, 3.0, 0.0)))
+            .is_equal_to(Color::new(0.5, 0.5, 0.5));
+        assert_that!(p.color_at_point(Vector4::point(0.0, -2.0, 0.5)))
+            .is_equal_to(Color::new(0.5, 0.5, 0.5));
+        assert_that!(p.color_at_point(Vector4::point(0.6, 0.0, 0.8))).is_equal_to(light);
+    }
+
+    #[test]
+    fn turbulence_bends_the_wood_grain() {
+        let straight = WoodPattern::new(Color::white(), Color::black()).with_turbulence(0.0);
+        let bent = WoodPattern::new(Color::white(), Color::black()).with_turbulence(0.5);
+        let point = Vector4::point(0.3, 0.4, 0.2);
+
+        assert_that!(bent.color_at_point(point)).is_not_equal_to(straight.color_at_point(point));
+    }
+
+    #[test]
+    fn marble_without_turbulence_has_veins_at_whole_numbers_of_x() {
+        let (base, vein) = (Color::white(), Color::black());
+        let p = MarblePattern::new(base, vein).with_turbulence(0.0);
+
+        assert_that!(p.color_at_point(Vector4::point(1.0, 0.0, 0.0)).r).is_close_to(0.0, 0.0001);
+        assert_that!(p.color_at_point(Vector4::point(1.5, 4.0, 2.0)).r).is_close_to(1.0, 0.0001);
+    }
+
+    #[test]
+    fn granite_mixes_its_two_colours() {
+        let p = GranitePattern::new(Color::white(), Color::black()).with_seed(3);
+
+        let values: Vec<f32> = (0..50)
+            .map(|i| p.color_at_point(Vector4::point(i as f32 * 0.37, 0.1, 0.2)).r)
+            .collect();
+
+        assert_that!(values.iter().all(|&v| (0.0..=1.0).contains(&v))).is_true();
+        assert_that!(values.iter().any(|&v| v < 0.5)).is_true();
+        assert_that!(values.iter().any(|&v| v > 0.5)).is_true();
+    }
+
+    #[test]
+    fn procedural_patterns_are_the_same_for_the_same_seed() {
+        let point = Vector4::point(0.3, 0.4, 0.2);
+        let marble = || MarblePattern::new(Color::white(), Color::black()).with_seed(9);
+
+        assert_that!(marble().color_at_point(point)).is_equal_to(marble().color_at_point(point));
+        assert_that!(marble()).is_equal_to(marble());
+    }
+
+    fn fractal_palette() -> Vec<Color> {
+        vec![
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn points_in_the_mandelbrot_set_are_the_inside_colour() {
+        let p = FractalPattern::new(Fractal::Mandelbrot, fractal_palette())
+            .with_inside(Color::white());
+
+        assert_that!(p.color_at_point(Vector4::point(0.0, 5.0, 0.0))).is_equal_to(Color::white());
+        assert_that!(p.color_at_point(Vector4::point(-1.0, 0.0, 0.0))).is_equal_to(Color::white());
+        assert_that!(p.color_at_point(Vector4::point(0.25, 0.0, 0.0)))
+            .is_equal_to(Color::white());
+    }
+
+    #[test]
+    fn escaping_points_are_coloured_by_their_escape_time() {
+        let p = FractalPattern::new(Fractal::Mandelbrot, fractal_palette());
+
+        // 0, then 3, which is outside
+        assert_that!(p.color_at_point(Vector4::point(3.0, 0.0, 0.0)))
+            .is_equal_to(Color::new(0.0, 1.0, 0.0));
+        // 0, 1, 2, then 5
+        assert_that!(p.color_at_point(Vector4::point(1.0, 0.0, 0.0)))
+            .is_equal_to(Color::new(1.0, 0.0, 0.0));
+        // i, i - 1, -i, i - 1, and so on, which never escapes
+        assert_that!(p.color_at_point(Vector4::point(0.0, 0.0, 1.0))).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn the_palette_repeats() {
+        let p = FractalPattern::new(Fractal::Mandelbrot, vec![Color::white(), Color::black()]);
+
+        // 0, 2, then 6
+        assert_that!(p.color_at_point(Vector4::point(2.0, 0.0, 0.0))).is_equal_to(Color::white());
+        // 0, 1, 2, then 5
+        assert_that!(p.color_at_point(Vector4::point(1.0, 0.0, 0.0))).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn julia_sets_iterate_from_the_point() {
+        let p = FractalPattern::new(Fractal::Julia(0.0, 0.0), fractal_palette());
+
+        // With a constant of zero the set is the unit disc
+        assert_that!(p.color_at_point(Vector4::point(0.5, 0.0, 0.5))).is_equal_to(Color::black());
+        assert_that!(p.color_at_point(Vector4::point(1.5, 0.0, 0.0)))
+            .is_equal_to(Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn a_fractal_runs_out_of_iterations() {
+        let p = FractalPattern::new(Fractal::Mandelbrot, fractal_palette()).with_max_iterations(2);
+
+        assert_that!(p.color_at_point(Vector4::point(1.0, 0.0, 0.0))).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn a_closure_pattern_calls_its_closure() {
+        let p = FnPattern::new(|point| Color::new(point.x, point.y, point.z));
+
+        assert_that!(p.color_at_point(Vector4::point(0.1, 0.2, 0.3)))
+            .is_equal_to(Color::new(0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    fn closure_patterns_are_told_apart_by_name() {
+        let red = FnPattern::new(|_| Color::new(1.0, 0.0, 0.0)).with_name("red");
+        let blue = FnPattern::new(|_| Color::new(0.0, 0.0, 1.0)).with_name("blue");
+
+        let (red, copy, blue): (&dyn Pattern, &dyn Pattern, &dyn Pattern) =
+            (&red, &red.clone(), &blue);
+        assert_that!(red == copy).is_true();
+        assert_that!(red == blue).is_false();
+    }
+
+    fn heatmap(field: ScalarField) -> PalettePattern {
+        PalettePattern::new(
+            field,
+            vec![
+                (1.0, Color::new(1.0, 0.0, 0.0)),
+                (0.0, Color::new(0.0, 0.0, 1.0)),
+                (0.5, Color::new(0.0, 1.0, 0.0)),
+            ],
+        )
+    }
+
+    #[test]
+    fn the_palette_blends_between_stops() {
+        let p = heatmap(ScalarField::Luminance(Rc::new(SolidPattern::default())));
+
+        assert_that!(p.color_at_value(0.0)).is_equal_to(Color::new(0.0, 0.0, 1.0));
+        assert_that!(p.color_at_value(0.25)).is_equal_to(Color::new(0.0, 0.5, 0.5));
+        assert_that!(p.color_at_value(0.5)).is_equal_to(Color::new(0.0, 1.0, 0.0));
+        assert_that!(p.color_at_value(0.75)).is_equal_to(Color::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn values_beyond_the_ends_take_the_end_colours() {
+        let p = heatmap(ScalarField::Luminance(Rc::new(SolidPattern::default())));
+
+        assert_that!(p.color_at_value(-3.0)).is_equal_to(Color::new(0.0, 0.0, 1.0));
+        assert_that!(p.color_at_value(1.5)).is_equal_to(Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn the_palette_can_map_another_patterns_luminance() {
+        let stripes = StripePattern {
+            color1: Color::white(),
+            color2: Color::black(),
+        };
+        let p = heatmap(ScalarField::Luminance(Rc::new(stripes)));
+
+        assert_that!(p.color_at_point(Vector4::point(0.5, 0.0, 0.0)).r).is_close_to(1.0, 0.0001);
+        assert_that!(p.color_at_point(Vector4::point(1.5, 0.0, 0.0)))
+            .is_equal_to(Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn the_palette_can_map_noise() {
+        let p = heatmap(ScalarField::Noise(Noise::new(0), 1.0));
+
+        // Noise is zero on whole-number points, which is halfway along the gradient
+        assert_that!(p.color_at_point(Vector4::point(2.0, 1.0, -3.0)))
+            .is_equal_to(Color::new(0.0, 1.0, 0.0));
+    }
 }
@@ -1,7 +1,11 @@
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
 use crate::consts::EPSILON;
 use crate::material::{Material, MaterialBuilder};
 use crate::matrix::Matrix;
-use crate::shape::Shape;
+use crate::shape::{shared_user_data, Shape, UserData};
+use crate::transform::Transform;
 use crate::vector4::Vector4;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -9,14 +13,26 @@ pub struct Plane {
     // Note: we store the inverse of the transform as an optimisation.
     inv_transform: Matrix<4>,
     material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
 }
 
 pub struct PlaneBuilder {
     transform: Matrix<4>,
     material: Material,
+    name: Option<String>,
+    user_data: UserData,
 }
 
 impl Shape for Plane {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
@@ -29,6 +45,27 @@ impl Shape for Plane {
         &self.inv_transform
     }
 
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Vector4::point(f32::NEG_INFINITY, 0.0, f32::NEG_INFINITY),
+            Vector4::point(f32::INFINITY, 0.0, f32::INFINITY),
+        )
+    }
+
     fn local_intersect(&self, ray: &crate::ray::Ray) -> Vec<f32> {
         let mut result = Vec::default();
 
@@ -45,6 +82,10 @@ impl Shape for Plane {
         Vector4::vector(0.0, 1.0, 0.0)
     }
 
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        Vector4::point(object_point.x, 0.0, object_point.z)
+    }
+
     fn lighting(
         &self,
         light: &crate::light::PointLight,
@@ -63,9 +104,52 @@ impl PlaneBuilder {
         Self {
             transform: Matrix::identity(),
             material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
         }
     }
 
+    /// A plane through `point` facing along `normal`, in place of the default xz plane.
+    pub fn from_point_normal(point: Vector4, normal: Vector4) -> Self {
+        assert!(point.is_point(), "Point must be a point");
+        assert!(
+            normal.is_vector() && normal.magnitude() > EPSILON,
+            "Normal must be a non-zero vector"
+        );
+
+        // Build an orthonormal basis around the normal, keeping the default orientation when the
+        // normal is already the y axis. Fall back to x as the helper when the normal is along z.
+        let up = normal.normalize();
+        let helper = if up.z.abs() > 1.0 - EPSILON {
+            Vector4::vector(1.0, 0.0, 0.0)
+        } else {
+            Vector4::vector(0.0, 0.0, 1.0)
+        };
+        let tangent = up.cross_product(&helper).normalize();
+        let bitangent = tangent.cross_product(&up);
+        let orientation: Matrix<4> = Matrix::from([
+            [tangent.x, up.x, bitangent.x, 0.0],
+            [tangent.y, up.y, bitangent.y, 0.0],
+            [tangent.z, up.z, bitangent.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        Self::new().with_transform(Matrix::translation(point.x, point.y, point.z) * orientation)
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
     pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
         self.transform = transform;
 
@@ -82,6 +166,8 @@ impl PlaneBuilder {
         Plane {
             inv_transform: self.transform.try_inverse().unwrap(),
             material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
         }
     }
 }
@@ -160,4 +246,83 @@ mod tests {
         assert_that!(xs.len()).is_equal_to(1);
         assert_that!(xs[0]).is_equal_to(1.0);
     }
+
+    #[test]
+    fn a_plane_may_be_assigned_a_name() {
+        let p = PlaneBuilder::new().with_name("floor").build();
+
+        assert_that!(p.name()).is_some().is_equal_to("floor");
+    }
+
+    #[test]
+    fn a_plane_has_a_bounding_box() {
+        let p = PlaneBuilder::new().build();
+
+        let b = p.bounds();
+
+        assert_that!(b.min.x).is_equal_to(f32::NEG_INFINITY);
+        assert_that!(b.min.y).is_equal_to(0.0);
+        assert_that!(b.max.z).is_equal_to(f32::INFINITY);
+        assert_that!(b.is_bounded()).is_false();
+    }
+
+    #[test]
+    fn a_plane_from_a_point_and_the_y_axis_is_the_default_plane() {
+        let p = PlaneBuilder::from_point_normal(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        )
+        .build();
+
+        assert_that!(p.transformation()).is_equal_to(Matrix::identity());
+    }
+
+    #[rstest]
+    #[case(Vector4::vector(1.0, 0.0, 0.0))]
+    #[case(Vector4::vector(0.0, 0.0, -1.0))]
+    #[case(Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::vector(0.0, -2.0, 0.0))]
+    #[case(Vector4::vector(1.0, 1.0, -1.0))]
+    fn a_plane_from_a_point_and_normal_faces_along_the_normal(#[case] normal: Vector4) {
+        let point = Vector4::point(1.0, 2.0, 3.0);
+        let p = PlaneBuilder::from_point_normal(point, normal).build();
+
+        assert_that!(p.normal_at(&point)).is_equal_to(normal.normalize());
+    }
+
+    #[test]
+    fn a_ray_hits_a_plane_from_a_point_and_normal_at_the_point() {
+        let p = PlaneBuilder::from_point_normal(
+            Vector4::point(0.0, 0.0, 10.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+        )
+        .build();
+        let r = Ray::new(
+            Vector4::point(2.0, 3.0, 0.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = p.intersect(&r);
+
+        assert_that!(xs).is_equal_to(vec![10.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_plane_needs_a_non_zero_normal() {
+        PlaneBuilder::from_point_normal(Vector4::point(0.0, 0.0, 0.0), Vector4::vector(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn the_closest_point_on_a_plane() {
+        let p = PlaneBuilder::from_point_normal(
+            Vector4::point(0.0, 0.0, 3.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+        )
+        .build();
+
+        assert_that!(p.closest_point(&Vector4::point(1.0, 2.0, -1.0)))
+            .is_equal_to(Vector4::point(1.0, 2.0, 3.0));
+        assert_that!(p.distance_to(&Vector4::point(1.0, 2.0, -1.0))).is_close_to(4.0, EPSILON);
+    }
 }
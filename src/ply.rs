@@ -0,0 +1,246 @@
+use std::fmt::{Display, Error, Formatter};
+use std::path::Path;
+
+use crate::vector4::Vector4;
+
+/// A polygon mesh read from a PLY file, the format of the Stanford 3D Scanning Repository and of
+/// many scanners, with its faces cut into triangles.
+///
+/// Only ASCII files are read. Each vertex takes its position from its `x`, `y` and `z`
+/// properties, and each face is a list of the indices of its corners, cut into a fan of triangles
+/// if it has more than three. Other properties and elements are skipped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlyMesh {
+    pub points: Vec<Vector4>,
+    pub faces: Vec<[usize; 3]>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParsePlyError(&'static str);
+
+impl std::error::Error for ParsePlyError {}
+
+impl Display for ParsePlyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "invalid PLY file: {}", self.0)
+    }
+}
+
+/// An element declared in the header, such as `vertex`, with how many there are and the names
+/// of its properties.
+struct Element<'a> {
+    name: &'a str,
+    count: usize,
+    properties: Vec<&'a str>,
+}
+
+impl PlyMesh {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        Self::parse(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParsePlyError> {
+        let mut lines = text.lines().map(str::trim);
+        if lines.next() != Some("ply") {
+            return Err(ParsePlyError("not a PLY file"));
+        }
+
+        let mut elements: Vec<Element> = Vec::new();
+        loop {
+            let line = lines.next().ok_or(ParsePlyError("the header never ends"))?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["end_header"] => break,
+                ["format", "ascii", _] => {}
+                ["format", ..] => return Err(ParsePlyError("only ASCII files are supported")),
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                ["element", name, count] => {
+                    // Each element takes a line, so there can't be more than there are lines
+                    let count = count
+                        .parse()
+                        .ok()
+                        .filter(|&count: &usize| count <= text.len())
+                        .ok_or(ParsePlyError("expected a count"))?;
+                    elements.push(Element {
+                        name,
+                        count,
+                        properties: Vec::new(),
+                    });
+                }
+                ["property", "list", _, _, name] | ["property", _, name] => {
+                    let element = elements
+                        .last_mut()
+                        .ok_or(ParsePlyError("a property comes before any element"))?;
+                    element.properties.push(name);
+                }
+                _ => return Err(ParsePlyError("unexpected line in the header")),
+            }
+        }
+
+        let mut points = Vec::new();
+        let mut faces = Vec::new();
+        for element in &elements {
+            let rows = (0..element.count).map(|_| {
+                let line = lines
+                    .next()
+                    .ok_or(ParsePlyError("the file ends too soon"))?;
+                line.split_whitespace()
+                    .map(|token| token.parse::<f32>())
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|_| ParsePlyError("expected a number"))
+            });
+            match element.name {
+                "vertex" => {
+                    let property = |name| {
+                        element
+                            .properties
+                            .iter()
+                            .position(|&p| p == name)
+                            .ok_or(ParsePlyError("vertices need x, y and z"))
+                    };
+                    let (x, y, z) = (property("x")?, property("y")?, property("z")?);
+                    for row in rows {
+                        let row = row?;
+                        let value = |i: usize| {
+                            row.get(i)
+                                .copied()
+                                .filter(|v| v.is_finite())
+                                .ok_or(ParsePlyError("a vertex is missing a finite coordinate"))
+                        };
+                        points.push(Vector4::point(value(x)?, value(y)?, value(z)?));
+                    }
+                }
+                "face" => {
+                    for row in rows {
+                        // A face starts with how many corners it has, then their indices
+                        let row = row?;
+                        let corners = row
+                            .split_first()
+                            .filter(|(&n, rest)| n >= 3.0 && n as usize <= rest.len())
+                            .map(|(&n, rest)| &rest[..n as usize])
+                            .ok_or(ParsePlyError("a face needs at least three corners"))?;
+                        let corners = corners
+                            .iter()
+                            .map(|&index| {
+                                Some(index)
+                                    .filter(|i| i.fract() == 0.0 && (0.0..1e9).contains(i))
+                                    .map(|i| i as usize)
+                                    .ok_or(ParsePlyError("expected an index"))
+                            })
+                            .collect::<Result<Vec<usize>, _>>()?;
+                        for i in 1..corners.len() - 1 {
+                            faces.push([corners[0], corners[i], corners[i + 1]]);
+                        }
+                    }
+                }
+                _ => {
+                    for row in rows {
+                        row?;
+                    }
+                }
+            }
+        }
+        if faces.iter().flatten().any(|&i| i >= points.len()) {
+            return Err(ParsePlyError("a face refers to a vertex that isn't there"));
+        }
+
+        Ok(Self { points, faces })
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::testing::mutations;
+
+    /// A tetrahedron with one square face on a pyramid, in the layout of the Stanford scans.
+    const PYRAMID: &str = "ply
+format ascii 1.0
+comment zipper output
+element vertex 5
+property float x
+property float y
+property float z
+property float confidence
+property float intensity
+element face 5
+property list uchar int vertex_indices
+end_header
+0 0 0 1 0.5
+1 0 0 1 0.5
+1 0 1 1 0.5
+0 0 1 1 0.5
+0.5 1 0.5 0.8 0.4
+4 0 1 2 3
+3 0 4 1
+3 1 4 2
+3 2 4 3
+3 3 4 0
+";
+
+    #[test]
+    fn reading_a_mesh() {
+        let mesh = PlyMesh::parse(PYRAMID).unwrap();
+
+        assert_that!(mesh.points.len()).is_equal_to(5);
+        assert_that!(mesh.points[4]).is_equal_to(Vector4::point(0.5, 1.0, 0.5));
+        // The square base is cut in two
+        assert_that!(mesh.faces.len()).is_equal_to(6);
+        assert_that!(mesh.faces[..2].to_vec()).is_equal_to(vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn other_elements_are_skipped() {
+        let text = PYRAMID.replace(
+            "end_header",
+            "element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header",
+        ) + "0 1\n";
+
+        assert_that!(PlyMesh::parse(&text).map(|mesh| mesh.faces.len())).is_equal_to(Ok(6));
+    }
+
+    #[rstest]
+    #[case("", "not a PLY file")]
+    #[case(
+        "ply\nformat binary_little_endian 1.0\nend_header\n",
+        "only ASCII files are supported"
+    )]
+    #[case("ply\nformat ascii 1.0\n", "the header never ends")]
+    #[case(
+        "ply\nelement vertex 1\nproperty float x\nend_header\n0\n",
+        "vertices need x, y and z"
+    )]
+    #[case(
+        "ply\nelement face 1\nproperty list uchar int i\nend_header\n3 0 1 2\n",
+        "a face refers to a vertex that isn't there"
+    )]
+    #[case(
+        "ply\nelement face 1\nproperty list uchar int i\nend_header\n2 0 1\n",
+        "a face needs at least three corners"
+    )]
+    #[case(
+        "ply\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\n\
+         end_header\n0 0 0\n",
+        "the file ends too soon"
+    )]
+    fn files_that_cant_be_read(#[case] text: &str, #[case] error: &'static str) {
+        assert_that!(PlyMesh::parse(text)).is_equal_to(Err(ParsePlyError(error)));
+    }
+
+    #[test]
+    fn parsing_a_corrupted_mesh_never_panics() {
+        for text in mutations(PYRAMID, 2000) {
+            let _ = PlyMesh::parse(&text);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{Error, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+/// Write 8-bit RGBA pixels, four bytes each with rows from the top down, as a PNG file.
+///
+/// The image data is stored without compression, which keeps the encoder tiny at the cost of
+/// larger files.
+pub fn write_rgba(
+    file: &mut impl Write,
+    width: usize,
+    height: usize,
+    rgba: &[u8],
+) -> Result<(), Error> {
+    assert_eq!(
+        rgba.len(),
+        width * height * 4,
+        "Pixel data doesn't match the image size"
+    );
+
+    file.write_all(&SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, default compression and filtering, not interlaced
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(file, b"IHDR", &header)?;
+
+    // Each row starts with its filter type, which is always "none" here
+    let mut scanlines = Vec::with_capacity(height * (width * 4 + 1));
+    for row in rgba.chunks(width * 4) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(file, b"IDAT", &zlib_stored(&scanlines))?;
+
+    write_chunk(file, b"IEND", &[])
+}
+
+fn write_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> Result<(), Error> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+    let crc = crc32(&[&kind[..], data].concat());
+    file.write_all(&crc.to_be_bytes())
+}
+
+/// Wrap `data` in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks = data.chunks(MAX_STORED_BLOCK).collect::<Vec<_>>();
+    if blocks.is_empty() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        let last = i == blocks.len() - 1;
+        stream.push(last as u8);
+        let length = block.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::*;
+
+    /// The chunks of a PNG file as (type, data) pairs.
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut chunks = vec![];
+        let mut rest = &png[SIGNATURE.len()..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = String::from_utf8(rest[4..8].to_vec()).unwrap();
+            chunks.push((kind, rest[8..8 + length].to_vec()));
+            rest = &rest[12 + length..];
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_that!(crc32(b"IEND")).is_equal_to(0xae42_6082);
+        assert_that!(adler32(b"Wikipedia")).is_equal_to(0x11e6_0398);
+    }
+
+    #[test]
+    fn a_png_starts_with_the_signature_and_header() {
+        let mut file = vec![];
+
+        write_rgba(&mut file, 3, 2, &[0; 24]).unwrap();
+
+        assert_that!(file[..8].to_vec()).is_equal_to(SIGNATURE.to_vec());
+        let chunks = chunks(&file);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_that!(kinds).is_equal_to(vec!["IHDR", "IDAT", "IEND"]);
+        assert_that!(chunks[0].1).is_equal_to(vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+    }
+
+    #[test]
+    fn the_image_data_holds_each_row_after_its_filter_byte() {
+        let rgba = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut file = vec![];
+
+        write_rgba(&mut file, 1, 2, &rgba).unwrap();
+
+        let data = &chunks(&file)[1].1;
+        // zlib header, then a single final stored block of 10 bytes
+        assert_that!(data[..7].to_vec()).is_equal_to(vec![0x78, 0x01, 1, 10, 0, 0xf5, 0xff]);
+        assert_that!(data[7..17].to_vec()).is_equal_to(vec![0, 1, 2, 3, 4, 0, 5, 6, 7, 8]);
+        let checksum = adler32(&[0, 1, 2, 3, 4, 0, 5, 6, 7, 8]).to_be_bytes();
+        assert_that!(data[17..].to_vec()).is_equal_to(checksum.to_vec());
+    }
+
+    #[test]
+    fn large_images_are_split_into_several_blocks() {
+        let stream = zlib_stored(&vec![7; MAX_STORED_BLOCK + 10]);
+
+        assert_that!(stream[2]).is_equal_to(0);
+        let second = 2 + 5 + MAX_STORED_BLOCK;
+        assert_that!(stream[second..second + 5].to_vec()).is_equal_to(vec![1, 10, 0, 0xf5, 0xff]);
+    }
+}
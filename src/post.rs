@@ -0,0 +1,249 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+// Contrast pivots around 18% grey, the usual photographic mid-tone in linear light
+const MID_GREY: f32 = 0.18;
+
+/// One step of post-processing, applied to the linear image before it is quantised.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Adjustment {
+    /// Brighten (positive) or darken (negative) by a number of stops.
+    Exposure(f32),
+    /// Scale the channels so that light of this colour comes out neutral.
+    WhiteBalance(Color),
+    /// Above 1.0 pushes values away from mid grey, below 1.0 pulls them towards it.
+    Contrast(f32),
+    /// 0.0 is greyscale, 1.0 leaves the colours alone and above 1.0 makes them more vivid.
+    Saturation(f32),
+    /// How much to darken the corners, from 0.0 (not at all) to 1.0 (black).
+    Vignette(f32),
+}
+
+/// A list of adjustments, applied in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostProcess {
+    adjustments: Vec<Adjustment>,
+}
+
+pub struct PostProcessBuilder {
+    adjustments: Vec<Adjustment>,
+}
+
+impl Adjustment {
+    /// Adjust `color`, found `distance` from the centre of the image, where 1.0 is a corner.
+    fn apply(&self, color: Color, distance: f32) -> Color {
+        match *self {
+            Adjustment::Exposure(stops) => color * 2.0_f32.powf(stops),
+            Adjustment::WhiteBalance(white) => {
+                let scale = |channel: f32| {
+                    if channel > 0.0 {
+                        white.luminance() / channel
+                    } else {
+                        1.0
+                    }
+                };
+                color * Color::new(scale(white.r), scale(white.g), scale(white.b))
+            }
+            Adjustment::Contrast(contrast) => {
+                let curve = |channel: f32| MID_GREY * (channel.max(0.0) / MID_GREY).powf(contrast);
+                Color::new(curve(color.r), curve(color.g), curve(color.b))
+            }
+            Adjustment::Saturation(saturation) => {
+                let grey = Color::white() * color.luminance();
+                grey.lerp(&color, saturation).clamp(0.0, f32::INFINITY)
+            }
+            Adjustment::Vignette(strength) => color * (1.0 - strength * distance * distance),
+        }
+    }
+}
+
+impl PostProcess {
+    pub fn adjustments(&self) -> &[Adjustment] {
+        &self.adjustments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adjustments.is_empty()
+    }
+
+    /// Run every adjustment over the whole canvas.
+    pub fn apply(&self, canvas: &mut Canvas) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (half_width, half_height) = (canvas.width() as f32 / 2.0, canvas.height() as f32 / 2.0);
+        let corner = (half_width * half_width + half_height * half_height).sqrt();
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let dx = x as f32 + 0.5 - half_width;
+                let dy = y as f32 + 0.5 - half_height;
+                let distance = (dx * dx + dy * dy).sqrt() / corner;
+
+                let color = self
+                    .adjustments
+                    .iter()
+                    .fold(canvas.pixel_at(x, y), |color, adjustment| {
+                        adjustment.apply(color, distance)
+                    });
+                canvas.write_pixel(x, y, &color);
+            }
+        }
+    }
+}
+
+impl PostProcessBuilder {
+    pub fn new() -> Self {
+        Self {
+            adjustments: Vec::new(),
+        }
+    }
+
+    pub fn with_exposure(self, stops: f32) -> Self {
+        self.with_adjustment(Adjustment::Exposure(stops))
+    }
+
+    pub fn with_white_balance(self, white: Color) -> Self {
+        self.with_adjustment(Adjustment::WhiteBalance(white))
+    }
+
+    pub fn with_contrast(self, contrast: f32) -> Self {
+        self.with_adjustment(Adjustment::Contrast(contrast))
+    }
+
+    pub fn with_saturation(self, saturation: f32) -> Self {
+        self.with_adjustment(Adjustment::Saturation(saturation))
+    }
+
+    pub fn with_vignette(self, strength: f32) -> Self {
+        self.with_adjustment(Adjustment::Vignette(strength))
+    }
+
+    pub fn with_adjustment(mut self, adjustment: Adjustment) -> Self {
+        self.adjustments.push(adjustment);
+
+        self
+    }
+
+    pub fn build(self) -> PostProcess {
+        PostProcess {
+            adjustments: self.adjustments,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn filled(color: Color) -> Canvas {
+        let mut canvas = Canvas::new(5, 5);
+        canvas.fill(&color);
+
+        canvas
+    }
+
+    #[test]
+    fn an_empty_pipeline_leaves_the_image_alone() {
+        let color = Color::new(0.3, 0.6, 0.9);
+        let mut canvas = filled(color);
+
+        PostProcess::default().apply(&mut canvas);
+
+        assert_that!(canvas.pixel_at(0, 0)).is_equal_to(color);
+    }
+
+    #[rstest]
+    #[case(1.0, 0.4)]
+    #[case(-1.0, 0.1)]
+    #[case(0.0, 0.2)]
+    fn exposure_is_measured_in_stops(#[case] stops: f32, #[case] expected: f32) {
+        let mut canvas = filled(Color::white() * 0.2);
+
+        PostProcessBuilder::new()
+            .with_exposure(stops)
+            .build()
+            .apply(&mut canvas);
+
+        assert_approx_eq!(canvas.pixel_at(2, 2), Color::white() * expected, 0.0001);
+    }
+
+    #[test]
+    fn white_balance_makes_the_reference_colour_neutral() {
+        let warm = Color::new(1.0, 0.8, 0.6);
+        let mut canvas = filled(warm);
+
+        PostProcessBuilder::new()
+            .with_white_balance(warm)
+            .build()
+            .apply(&mut canvas);
+
+        let balanced = canvas.pixel_at(2, 2);
+        assert_approx_eq!(balanced, Color::white() * warm.luminance(), 0.0001);
+    }
+
+    #[test]
+    fn contrast_spreads_values_around_mid_grey() {
+        let mut canvas = Canvas::new(3, 1);
+        canvas.write_pixel(0, 0, &(Color::white() * 0.05));
+        canvas.write_pixel(1, 0, &(Color::white() * MID_GREY));
+        canvas.write_pixel(2, 0, &(Color::white() * 0.5));
+
+        PostProcessBuilder::new()
+            .with_contrast(1.5)
+            .build()
+            .apply(&mut canvas);
+
+        assert_that!(canvas.pixel_at(0, 0).r).is_less_than(0.05);
+        assert_that!(canvas.pixel_at(1, 0).r).is_close_to(MID_GREY, 0.0001);
+        assert_that!(canvas.pixel_at(2, 0).r).is_greater_than(0.5);
+    }
+
+    #[test]
+    fn zero_saturation_gives_greyscale_with_the_same_luminance() {
+        let color = Color::new(0.8, 0.2, 0.1);
+        let mut canvas = filled(color);
+
+        PostProcessBuilder::new()
+            .with_saturation(0.0)
+            .build()
+            .apply(&mut canvas);
+
+        assert_approx_eq!(canvas.pixel_at(2, 2), Color::white() * color.luminance(), 0.0001);
+    }
+
+    #[test]
+    fn a_vignette_darkens_the_corners_more_than_the_centre() {
+        let mut canvas = filled(Color::white());
+
+        PostProcessBuilder::new()
+            .with_vignette(0.5)
+            .build()
+            .apply(&mut canvas);
+
+        let centre = canvas.pixel_at(2, 2);
+        let corner = canvas.pixel_at(0, 0);
+        assert_that!(centre.r).is_close_to(1.0, 0.0001);
+        assert_that!(corner.r).is_less_than(centre.r);
+        assert_that!(corner.r).is_greater_than(0.5);
+    }
+
+    #[test]
+    fn adjustments_are_applied_in_order() {
+        let post = PostProcessBuilder::new()
+            .with_exposure(1.0)
+            .with_saturation(0.5)
+            .build();
+
+        assert_that!(post.adjustments())
+            .is_equal_to([Adjustment::Exposure(1.0), Adjustment::Saturation(0.5)].as_slice());
+    }
+}
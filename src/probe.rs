@@ -0,0 +1,109 @@
+//! Reflection probes: the scene captured into a small cube map from a point, so reflections can
+//! be looked up instead of traced. Every point near a probe sees the same reflections, as if the
+//! scene were infinitely far away, and the low resolution blurs them, which suits glossy surfaces
+//! and interactive previews.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::environment::{CubeFace, CubeMap, Environment};
+use crate::ray::Ray;
+use crate::settings::RenderSettings;
+use crate::vector4::Vector4;
+use crate::world::World;
+
+/// The scene as seen in every direction from one point.
+#[derive(Debug)]
+pub struct ReflectionProbe {
+    position: Vector4,
+    map: CubeMap,
+}
+
+impl ReflectionProbe {
+    /// Trace the scene from `position` into six faces `size` pixels square.
+    pub fn capture(
+        world: &World,
+        position: Vector4,
+        size: usize,
+        settings: &RenderSettings,
+    ) -> Self {
+        let faces = [
+            CubeFace::Right,
+            CubeFace::Left,
+            CubeFace::Up,
+            CubeFace::Down,
+            CubeFace::Front,
+            CubeFace::Back,
+        ]
+        .map(|face| {
+            let mut canvas = Canvas::new(size, size);
+            for y in 0..size {
+                for x in 0..size {
+                    // The canvas runs down from the top, texture coordinates up from the bottom
+                    let u = (x as f32 + 0.5) / size as f32;
+                    let v = 1.0 - (y as f32 + 0.5) / size as f32;
+                    let ray = Ray::new(position, CubeMap::direction(face, u, v).normalize());
+                    let color = world.color_at_depth(&ray, settings, settings.max_depth);
+                    canvas.write_pixel(x, y, &color);
+                }
+            }
+            canvas
+        });
+
+        Self {
+            position,
+            map: CubeMap::new(faces),
+        }
+    }
+
+    pub fn position(&self) -> Vector4 {
+        self.position
+    }
+
+    /// The colour reflected towards the probe from `direction`.
+    pub fn color_in_direction(&self, direction: Vector4) -> Color {
+        self.map.color_in_direction(direction)
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::material::MaterialBuilder;
+    use crate::matrix::Matrix;
+    use crate::plane::PlaneBuilder;
+    use crate::transform::Transform;
+    use crate::world::WorldBuilder;
+
+    #[test]
+    fn a_probe_sees_the_scene_around_it() {
+        // A bright floor below and nothing above
+        let floor = PlaneBuilder::new()
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .with_material(
+                MaterialBuilder::new()
+                    .with_color(Color::white())
+                    .with_ambient(1.0)
+                    .with_diffuse(0.0)
+                    .with_specular(0.0)
+                    .build(),
+            )
+            .build();
+        let world = WorldBuilder::new().with_object(Rc::new(floor)).build();
+        let settings = RenderSettings::default();
+
+        let probe = ReflectionProbe::capture(&world, Vector4::point(0.0, 0.0, 0.0), 4, &settings);
+
+        assert_that!(probe.color_in_direction(Vector4::vector(0.0, -1.0, 0.0)))
+            .is_equal_to(Color::white());
+        assert_that!(probe.color_in_direction(Vector4::vector(0.2, 1.0, 0.0)))
+            .is_equal_to(settings.background);
+    }
+}
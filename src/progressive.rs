@@ -0,0 +1,214 @@
+//! Rendering for interactive previews: each frame adds one sample per pixel to what is already
+//! there, so the image sharpens for as long as the camera stays still, and starts again when it
+//! moves.
+
+use crate::accumulation::AccumulationBuffer;
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::filter;
+use crate::rng::Rng;
+use crate::settings::RenderSettings;
+use crate::world::World;
+
+/// An image built up a frame at a time from one camera.
+pub struct ProgressiveRender {
+    camera: Camera,
+    buffer: AccumulationBuffer,
+    frames: u32,
+}
+
+impl ProgressiveRender {
+    pub fn new(camera: Camera) -> Self {
+        let buffer = AccumulationBuffer::new(camera.hsize(), camera.vsize());
+
+        Self {
+            camera,
+            buffer,
+            frames: 0,
+        }
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// How many frames have gone into the image since it was last reset.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Look through `camera` from now on. The image starts again if the camera has moved or
+    /// changed in any other way, and carries on refining if it hasn't.
+    pub fn set_camera(&mut self, camera: Camera) {
+        if camera == self.camera {
+            return;
+        }
+
+        if (camera.hsize(), camera.vsize()) != (self.camera.hsize(), self.camera.vsize()) {
+            self.buffer = AccumulationBuffer::new(camera.hsize(), camera.vsize());
+        }
+        self.camera = camera;
+        self.reset();
+    }
+
+    /// Throw away everything rendered so far, for when the world has changed.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.frames = 0;
+    }
+
+    /// Add one sample to every pixel. The first frame samples the middle of each pixel, as a
+    /// single-sample render does; later ones step through a Halton sequence across the pixel to
+    /// smooth the edges.
+    /// `settings.samples_per_pixel` is ignored, as the frames take its place. With adaptive
+    /// sampling, pixels whose samples already agree are left as they are.
+    pub fn render_frame(&mut self, world: &World, settings: &RenderSettings) {
+        let frame = self.frames;
+        for y in 0..self.camera.vsize() {
+            for x in 0..self.camera.hsize() {
+                if let Some(adaptive) = settings.adaptive {
+                    if !adaptive.needs_more(frame as usize, self.buffer.variance(x, y)) {
+                        continue;
+                    }
+                }
+                let offset = if frame == 0 {
+                    (0.5, 0.5)
+                } else {
+                    // The same shift of the sequence every frame, so the frames between them
+                    // cover the pixel evenly
+                    let mut rng = Rng::for_pixel(settings.frame_seed(), x, y, self.camera.hsize());
+                    filter::sample_offset(frame - 1, (rng.next_f32(), rng.next_f32()))
+                };
+                let ray = self.camera.ray_through_pixel(x, y, offset.0, offset.1);
+                let (color, _) = world.sample(&ray, settings);
+                self.buffer
+                    .add_filtered_sample(x, y, offset, color, settings.filter);
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// The image so far, exposed by the camera and with the settings' post-processing applied.
+    pub fn image(&self, settings: &RenderSettings) -> Canvas {
+        let mut canvas = self.buffer.to_canvas();
+        self.camera.expose(&mut canvas);
+        settings.post.apply(&mut canvas);
+
+        canvas
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::camera::CameraBuilder;
+    use crate::matrix::Matrix;
+    use crate::settings::AdaptiveSampling;
+    use crate::transform::Transform;
+    use crate::vector4::Vector4;
+
+    fn camera_at(from: Vector4) -> Camera {
+        CameraBuilder::new()
+            .with_hsize(8)
+            .with_vsize(6)
+            .with_field_of_view(PI / 3.0)
+            .with_transform(Matrix::view_transform(
+                from,
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .build()
+    }
+
+    #[fixture]
+    fn camera() -> Camera {
+        camera_at(Vector4::point(0.0, 0.0, -5.0))
+    }
+
+    #[rstest]
+    fn the_first_frame_matches_a_single_sample_render(camera: Camera) {
+        let world = World::default_world();
+        let settings = RenderSettings::default();
+        let expected = camera.render(&world, &settings);
+        let mut progressive = ProgressiveRender::new(camera);
+
+        progressive.render_frame(&world, &settings);
+
+        assert_that!(progressive.frames()).is_equal_to(1);
+        assert_that!(progressive.image(&settings).pixel_at(4, 3))
+            .is_equal_to(expected.pixel_at(4, 3));
+    }
+
+    #[rstest]
+    fn frames_accumulate_while_the_camera_is_still(camera: Camera) {
+        let world = World::default_world();
+        let settings = RenderSettings::default();
+        let mut progressive = ProgressiveRender::new(camera);
+
+        for _ in 0..3 {
+            progressive.render_frame(&world, &settings);
+        }
+        progressive.set_camera(camera_at(Vector4::point(0.0, 0.0, -5.0)));
+
+        assert_that!(progressive.frames()).is_equal_to(3);
+    }
+
+    #[rstest]
+    fn moving_the_camera_starts_again(camera: Camera) {
+        let world = World::default_world();
+        let settings = RenderSettings::default();
+        let mut progressive = ProgressiveRender::new(camera);
+        progressive.render_frame(&world, &settings);
+        progressive.render_frame(&world, &settings);
+
+        progressive.set_camera(camera_at(Vector4::point(1.0, 0.0, -5.0)));
+
+        assert_that!(progressive.frames()).is_equal_to(0);
+        let image = progressive.image(&settings);
+        assert_that!(image.pixel_at(4, 3)).is_equal_to(crate::color::Color::black());
+    }
+
+    #[rstest]
+    fn resizing_the_camera_resizes_the_image(camera: Camera) {
+        let mut progressive = ProgressiveRender::new(camera);
+
+        progressive.set_camera(camera_at(Vector4::point(0.0, 0.0, -5.0)).preview(4));
+
+        let image = progressive.image(&RenderSettings::default());
+        assert_that!((image.width(), image.height())).is_equal_to((4, 3));
+    }
+
+    #[rstest]
+    fn adaptive_sampling_stops_refining_settled_pixels(camera: Camera) {
+        let world = World::default_world();
+        let settings = RenderSettings {
+            adaptive: Some(AdaptiveSampling {
+                min_samples: 2,
+                max_samples: 2,
+                ..AdaptiveSampling::default()
+            }),
+            ..RenderSettings::default()
+        };
+        let mut progressive = ProgressiveRender::new(camera);
+        progressive.render_frame(&world, &settings);
+        progressive.render_frame(&world, &settings);
+        let settled = progressive.image(&settings);
+
+        progressive.render_frame(&world, &settings);
+        progressive.render_frame(&world, &settings);
+
+        let image = progressive.image(&settings);
+        for (x, y) in [(0, 0), (4, 3), (7, 5)] {
+            assert_that!(image.pixel_at(x, y)).is_equal_to(settled.pixel_at(x, y));
+        }
+    }
+}
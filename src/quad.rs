@@ -0,0 +1,243 @@
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::shape::{area_scale, shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// A flat square spanning -1 to 1 in x and z, facing up the y axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quad {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+}
+
+pub struct QuadBuilder {
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+}
+
+impl Shape for Quad {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Vector4::point(-1.0, 0.0, -1.0),
+            Vector4::point(1.0, 0.0, 1.0),
+        )
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        if ray.direction.y.abs() < EPSILON {
+            return Vec::default();
+        }
+
+        let t = -ray.origin.y / ray.direction.y;
+        let hit = ray.position(t);
+        if hit.x.abs() > 1.0 || hit.z.abs() > 1.0 {
+            return Vec::default();
+        }
+
+        vec![t]
+    }
+
+    fn local_normal_at(&self, _object_point: Vector4) -> Vector4 {
+        Vector4::vector(0.0, 1.0, 0.0)
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        Vector4::point(
+            object_point.x.clamp(-1.0, 1.0),
+            0.0,
+            object_point.z.clamp(-1.0, 1.0),
+        )
+    }
+
+    fn surface_area(&self) -> Option<f32> {
+        let normal = Vector4::vector(0.0, 1.0, 0.0);
+
+        Some(4.0 * area_scale(&self.transformation(), &self.inv_transform, normal))
+    }
+
+    fn sample_surface(&self, rng: &mut Rng) -> Option<(Vector4, Vector4, f32)> {
+        let object_point =
+            Vector4::point(2.0 * rng.next_f32() - 1.0, 0.0, 2.0 * rng.next_f32() - 1.0);
+        let point = self.transformation() * object_point;
+
+        Some((point, self.normal_at(&point), 1.0 / self.surface_area()?))
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        self.material
+            .lighting(light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl QuadBuilder {
+    pub fn new() -> Self {
+        Self {
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    pub fn build(self) -> impl Shape {
+        Quad {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use crate::transform::Transform;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Vector4::point(0.5, 1.0, -0.5), vec![1.0])]
+    #[case(Vector4::point(1.0, 1.0, 1.0), vec![1.0])]
+    #[case(Vector4::point(1.5, 1.0, 0.0), vec![])]
+    #[case(Vector4::point(0.0, 1.0, -1.5), vec![])]
+    fn intersecting_a_quad(#[case] origin: Vector4, #[case] expected: Vec<f32>) {
+        let q = QuadBuilder::new().build();
+        let r = Ray::new(origin, Vector4::vector(0.0, -1.0, 0.0));
+
+        assert_that!(q.local_intersect(&r)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn a_ray_parallel_to_a_quad_misses() {
+        let q = QuadBuilder::new().build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        assert_that!(q.local_intersect(&r)).is_empty();
+    }
+
+    #[test]
+    fn the_closest_point_on_a_quad() {
+        let q = QuadBuilder::new().build();
+
+        assert_that!(q.local_closest_point(Vector4::point(3.0, 2.0, 0.5)))
+            .is_equal_to(Vector4::point(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn the_surface_area_of_a_transformed_quad() {
+        let q = QuadBuilder::new()
+            .with_transform(Matrix::rotation_x(1.0) * Matrix::scaling(2.0, 1.0, 0.5))
+            .build();
+
+        assert_that!(q.surface_area())
+            .is_some()
+            .is_close_to(4.0, EPSILON);
+    }
+
+    #[test]
+    fn samples_lie_on_the_quad() {
+        let q = QuadBuilder::new()
+            .with_transform(Matrix::translation(0.0, 2.0, 0.0) * Matrix::scaling(3.0, 1.0, 3.0))
+            .build();
+        let mut rng = Rng::new(5);
+
+        for _ in 0..100 {
+            let (point, normal, pdf) = q.sample_surface(&mut rng).unwrap();
+
+            assert_that!(point.y).is_close_to(2.0, EPSILON);
+            assert_that!(point.x.abs()).is_less_than_or_equal_to(3.0);
+            assert_that!(point.z.abs()).is_less_than_or_equal_to(3.0);
+            assert_that!(normal).is_equal_to(Vector4::vector(0.0, 1.0, 0.0));
+            assert_that!(pdf).is_close_to(1.0 / 36.0, EPSILON);
+        }
+    }
+}
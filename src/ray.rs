@@ -1,16 +1,38 @@
 use crate::matrix::Matrix;
 use crate::vector4::Vector4;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Ray {
     pub origin: Vector4,
     pub direction: Vector4,
+    /// How far along the ray, in multiples of its direction, things start to be seen.
+    pub t_min: f32,
+    /// How far along the ray things stop being seen. Nothing at or beyond it is.
+    pub t_max: f32,
 }
 
 impl Ray {
     pub fn new(origin: Vector4, direction: Vector4) -> Self {
         assert!(origin.is_point(), "Origin must be a point");
         assert!(direction.is_vector(), "Direction must be a vector");
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            t_min: 0.0,
+            t_max: f32::INFINITY,
+        }
+    }
+
+    /// The same ray, only seeing things from `t_min` to `t_max` along it.
+    pub fn with_range(mut self, t_min: f32, t_max: f32) -> Self {
+        self.t_min = t_min;
+        self.t_max = t_max;
+        self
+    }
+
+    /// Is `t` between the ray's near and far limits?
+    pub fn in_range(&self, t: f32) -> bool {
+        self.t_min <= t && t < self.t_max
     }
 
     pub fn position(&self, t: f32) -> Vector4 {
@@ -21,6 +43,7 @@ impl Ray {
         Self {
             origin: *transformation * self.origin,
             direction: *transformation * self.direction,
+            ..*self
         }
     }
 }
@@ -31,7 +54,7 @@ Tests
 
 #[cfg(test)]
 mod tests {
-    use spectral::assert_that;
+    use spectral::prelude::*;
 
     use super::*;
     use crate::transform::Transform;
@@ -105,4 +128,35 @@ mod tests {
         assert_that!(r2.origin).is_equal_to(Vector4::point(2.0, 6.0, 12.0));
         assert_that!(r2.direction).is_equal_to(Vector4::vector(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn a_ray_sees_everything_in_front_of_it_unless_limited() {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        assert_that!(r.in_range(1e9)).is_true();
+        assert_that!(r.in_range(-0.1)).is_false();
+
+        let limited = r.with_range(1.0, 5.0);
+
+        assert_that!(limited.in_range(0.5)).is_false();
+        assert_that!(limited.in_range(3.0)).is_true();
+        assert_that!(limited.in_range(5.0)).is_false();
+        assert_that!(limited.in_range(5.5)).is_false();
+    }
+
+    #[test]
+    fn transforming_a_ray_keeps_its_range() {
+        let r = Ray::new(
+            Vector4::point(1.0, 2.0, 3.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        )
+        .with_range(1.0, 4.0);
+
+        let r2 = r.transform(&Matrix::scaling(2.0, 3.0, 4.0));
+
+        assert_that!((r2.t_min, r2.t_max)).is_equal_to((1.0, 4.0));
+    }
 }
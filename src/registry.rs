@@ -0,0 +1,447 @@
+//! The shape and pattern types a scene can name, and how to make each one from the parameters
+//! given with it. Other crates register their own types here to use them in scenes.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use crate::color::Color;
+use crate::cube::CubeBuilder;
+use crate::cylinder::CylinderBuilder;
+use crate::pattern::{GranitePattern, MarblePattern, Pattern, WoodPattern};
+use crate::plane::PlaneBuilder;
+use crate::shape::{Shape, UserData};
+use crate::sphere::SphereBuilder;
+
+/// A parameter value, as a scene file gives it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f32),
+    Text(String),
+    List(Vec<Value>),
+}
+
+/// The named parameters given for one shape or pattern.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    values: HashMap<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegistryError {
+    /// No constructor has been registered for the type.
+    UnknownType(String),
+    /// A parameter the type needs was not given.
+    MissingParameter(String),
+    /// A parameter was given, but not as the kind of value the type needs.
+    BadParameter(String),
+}
+
+impl std::error::Error for RegistryError {}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::UnknownType(name) => write!(f, "unknown type '{}'", name),
+            RegistryError::MissingParameter(key) => write!(f, "missing parameter '{}'", key),
+            RegistryError::BadParameter(key) => write!(f, "bad value for parameter '{}'", key),
+        }
+    }
+}
+
+type ShapeConstructor = Box<dyn Fn(&Params) -> Result<Rc<dyn Shape>, RegistryError>>;
+type PatternConstructor = Box<dyn Fn(&Params) -> Result<Rc<dyn Pattern>, RegistryError>>;
+
+/// Constructors for shapes and patterns by type name. Transforms and materials are common to
+/// every shape, so they are left to whatever reads the scene; constructors only deal with what
+/// is particular to their type.
+#[derive(Default)]
+pub struct SceneRegistry {
+    shapes: HashMap<String, ShapeConstructor>,
+    patterns: HashMap<String, PatternConstructor>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: &str, value: Value) -> Self {
+        self.values.insert(key.to_string(), value);
+
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn number(&self, key: &str) -> Result<f32, RegistryError> {
+        match self.get(key) {
+            Some(Value::Number(n)) => Ok(*n),
+            Some(_) => Err(RegistryError::BadParameter(key.to_string())),
+            None => Err(RegistryError::MissingParameter(key.to_string())),
+        }
+    }
+
+    pub fn number_or(&self, key: &str, default: f32) -> Result<f32, RegistryError> {
+        self.or(key, default, Self::number)
+    }
+
+    pub fn bool_or(&self, key: &str, default: bool) -> Result<bool, RegistryError> {
+        self.or(key, default, |params, key| match params.get(key) {
+            Some(Value::Bool(b)) => Ok(*b),
+            _ => Err(RegistryError::BadParameter(key.to_string())),
+        })
+    }
+
+    pub fn text(&self, key: &str) -> Result<&str, RegistryError> {
+        match self.get(key) {
+            Some(Value::Text(text)) => Ok(text),
+            Some(_) => Err(RegistryError::BadParameter(key.to_string())),
+            None => Err(RegistryError::MissingParameter(key.to_string())),
+        }
+    }
+
+    /// A colour given as a list of red, green and blue, or as a colour temperature such as
+    /// `"2700K"`.
+    pub fn color(&self, key: &str) -> Result<Color, RegistryError> {
+        let bad = || RegistryError::BadParameter(key.to_string());
+        match self.get(key) {
+            Some(Value::List(values)) => match values.as_slice() {
+                [Value::Number(r), Value::Number(g), Value::Number(b)] => {
+                    Ok(Color::new(*r, *g, *b))
+                }
+                _ => Err(bad()),
+            },
+            Some(Value::Text(text)) => text
+                .strip_suffix(['K', 'k'])
+                .and_then(|kelvin| kelvin.trim().parse::<f32>().ok())
+                .filter(|kelvin| *kelvin > 0.0)
+                .map(Color::from_kelvin)
+                .ok_or_else(bad),
+            Some(_) => Err(bad()),
+            None => Err(RegistryError::MissingParameter(key.to_string())),
+        }
+    }
+
+    pub fn color_or(&self, key: &str, default: Color) -> Result<Color, RegistryError> {
+        self.or(key, default, Self::color)
+    }
+
+    fn or<T>(
+        &self,
+        key: &str,
+        default: T,
+        read: impl Fn(&Self, &str) -> Result<T, RegistryError>,
+    ) -> Result<T, RegistryError> {
+        match self.get(key) {
+            Some(_) => read(self, key),
+            None => Ok(default),
+        }
+    }
+
+    fn name(&self) -> Result<Option<&str>, RegistryError> {
+        match self.get("name") {
+            Some(_) => self.text("name").map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The tags for a shape, given under `user_data` as a list of `"key=value"` texts.
+    pub fn user_data(&self) -> Result<UserData, RegistryError> {
+        let bad = || RegistryError::BadParameter("user_data".to_string());
+        let values = match self.get("user_data") {
+            Some(Value::List(values)) => values,
+            Some(_) => return Err(bad()),
+            None => return Ok(UserData::new()),
+        };
+
+        values
+            .iter()
+            .map(|value| match value {
+                Value::Text(text) => text
+                    .split_once('=')
+                    .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                    .ok_or_else(bad),
+                _ => Err(bad()),
+            })
+            .collect()
+    }
+}
+
+impl SceneRegistry {
+    /// A registry with nothing in it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the shapes and procedural patterns that come with the ray tracer.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register("sphere", |params| {
+            let mut builder = SphereBuilder::new();
+            if let Some(name) = params.name()? {
+                builder = builder.with_name(name);
+            }
+            for (key, value) in params.user_data()? {
+                builder = builder.with_user_data(&key, &value);
+            }
+            Ok(Rc::new(builder.build()))
+        });
+        registry.register("plane", |params| {
+            let mut builder = PlaneBuilder::new();
+            if let Some(name) = params.name()? {
+                builder = builder.with_name(name);
+            }
+            for (key, value) in params.user_data()? {
+                builder = builder.with_user_data(&key, &value);
+            }
+            Ok(Rc::new(builder.build()))
+        });
+        registry.register("cube", |params| {
+            let mut builder = CubeBuilder::new();
+            if let Some(name) = params.name()? {
+                builder = builder.with_name(name);
+            }
+            for (key, value) in params.user_data()? {
+                builder = builder.with_user_data(&key, &value);
+            }
+            Ok(Rc::new(builder.build()))
+        });
+        registry.register("cylinder", |params| {
+            let mut builder = CylinderBuilder::new()
+                .with_minimum(params.number_or("minimum", f32::NEG_INFINITY)?)
+                .with_maximum(params.number_or("maximum", f32::INFINITY)?)
+                .with_closed(params.bool_or("closed", false)?);
+            if let Some(name) = params.name()? {
+                builder = builder.with_name(name);
+            }
+            for (key, value) in params.user_data()? {
+                builder = builder.with_user_data(&key, &value);
+            }
+            Ok(Rc::new(builder.build()))
+        });
+
+        registry.register_pattern("wood", |params| {
+            Ok(Rc::new(
+                WoodPattern::new(params.color("light")?, params.color("dark")?)
+                    .with_scale(params.number_or("scale", 4.0)?)
+                    .with_turbulence(params.number_or("turbulence", 0.2)?)
+                    .with_seed(params.number_or("seed", 0.0)? as u64),
+            ))
+        });
+        registry.register_pattern("marble", |params| {
+            Ok(Rc::new(
+                MarblePattern::new(params.color("base")?, params.color("vein")?)
+                    .with_seed(params.number_or("seed", 0.0)? as u64),
+            ))
+        });
+        registry.register_pattern("granite", |params| {
+            Ok(Rc::new(
+                GranitePattern::new(params.color("first")?, params.color("second")?)
+                    .with_seed(params.number_or("seed", 0.0)? as u64),
+            ))
+        });
+
+        registry
+    }
+
+    /// Make shapes of type `name` with `constructor`, in place of any it was made with before.
+    pub fn register(
+        &mut self,
+        name: &str,
+        constructor: impl Fn(&Params) -> Result<Rc<dyn Shape>, RegistryError> + 'static,
+    ) {
+        self.shapes.insert(name.to_string(), Box::new(constructor));
+    }
+
+    /// Make patterns of type `name` with `constructor`, in place of any it was made with before.
+    pub fn register_pattern(
+        &mut self,
+        name: &str,
+        constructor: impl Fn(&Params) -> Result<Rc<dyn Pattern>, RegistryError> + 'static,
+    ) {
+        self.patterns
+            .insert(name.to_string(), Box::new(constructor));
+    }
+
+    pub fn shape(&self, name: &str, params: &Params) -> Result<Rc<dyn Shape>, RegistryError> {
+        match self.shapes.get(name) {
+            Some(constructor) => constructor(params),
+            None => Err(RegistryError::UnknownType(name.to_string())),
+        }
+    }
+
+    pub fn pattern(&self, name: &str, params: &Params) -> Result<Rc<dyn Pattern>, RegistryError> {
+        match self.patterns.get(name) {
+            Some(constructor) => constructor(params),
+            None => Err(RegistryError::UnknownType(name.to_string())),
+        }
+    }
+
+    /// The names of the shape types, in alphabetical order.
+    pub fn shape_types(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.shapes.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        names
+    }
+
+    /// The names of the pattern types, in alphabetical order.
+    pub fn pattern_types(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.patterns.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        names
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::matrix::Matrix;
+    use crate::ray::Ray;
+    use crate::transform::Transform;
+    use crate::vector4::Vector4;
+
+    fn rgb(r: f32, g: f32, b: f32) -> Value {
+        Value::List(vec![Value::Number(r), Value::Number(g), Value::Number(b)])
+    }
+
+    #[fixture]
+    fn registry() -> SceneRegistry {
+        SceneRegistry::builtin()
+    }
+
+    #[rstest]
+    fn the_builtin_types(registry: SceneRegistry) {
+        assert_that!(registry.shape_types())
+            .is_equal_to(vec!["cube", "cylinder", "plane", "sphere"]);
+        assert_that!(registry.pattern_types()).is_equal_to(vec!["granite", "marble", "wood"]);
+    }
+
+    #[rstest]
+    fn making_a_builtin_shape(registry: SceneRegistry) {
+        let params = Params::new()
+            .with("name", Value::Text("post".to_string()))
+            .with("minimum", Value::Number(0.0))
+            .with("maximum", Value::Number(2.0))
+            .with("closed", Value::Bool(true));
+
+        let shape = registry.shape("cylinder", &params).unwrap();
+
+        assert_that!(shape.name()).is_equal_to(Some("post"));
+        assert_that!(shape.bounds().max.y).is_equal_to(2.0);
+    }
+
+    #[rstest]
+    fn registering_a_new_shape(mut registry: SceneRegistry) {
+        // A sphere of any radius, made by scaling the unit sphere
+        registry.register("ball", |params| {
+            let r = params.number("radius")?;
+            let sphere: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+            Ok(sphere.transformed(&Matrix::scaling(r, r, r)))
+        });
+        let ray = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let ball = registry
+            .shape("ball", &Params::new().with("radius", Value::Number(2.0)))
+            .unwrap();
+
+        assert_that!(ball.intersect(&ray)).is_equal_to(vec![3.0, 7.0]);
+        assert_that!(registry.shape_types()).contains("ball");
+    }
+
+    #[rstest]
+    fn builtin_shapes_carry_their_user_data(registry: SceneRegistry) {
+        let params = Params::new().with(
+            "user_data",
+            Value::List(vec![
+                Value::Text("layer=props".to_string()),
+                Value::Text("matte = 1".to_string()),
+            ]),
+        );
+
+        let shape = registry.shape("cube", &params).unwrap();
+
+        let user_data = shape.user_data().unwrap();
+        assert_that!(user_data.get("layer")).is_equal_to(Some(&"props".to_string()));
+        assert_that!(user_data.get("matte")).is_equal_to(Some(&"1".to_string()));
+    }
+
+    #[rstest]
+    fn making_a_builtin_pattern(registry: SceneRegistry) {
+        let params = Params::new()
+            .with("light", rgb(1.0, 0.8, 0.6))
+            .with("dark", rgb(0.4, 0.2, 0.1))
+            .with("turbulence", Value::Number(0.0));
+
+        let pattern = registry.pattern("wood", &params).unwrap();
+
+        assert_that!(pattern.color_at_point(Vector4::point(0.0, 0.0, 0.0)))
+            .is_equal_to(Color::new(1.0, 0.8, 0.6));
+    }
+
+    #[rstest]
+    #[case(Value::Text("2700K".to_string()), Some(Color::from_kelvin(2700.0)))]
+    #[case(Value::Text("6500 k".to_string()), Some(Color::from_kelvin(6500.0)))]
+    #[case(Value::Text("warm".to_string()), None)]
+    #[case(Value::Text("-5K".to_string()), None)]
+    fn colours_given_as_temperatures(#[case] value: Value, #[case] expected: Option<Color>) {
+        let params = Params::new().with("tint", value);
+
+        assert_that!(params.color("tint").ok()).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case("torus", Params::new(), RegistryError::UnknownType("torus".to_string()))]
+    #[case(
+        "cylinder",
+        Params::new().with("closed", Value::Number(1.0)),
+        RegistryError::BadParameter("closed".to_string())
+    )]
+    #[case(
+        "sphere",
+        Params::new().with("name", Value::Bool(true)),
+        RegistryError::BadParameter("name".to_string())
+    )]
+    #[case(
+        "plane",
+        Params::new().with("user_data", Value::List(vec![Value::Text("layer".to_string())])),
+        RegistryError::BadParameter("user_data".to_string())
+    )]
+    fn a_shape_that_cannot_be_made(
+        registry: SceneRegistry,
+        #[case] name: &str,
+        #[case] params: Params,
+        #[case] expected: RegistryError,
+    ) {
+        assert_that!(registry.shape(name, &params).err()).is_equal_to(Some(expected));
+    }
+
+    #[rstest]
+    #[case(Params::new(), RegistryError::MissingParameter("base".to_string()))]
+    #[case(
+        Params::new().with("base", rgb(1.0, 1.0, 1.0)).with("vein", Value::Number(0.5)),
+        RegistryError::BadParameter("vein".to_string())
+    )]
+    fn a_pattern_that_cannot_be_made(
+        registry: SceneRegistry,
+        #[case] params: Params,
+        #[case] expected: RegistryError,
+    ) {
+        assert_that!(registry.pattern("marble", &params).err()).is_equal_to(Some(expected));
+    }
+}
@@ -0,0 +1,124 @@
+/// A small pseudo-random number generator (PCG32) so sampling is fast and reproducible from a
+/// seed. Different streams with the same seed give independent sequences.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0)
+    }
+
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    /// The generator for pixel `(x, y)` of an image `width` pixels wide. It depends on nothing
+    /// else, so a pixel comes out the same whatever order the pixels are rendered in and however
+    /// they are shared out.
+    pub fn for_pixel(seed: u64, x: usize, y: usize, width: usize) -> Self {
+        Self::with_stream(seed, (y * width + x) as u64)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// A number uniformly distributed in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        // Use the top 24 bits, which is all an f32 mantissa can hold
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// The seed for frame `frame` of a render seeded with `seed`, so that each frame of an animation
+/// has its own noise. Frame 0 keeps the seed as it is.
+pub fn frame_seed(seed: u64, frame: u64) -> u64 {
+    seed ^ frame.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn the_generator_matches_the_pcg32_reference_output() {
+        let mut rng = Rng::with_stream(42, 54);
+
+        let values: Vec<u32> = (0..6).map(|_| rng.next_u32()).collect();
+
+        assert_that!(values).is_equal_to(vec![
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ]);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_sequence() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+
+        for _ in 0..10 {
+            assert_that!(a.next_u32()).is_equal_to(b.next_u32());
+        }
+    }
+
+    #[test]
+    fn different_streams_give_different_sequences() {
+        let mut a = Rng::with_stream(7, 1);
+        let mut b = Rng::with_stream(7, 2);
+
+        let xs: Vec<u32> = (0..4).map(|_| a.next_u32()).collect();
+        let ys: Vec<u32> = (0..4).map(|_| b.next_u32()).collect();
+
+        assert_that!(xs).is_not_equal_to(ys);
+    }
+
+    #[test]
+    fn floats_are_in_the_unit_interval() {
+        let mut rng = Rng::new(1);
+
+        for _ in 0..1000 {
+            let f = rng.next_f32();
+            assert_that!(f).is_greater_than_or_equal_to(0.0);
+            assert_that!(f).is_less_than(1.0);
+        }
+    }
+
+    #[test]
+    fn pixels_have_their_own_streams() {
+        let first = |x, y, seed| Rng::for_pixel(seed, x, y, 10).next_u32();
+
+        assert_that!(first(3, 2, 0)).is_equal_to(Rng::with_stream(0, 23).next_u32());
+        assert_that!(first(3, 2, 0)).is_not_equal_to(first(2, 3, 0));
+        assert_that!(first(3, 2, 0)).is_not_equal_to(first(3, 2, 1));
+    }
+
+    #[test]
+    fn each_frame_has_its_own_seed() {
+        assert_that!(frame_seed(42, 0)).is_equal_to(42);
+        assert_that!(frame_seed(42, 1)).is_not_equal_to(frame_seed(42, 2));
+    }
+}
@@ -0,0 +1,714 @@
+//! Scenes read from JSON text, so they can be written by hand or by other programs and given to
+//! the renderer without recompiling it.
+//!
+//! A scene is an object with a `camera`, a list of `lights` and a list of `objects`:
+//!
+//! ```json
+//! {
+//!   "camera": { "width": 400, "height": 300, "field_of_view": 1.047,
+//!               "from": [0, 1.5, -5], "to": [0, 1, 0], "up": [0, 1, 0] },
+//!   "lights": [ { "position": [-10, 10, -10], "intensity": [1, 1, 1] } ],
+//!   "objects": [
+//!     { "type": "sphere", "name": "ball",
+//!       "transform": [["scale", 0.5, 0.5, 0.5], ["translate", 0, 1, 0]],
+//!       "material": { "color": [1, 0.2, 0.2], "specular": 0.3 } },
+//!     { "type": "group", "children": [ { "type": "plane" } ] }
+//!   ]
+//! }
+//! ```
+//!
+//! Shapes other than groups, and patterns, are made by the `SceneRegistry`, which is given every
+//! key of the object except `type`, `transform`, `material` and `children`. Transforms are
+//! applied in the order they are listed. A camera without `from` is placed to fit the whole
+//! scene in view.
+
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use crate::camera::{CameraBuilder, FitMode};
+use crate::group::GroupBuilder;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::registry::{Params, RegistryError, SceneRegistry, Value};
+use crate::shape::Shape;
+use crate::transform::Transform;
+use crate::vector4::Vector4;
+use crate::world::{SceneProblem, World, WorldBuilder};
+
+/// How deeply lists and objects may be nested, so that a hostile document can't use up the stack.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneError {
+    /// The text isn't JSON; the byte offset says where reading it went wrong.
+    Syntax(usize, String),
+    /// The JSON doesn't describe a scene.
+    Invalid(String),
+    /// The registry couldn't make one of the shapes or patterns.
+    Registry(RegistryError),
+}
+
+impl std::error::Error for SceneError {}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Syntax(offset, what) => write!(f, "{} at byte {}", what, offset),
+            SceneError::Invalid(what) => write!(f, "{}", what),
+            SceneError::Registry(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<RegistryError> for SceneError {
+    fn from(e: RegistryError) -> Self {
+        SceneError::Registry(e)
+    }
+}
+
+/// Read the scene in `text`, making its shapes and patterns with `registry`. The camera is
+/// returned unbuilt so that the caller can change it further.
+pub fn from_json(
+    text: &str,
+    registry: &SceneRegistry,
+) -> Result<(World, CameraBuilder), SceneError> {
+    let scene = Parser::new(text).document()?;
+    let members = scene.members("the scene")?;
+
+    let mut world = WorldBuilder::new();
+    for light in field(members, "lights").map_or(Ok(&[][..]), |l| l.items("lights"))? {
+        let light = light.members("a light")?;
+        let position = match field(light, "position") {
+            Some(position) => position.point("a light's position")?,
+            None => return Err(invalid("a light needs a position")),
+        };
+        let intensity =
+            params(light, &[])?.color_or("intensity", PointLight::default().intensity)?;
+        world = world.with_light(PointLight::new(position, intensity));
+    }
+    for object in field(members, "objects").map_or(Ok(&[][..]), |o| o.items("objects"))? {
+        world = world.with_object(shape(object, registry)?);
+    }
+    let world = world.build();
+
+    let camera = match field(members, "camera") {
+        Some(camera) => camera.members("the camera")?,
+        None => &[],
+    };
+    let number = |key: &str, default: f32| match field(camera, key) {
+        Some(Json::Number(n)) => Ok(*n),
+        Some(_) => Err(invalid(&format!("the camera's {} should be a number", key))),
+        None => Ok(default),
+    };
+    let builder = CameraBuilder::new()
+        .with_hsize(number("width", 400.0)?.max(1.0) as usize)
+        .with_vsize(number("height", 300.0)?.max(1.0) as usize)
+        .with_field_of_view(number("field_of_view", PI / 3.0)?);
+    let builder = match field(camera, "from") {
+        Some(from) => {
+            let from = from.point("the camera's from")?;
+            let to = field(camera, "to").map_or(Ok(Vector4::point(0.0, 0.0, 0.0)), |to| {
+                to.point("the camera's to")
+            })?;
+            let up = field(camera, "up").map_or(Ok(Vector4::vector(0.0, 1.0, 0.0)), |up| {
+                up.vector("the camera's up")
+            })?;
+            let view = Matrix::try_view_transform(from, to, up)
+                .map_err(|e| invalid(&format!("the camera can't be placed: {}", e)))?;
+            builder.with_transform(view)
+        }
+        None => builder.framing(&world, FitMode::Fit),
+    };
+
+    Ok((world, builder))
+}
+
+fn shape(object: &Json, registry: &SceneRegistry) -> Result<Rc<dyn Shape>, SceneError> {
+    let members = object.members("an object")?;
+    let kind = match field(members, "type") {
+        Some(Json::Text(kind)) => kind.as_str(),
+        _ => return Err(invalid("an object needs a type")),
+    };
+
+    let mut shape = if kind == "group" {
+        let mut group = GroupBuilder::new();
+        if let Some(name) = params(members, &["children"])?.get("name") {
+            match name {
+                Value::Text(name) => group = group.with_name(name),
+                _ => return Err(RegistryError::BadParameter("name".to_string()).into()),
+            }
+        }
+        for child in field(members, "children").map_or(Ok(&[][..]), |c| c.items("children"))? {
+            group = group.with_child(shape(child, registry)?);
+        }
+        Rc::new(group.build())
+    } else {
+        registry.shape(kind, &params(members, &["children"])?)?
+    };
+    if let Some(material) = field(members, "material") {
+        shape = shape.with_material(&self::material(material, registry)?);
+    }
+    if let Some(transform) = field(members, "transform") {
+        let transform = self::transform(transform)?;
+        // Shapes invert their transforms, so one that flattens them can't be used. This is the
+        // problem `World::check` would find, but found before a shape tries to invert it.
+        if !invertible(&transform) {
+            let problem = SceneProblem::BadTransform(shape.name().map(String::from));
+            return Err(invalid(&problem.to_string()));
+        }
+        shape = shape.transformed(&transform);
+    }
+
+    Ok(shape)
+}
+
+fn material(material: &Json, registry: &SceneRegistry) -> Result<Material, SceneError> {
+    let members = material.members("a material")?;
+    let params = params(members, &["pattern"])?;
+    let number = |key: &str| params.get(key).map(|_| params.number(key)).transpose();
+
+    let mut builder = MaterialBuilder::new();
+    if params.get("color").is_some() {
+        builder = builder.with_color(params.color("color")?);
+    }
+    if let Some(pattern) = field(members, "pattern") {
+        let pattern_members = pattern.members("a pattern")?;
+        let kind = match field(pattern_members, "type") {
+            Some(Json::Text(kind)) => kind,
+            _ => return Err(invalid("a pattern needs a type")),
+        };
+        builder = builder
+            .with_shared_pattern(registry.pattern(kind, &self::params(pattern_members, &[])?)?);
+    }
+    if let Some(ambient) = number("ambient")? {
+        builder = builder.with_ambient(ambient);
+    }
+    if let Some(diffuse) = number("diffuse")? {
+        builder = builder.with_diffuse(diffuse);
+    }
+    if let Some(specular) = number("specular")? {
+        builder = builder.with_specular(specular);
+    }
+    if let Some(shininess) = number("shininess")? {
+        builder = builder.with_shininess(shininess);
+    }
+    if let Some(reflective) = number("reflective")? {
+        builder = builder.with_reflective(reflective);
+    }
+    if let Some(transparency) = number("transparency")? {
+        builder = builder.with_transparency(transparency);
+    }
+    if let Some(refractive_index) = number("refractive_index")? {
+        builder = builder.with_refractive_index(refractive_index);
+    }
+
+    Ok(builder.build())
+}
+
+/// The transforms listed, each one applied after those before it.
+fn transform(transform: &Json) -> Result<Matrix<4>, SceneError> {
+    let mut matrix = Matrix::identity();
+    for step in transform.items("a transform")? {
+        let step = step.items("a transform step")?;
+        let (kind, args) = match step.split_first() {
+            Some((Json::Text(kind), args)) => (kind.as_str(), args),
+            _ => return Err(invalid("a transform step should start with its kind")),
+        };
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                Json::Number(n) => Ok(*n),
+                _ => Err(invalid(&format!(
+                    "the arguments to {} should be numbers",
+                    kind
+                ))),
+            })
+            .collect::<Result<Vec<f32>, _>>()?;
+        let step = match (kind, &args[..]) {
+            ("translate", &[x, y, z]) => Matrix::translation(x, y, z),
+            ("scale", &[x, y, z]) => Matrix::scaling(x, y, z),
+            ("rotate_x", &[r]) => Matrix::rotation_x(r),
+            ("rotate_y", &[r]) => Matrix::rotation_y(r),
+            ("rotate_z", &[r]) => Matrix::rotation_z(r),
+            ("shear", &[xy, xz, yx, yz, zx, zy]) => Matrix::shearing(xy, xz, yx, yz, zx, zy),
+            _ => return Err(invalid(&format!("can't {} by {:?}", kind, args))),
+        };
+        matrix = step * matrix;
+    }
+
+    Ok(matrix)
+}
+
+fn invertible(matrix: &Matrix<4>) -> bool {
+    let determinant = matrix.determinant();
+    determinant != 0.0 && determinant.is_finite()
+}
+
+/// The members of an object as registry parameters, leaving out those read by the scene itself
+/// and any in `skip`.
+fn params(members: &[(String, Json)], skip: &[&str]) -> Result<Params, SceneError> {
+    let mut params = Params::new();
+    for (key, value) in members {
+        if ["type", "transform", "material"].contains(&key.as_str()) || skip.contains(&key.as_str())
+        {
+            continue;
+        }
+        params = params.with(key, value.to_value(key)?);
+    }
+
+    Ok(params)
+}
+
+fn field<'a>(members: &'a [(String, Json)], key: &str) -> Option<&'a Json> {
+    members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn invalid(what: &str) -> SceneError {
+    SceneError::Invalid(what.to_string())
+}
+
+/// A JSON value. Objects keep their members in the order they were written.
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f32),
+    Text(String),
+    List(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn members(&self, what: &str) -> Result<&[(String, Json)], SceneError> {
+        match self {
+            Json::Object(members) => Ok(members),
+            _ => Err(invalid(&format!("{} should be an object", what))),
+        }
+    }
+
+    fn items(&self, what: &str) -> Result<&[Json], SceneError> {
+        match self {
+            Json::List(items) => Ok(items),
+            _ => Err(invalid(&format!("{} should be a list", what))),
+        }
+    }
+
+    fn triple(&self, what: &str) -> Result<(f32, f32, f32), SceneError> {
+        match self.items(what)? {
+            [Json::Number(x), Json::Number(y), Json::Number(z)] => Ok((*x, *y, *z)),
+            _ => Err(invalid(&format!("{} should be three numbers", what))),
+        }
+    }
+
+    fn point(&self, what: &str) -> Result<Vector4, SceneError> {
+        let (x, y, z) = self.triple(what)?;
+        Ok(Vector4::point(x, y, z))
+    }
+
+    fn vector(&self, what: &str) -> Result<Vector4, SceneError> {
+        let (x, y, z) = self.triple(what)?;
+        Ok(Vector4::vector(x, y, z))
+    }
+
+    fn to_value(&self, key: &str) -> Result<Value, SceneError> {
+        match self {
+            Json::Bool(b) => Ok(Value::Bool(*b)),
+            Json::Number(n) => Ok(Value::Number(*n)),
+            Json::Text(text) => Ok(Value::Text(text.clone())),
+            Json::List(items) => Ok(Value::List(
+                items
+                    .iter()
+                    .map(|item| item.to_value(key))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Json::Null | Json::Object(_) => {
+                Err(RegistryError::BadParameter(key.to_string()).into())
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            offset: 0,
+            depth: 0,
+        }
+    }
+
+    /// The one value that makes up the whole text.
+    fn document(&mut self) -> Result<Json, SceneError> {
+        let value = self.value()?;
+        self.skip_space();
+        if self.offset < self.text.len() {
+            return Err(self.error("expected the end of the text"));
+        }
+
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, SceneError> {
+        self.skip_space();
+        match self.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::list),
+            Some('"') => self.string().map(Json::Text),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of text")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<Json, SceneError>,
+    ) -> Result<Json, SceneError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let value = read(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn object(&mut self) -> Result<Json, SceneError> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_space();
+        if self.eat('}') {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_space();
+            let key = self.string()?;
+            self.skip_space();
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_space();
+            if self.eat('}') {
+                return Ok(Json::Object(members));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn list(&mut self) -> Result<Json, SceneError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_space();
+        if self.eat(']') {
+            return Ok(Json::List(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_space();
+            if self.eat(']') {
+                return Ok(Json::List(items));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, SceneError> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.next() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let digits = self.text.get(self.offset..self.offset + 4);
+                            let code = digits.and_then(|d| u32::from_str_radix(d, 16).ok());
+                            self.offset += 4;
+                            code.and_then(char::from_u32)
+                                .ok_or_else(|| self.error("bad unicode escape"))?
+                        }
+                        _ => return Err(self.error("bad escape")),
+                    };
+                    text.push(escaped);
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, SceneError> {
+        let start = self.offset;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+            self.offset += 1;
+        }
+        self.text[start..self.offset]
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| SceneError::Syntax(start, "bad number".to_string()))
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, SceneError> {
+        if !self.text[self.offset..].starts_with(word) {
+            return Err(self.error("expected a value"));
+        }
+        self.offset += word.len();
+
+        Ok(value)
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.offset += 1;
+        }
+
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SceneError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn error(&self, what: &str) -> SceneError {
+        SceneError::Syntax(self.offset, what.to_string())
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::settings::RenderSettings;
+    use crate::testing::mutations;
+
+    #[fixture]
+    fn registry() -> SceneRegistry {
+        SceneRegistry::builtin()
+    }
+
+    #[rstest]
+    #[case("null", Json::Null)]
+    #[case(" true ", Json::Bool(true))]
+    #[case("-1.5e1", Json::Number(-15.0))]
+    #[case(r#""a\"bA""#, Json::Text("a\"bA".to_string()))]
+    #[case("[1, [], {}]", Json::List(vec![
+        Json::Number(1.0),
+        Json::List(vec![]),
+        Json::Object(vec![]),
+    ]))]
+    #[case(r#"{"b": 1, "a": false}"#, Json::Object(vec![
+        ("b".to_string(), Json::Number(1.0)),
+        ("a".to_string(), Json::Bool(false)),
+    ]))]
+    fn reading_json_values(#[case] text: &str, #[case] expected: Json) {
+        assert_that!(Parser::new(text).document()).is_equal_to(Ok(expected));
+    }
+
+    #[rstest]
+    #[case("", 0)]
+    #[case("[1, 2", 5)]
+    #[case(r#"{"a" 1}"#, 5)]
+    #[case("[1] 2", 4)]
+    #[case("nope", 0)]
+    fn where_bad_json_goes_wrong(#[case] text: &str, #[case] offset: usize) {
+        let result = Parser::new(text).document();
+
+        assert_that!(matches!(result, Err(SceneError::Syntax(at, _)) if at == offset)).is_true();
+    }
+
+    #[test]
+    fn nesting_is_limited_so_deep_documents_cant_overflow_the_stack() {
+        let deep = "[".repeat(200_000);
+        let allowed = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+
+        let result = Parser::new(&deep).document();
+
+        assert_that!(matches!(result, Err(SceneError::Syntax(at, _)) if at == MAX_DEPTH)).is_true();
+        assert_that!(Parser::new(&allowed).document()).is_ok();
+    }
+
+    #[rstest]
+    fn reading_a_scene(registry: SceneRegistry) {
+        let text = r#"{
+            "camera": { "width": 40, "height": 30, "from": [0, 0, -5], "to": [0, 0, 0] },
+            "lights": [ { "position": [-10, 10, -10], "intensity": [1, 1, 1] } ],
+            "objects": [
+                { "type": "sphere", "name": "ball",
+                  "transform": [["scale", 2, 2, 2], ["translate", 0, 1, 0]],
+                  "material": { "color": [1, 0, 0], "ambient": 0.5 } },
+                { "type": "plane" }
+            ]
+        }"#;
+
+        let (world, camera) = from_json(text, &registry).unwrap();
+        let camera = camera.build();
+
+        assert_that!(world.lights().len()).is_equal_to(1);
+        assert_that!(world.objects().len()).is_equal_to(2);
+        let ball = world.object_by_name("ball").unwrap();
+        assert_that!(ball.transformation())
+            .is_equal_to(Matrix::translation(0.0, 1.0, 0.0) * Matrix::scaling(2.0, 2.0, 2.0));
+        assert_that!(ball.material()).is_equal_to(
+            &MaterialBuilder::new()
+                .with_color(Color::new(1.0, 0.0, 0.0))
+                .with_ambient(0.5)
+                .build(),
+        );
+        assert_that!((camera.hsize(), camera.vsize())).is_equal_to((40, 30));
+    }
+
+    #[rstest]
+    fn a_group_passes_its_material_and_transform_to_its_children(registry: SceneRegistry) {
+        let text = r#"{ "objects": [ {
+            "type": "group", "name": "pair",
+            "transform": [["translate", 1, 0, 0]],
+            "material": { "color": [0, 0, 1] },
+            "children": [ { "type": "sphere", "name": "a" }, { "type": "cube", "name": "b" } ]
+        } ] }"#;
+
+        let (world, _) = from_json(text, &registry).unwrap();
+        let pair = world.object_by_name("pair").unwrap();
+
+        assert_that!(pair.children().len()).is_equal_to(2);
+        for child in pair.children() {
+            assert_that!(child.transformation()).is_equal_to(Matrix::translation(1.0, 0.0, 0.0));
+            assert_that!(child
+                .material()
+                .pattern()
+                .color_at_point(Vector4::point(0.0, 0.0, 0.0)))
+            .is_equal_to(Color::new(0.0, 0.0, 1.0));
+        }
+    }
+
+    #[rstest]
+    fn a_scene_without_a_camera_position_is_framed_to_fit(registry: SceneRegistry) {
+        let text = r#"{ "objects": [ { "type": "sphere" } ] }"#;
+
+        let (world, camera) = from_json(text, &registry).unwrap();
+        let camera = camera.build();
+
+        assert_that!(camera.world_to_pixel(Vector4::point(0.0, 0.0, 0.0))).is_some();
+        assert_that!(world.lights().len()).is_equal_to(1);
+    }
+
+    #[rstest]
+    fn patterns_come_from_the_registry(registry: SceneRegistry) {
+        let text = r#"{ "objects": [ { "type": "sphere", "name": "s", "material": {
+            "pattern": { "type": "marble", "base": [1, 1, 1], "vein": [0, 0, 0] }
+        } } ] }"#;
+
+        let (world, _) = from_json(text, &registry).unwrap();
+        let sphere = world.object_by_name("s").unwrap();
+
+        assert_that!(sphere.material().pattern().to_string()).starts_with("(MarblePattern");
+    }
+
+    #[rstest]
+    #[case(r#"{ "objects": [ { "type": "torus" } ] }"#,
+        SceneError::Registry(RegistryError::UnknownType("torus".to_string())))]
+    #[case(r#"{ "objects": [ { "name": "x" } ] }"#,
+        SceneError::Invalid("an object needs a type".to_string()))]
+    #[case(r#"{ "objects": [ { "type": "sphere", "transform": [["scale", 1]] } ] }"#,
+        SceneError::Invalid("can't scale by [1.0]".to_string()))]
+    #[case(r#"{ "lights": [ { "intensity": [1, 1, 1] } ] }"#,
+        SceneError::Invalid("a light needs a position".to_string()))]
+    #[case(r#"{ "objects": [ { "type": "sphere", "extra": null } ] }"#,
+        SceneError::Registry(RegistryError::BadParameter("extra".to_string())))]
+    #[case(r#"{ "objects": [ { "type": "sphere", "transform": [["scale", 0, 1, 1]] } ] }"#,
+        SceneError::Invalid("the transform of an unnamed shape is not invertible".to_string()))]
+    #[case(r#"{ "objects": [ { "type": "cube", "name": "box",
+            "transform": [["scale", 1, 0, 1]] } ] }"#,
+        SceneError::Invalid("the transform of box is not invertible".to_string()))]
+    #[case(r#"{ "camera": { "from": [0, 0, -5], "to": [0, 0, -5] } }"#,
+        SceneError::Invalid("the camera can't be placed: the view has no direction".to_string()))]
+    fn scenes_that_cant_be_read(
+        registry: SceneRegistry,
+        #[case] text: &str,
+        #[case] error: SceneError,
+    ) {
+        assert_that!(from_json(text, &registry).map(|_| ())).is_equal_to(Err(error));
+    }
+
+    #[rstest]
+    fn reading_a_corrupted_scene_never_panics(registry: SceneRegistry) {
+        let text = r#"{
+            "camera": { "width": 8, "height": 6, "field_of_view": 1.0,
+                        "from": [0, 1.5, -5], "to": [0, 1, 0], "up": [0, 1, 0] },
+            "lights": [ { "position": [-10, 10, -10], "intensity": [1, 1, 1] } ],
+            "objects": [
+                { "type": "sphere", "name": "ball",
+                  "transform": [["scale", 0.5, 0.5, 0.5], ["translate", 0, 1, 0],
+                                ["rotate_y", 0.5], ["shear", 0, 0, 0, 0, 0, 0]],
+                  "material": { "color": [1, 0.2, 0.2], "specular": 0.3, "reflective": 0.5,
+                                "transparency": 0.5, "refractive_index": 1.5,
+                                "pattern": { "type": "wood", "light": [1, 1, 1],
+                                             "dark": [0, 0, 0], "scale": 2 } } },
+                { "type": "cylinder", "minimum": 0, "maximum": 1, "closed": true },
+                { "type": "group", "transform": [["scale", 2, 2, 2]],
+                  "children": [ { "type": "cube" }, { "type": "group", "children": [] } ] },
+                { "type": "plane" }
+            ]
+        }"#;
+        assert_that!(from_json(text, &registry).map(|_| ())).is_ok();
+
+        for text in mutations(text, 2000) {
+            if let Ok((world, camera)) = from_json(&text, &registry) {
+                let camera = camera.with_hsize(4).with_vsize(3).build();
+                let _ = camera.render(&world, &RenderSettings::default());
+            }
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! A small HTTP server for running renders on another machine. It speaks just enough HTTP/1.1
+//! for `curl` and scripts:
+//!
+//! - `POST /render?width=640&height=480` starts rendering the scene at that size. The body may
+//!   be a scene in the JSON form `scene::from_json` reads, whose camera sets the size if the
+//!   query doesn't; with no body the default world is rendered, framed to fit.
+//! - `GET /progress` gives how far the render has got, from 0 to 1, as plain text
+//! - `GET /image` gives the finished image as a PNG, or says why the render failed
+//!
+//! Only one render runs at a time, and one request is answered at a time, so clients that are
+//! slow to send or receive are cut off after a few seconds. There is no authentication, so only
+//! listen on addresses that trusted clients can reach.
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::camera::{CameraBuilder, FitMode};
+use crate::canvas::Canvas;
+use crate::registry::SceneRegistry;
+use crate::scene;
+use crate::settings::RenderSettings;
+use crate::world::World;
+
+/// The largest image, in pixels along either side, the server will render.
+const MAX_SIZE: usize = 8192;
+/// How long a client has to send each part of its request, or take each part of the reply,
+/// unless the server is given another time.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// The longest request line or header, in bytes.
+const MAX_LINE: usize = 8192;
+/// The most headers a request may have.
+const MAX_HEADERS: usize = 64;
+/// The largest request body, in bytes.
+const MAX_BODY: usize = 1 << 20;
+
+/// A reply to a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: u16, text: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: format!("{}\n", text).into_bytes(),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> Result<(), Error> {
+        let reason = match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            404 => "Not Found",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            500 => "Internal Server Error",
+            _ => "",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)
+    }
+}
+
+/// A request as far as the server is concerned.
+struct Request {
+    method: String,
+    target: String,
+    body: Vec<u8>,
+}
+
+/// The state of the latest render, shared with the thread doing it.
+#[derive(Default)]
+struct Job {
+    running: AtomicBool,
+    /// How far through the render is, as the bits of an `f32` from 0 to 1.
+    progress: AtomicU32,
+    /// The finished image as a PNG, or why the render failed.
+    image: Mutex<Option<Result<Vec<u8>, String>>>,
+}
+
+pub struct RenderServer {
+    job: Arc<Job>,
+    worker: Option<JoinHandle<()>>,
+    timeout: Duration,
+}
+
+impl Default for RenderServer {
+    fn default() -> Self {
+        Self {
+            job: Arc::default(),
+            worker: None,
+            timeout: TIMEOUT,
+        }
+    }
+}
+
+impl RenderServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give clients `timeout` to send each part of a request and take each part of the reply.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+
+        self
+    }
+
+    /// Answer requests on `address` until the listener fails.
+    pub fn serve(&mut self, address: impl ToSocketAddrs) -> Result<(), Error> {
+        let listener = TcpListener::bind(address)?;
+        for stream in listener.incoming() {
+            // A client that goes away mid-request only loses its own reply
+            let _ = self.answer(stream?);
+        }
+
+        Ok(())
+    }
+
+    fn answer(&mut self, mut stream: TcpStream) -> Result<(), Error> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => self.handle(&request.method, &request.target, &request.body),
+            Err(response) => response,
+        };
+        response.write_to(&mut stream)
+    }
+
+    /// The response to a request for `target` with `method` and `body`, starting a render if
+    /// that is what was asked for.
+    pub fn handle(&mut self, method: &str, target: &str, body: &[u8]) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        match (method, path) {
+            ("POST", "/render") => self.start(query, body),
+            ("GET", "/progress") => {
+                let progress = f32::from_bits(self.job.progress.load(Ordering::Relaxed));
+                Response::text(200, &format!("{:.3}", progress))
+            }
+            ("GET", "/image") => match self.job.image.lock().unwrap().as_ref() {
+                Some(Ok(png)) => Response {
+                    status: 200,
+                    content_type: "image/png",
+                    body: png.clone(),
+                },
+                Some(Err(e)) => Response::text(500, &format!("the render failed: {}", e)),
+                None => Response::text(404, "no image has been rendered yet"),
+            },
+            _ => Response::text(404, "not found"),
+        }
+    }
+
+    /// Wait for the render in progress, if there is one, to finish.
+    pub fn wait(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    fn start(&mut self, query: &str, body: &[u8]) -> Response {
+        let scene = if body.is_empty() {
+            None
+        } else {
+            let Ok(text) = std::str::from_utf8(body) else {
+                return Response::text(400, "the scene must be UTF-8");
+            };
+            // Read the scene here to report any problem with it, though it has to be read
+            // again on the thread that renders it
+            let camera = catch(|| {
+                scene::from_json(text, &SceneRegistry::builtin()).map(|(_, camera)| camera.build())
+            });
+            match camera {
+                Ok(Ok(camera)) => Some((text.to_string(), camera)),
+                Ok(Err(e)) => return Response::text(400, &format!("bad scene: {}", e)),
+                Err(e) => {
+                    return Response::text(400, &format!("bad scene: reading it failed: {}", e))
+                }
+            }
+        };
+        let size = match (image_size(query), &scene) {
+            (Some(size), _) => Some(size),
+            (None, Some((_, camera))) if query.is_empty() => Some((camera.hsize(), camera.vsize()))
+                .filter(|&(w, h)| w <= MAX_SIZE && h <= MAX_SIZE),
+            (None, _) => None,
+        };
+        let Some((width, height)) = size else {
+            return Response::text(400, &format!("width and height must be 1 to {}", MAX_SIZE));
+        };
+        if self.job.running.swap(true, Ordering::AcqRel) {
+            return Response::text(409, "a render is already running");
+        }
+        self.wait();
+
+        self.job
+            .progress
+            .store(0.0_f32.to_bits(), Ordering::Relaxed);
+        *self.job.image.lock().unwrap() = None;
+        let job = Arc::clone(&self.job);
+        // Shapes are shared with `Rc`, so the world is built on the thread that renders it
+        let scene = scene.map(|(text, _)| text);
+        self.worker = Some(std::thread::spawn(move || {
+            let _running = Running(&job.running);
+            let png = catch(|| render_png(scene.as_deref(), width, height, &job.progress));
+            *job.image.lock().unwrap() = Some(png.and_then(|png| png.map_err(|e| e.to_string())));
+        }));
+
+        Response::text(202, "rendering")
+    }
+}
+
+/// Clears the flag it holds when dropped, so that a render that panics still lets the next one
+/// start.
+struct Running<'a>(&'a AtomicBool);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// The result of `work`, or the message it panicked with. Scenes come from clients, so a scene
+/// that finds a panic in the renderer mustn't take the server down with it.
+fn catch<T>(work: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(work)).map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "it panicked".to_string())
+    })
+}
+
+/// Read a request, or the response to give if it is too big, too slow or not HTTP.
+fn read_request(reader: &mut impl BufRead) -> Result<Request, Response> {
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Response::text(400, "malformed request"));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    // Only the length of the body matters among the headers
+    let mut length = 0;
+    for _ in 0..=MAX_HEADERS {
+        read_line(reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            if length > MAX_BODY {
+                return Err(Response::text(413, "the body is too large"));
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).map_err(|e| read_error(&e))?;
+            return Ok(Request {
+                method,
+                target,
+                body,
+            });
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                let Ok(value) = value.trim().parse() else {
+                    return Err(Response::text(400, "bad content length"));
+                };
+                length = value;
+            }
+        }
+    }
+
+    Err(Response::text(400, "too many headers"))
+}
+
+/// Read one line of no more than `MAX_LINE` bytes into `line`, in place of what was there.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), Response> {
+    line.clear();
+    reader
+        .take(MAX_LINE as u64)
+        .read_line(line)
+        .map_err(|e| read_error(&e))?;
+    if !line.ends_with('\n') {
+        return Err(Response::text(400, "line too long or cut short"));
+    }
+
+    Ok(())
+}
+
+fn read_error(error: &Error) -> Response {
+    match error.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => Response::text(408, "too slow"),
+        _ => Response::text(400, &format!("couldn't read the request: {}", error)),
+    }
+}
+
+/// The `width` and `height` in a query string like `width=640&height=480`.
+fn image_size(query: &str) -> Option<(usize, usize)> {
+    let value = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, value)| value.parse::<usize>().ok())
+            .filter(|&v| (1..=MAX_SIZE).contains(&v))
+    };
+
+    Some((value("width")?, value("height")?))
+}
+
+/// Render the scene in the JSON `scene`, or the default world, `width` by `height` pixels.
+fn render_png(
+    scene: Option<&str>,
+    width: usize,
+    height: usize,
+    progress: &AtomicU32,
+) -> Result<Vec<u8>, Error> {
+    let (world, camera) = match scene {
+        Some(text) => {
+            let (world, camera) = scene::from_json(text, &SceneRegistry::builtin())
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            (world, camera.with_hsize(width).with_vsize(height))
+        }
+        None => {
+            let world = World::default_world();
+            let camera = CameraBuilder::new()
+                .with_hsize(width)
+                .with_vsize(height)
+                .framing(&world, FitMode::Fit);
+            (world, camera)
+        }
+    };
+    let camera = camera.build();
+    let settings = RenderSettings::default();
+
+    let mut canvas = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            canvas.write_pixel(x, y, &camera.render_pixel_with(&world, x, y, &settings));
+        }
+        progress.store(
+            ((y + 1) as f32 / height as f32).to_bits(),
+            Ordering::Relaxed,
+        );
+    }
+    settings.post.apply(&mut canvas);
+
+    let mut png = Vec::new();
+    canvas.save_png(&mut png)?;
+
+    Ok(png)
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("width=640&height=480", Some((640, 480)))]
+    #[case("height=2&width=3&samples=4", Some((3, 2)))]
+    #[case("width=640", None)]
+    #[case("width=0&height=480", None)]
+    #[case("width=99999&height=480", None)]
+    #[case("width=x&height=480", None)]
+    fn reading_the_image_size(#[case] query: &str, #[case] expected: Option<(usize, usize)>) {
+        assert_that!(image_size(query)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn rendering_and_fetching_the_image() {
+        let mut server = RenderServer::new();
+        assert_that!(server.handle("GET", "/image", b"").status).is_equal_to(404);
+
+        let started = server.handle("POST", "/render?width=8&height=6", b"");
+        server.wait();
+
+        assert_that!(started.status).is_equal_to(202);
+        assert_that!(server.handle("GET", "/progress", b"").body).is_equal_to(b"1.000\n".to_vec());
+        let image = server.handle("GET", "/image", b"");
+        assert_that!(image.content_type).is_equal_to("image/png");
+        assert_that!(image.body[1..4].to_vec()).is_equal_to(b"PNG".to_vec());
+    }
+
+    #[test]
+    fn rendering_a_scene_sent_with_the_request() {
+        let mut server = RenderServer::new();
+        let scene =
+            br#"{ "camera": { "width": 5, "height": 4 }, "objects": [ { "type": "cube" } ] }"#;
+
+        let started = server.handle("POST", "/render", scene);
+        server.wait();
+
+        assert_that!(started.status).is_equal_to(202);
+        let image = server.handle("GET", "/image", b"");
+        assert_that!(image.body[1..4].to_vec()).is_equal_to(b"PNG".to_vec());
+        // The width and height of the PNG's header
+        assert_that!(image.body[16..24].to_vec()).is_equal_to(vec![0, 0, 0, 5, 0, 0, 0, 4]);
+    }
+
+    #[rstest]
+    #[case(&b"{ \"objects\": [ { \"type\": \"torus\" } ] }"[..])]
+    #[case(&b"{ nope"[..])]
+    #[case(&b"\xff"[..])]
+    #[case(&br#"{"objects":[{"type":"sphere","transform":[["scale",0,1,1]]}]}"#[..])]
+    fn bad_scenes_are_refused(#[case] scene: &[u8]) {
+        let mut server = RenderServer::new();
+
+        assert_that!(server.handle("POST", "/render", scene).status).is_equal_to(400);
+    }
+
+    #[test]
+    fn a_scene_nested_too_deeply_is_refused() {
+        let mut server = RenderServer::new();
+        let scene = "[".repeat(200_000);
+
+        assert_that!(server.handle("POST", "/render", scene.as_bytes()).status).is_equal_to(400);
+    }
+
+    #[test]
+    fn a_panic_is_caught_with_its_message() {
+        let result = catch(|| -> u8 { panic!("no {}", "way") });
+
+        assert_that!(result).is_equal_to(Err("no way".to_string()));
+        assert_that!(catch(|| 1)).is_equal_to(Ok(1));
+    }
+
+    #[test]
+    fn a_failed_render_is_reported() {
+        let mut server = RenderServer::new();
+        *server.job.image.lock().unwrap() = Some(Err("it panicked".to_string()));
+
+        let image = server.handle("GET", "/image", b"");
+
+        assert_that!(image.status).is_equal_to(500);
+        assert_that!(image.body).is_equal_to(b"the render failed: it panicked\n".to_vec());
+    }
+
+    #[test]
+    fn a_render_that_panics_lets_the_next_one_start() {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+
+        let result = std::thread::spawn(move || {
+            let _running = Running(&flag);
+            panic!("the render failed");
+        })
+        .join();
+
+        assert_that!(result.is_err()).is_true();
+        assert_that!(running.load(Ordering::Acquire)).is_false();
+    }
+
+    #[test]
+    fn reading_a_request_with_a_body() {
+        let mut text = &b"POST /render HTTP/1.1\r\nContent-Length: 4\r\n\r\nbodyextra"[..];
+
+        let request = read_request(&mut text).ok().unwrap();
+
+        assert_that!(request.method.as_str()).is_equal_to("POST");
+        assert_that!(request.target.as_str()).is_equal_to("/render");
+        assert_that!(request.body).is_equal_to(b"body".to_vec());
+    }
+
+    #[rstest]
+    #[case(format!("GET /{} HTTP/1.1\r\n\r\n", "x".repeat(MAX_LINE)), 400)]
+    #[case(format!("GET / HTTP/1.1\r\n{}\r\n", "A: b\r\n".repeat(MAX_HEADERS + 1)), 400)]
+    #[case(format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1), 413)]
+    #[case("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort".to_string(), 400)]
+    #[case("GET / HTTP/1.1\r\nHost: cut off".to_string(), 400)]
+    #[case("nonsense\r\n\r\n".to_string(), 400)]
+    fn requests_that_are_refused(#[case] text: String, #[case] status: u16) {
+        let response = read_request(&mut text.as_bytes()).err().unwrap();
+
+        assert_that!(response.status).is_equal_to(status);
+    }
+
+    #[test]
+    fn a_client_that_sends_nothing_is_cut_off() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        RenderServer::new()
+            .with_timeout(Duration::from_millis(50))
+            .answer(stream)
+            .unwrap();
+
+        assert_that!(client.join().unwrap().starts_with("HTTP/1.1 408")).is_true();
+    }
+
+    #[rstest]
+    #[case("GET", "/render")]
+    #[case("POST", "/nowhere")]
+    fn unknown_requests_are_not_found(#[case] method: &str, #[case] target: &str) {
+        let mut server = RenderServer::new();
+
+        assert_that!(server.handle(method, target, b"").status).is_equal_to(404);
+    }
+
+    #[test]
+    fn answering_over_a_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .write_all(b"GET /progress HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        RenderServer::new().answer(stream).unwrap();
+
+        let reply = client.join().unwrap();
+        assert_that!(reply.starts_with("HTTP/1.1 200 OK\r\n")).is_true();
+        assert_that!(reply.ends_with("\r\n\r\n0.000\n")).is_true();
+    }
+}
@@ -0,0 +1,207 @@
+use crate::accumulation;
+use crate::camera::DebugChannel;
+use crate::color::Color;
+use crate::consts::MAX_RECURSION_DEPTH;
+use crate::denoise::Denoiser;
+use crate::filter::PixelFilter;
+use crate::intersection::SurfaceBias;
+use crate::post::PostProcess;
+use crate::rng;
+
+/// How the colour of each camera ray is worked out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    /// Direct lighting with recursive reflection and refraction.
+    Whitted,
+    /// One of the false-colour diagnostic views.
+    Debug(DebugChannel),
+}
+
+/// Which lights are sampled at each point being shaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LightSampling {
+    /// Every light, every time. Exact, but slow with many lights.
+    #[default]
+    All,
+    /// This many lights, each as likely to be picked as any other.
+    Uniform(usize),
+    /// This many lights, picked in proportion to their brightness.
+    Power(usize),
+}
+
+/// Ends chains of reflection and refraction early at random once they are some bounces deep,
+/// instead of always following them to `max_depth`.
+///
+/// A ray carries on with a chance that follows how much it adds to the surface it leaves, and
+/// the rays that do carry on are brightened to make up for the ones that don't, so the image is
+/// the same on average but costs less. Bounding the chance from below stops the few survivors of
+/// faint rays being brightened so much that they show up as fireflies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RussianRoulette {
+    /// How many bounces every ray gets before any is ended early.
+    pub start_depth: usize,
+    pub min_survival: f32,
+    pub max_survival: f32,
+}
+
+impl Default for RussianRoulette {
+    fn default() -> Self {
+        // Clear glass passes on all of its light, so it always survives and never sparkles
+        Self {
+            start_depth: 3,
+            min_survival: 0.25,
+            max_survival: 1.0,
+        }
+    }
+}
+
+/// Takes more samples only in the pixels that need them, going on until the estimated variance
+/// of a pixel's mean colour falls below `threshold`. Flat areas such as the background settle
+/// after `min_samples`, while edges and soft shadows get up to `max_samples`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: usize,
+    pub max_samples: usize,
+    pub threshold: f32,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 4,
+            max_samples: 64,
+            threshold: 0.0001,
+        }
+    }
+}
+
+impl AdaptiveSampling {
+    /// Should another sample be taken in a pixel that has `count` samples with `variance`
+    /// between them?
+    pub fn needs_more(&self, count: usize, variance: f32) -> bool {
+        if count < self.min_samples.max(1) {
+            return true;
+        }
+
+        count < self.max_samples && variance / count as f32 > self.threshold
+    }
+}
+
+/// Pulls down the camera samples in a pixel that are far brighter than the rest of it, such as a
+/// lone lucky path through a caustic, which would otherwise leave a white speck.
+///
+/// The pixel's typical brightness is the median of means of its samples' luminance, taken over
+/// `groups` groups. A sample is scaled down, keeping its hue, if it is more than `max_ratio` times
+/// that and brighter than white, so edges between dark and lit areas keep their lit samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutlierRejection {
+    pub groups: usize,
+    pub max_ratio: f32,
+}
+
+impl Default for OutlierRejection {
+    fn default() -> Self {
+        Self {
+            groups: 4,
+            max_ratio: 8.0,
+        }
+    }
+}
+
+/// Everything that controls how a world is rendered, as opposed to what is in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSettings {
+    /// How many times a ray may be reflected or refracted before its contribution is dropped.
+    pub max_depth: usize,
+    /// How far secondary rays start from the surface they leave.
+    pub shadow_bias: SurfaceBias,
+    /// Test the object that last blocked a light before any other when casting a shadow ray
+    /// towards it. The image is the same either way; only the time taken differs.
+    pub shadow_cache: bool,
+    /// Camera rays per pixel. More than one jitters the rays across the pixel to antialias edges.
+    pub samples_per_pixel: usize,
+    /// Vary the samples taken in each pixel with how noisy it is, in place of
+    /// `samples_per_pixel`. The AOV layers keep to `samples_per_pixel`.
+    pub adaptive: Option<AdaptiveSampling>,
+    /// How the samples are weighted into the pixels around where they were taken.
+    pub filter: PixelFilter,
+    /// The colour of rays that hit nothing.
+    pub background: Color,
+    pub integrator: Integrator,
+    pub light_sampling: LightSampling,
+    pub roulette: Option<RussianRoulette>,
+    /// The brightest any one camera sample may be. Brighter samples are scaled down, keeping their
+    /// hue, so that a rare very bright path can't leave a lone speck in the image.
+    pub max_sample_radiance: Option<f32>,
+    /// Look reflections up in the world's reflection probes, where it has any, instead of tracing
+    /// them. Much quicker, but only roughly right, so best kept for previews.
+    pub reflection_probes: bool,
+    /// Pull down the samples in a pixel that stand far out from the others.
+    pub outlier_rejection: Option<OutlierRejection>,
+    /// Smooth out sampling noise in the finished image, guided by the normals and depth.
+    pub denoise: Option<Denoiser>,
+    /// Adjustments made to the finished image, such as exposure and white balance.
+    pub post: PostProcess,
+    /// Where every random choice starts from. The same seed gives the same image, whatever order
+    /// the pixels are rendered in.
+    pub seed: u64,
+    /// The frame of an animation being rendered, mixed into the seed so each frame has its own
+    /// noise.
+    pub frame: u64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_RECURSION_DEPTH,
+            shadow_bias: SurfaceBias::default(),
+            shadow_cache: true,
+            samples_per_pixel: 1,
+            adaptive: None,
+            filter: PixelFilter::Box,
+            background: Color::black(),
+            integrator: Integrator::Whitted,
+            light_sampling: LightSampling::All,
+            roulette: Some(RussianRoulette::default()),
+            max_sample_radiance: Some(10.0),
+            outlier_rejection: None,
+            reflection_probes: false,
+            denoise: None,
+            post: PostProcess::default(),
+            seed: 0,
+            frame: 0,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The seed for the frame being rendered.
+    pub fn frame_seed(&self) -> u64 {
+        rng::frame_seed(self.seed, self.frame)
+    }
+
+    /// Scale `sample` down to `max_sample_radiance`, if it is brighter.
+    pub fn clamp_sample(&self, sample: Color) -> Color {
+        let brightest = sample.r.max(sample.g).max(sample.b);
+        match self.max_sample_radiance {
+            Some(max) if brightest > max => sample * (max / brightest),
+            _ => sample,
+        }
+    }
+
+    /// Scale down the samples taken in one pixel that `outlier_rejection` finds too bright.
+    pub fn reject_outliers(&self, samples: &mut [Color]) {
+        let Some(rejection) = self.outlier_rejection else {
+            return;
+        };
+
+        let luminances: Vec<f32> = samples.iter().map(Color::luminance).collect();
+        let typical = accumulation::median_of_means(&luminances, rejection.groups);
+        let limit = (typical * rejection.max_ratio).max(1.0);
+        for (sample, luminance) in samples.iter_mut().zip(luminances) {
+            if luminance > limit {
+                *sample = *sample * (limit / luminance);
+            }
+        }
+    }
+}
@@ -1,18 +1,102 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
+use std::mem;
+use std::rc::Rc;
 
+use crate::bounds::BoundingBox;
 use crate::color::Color;
 use crate::light::PointLight;
 use crate::material::Material;
 use crate::matrix::Matrix;
 use crate::ray::Ray;
+use crate::rng::Rng;
 use crate::vector4::Vector4;
 
+/// Tags a scene gives a shape, as keys and values, for tools built on the ray tracer such as AOVs
+/// and custom integrators to pick objects out by. The renderer itself never reads them.
+pub type UserData = HashMap<String, String>;
+
 pub trait Shape {
+    fn name(&self) -> Option<&str>;
+
+    /// The name of the shape's type, such as `Sphere`, for reports on what a scene is made of.
+    fn type_name(&self) -> &'static str {
+        let path = std::any::type_name::<Self>();
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
+    /// The tags the shape was given, if it was given any.
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        None
+    }
+
     fn material(&self) -> &Material;
 
     fn transformation(&self) -> Matrix<4>;
     fn inv_transform(&self) -> &Matrix<4>;
 
+    /// A copy of this shape with `transform` applied on top of its own transformation.
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape>;
+
+    /// A copy of this shape made of `material` instead; a group passes it on to its children.
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape>;
+
+    /// The axis-aligned box enclosing the shape in object space.
+    fn bounds(&self) -> BoundingBox;
+
+    /// The box enclosing the shape in the space of its parent, i.e. after its own transform.
+    fn parent_space_bounds(&self) -> BoundingBox {
+        self.bounds().transform(&self.transformation())
+    }
+
+    /// The surface area of the shape in world space, or `None` for shapes that can't be sampled.
+    fn surface_area(&self) -> Option<f32> {
+        None
+    }
+
+    /// A point chosen at random on the surface in world space, with the normal there and the
+    /// probability density per unit area of choosing it. Most shapes spread their samples evenly,
+    /// so the density is one over the area, but it can vary over the surface, as on a sphere
+    /// scaled more along one axis than another. `None` for shapes that can't be sampled.
+    fn sample_surface(&self, _rng: &mut Rng) -> Option<(Vector4, Vector4, f32)> {
+        None
+    }
+
+    /// The texture coordinates at a point on the shape, for shapes that carry them.
+    fn uv_at(&self, _world_point: &Vector4) -> Option<(f32, f32)> {
+        None
+    }
+
+    /// How the point on the shape moves as each of its texture coordinates grows, as dp/du and
+    /// dp/dv in world space, for shapes that carry texture coordinates. They run along the surface,
+    /// and give texture lookups and shading a direction to work in across it.
+    fn uv_derivatives_at(&self, _world_point: &Vector4) -> Option<(Vector4, Vector4)> {
+        None
+    }
+
+    /// The shapes grouped under this one. Only groups have children.
+    fn children(&self) -> &[Rc<dyn Shape>] {
+        &[]
+    }
+
+    /// How many triangles make up the shape, including any it groups or instances.
+    fn triangle_count(&self) -> usize {
+        0
+    }
+
+    /// Roughly how many bytes the shape takes, with everything it owns apart from textures.
+    /// Data shared with other shapes is only counted if its address isn't in `counted` yet, and
+    /// is then added, so that it is counted once however many shapes share it.
+    fn memory_size(&self, _counted: &mut HashSet<*const ()>) -> usize {
+        mem::size_of_val(self)
+    }
+
+    /// For groups, a copy with any groups nested inside replaced by their contents, so that every
+    /// child is a shape with a surface. `None` for anything that isn't a group.
+    fn flattened(&self) -> Option<Rc<dyn Shape>> {
+        None
+    }
+
     fn intersect(&self, ray: &Ray) -> Vec<f32> {
         let transformed_ray = ray.transform(&self.inv_transform());
         self.local_intersect(&transformed_ray)
@@ -29,6 +113,21 @@ pub trait Shape {
     }
     fn local_normal_at(&self, world_point: Vector4) -> Vector4;
 
+    /// The point on the shape's surface nearest to `world_point`.
+    ///
+    /// The search happens in object space, so it is exact for transforms that preserve distances
+    /// or scale uniformly, and an approximation under non-uniform scaling or shearing.
+    fn closest_point(&self, world_point: &Vector4) -> Vector4 {
+        let object_point = *self.inv_transform() * *world_point;
+        self.transformation() * self.local_closest_point(object_point)
+    }
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4;
+
+    /// How far `world_point` is from the shape's surface, measured from either side.
+    fn distance_to(&self, world_point: &Vector4) -> f32 {
+        (self.closest_point(world_point) - *world_point).magnitude()
+    }
+
     // TODO: Put a default implementation here
     fn lighting(
         &self,
@@ -40,15 +139,33 @@ pub trait Shape {
     ) -> Color;
 }
 
+/// The user data a builder has collected, shared between the copies of the shape it builds, or
+/// `None` if there is none.
+pub(crate) fn shared_user_data(user_data: UserData) -> Option<Rc<UserData>> {
+    (!user_data.is_empty()).then(|| Rc::new(user_data))
+}
+
+/// How much `transform` scales the area of a flat patch of surface with the given object space
+/// normal.
+pub(crate) fn area_scale(transform: &Matrix<4>, inv_transform: &Matrix<4>, normal: Vector4) -> f32 {
+    let mut scaled_normal = inv_transform.transpose() * normal;
+    scaled_normal.w = 0.0;
+
+    transform.determinant().abs() * scaled_normal.magnitude()
+}
+
 impl PartialEq for dyn Shape {
     fn eq(&self, other: &Self) -> bool {
-        self.material() == other.material() && self.transformation() == other.transformation()
+        self.name() == other.name()
+            && self.material() == other.material()
+            && self.transformation() == other.transformation()
     }
 }
 
 impl Debug for dyn Shape {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Shape")
+            .field("name", &self.name())
             .field("material", &self.material())
             .field("transform", &self.transformation())
             .finish()
@@ -65,7 +182,9 @@ mod tests {
     use spectral::prelude::*;
 
     use super::*;
+    use crate::intersection::Intersection;
     use crate::material::MaterialBuilder;
+    use crate::plane::PlaneBuilder;
     use crate::sphere::SphereBuilder;
     use crate::transform::Transform;
 
@@ -116,4 +235,35 @@ mod tests {
         assert_that!(xs[0]).is_equal_to(3.0);
         assert_that!(xs[1]).is_equal_to(7.0);
     }
+
+    #[test]
+    fn shapes_cannot_be_sampled_by_default() {
+        let p = PlaneBuilder::new().build();
+
+        assert_that!(p.surface_area()).is_none();
+        assert_that!(p.sample_surface(&mut Rng::new(0))).is_none();
+    }
+
+    #[test]
+    fn shapes_have_no_user_data_unless_given_some() {
+        let p = PlaneBuilder::new().build();
+
+        assert_that!(p.user_data()).is_none();
+    }
+
+    #[test]
+    fn user_data_reaches_the_hit() {
+        let s: Rc<dyn Shape> =
+            Rc::new(SphereBuilder::new().with_user_data("layer", "hero").build())
+                .transformed(&Matrix::translation(0.0, 0.0, 1.0));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let comps = Intersection::new(5.0, s).prepare_computations(&r);
+
+        let user_data = comps.user_data.unwrap();
+        assert_that!(user_data.get("layer").map(String::as_str)).is_equal_to(Some("hero"));
+    }
 }
@@ -0,0 +1,8 @@
+//! Ready-made geometry assembled from the primitive shapes.
+
+pub mod compound;
+pub mod displace;
+pub mod examples;
+pub mod patch;
+pub mod prism;
+pub mod subdivision;
@@ -0,0 +1,256 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use crate::cube::CubeBuilder;
+use crate::cylinder::CylinderBuilder;
+use crate::group::GroupBuilder;
+use crate::material::Material;
+use crate::matrix::Matrix;
+use crate::sphere::SphereBuilder;
+use crate::transform::Transform;
+
+/// The book's hexagon: six spheres joined by six cylinders, lying in the xz plane with a radius
+/// of 1 about the origin.
+pub fn hexagon(material: &Material) -> GroupBuilder {
+    (0..6).fold(GroupBuilder::new(), |hex, n| {
+        let side = hexagon_side(material)
+            .with_transform(Matrix::rotation_y(n as f32 * PI / 3.0))
+            .build();
+        hex.with_child(Rc::new(side))
+    })
+}
+
+fn hexagon_side(material: &Material) -> GroupBuilder {
+    let corner = SphereBuilder::new()
+        .with_transform(Matrix::translation(0.0, 0.0, -1.0) * Matrix::scaling(0.25, 0.25, 0.25))
+        .with_material(material.clone())
+        .build();
+    let edge = CylinderBuilder::new()
+        .with_minimum(0.0)
+        .with_maximum(1.0)
+        .with_transform(
+            Matrix::translation(0.0, 0.0, -1.0)
+                * Matrix::rotation_y(-PI / 6.0)
+                * Matrix::rotation_z(-PI / 2.0)
+                * Matrix::scaling(0.25, 1.0, 0.25),
+        )
+        .with_material(material.clone())
+        .build();
+
+    GroupBuilder::new()
+        .with_child(Rc::new(corner))
+        .with_child(Rc::new(edge))
+}
+
+/// A cylinder along the y axis with hemispherical ends. `length` is the distance between the
+/// centres of the two ends, so the capsule is `length + 2 * radius` tall overall.
+pub fn capsule(radius: f32, length: f32, material: &Material) -> GroupBuilder {
+    let half = length / 2.0;
+    let body = CylinderBuilder::new()
+        .with_minimum(-half)
+        .with_maximum(half)
+        .with_transform(Matrix::scaling(radius, 1.0, radius))
+        .with_material(material.clone())
+        .build();
+
+    let mut group = GroupBuilder::new().with_child(Rc::new(body));
+    for y in [-half, half] {
+        let end = SphereBuilder::new()
+            .with_transform(
+                Matrix::translation(0.0, y, 0.0) * Matrix::scaling(radius, radius, radius),
+            )
+            .with_material(material.clone())
+            .build();
+        group = group.with_child(Rc::new(end));
+    }
+
+    group
+}
+
+/// A box of the given overall size centred on the origin, with its edges and corners rounded
+/// off to `radius`. Built from three overlapping slabs, twelve edge cylinders and eight corner
+/// spheres.
+///
+/// Panics unless `radius` is positive and less than half of each dimension.
+pub fn rounded_box(
+    width: f32,
+    height: f32,
+    depth: f32,
+    radius: f32,
+    material: &Material,
+) -> GroupBuilder {
+    assert!(
+        radius > 0.0 && 2.0 * radius < width.min(height).min(depth),
+        "Rounding radius must be positive and less than half of each dimension"
+    );
+
+    // Half extents of the inner box whose edges the rounding follows
+    let (hx, hy, hz) = (
+        width / 2.0 - radius,
+        height / 2.0 - radius,
+        depth / 2.0 - radius,
+    );
+    let mut group = GroupBuilder::new();
+
+    for slab in [
+        Matrix::scaling(hx + radius, hy, hz),
+        Matrix::scaling(hx, hy + radius, hz),
+        Matrix::scaling(hx, hy, hz + radius),
+    ] {
+        let cube = CubeBuilder::new()
+            .with_transform(slab)
+            .with_material(material.clone())
+            .build();
+        group = group.with_child(Rc::new(cube));
+    }
+
+    let scale = Matrix::scaling(radius, 1.0, radius);
+    for a in [-1.0, 1.0] {
+        for b in [-1.0, 1.0] {
+            for (half_length, transform) in [
+                (
+                    hx,
+                    Matrix::translation(0.0, a * hy, b * hz) * Matrix::rotation_z(PI / 2.0) * scale,
+                ),
+                (hy, Matrix::translation(a * hx, 0.0, b * hz) * scale),
+                (
+                    hz,
+                    Matrix::translation(a * hx, b * hy, 0.0) * Matrix::rotation_x(PI / 2.0) * scale,
+                ),
+            ] {
+                let edge = CylinderBuilder::new()
+                    .with_minimum(-half_length)
+                    .with_maximum(half_length)
+                    .with_transform(transform)
+                    .with_material(material.clone())
+                    .build();
+                group = group.with_child(Rc::new(edge));
+            }
+        }
+    }
+
+    for x in [-hx, hx] {
+        for y in [-hy, hy] {
+            for z in [-hz, hz] {
+                let corner = SphereBuilder::new()
+                    .with_transform(
+                        Matrix::translation(x, y, z) * Matrix::scaling(radius, radius, radius),
+                    )
+                    .with_material(material.clone())
+                    .build();
+                group = group.with_child(Rc::new(corner));
+            }
+        }
+    }
+
+    group
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::material::MaterialBuilder;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::vector4::Vector4;
+
+    use super::*;
+
+    #[test]
+    fn a_hexagon_has_six_sides_of_a_corner_and_an_edge() {
+        let hex = hexagon(&MaterialBuilder::new().build()).build();
+
+        assert_that!(hex.children().len()).is_equal_to(6);
+        for side in hex.children() {
+            assert_that!(side.children().len()).is_equal_to(2);
+        }
+    }
+
+    #[test]
+    fn a_ray_hits_a_corner_of_the_hexagon() {
+        let hex = hexagon(&MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = hex.intersect(&r);
+
+        assert_that!(xs[0]).is_close_to(3.75, 0.0001);
+    }
+
+    #[test]
+    fn a_ray_passes_through_the_middle_of_the_hexagon() {
+        let hex = hexagon(&MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(0.0, 5.0, 0.0),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+
+        assert_that!(hex.intersect(&r)).is_empty();
+    }
+
+    #[test]
+    fn a_ray_along_the_axis_of_a_capsule_hits_its_ends() {
+        let c = capsule(0.5, 2.0, &MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(0.0, 5.0, 0.0),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+
+        let xs = c.intersect(&r);
+
+        assert_that!(xs).is_equal_to(vec![3.5, 4.5, 5.5, 6.5]);
+    }
+
+    #[test]
+    fn a_ray_across_a_capsule_hits_its_body() {
+        let c = capsule(0.5, 2.0, &MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(-5.0, 0.0, 0.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        );
+
+        let xs = c.intersect(&r);
+
+        assert_that!(xs).is_equal_to(vec![4.5, 5.5]);
+    }
+
+    #[test]
+    fn a_rounded_box_is_flat_across_its_faces() {
+        let b = rounded_box(2.0, 2.0, 2.0, 0.25, &MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = b.intersect(&r);
+
+        assert_that!(xs[0]).is_close_to(4.0, 0.0001);
+    }
+
+    #[test]
+    fn a_rounded_box_has_rounded_corners() {
+        let b = rounded_box(2.0, 2.0, 2.0, 0.25, &MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(-5.0, -5.0, -5.0),
+            Vector4::vector(1.0, 1.0, 1.0).normalize(),
+        );
+
+        let xs = b.intersect(&r);
+
+        // A sharp cube would be hit at 4√3; the corner sphere is hit at 5√3 - (0.75√3 + 0.25)
+        assert_that!(xs[0]).is_close_to(7.1112, 0.001);
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_rounding_radius_must_fit_inside_the_box() {
+        rounded_box(1.0, 2.0, 2.0, 0.5, &MaterialBuilder::new().build());
+    }
+}
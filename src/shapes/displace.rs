@@ -0,0 +1,254 @@
+//! Displacement mapping: surfaces cut finely into triangles whose corners are pushed in or out
+//! along the surface normal, so that bumps change the outline of a shape as well as its shading.
+//!
+//! The surface is cut up once, when it is built, so the groups that come back have exact bounds
+//! that already take in the bumps.
+
+use std::rc::Rc;
+
+use crate::consts::EPSILON;
+use crate::group::GroupBuilder;
+use crate::material::Material;
+use crate::pattern::ScalarField;
+use crate::shape::Shape;
+use crate::shapes::patch::BezierPatch;
+use crate::triangle::{Triangle, TriangleBuilder};
+use crate::vector4::Vector4;
+
+/// How far to push a surface out along its normal at each point: `scale` times the value of a
+/// field, which runs from 0 to 1 for noise. A negative scale pushes the surface in.
+#[derive(Clone, Debug)]
+pub struct Displacement {
+    field: ScalarField,
+    scale: f32,
+}
+
+impl Displacement {
+    pub fn new(field: ScalarField, scale: f32) -> Self {
+        Self { field, scale }
+    }
+
+    /// How far the surface moves out at `object_point`.
+    pub fn offset_at(&self, object_point: Vector4) -> f32 {
+        self.scale * self.field.value_at(object_point)
+    }
+}
+
+/// `triangle` cut into `subdivisions` pieces along each edge and displaced along its normal, which
+/// is blended across the face if the triangle has vertex normals. The pieces keep the triangle's
+/// material, and the group takes its transform.
+pub fn displace_triangle(
+    triangle: &Triangle,
+    subdivisions: usize,
+    displacement: &Displacement,
+) -> GroupBuilder {
+    let n = subdivisions.max(1);
+    let [p1, p2, p3] = triangle.vertices();
+    let (e1, e2) = (p2 - p1, p3 - p1);
+
+    let mut points = Vec::new();
+    let mut rows = Vec::new();
+    for j in 0..=n {
+        let row: Vec<usize> = (0..=n - j)
+            .map(|i| {
+                let point = p1 + e1 * (i as f32 / n as f32) + e2 * (j as f32 / n as f32);
+                let normal = triangle.local_normal_at(point);
+                points.push(point + normal * displacement.offset_at(point));
+                points.len() - 1
+            })
+            .collect();
+        rows.push(row);
+    }
+
+    let mut faces = Vec::new();
+    for j in 0..n {
+        for i in 0..n - j {
+            faces.push([rows[j][i], rows[j][i + 1], rows[j + 1][i]]);
+            if i + 1 < n - j {
+                faces.push([rows[j][i + 1], rows[j + 1][i + 1], rows[j + 1][i]]);
+            }
+        }
+    }
+
+    smooth_mesh(&points, &faces, triangle.material()).with_transform(triangle.transformation())
+}
+
+/// `patch` cut into triangles `resolution` along each side, as `BezierPatch::tessellate` does,
+/// and displaced along its normal.
+pub fn displace_patch(
+    patch: &BezierPatch,
+    resolution: usize,
+    displacement: &Displacement,
+    material: &Material,
+) -> GroupBuilder {
+    let n = resolution.max(1);
+    let points: Vec<Vector4> = (0..=n)
+        .flat_map(|j| (0..=n).map(move |i| (i as f32 / n as f32, j as f32 / n as f32)))
+        .map(|(u, v)| {
+            let point = patch.point_at(u, v);
+            point + patch.normal_at(u, v) * displacement.offset_at(point)
+        })
+        .collect();
+
+    let index = |i: usize, j: usize| j * (n + 1) + i;
+    let mut faces = Vec::new();
+    for j in 0..n {
+        for i in 0..n {
+            let (a, b, c, d) = (
+                index(i, j),
+                index(i + 1, j),
+                index(i + 1, j + 1),
+                index(i, j + 1),
+            );
+            faces.push([a, b, c]);
+            faces.push([a, c, d]);
+        }
+    }
+
+    smooth_mesh(&points, &faces, material)
+}
+
+/// Smooth triangles between `points`, with the normal at each point the average of the faces
+/// around it, weighted by their areas. Faces with no area are left out.
+pub(crate) fn smooth_mesh(
+    points: &[Vector4],
+    faces: &[[usize; 3]],
+    material: &Material,
+) -> GroupBuilder {
+    let face_normal = |[a, b, c]: [usize; 3]| {
+        let (e1, e2) = (points[b] - points[a], points[c] - points[a]);
+        let normal = e2.cross_product(&e1);
+        if normal.magnitude() <= EPSILON * e1.magnitude() * e2.magnitude() {
+            None
+        } else {
+            Some(normal)
+        }
+    };
+
+    let mut normals = vec![Vector4::vector(0.0, 0.0, 0.0); points.len()];
+    for &face in faces {
+        if let Some(normal) = face_normal(face) {
+            for corner in face {
+                normals[corner] = normals[corner] + normal;
+            }
+        }
+    }
+
+    let mut group = GroupBuilder::new();
+    for &face in faces {
+        if face_normal(face).is_none() {
+            continue;
+        }
+        let [a, b, c] = face;
+        let triangle = TriangleBuilder::new(points[a], points[b], points[c])
+            .with_normals([normals[a], normals[b], normals[c]])
+            .with_material(material.clone())
+            .build();
+        group = group.with_child(Rc::new(triangle));
+    }
+
+    group
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::material::MaterialBuilder;
+    use crate::matrix::Matrix;
+    use crate::pattern::{FnPattern, SolidPattern};
+    use crate::ray::Ray;
+    use crate::transform::Transform;
+
+    /// A triangle in the xz plane facing up.
+    #[fixture]
+    fn floor() -> Triangle {
+        TriangleBuilder::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::point(2.0, 0.0, 0.0),
+            Vector4::point(0.0, 0.0, 2.0),
+        )
+        .build()
+    }
+
+    fn raised(by: f32) -> Displacement {
+        Displacement::new(ScalarField::Luminance(Rc::new(SolidPattern::default())), by)
+    }
+
+    fn down_from(x: f32, z: f32) -> Ray {
+        Ray::new(Vector4::point(x, 5.0, z), Vector4::vector(0.0, -1.0, 0.0))
+    }
+
+    #[rstest]
+    fn a_displaced_triangle_is_cut_into_pieces(floor: Triangle) {
+        let group = displace_triangle(&floor, 4, &raised(0.0)).build();
+
+        assert_that!(group.children().len()).is_equal_to(16);
+        assert_that!(group.intersect(&down_from(0.3, 0.6))).is_equal_to(vec![5.0]);
+    }
+
+    #[rstest]
+    fn a_displaced_triangle_is_pushed_out_along_its_normal(floor: Triangle) {
+        let group = displace_triangle(&floor, 4, &raised(0.5)).build();
+
+        assert_that!(group.intersect(&down_from(0.3, 0.6))).is_equal_to(vec![4.5]);
+        assert_that!(group.bounds().max.y).is_close_to(0.5, 0.0001);
+    }
+
+    #[rstest]
+    fn displacement_tilts_the_normals(floor: Triangle) {
+        let ramp = FnPattern::new(|p| Color::new(p.x, p.x, p.x));
+        let displacement = Displacement::new(ScalarField::Luminance(Rc::new(ramp)), 1.0);
+        let group = displace_triangle(&floor, 4, &displacement).build();
+        let r = down_from(0.6, 0.3);
+
+        let xs = group.intersect(&r);
+
+        // The triangle now climbs as a slope of 1 along x
+        assert_that!(xs).is_equal_to(vec![4.4]);
+        let point = r.position(xs[0]);
+        for piece in group.children() {
+            if piece.distance_to(&point) < EPSILON {
+                assert_that!(piece.normal_at(&point))
+                    .is_equal_to(Vector4::vector(-1.0, 1.0, 0.0).normalize());
+            }
+        }
+    }
+
+    #[test]
+    fn a_displaced_triangle_keeps_its_transform() {
+        let triangle = TriangleBuilder::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::point(2.0, 0.0, 0.0),
+            Vector4::point(0.0, 0.0, 2.0),
+        )
+        .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+        .build();
+
+        let group = displace_triangle(&triangle, 2, &raised(0.5)).build();
+
+        assert_that!(group.intersect(&down_from(0.3, 0.6))).is_equal_to(vec![3.5]);
+    }
+
+    #[test]
+    fn a_displaced_patch_is_pushed_out_along_its_normal() {
+        let mut control = [Vector4::point(0.0, 0.0, 0.0); 16];
+        for (index, point) in control.iter_mut().enumerate() {
+            *point = Vector4::point((index % 4) as f32, 0.0, (index / 4) as f32);
+        }
+        let patch = BezierPatch::new(control);
+
+        let group =
+            displace_patch(&patch, 3, &raised(0.25), &MaterialBuilder::new().build()).build();
+
+        assert_that!(group.children().len()).is_equal_to(18);
+        assert_that!(group.intersect(&down_from(1.1, 2.7))).is_equal_to(vec![4.75]);
+    }
+}
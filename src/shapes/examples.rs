@@ -0,0 +1,330 @@
+//! Well-known test models, for trying out materials and lighting, and for benchmarks that need
+//! something more demanding than a few spheres.
+
+use std::path::Path;
+
+use crate::group::GroupBuilder;
+use crate::lazy_mesh::LazyMeshBuilder;
+use crate::material::Material;
+use crate::ply::PlyMesh;
+use crate::shapes::patch::{bezier_surface, BezierPatch};
+use crate::vector4::Vector4;
+
+/// The Stanford bunny, read from one of the scans in `bunny.tar.gz` from the Stanford 3D
+/// Scanning Repository, which are free to use but too big to keep here. The low-res
+/// `bunny/reconstruction/bun_zipper_res4.ply` has 948 faces, and the full `bun_zipper.ply` about
+/// 70,000. The scan is in its own units, with y up, and is moved to stand on the xz plane
+/// centred on the y axis.
+pub fn bunny(path: impl AsRef<Path>, material: &Material) -> std::io::Result<LazyMeshBuilder> {
+    let mesh = PlyMesh::load(path)?;
+    if mesh.faces.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the bunny has no faces",
+        ));
+    }
+
+    let (mut min, mut max) = (mesh.points[0], mesh.points[0]);
+    for point in &mesh.points {
+        min = Vector4::point(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z));
+        max = Vector4::point(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z));
+    }
+    let offset = Vector4::vector((min.x + max.x) / 2.0, min.y, (min.z + max.z) / 2.0);
+    let points = mesh.points.iter().map(|&point| point - offset).collect();
+    // The scan's faces wind the opposite way to ours, so their normals would point inwards
+    let faces = mesh.faces.iter().map(|&[a, b, c]| [a, c, b]).collect();
+
+    Ok(LazyMeshBuilder::new(points, faces).with_material(material.clone()))
+}
+
+/// The Utah teapot, from Martin Newell's 32 Bézier patches, each cut into triangles `resolution`
+/// along each side. It stands on the xz plane, 3.15 tall, with its spout towards +x.
+pub fn teapot(resolution: usize, material: &Material) -> GroupBuilder {
+    bezier_surface(&teapot_patches(), resolution, material)
+}
+
+/// The patches of the Utah teapot. The data gives a quarter of the rim, body, lid and bottom and
+/// half of the handle and spout; the rest are their mirror images.
+pub fn teapot_patches() -> Vec<BezierPatch> {
+    let mut patches = Vec::new();
+    for (index, patch) in TEAPOT_PATCHES.iter().enumerate() {
+        let mirrors: &[(f32, f32)] = if index < 6 {
+            &[(1.0, 1.0), (-1.0, 1.0), (1.0, -1.0), (-1.0, -1.0)]
+        } else {
+            &[(1.0, 1.0), (1.0, -1.0)]
+        };
+        for &(sx, sy) in mirrors {
+            let mut control = [Vector4::point(0.0, 0.0, 0.0); 16];
+            for (i, &point) in patch.iter().enumerate() {
+                // Newell's patches wind the opposite way to ours, so their normals would point
+                // inwards. Run their rows the other way, except in mirror images, which are
+                // turned inside out already.
+                let column = if sx * sy > 0.0 { 3 - i % 4 } else { i % 4 };
+                let [x, y, z] = TEAPOT_POINTS[point];
+                // The data has z up, so stand it on the xz plane instead
+                control[4 * (i / 4) + column] = Vector4::point(sx * x, z, -sy * y);
+            }
+            patches.push(BezierPatch::new(control));
+        }
+    }
+
+    patches
+}
+
+/// The rim, body (two patches), lid (two), bottom, handle (two) and spout (two) of the teapot,
+/// as indices into `TEAPOT_POINTS`.
+const TEAPOT_PATCHES: [[usize; 16]; 10] = [
+    [102, 103, 104, 105, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [
+        12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+    ],
+    [
+        24, 25, 26, 27, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40,
+    ],
+    [
+        96, 96, 96, 96, 97, 98, 99, 100, 101, 101, 101, 101, 0, 1, 2, 3,
+    ],
+    [
+        0, 1, 2, 3, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117,
+    ],
+    [
+        118, 118, 118, 118, 124, 122, 119, 121, 123, 126, 125, 120, 40, 39, 38, 37,
+    ],
+    [
+        41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56,
+    ],
+    [
+        53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 28, 65, 66, 67,
+    ],
+    [
+        68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83,
+    ],
+    [
+        80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95,
+    ],
+];
+
+/// Newell's control points, with z up.
+const TEAPOT_POINTS: [[f32; 3]; 127] = [
+    [0.2, 0.0, 2.7],
+    [0.2, -0.112, 2.7],
+    [0.112, -0.2, 2.7],
+    [0.0, -0.2, 2.7],
+    [1.3375, 0.0, 2.53125],
+    [1.3375, -0.749, 2.53125],
+    [0.749, -1.3375, 2.53125],
+    [0.0, -1.3375, 2.53125],
+    [1.4375, 0.0, 2.53125],
+    [1.4375, -0.805, 2.53125],
+    [0.805, -1.4375, 2.53125],
+    [0.0, -1.4375, 2.53125],
+    [1.5, 0.0, 2.4],
+    [1.5, -0.84, 2.4],
+    [0.84, -1.5, 2.4],
+    [0.0, -1.5, 2.4],
+    [1.75, 0.0, 1.875],
+    [1.75, -0.98, 1.875],
+    [0.98, -1.75, 1.875],
+    [0.0, -1.75, 1.875],
+    [2.0, 0.0, 1.35],
+    [2.0, -1.12, 1.35],
+    [1.12, -2.0, 1.35],
+    [0.0, -2.0, 1.35],
+    [2.0, 0.0, 0.9],
+    [2.0, -1.12, 0.9],
+    [1.12, -2.0, 0.9],
+    [0.0, -2.0, 0.9],
+    [-2.0, 0.0, 0.9],
+    [2.0, 0.0, 0.45],
+    [2.0, -1.12, 0.45],
+    [1.12, -2.0, 0.45],
+    [0.0, -2.0, 0.45],
+    [1.5, 0.0, 0.225],
+    [1.5, -0.84, 0.225],
+    [0.84, -1.5, 0.225],
+    [0.0, -1.5, 0.225],
+    [1.5, 0.0, 0.15],
+    [1.5, -0.84, 0.15],
+    [0.84, -1.5, 0.15],
+    [0.0, -1.5, 0.15],
+    [-1.6, 0.0, 2.025],
+    [-1.6, -0.3, 2.025],
+    [-1.5, -0.3, 2.25],
+    [-1.5, 0.0, 2.25],
+    [-2.3, 0.0, 2.025],
+    [-2.3, -0.3, 2.025],
+    [-2.5, -0.3, 2.25],
+    [-2.5, 0.0, 2.25],
+    [-2.7, 0.0, 2.025],
+    [-2.7, -0.3, 2.025],
+    [-3.0, -0.3, 2.25],
+    [-3.0, 0.0, 2.25],
+    [-2.7, 0.0, 1.8],
+    [-2.7, -0.3, 1.8],
+    [-3.0, -0.3, 1.8],
+    [-3.0, 0.0, 1.8],
+    [-2.7, 0.0, 1.575],
+    [-2.7, -0.3, 1.575],
+    [-3.0, -0.3, 1.35],
+    [-3.0, 0.0, 1.35],
+    [-2.5, 0.0, 1.125],
+    [-2.5, -0.3, 1.125],
+    [-2.65, -0.3, 0.9375],
+    [-2.65, 0.0, 0.9375],
+    [-2.0, -0.3, 0.9],
+    [-1.9, -0.3, 0.6],
+    [-1.9, 0.0, 0.6],
+    [1.7, 0.0, 1.425],
+    [1.7, -0.66, 1.425],
+    [1.7, -0.66, 0.6],
+    [1.7, 0.0, 0.6],
+    [2.6, 0.0, 1.425],
+    [2.6, -0.66, 1.425],
+    [3.1, -0.66, 0.825],
+    [3.1, 0.0, 0.825],
+    [2.3, 0.0, 2.1],
+    [2.3, -0.25, 2.1],
+    [2.4, -0.25, 2.025],
+    [2.4, 0.0, 2.025],
+    [2.7, 0.0, 2.4],
+    [2.7, -0.25, 2.4],
+    [3.3, -0.25, 2.4],
+    [3.3, 0.0, 2.4],
+    [2.8, 0.0, 2.475],
+    [2.8, -0.25, 2.475],
+    [3.525, -0.25, 2.49375],
+    [3.525, 0.0, 2.49375],
+    [2.9, 0.0, 2.475],
+    [2.9, -0.15, 2.475],
+    [3.45, -0.15, 2.5125],
+    [3.45, 0.0, 2.5125],
+    [2.8, 0.0, 2.4],
+    [2.8, -0.15, 2.4],
+    [3.2, -0.15, 2.4],
+    [3.2, 0.0, 2.4],
+    [0.0, 0.0, 3.15],
+    [0.8, 0.0, 3.15],
+    [0.8, -0.45, 3.15],
+    [0.45, -0.8, 3.15],
+    [0.0, -0.8, 3.15],
+    [0.0, 0.0, 2.85],
+    [1.4, 0.0, 2.4],
+    [1.4, -0.784, 2.4],
+    [0.784, -1.4, 2.4],
+    [0.0, -1.4, 2.4],
+    [0.4, 0.0, 2.55],
+    [0.4, -0.224, 2.55],
+    [0.224, -0.4, 2.55],
+    [0.0, -0.4, 2.55],
+    [1.3, 0.0, 2.55],
+    [1.3, -0.728, 2.55],
+    [0.728, -1.3, 2.55],
+    [0.0, -1.3, 2.55],
+    [1.3, 0.0, 2.4],
+    [1.3, -0.728, 2.4],
+    [0.728, -1.3, 2.4],
+    [0.0, -1.3, 2.4],
+    [0.0, 0.0, 0.0],
+    [1.425, -0.798, 0.0],
+    [1.5, 0.0, 0.075],
+    [1.425, 0.0, 0.0],
+    [0.798, -1.425, 0.0],
+    [0.0, -1.5, 0.075],
+    [0.0, -1.425, 0.0],
+    [1.5, -0.84, 0.075],
+    [0.84, -1.5, 0.075],
+];
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::material::MaterialBuilder;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+
+    #[test]
+    fn the_teapot_is_made_of_32_patches() {
+        assert_that!(teapot_patches().len()).is_equal_to(32);
+    }
+
+    #[test]
+    fn the_teapot_stands_on_the_ground() {
+        let corners: Vec<Vector4> = teapot_patches()
+            .iter()
+            .flat_map(|patch| {
+                [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(u, v)| patch.point_at(u, v))
+            })
+            .collect();
+
+        let lowest = corners.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+        let highest = corners
+            .iter()
+            .map(|p| p.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+        assert_that!(lowest).is_close_to(0.0, 0.0001);
+        assert_that!(highest).is_close_to(3.15, 0.0001);
+    }
+
+    #[test]
+    fn the_mirrored_patches_of_the_body_all_face_outwards() {
+        // The body is the second and third groups of four patches
+        for patch in &teapot_patches()[4..12] {
+            let point = patch.point_at(0.5, 0.5);
+            let outwards = Vector4::vector(point.x, 0.0, point.z);
+
+            assert_that!(patch.normal_at(0.5, 0.5).dot(&outwards)).is_greater_than(0.0);
+        }
+    }
+
+    #[test]
+    fn the_bunny_stands_on_the_ground_facing_outwards() {
+        // A pyramid laid out like the bunny scans, wound anticlockwise as seen from outside
+        let path = std::env::temp_dir().join(format!("bunny-{}.ply", std::process::id()));
+        std::fs::write(
+            &path,
+            "ply\nformat ascii 1.0\nelement vertex 5\nproperty float x\nproperty float y\n\
+             property float z\nproperty float confidence\nelement face 5\n\
+             property list uchar int vertex_indices\nend_header\n\
+             1 1 1 1\n3 1 1 1\n3 1 3 1\n1 1 3 1\n2 3 2 1\n\
+             4 0 1 2 3\n3 0 4 1\n3 1 4 2\n3 2 4 3\n3 3 4 0\n",
+        )
+        .unwrap();
+
+        let bunny = bunny(&path, &MaterialBuilder::new().build())
+            .unwrap()
+            .build();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_that!(bunny.bounds().min).is_equal_to(Vector4::point(-1.0, 0.0, -1.0));
+        assert_that!(bunny.bounds().max).is_equal_to(Vector4::point(1.0, 2.0, 1.0));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.5, -10.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = bunny.intersect(&r);
+        assert_that!(xs.len()).is_equal_to(2);
+        let normal = bunny.normal_at(&r.position(xs[0]));
+        assert_that!(normal.z).is_less_than(0.0);
+    }
+
+    #[test]
+    fn a_ray_through_the_teapot_passes_in_and_out_of_its_body() {
+        let teapot = teapot(8, &MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(0.3, 1.0, -10.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = teapot.intersect(&r);
+
+        assert_that!(xs.len()).is_equal_to(2);
+        assert_that!(xs[0] + xs[1]).is_close_to(20.0, 0.01);
+        assert_that!(xs[0]).is_close_to(8.0, 0.05);
+    }
+}
@@ -0,0 +1,276 @@
+//! Bicubic patches, the curved surfaces that models like the Utah teapot are made from, cut into
+//! smooth triangles for rendering.
+
+use std::rc::Rc;
+
+use crate::consts::EPSILON;
+use crate::group::GroupBuilder;
+use crate::material::Material;
+use crate::triangle::TriangleBuilder;
+use crate::vector4::Vector4;
+
+/// A bicubic Bézier patch over a 4×4 grid of control points. It passes through the four corner
+/// points and is pulled towards the rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BezierPatch {
+    control: [Vector4; 16],
+}
+
+impl BezierPatch {
+    /// A patch over control points given row by row: `u` runs along each row, and `v` from the
+    /// first row to the last.
+    pub fn new(control: [Vector4; 16]) -> Self {
+        Self { control }
+    }
+
+    /// The patch a uniform cubic B-spline makes over the middle of its 4×4 grid of control
+    /// points, laid out as for `new`. Unlike Bézier patches, B-spline patches that share three
+    /// rows or columns of control points always join smoothly.
+    pub fn from_b_spline(control: [Vector4; 16]) -> Self {
+        let blend = |p: [Vector4; 4]| {
+            [
+                (p[0] + p[1] * 4.0 + p[2]) / 6.0,
+                (p[1] * 4.0 + p[2] * 2.0) / 6.0,
+                (p[1] * 2.0 + p[2] * 4.0) / 6.0,
+                (p[1] + p[2] * 4.0 + p[3]) / 6.0,
+            ]
+        };
+
+        let mut rows = control;
+        for row in 0..4 {
+            let blended = blend([0, 1, 2, 3].map(|column| control[4 * row + column]));
+            rows[4 * row..4 * row + 4].copy_from_slice(&blended);
+        }
+        let mut bezier = rows;
+        for column in 0..4 {
+            let blended = blend([0, 1, 2, 3].map(|row| rows[4 * row + column]));
+            for (row, point) in blended.iter().enumerate() {
+                bezier[4 * row + column] = *point;
+            }
+        }
+
+        Self::new(bezier)
+    }
+
+    pub fn point_at(&self, u: f32, v: f32) -> Vector4 {
+        self.weighted_sum(bernstein(u), bernstein(v))
+    }
+
+    /// The unit normal at `(u, v)`, on the side the control points wind anticlockwise round,
+    /// looking from `u` towards `v`.
+    pub fn normal_at(&self, u: f32, v: f32) -> Vector4 {
+        let normal = |u: f32, v: f32| {
+            let along_u = self.weighted_sum(bernstein_derivative(u), bernstein(v));
+            let along_v = self.weighted_sum(bernstein(u), bernstein_derivative(v));
+            along_v.cross_product(&along_u)
+        };
+
+        let n = normal(u, v);
+        if n.magnitude() > EPSILON {
+            return n.normalize();
+        }
+        // Where the patch pinches to a point, as at the top of the teapot's lid, the surface has
+        // no slope along one side, so take the normal from just inside the patch
+        let inside = |t: f32| t + (0.5 - t) * 0.001;
+        normal(inside(u), inside(v)).normalize()
+    }
+
+    /// The patch as a group of smooth triangles, `resolution` along each side. Triangles that
+    /// have no area, where the patch pinches to a point, are left out.
+    pub fn tessellate(&self, resolution: usize, material: &Material) -> GroupBuilder {
+        let n = resolution.max(1);
+        let vertices: Vec<(Vector4, Vector4)> = (0..=n)
+            .flat_map(|j| (0..=n).map(move |i| (i as f32 / n as f32, j as f32 / n as f32)))
+            .map(|(u, v)| (self.point_at(u, v), self.normal_at(u, v)))
+            .collect();
+        let vertex = |i: usize, j: usize| vertices[j * (n + 1) + i];
+
+        let mut group = GroupBuilder::new();
+        for j in 0..n {
+            for i in 0..n {
+                let (a, b, c, d) = (
+                    vertex(i, j),
+                    vertex(i + 1, j),
+                    vertex(i + 1, j + 1),
+                    vertex(i, j + 1),
+                );
+                for [p1, p2, p3] in [[a, b, c], [a, c, d]] {
+                    let (e1, e2) = (p2.0 - p1.0, p3.0 - p1.0);
+                    if e2.cross_product(&e1).magnitude()
+                        <= EPSILON * e1.magnitude() * e2.magnitude()
+                    {
+                        continue;
+                    }
+                    let face = TriangleBuilder::new(p1.0, p2.0, p3.0)
+                        .with_normals([p1.1, p2.1, p3.1])
+                        .with_material(material.clone())
+                        .build();
+                    group = group.with_child(Rc::new(face));
+                }
+            }
+        }
+
+        group
+    }
+
+    fn weighted_sum(&self, u_weights: [f32; 4], v_weights: [f32; 4]) -> Vector4 {
+        let mut sum = Vector4::vector(0.0, 0.0, 0.0);
+        for (row, v_weight) in v_weights.iter().enumerate() {
+            for (column, u_weight) in u_weights.iter().enumerate() {
+                sum = sum + self.control[4 * row + column] * (u_weight * v_weight);
+            }
+        }
+
+        sum
+    }
+}
+
+/// All of `patches`, each cut into triangles `resolution` along each side, as one group.
+pub fn bezier_surface(
+    patches: &[BezierPatch],
+    resolution: usize,
+    material: &Material,
+) -> GroupBuilder {
+    patches.iter().fold(GroupBuilder::new(), |group, patch| {
+        group.with_child(Rc::new(patch.tessellate(resolution, material).build()))
+    })
+}
+
+/// The cubic Bernstein polynomials at `t`: how much each control point along a curve counts.
+fn bernstein(t: f32) -> [f32; 4] {
+    let s = 1.0 - t;
+
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+fn bernstein_derivative(t: f32) -> [f32; 4] {
+    let s = 1.0 - t;
+
+    [
+        -3.0 * s * s,
+        3.0 * s * s - 6.0 * t * s,
+        6.0 * t * s - 3.0 * t * t,
+        3.0 * t * t,
+    ]
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::material::MaterialBuilder;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+
+    /// Control points spaced 1 apart across the xz plane, with the middle four raised by `bulge`.
+    fn grid(bulge: f32) -> [Vector4; 16] {
+        let mut control = [Vector4::point(0.0, 0.0, 0.0); 16];
+        for (index, point) in control.iter_mut().enumerate() {
+            let (row, column) = (index / 4, index % 4);
+            let raised = (1..3).contains(&row) && (1..3).contains(&column);
+            *point = Vector4::point(column as f32, if raised { bulge } else { 0.0 }, row as f32);
+        }
+
+        control
+    }
+
+    #[fixture]
+    fn flat() -> BezierPatch {
+        BezierPatch::new(grid(0.0))
+    }
+
+    #[rstest]
+    fn a_patch_passes_through_its_corners(flat: BezierPatch) {
+        assert_that!(flat.point_at(0.0, 0.0)).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
+        assert_that!(flat.point_at(1.0, 0.0)).is_equal_to(Vector4::point(3.0, 0.0, 0.0));
+        assert_that!(flat.point_at(1.0, 1.0)).is_equal_to(Vector4::point(3.0, 0.0, 3.0));
+    }
+
+    #[rstest]
+    #[case(0.5, 0.5)]
+    #[case(0.0, 0.0)]
+    #[case(0.2, 0.9)]
+    fn a_flat_patch_faces_the_same_way_everywhere(
+        flat: BezierPatch,
+        #[case] u: f32,
+        #[case] v: f32,
+    ) {
+        assert_that!(flat.normal_at(u, v)).is_equal_to(Vector4::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn a_bulging_patch_curves_away_from_its_middle() {
+        let patch = BezierPatch::new(grid(1.0));
+
+        assert_that!(patch.point_at(0.5, 0.5).y).is_close_to(0.5625, 0.0001);
+        assert_that!(patch.normal_at(0.5, 0.5)).is_equal_to(Vector4::vector(0.0, 1.0, 0.0));
+        let near_corner = patch.normal_at(0.25, 0.25);
+        assert_that!(near_corner.x).is_less_than(0.0);
+        assert_that!(near_corner.z).is_less_than(0.0);
+    }
+
+    #[test]
+    fn a_patch_pinched_to_a_point_still_has_normals() {
+        let mut control = grid(0.0);
+        for point in control.iter_mut().take(4) {
+            *point = Vector4::point(1.5, 0.0, 0.0);
+        }
+        let patch = BezierPatch::new(control);
+
+        let n = patch.normal_at(0.5, 0.0);
+
+        assert_that!(n).is_equal_to(Vector4::vector(0.0, 1.0, 0.0));
+        // The four triangles along the pinched edge have no area
+        let group = patch.tessellate(4, &MaterialBuilder::new().build()).build();
+        assert_that!(group.children().len()).is_equal_to(28);
+    }
+
+    #[rstest]
+    fn a_tessellated_patch_is_hit_where_the_patch_is(flat: BezierPatch) {
+        let group = flat.tessellate(4, &MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(1.1, 5.0, 2.7),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+
+        assert_that!(group.children().len()).is_equal_to(32);
+        assert_that!(group.intersect(&r)).is_equal_to(vec![5.0]);
+    }
+
+    #[test]
+    fn the_triangles_of_a_patch_are_shaded_smoothly() {
+        let patch = BezierPatch::new(grid(1.0));
+        let group = patch.tessellate(2, &MaterialBuilder::new().build()).build();
+        let point = patch.point_at(0.5, 0.5);
+
+        for triangle in group.children() {
+            if triangle.distance_to(&point) < EPSILON {
+                assert_that!(triangle.normal_at(&point)).is_equal_to(patch.normal_at(0.5, 0.5));
+            }
+        }
+    }
+
+    #[test]
+    fn a_b_spline_patch_covers_the_middle_of_its_control_points() {
+        let patch = BezierPatch::from_b_spline(grid(0.0));
+
+        assert_that!(patch.point_at(0.0, 0.0)).is_equal_to(Vector4::point(1.0, 0.0, 1.0));
+        assert_that!(patch.point_at(1.0, 1.0)).is_equal_to(Vector4::point(2.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn a_surface_tessellates_each_patch() {
+        let patches = [BezierPatch::new(grid(0.0)), BezierPatch::new(grid(1.0))];
+
+        let surface = bezier_surface(&patches, 3, &MaterialBuilder::new().build()).build();
+
+        assert_that!(surface.children().len()).is_equal_to(2);
+        assert_that!(surface.children()[1].children().len()).is_equal_to(18);
+    }
+}
@@ -0,0 +1,223 @@
+//! Prisms made by extruding a flat outline, for signs, gears, floor plans and the like.
+//!
+//! The outline is given as points in the xz plane, and is swept straight up the y axis. Its
+//! ends are filled in by cutting the outline into triangles by ear clipping, which handles any
+//! simple polygon, concave or not, as long as its edges don't cross.
+
+use std::rc::Rc;
+
+use crate::group::GroupBuilder;
+use crate::material::Material;
+use crate::triangle::TriangleBuilder;
+use crate::vector4::Vector4;
+
+/// The outline `profile`, as (x, z) points in order around it either way, swept from y = 0 up
+/// to `height` and closed at both ends, as a group of triangles facing outwards.
+pub fn prism(profile: &[(f32, f32)], height: f32, material: &Material) -> GroupBuilder {
+    faces(profile, height)
+        .into_iter()
+        .fold(GroupBuilder::new(), |group, [p1, p2, p3]| {
+            let face = TriangleBuilder::new(p1, p2, p3)
+                .with_material(material.clone())
+                .build();
+            group.with_child(Rc::new(face))
+        })
+}
+
+/// The corners of each face of the prism, wound so that the triangles' normals point outwards.
+fn faces(profile: &[(f32, f32)], height: f32) -> Vec<[Vector4; 3]> {
+    let mut outline = profile.to_vec();
+    if signed_area(&outline) < 0.0 {
+        outline.reverse();
+    }
+    let at = |i: usize, y: f32| Vector4::point(outline[i].0, y, outline[i].1);
+
+    let mut faces = Vec::new();
+    for [a, b, c] in triangulate(&outline) {
+        faces.push([at(a, height), at(b, height), at(c, height)]);
+        faces.push([at(c, 0.0), at(b, 0.0), at(a, 0.0)]);
+    }
+    for a in 0..outline.len() {
+        let b = (a + 1) % outline.len();
+        faces.push([at(a, 0.0), at(b, 0.0), at(b, height)]);
+        faces.push([at(a, 0.0), at(b, height), at(a, height)]);
+    }
+
+    faces
+}
+
+/// Cut a simple polygon into triangles by ear clipping, as indices into `polygon`. Each
+/// triangle winds the same way round as the polygon does. A polygon whose edges cross may not
+/// be covered completely.
+pub fn triangulate(polygon: &[(f32, f32)]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    if signed_area(polygon) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        let ear = (0..n).find(|&i| is_ear(polygon, &remaining, corner(i)));
+        let i = match ear {
+            Some(i) => i,
+            // Only a polygon whose edges cross runs out of ears
+            None => break,
+        };
+        triangles.push(corner(i));
+        remaining.remove(i);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    if signed_area(polygon) < 0.0 {
+        for triangle in triangles.iter_mut() {
+            triangle.reverse();
+        }
+    }
+
+    triangles
+}
+
+/// Twice the area of the polygon, positive if it runs anticlockwise from the x axis to the z
+/// axis.
+fn signed_area(polygon: &[(f32, f32)]) -> f32 {
+    (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum()
+}
+
+/// Which way the path from `a` through `b` to `c` turns: positive if anticlockwise.
+fn turn(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0)
+}
+
+/// Can the corner at `b`, between `a` and `c`, be cut off an anticlockwise polygon? It can if it
+/// points outwards and no other corner lies inside the triangle it makes.
+fn is_ear(polygon: &[(f32, f32)], remaining: &[usize], [a, b, c]: [usize; 3]) -> bool {
+    let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+    if turn(pa, pb, pc) <= 0.0 {
+        return false;
+    }
+
+    remaining
+        .iter()
+        .filter(|&&i| i != a && i != b && i != c)
+        .map(|&i| polygon[i])
+        .all(|p| turn(pa, pb, p) < 0.0 || turn(pb, pc, p) < 0.0 || turn(pc, pa, p) < 0.0)
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::material::MaterialBuilder;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+
+    /// An L, with its notch in the corner nearest +x and +z.
+    #[fixture]
+    fn l_shape() -> Vec<(f32, f32)> {
+        vec![
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+        ]
+    }
+
+    fn area(polygon: &[(f32, f32)], triangles: &[[usize; 3]]) -> f32 {
+        triangles
+            .iter()
+            .map(|t| signed_area(&[polygon[t[0]], polygon[t[1]], polygon[t[2]]]) / 2.0)
+            .sum()
+    }
+
+    #[test]
+    fn a_square_is_cut_into_two_triangles() {
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+        let triangles = triangulate(&square);
+
+        assert_that!(triangles.len()).is_equal_to(2);
+        assert_that!(area(&square, &triangles)).is_close_to(1.0, 0.0001);
+    }
+
+    #[rstest]
+    fn a_concave_polygon_is_covered_exactly(l_shape: Vec<(f32, f32)>) {
+        let triangles = triangulate(&l_shape);
+
+        assert_that!(triangles.len()).is_equal_to(4);
+        assert_that!(area(&l_shape, &triangles)).is_close_to(3.0, 0.0001);
+    }
+
+    #[rstest]
+    fn a_clockwise_polygon_gives_clockwise_triangles(l_shape: Vec<(f32, f32)>) {
+        let clockwise: Vec<(f32, f32)> = l_shape.into_iter().rev().collect();
+
+        let triangles = triangulate(&clockwise);
+
+        assert_that!(area(&clockwise, &triangles)).is_close_to(-3.0, 0.0001);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.5, 5.0, 0.5), Vector4::vector(0.0, -1.0, 0.0), Some(4.0))]
+    #[case(Vector4::point(1.5, 5.0, 0.5), Vector4::vector(0.0, -1.0, 0.0), Some(4.0))]
+    #[case(Vector4::point(1.5, 5.0, 1.5), Vector4::vector(0.0, -1.0, 0.0), None)]
+    #[case(Vector4::point(5.0, 0.5, 1.5), Vector4::vector(-1.0, 0.0, 0.0), Some(4.0))]
+    fn intersecting_a_prism(
+        l_shape: Vec<(f32, f32)>,
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+        #[case] expected: Option<f32>,
+    ) {
+        let prism = prism(&l_shape, 1.0, &MaterialBuilder::new().build()).build();
+
+        let xs = prism.intersect(&Ray::new(origin, direction));
+
+        assert_that!(xs.first().copied()).is_equal_to(expected);
+    }
+
+    #[rstest]
+    fn the_faces_of_a_prism_point_outwards(l_shape: Vec<(f32, f32)>) {
+        let faces = faces(&l_shape, 1.0);
+
+        assert_that!(faces.len()).is_equal_to(2 * 4 + 2 * 6);
+        for [p1, p2, p3] in faces {
+            let normal = (p3 - p1).cross_product(&(p2 - p1));
+            let middle = Vector4::point(
+                (p1.x + p2.x + p3.x) / 3.0,
+                (p1.y + p2.y + p3.y) / 3.0,
+                (p1.z + p2.z + p3.z) / 3.0,
+            );
+
+            // Just outside each face is outside the L altogether
+            let outside = middle + normal.normalize() * 0.01;
+            let inside_outline =
+                (outside.x < 1.0 && outside.z < 2.0) || (outside.x < 2.0 && outside.z < 1.0);
+            let inside = inside_outline
+                && outside.x > 0.0
+                && outside.z > 0.0
+                && (0.0..1.0).contains(&outside.y);
+            assert_that!(inside).is_false();
+        }
+    }
+}
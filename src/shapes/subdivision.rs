@@ -0,0 +1,458 @@
+//! Subdivision surfaces: a coarse polygon cage, such as an imported model, smoothed by cutting
+//! every face into smaller ones again and again, each time easing the corners towards their
+//! neighbours.
+//!
+//! Edges with only one face are kept as boundaries, which stay put as curves of their own rather
+//! than being rounded off towards the inside.
+
+use std::collections::BTreeMap;
+
+use crate::group::GroupBuilder;
+use crate::material::Material;
+use crate::shapes::displace::smooth_mesh;
+use crate::vector4::Vector4;
+
+/// How each round of subdivision cuts up the faces and moves the points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheme {
+    /// Catmull and Clark's scheme, for cages of quads, though it takes any polygons: each round
+    /// turns every face into quads, one at each of its corners.
+    CatmullClark,
+    /// Loop's scheme, for cages made only of triangles: each round cuts every triangle into four.
+    Loop,
+}
+
+/// A polygon mesh that a subdivision surface is smoothed from: points, and faces that each list
+/// the indices of their corners in the order that `Triangle` takes its corners.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cage {
+    points: Vec<Vector4>,
+    faces: Vec<Vec<usize>>,
+}
+
+/// The faces along each edge, keyed by the indices of its ends, lowest first.
+type Edges = BTreeMap<(usize, usize), Vec<usize>>;
+
+impl Cage {
+    pub fn new(points: Vec<Vector4>, faces: Vec<Vec<usize>>) -> Self {
+        assert!(
+            faces.iter().flatten().all(|&i| i < points.len()),
+            "A face refers to a point that isn't there"
+        );
+
+        Self { points, faces }
+    }
+
+    pub fn points(&self) -> &[Vector4] {
+        &self.points
+    }
+
+    pub fn faces(&self) -> &[Vec<usize>] {
+        &self.faces
+    }
+
+    /// The cage after `levels` rounds of `scheme`.
+    pub fn subdivided(&self, scheme: Scheme, levels: usize) -> Cage {
+        (0..levels).fold(self.clone(), |cage, _| match scheme {
+            Scheme::CatmullClark => cage.catmull_clark(),
+            Scheme::Loop => cage.loop_subdivision(),
+        })
+    }
+
+    /// The faces as a group of smooth triangles, fanned out from the first corner of each.
+    pub fn to_group(&self, material: &Material) -> GroupBuilder {
+        let triangles: Vec<[usize; 3]> = self
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |i| [face[0], face[i], face[i + 1]]))
+            .collect();
+
+        smooth_mesh(&self.points, &triangles, material)
+    }
+
+    fn catmull_clark(&self) -> Cage {
+        let edges = self.edges();
+        let face_points: Vec<Vector4> = self
+            .faces
+            .iter()
+            .map(|face| average(face.iter().map(|&i| self.points[i])))
+            .collect();
+        let edge_points: Vec<Vector4> = edges
+            .iter()
+            .map(|(&(a, b), faces)| match faces[..] {
+                [f1, f2] => average([
+                    self.points[a],
+                    self.points[b],
+                    face_points[f1],
+                    face_points[f2],
+                ]),
+                _ => average([self.points[a], self.points[b]]),
+            })
+            .collect();
+
+        let (vertex_faces, vertex_edges) = (self.faces_around(), self.edges_around(&edges));
+        let vertex_points: Vec<Vector4> = (0..self.points.len())
+            .map(|v| {
+                let p = self.points[v];
+                if let Some(moved) = self.boundary_point(v, &edges, &vertex_edges[v]) {
+                    return moved;
+                }
+                let n = vertex_edges[v].len() as f32;
+                if vertex_faces[v].is_empty() {
+                    return p;
+                }
+                let f = average(vertex_faces[v].iter().map(|&face| face_points[face]));
+                let r = average(
+                    vertex_edges[v]
+                        .iter()
+                        .map(|&(a, b)| average([self.points[a], self.points[b]])),
+                );
+                (f + r * 2.0 + p * (n - 3.0)) / n
+            })
+            .collect();
+
+        // The new points are the moved old ones, then one for each face, then one for each edge
+        let face_base = self.points.len();
+        let edge_base = face_base + self.faces.len();
+        let edge_index: BTreeMap<(usize, usize), usize> = edges
+            .keys()
+            .enumerate()
+            .map(|(i, &edge)| (edge, edge_base + i))
+            .collect();
+        let edge_point = |a: usize, b: usize| edge_index[&(a.min(b), a.max(b))];
+
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let k = face.len();
+            for i in 0..k {
+                let (before, corner, after) = (face[(i + k - 1) % k], face[i], face[(i + 1) % k]);
+                faces.push(vec![
+                    corner,
+                    edge_point(corner, after),
+                    face_base + f,
+                    edge_point(before, corner),
+                ]);
+            }
+        }
+
+        let points = vertex_points
+            .into_iter()
+            .chain(face_points)
+            .chain(edge_points)
+            .collect();
+        Cage::new(points, faces)
+    }
+
+    fn loop_subdivision(&self) -> Cage {
+        assert!(
+            self.faces.iter().all(|face| face.len() == 3),
+            "Loop subdivision needs a cage made only of triangles"
+        );
+
+        let edges = self.edges();
+        let edge_points: Vec<Vector4> = edges
+            .iter()
+            .map(|(&(a, b), faces)| {
+                let (pa, pb) = (self.points[a], self.points[b]);
+                match faces[..] {
+                    [f1, f2] => {
+                        let opposite = |f: usize| {
+                            let corner = self.faces[f].iter().find(|&&i| i != a && i != b);
+                            self.points[*corner.unwrap()]
+                        };
+                        (pa + pb) * 0.375 + (opposite(f1) + opposite(f2)) * 0.125
+                    }
+                    _ => average([pa, pb]),
+                }
+            })
+            .collect();
+
+        let vertex_edges = self.edges_around(&edges);
+        let vertex_points: Vec<Vector4> = (0..self.points.len())
+            .map(|v| {
+                let p = self.points[v];
+                if let Some(moved) = self.boundary_point(v, &edges, &vertex_edges[v]) {
+                    return moved;
+                }
+                let n = vertex_edges[v].len();
+                if n == 0 {
+                    return p;
+                }
+                let beta = if n == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * n as f32)
+                };
+                let neighbours = vertex_edges[v]
+                    .iter()
+                    .map(|&(a, b)| self.points[if a == v { b } else { a }])
+                    .fold(Vector4::vector(0.0, 0.0, 0.0), |sum, q| sum + q);
+                p * (1.0 - n as f32 * beta) + neighbours * beta
+            })
+            .collect();
+
+        // The new points are the moved old ones, then one for each edge
+        let edge_base = self.points.len();
+        let edge_index: BTreeMap<(usize, usize), usize> = edges
+            .keys()
+            .enumerate()
+            .map(|(i, &edge)| (edge, edge_base + i))
+            .collect();
+        let edge_point = |a: usize, b: usize| edge_index[&(a.min(b), a.max(b))];
+
+        let mut faces = Vec::new();
+        for face in &self.faces {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let (ab, bc, ca) = (edge_point(a, b), edge_point(b, c), edge_point(c, a));
+            faces.push(vec![a, ab, ca]);
+            faces.push(vec![ab, b, bc]);
+            faces.push(vec![ca, bc, c]);
+            faces.push(vec![ab, bc, ca]);
+        }
+
+        let points = vertex_points.into_iter().chain(edge_points).collect();
+        Cage::new(points, faces)
+    }
+
+    fn edges(&self) -> Edges {
+        let mut edges = Edges::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let (a, b) = (face[i], face[(i + 1) % face.len()]);
+                edges.entry((a.min(b), a.max(b))).or_default().push(f);
+            }
+        }
+
+        edges
+    }
+
+    /// The faces around each point.
+    fn faces_around(&self) -> Vec<Vec<usize>> {
+        let mut around = vec![Vec::new(); self.points.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in face {
+                around[v].push(f);
+            }
+        }
+
+        around
+    }
+
+    /// The edges around each point.
+    fn edges_around(&self, edges: &Edges) -> Vec<Vec<(usize, usize)>> {
+        let mut around = vec![Vec::new(); self.points.len()];
+        for &(a, b) in edges.keys() {
+            around[a].push((a, b));
+            around[b].push((a, b));
+        }
+
+        around
+    }
+
+    /// Where point `v` moves to if it is on a boundary, which is the same for both schemes. A
+    /// point where boundaries meet, or that has more than two faces along an edge, stays put.
+    fn boundary_point(
+        &self,
+        v: usize,
+        edges: &Edges,
+        around: &[(usize, usize)],
+    ) -> Option<Vector4> {
+        let boundary: Vec<usize> = around
+            .iter()
+            .filter(|edge| edges[edge].len() != 2)
+            .map(|&(a, b)| if a == v { b } else { a })
+            .collect();
+
+        match boundary[..] {
+            [] => None,
+            [a, b] => Some(self.points[v] * 0.75 + (self.points[a] + self.points[b]) * 0.125),
+            _ => Some(self.points[v]),
+        }
+    }
+}
+
+/// A group of smooth triangles from `cage` after `levels` rounds of `scheme`.
+pub fn subdivision_surface(
+    cage: &Cage,
+    scheme: Scheme,
+    levels: usize,
+    material: &Material,
+) -> GroupBuilder {
+    cage.subdivided(scheme, levels).to_group(material)
+}
+
+fn average(points: impl IntoIterator<Item = Vector4>) -> Vector4 {
+    let (sum, count) = points
+        .into_iter()
+        .fold((Vector4::vector(0.0, 0.0, 0.0), 0), |(sum, count), p| {
+            (sum + p, count + 1)
+        });
+
+    sum / count as f32
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::material::MaterialBuilder;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+
+    /// The cube from -1 to 1, with its faces wound outwards.
+    #[fixture]
+    fn cube() -> Cage {
+        let points = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+                Vector4::point(coordinate(1), coordinate(2), coordinate(4))
+            })
+            .collect();
+        let faces = vec![
+            vec![0, 1, 3, 2],
+            vec![4, 6, 7, 5],
+            vec![0, 4, 5, 1],
+            vec![2, 3, 7, 6],
+            vec![0, 2, 6, 4],
+            vec![1, 5, 7, 3],
+        ];
+
+        Cage::new(points, faces)
+    }
+
+    /// The octahedron with its corners 1 from the origin along each axis, wound outwards.
+    #[fixture]
+    fn octahedron() -> Cage {
+        let points = vec![
+            Vector4::point(1.0, 0.0, 0.0),
+            Vector4::point(-1.0, 0.0, 0.0),
+            Vector4::point(0.0, 1.0, 0.0),
+            Vector4::point(0.0, -1.0, 0.0),
+            Vector4::point(0.0, 0.0, 1.0),
+            Vector4::point(0.0, 0.0, -1.0),
+        ];
+        let mut faces = Vec::new();
+        for x in [0, 1] {
+            for y in [2, 3] {
+                for z in [4, 5] {
+                    // An odd number of negative axes turns the winding round
+                    if (x + y + z) % 2 == 0 {
+                        faces.push(vec![x, z, y]);
+                    } else {
+                        faces.push(vec![x, y, z]);
+                    }
+                }
+            }
+        }
+
+        Cage::new(points, faces)
+    }
+
+    fn from_outside(cage: &Cage) -> Vector4 {
+        let group = cage.to_group(&MaterialBuilder::new().build()).build();
+        let r = Ray::new(
+            Vector4::point(5.0, 0.01, 0.02),
+            Vector4::vector(-1.0, 0.0, 0.0),
+        );
+        let xs = group.intersect(&r);
+        let point = r.position(xs[0]);
+
+        group
+            .children()
+            .iter()
+            .find(|piece| piece.distance_to(&point) < 0.0001)
+            .unwrap()
+            .normal_at(&point)
+    }
+
+    #[rstest]
+    fn catmull_clark_turns_each_corner_of_a_face_into_a_quad(cube: Cage) {
+        let once = cube.subdivided(Scheme::CatmullClark, 1);
+        let twice = cube.subdivided(Scheme::CatmullClark, 2);
+
+        assert_that!(once.points().len()).is_equal_to(8 + 6 + 12);
+        assert_that!(once.faces().len()).is_equal_to(24);
+        assert_that!(twice.faces().len()).is_equal_to(96);
+        assert_that!(once.faces().iter().all(|face| face.len() == 4)).is_true();
+    }
+
+    #[rstest]
+    fn catmull_clark_eases_the_corners_of_a_cube_inwards(cube: Cage) {
+        let once = cube.subdivided(Scheme::CatmullClark, 1);
+
+        let corner = once.points()[7];
+        assert_that!(corner).is_equal_to(Vector4::point(5.0 / 9.0, 5.0 / 9.0, 5.0 / 9.0));
+        // The faces' points sit in the middle of the old faces
+        assert_that!(once.points()[8]).is_equal_to(Vector4::point(0.0, 0.0, -1.0));
+        // Each edge's point is the average of its ends and the middles of its faces
+        assert_that!(once.points()[8 + 6]).is_equal_to(Vector4::point(0.0, -0.75, -0.75));
+    }
+
+    #[rstest]
+    fn loop_cuts_each_triangle_into_four(octahedron: Cage) {
+        let once = octahedron.subdivided(Scheme::Loop, 1);
+
+        assert_that!(once.points().len()).is_equal_to(6 + 12);
+        assert_that!(once.faces().len()).is_equal_to(32);
+        assert_that!(once.points()[0]).is_equal_to(Vector4::point(0.625, 0.0, 0.0));
+        // Three eighths of each end, and an eighth of each corner across from the edge
+        assert_that!(once.points()[6]).is_equal_to(Vector4::point(0.375, 0.375, 0.0));
+    }
+
+    #[rstest]
+    #[should_panic]
+    fn loop_subdivision_needs_triangles(cube: Cage) {
+        cube.subdivided(Scheme::Loop, 1);
+    }
+
+    #[test]
+    fn the_boundary_of_an_open_cage_stays_on_its_edges() {
+        let square = Cage::new(
+            vec![
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::point(0.0, 0.0, 1.0),
+                Vector4::point(1.0, 0.0, 1.0),
+                Vector4::point(1.0, 0.0, 0.0),
+            ],
+            vec![vec![0, 1, 2, 3]],
+        );
+
+        let once = square.subdivided(Scheme::CatmullClark, 1);
+
+        assert_that!(once.points()[0]).is_equal_to(Vector4::point(0.125, 0.0, 0.125));
+        assert_that!(once.points().iter().all(|p| p.y == 0.0)).is_true();
+    }
+
+    #[rstest]
+    #[case(Scheme::CatmullClark)]
+    #[case(Scheme::Loop)]
+    fn a_subdivided_surface_faces_outwards(cube: Cage, octahedron: Cage, #[case] scheme: Scheme) {
+        let cage = match scheme {
+            Scheme::CatmullClark => cube,
+            Scheme::Loop => octahedron,
+        };
+
+        let normal = from_outside(&cage.subdivided(scheme, 2));
+
+        assert_that!(normal.x).is_greater_than(0.99);
+    }
+
+    #[rstest]
+    fn a_subdivision_surface_is_a_group_of_triangles(cube: Cage) {
+        let surface = subdivision_surface(
+            &cube,
+            Scheme::CatmullClark,
+            2,
+            &MaterialBuilder::new().build(),
+        )
+        .build();
+
+        assert_that!(surface.children().len()).is_equal_to(2 * 96);
+    }
+}
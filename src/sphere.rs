@@ -1,9 +1,15 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
 use crate::color::Color;
+use crate::consts::EPSILON;
 use crate::light::PointLight;
-use crate::material::{Material, MaterialBuilder};
+use crate::material::{presets, Material, MaterialBuilder};
 use crate::matrix::Matrix;
 use crate::ray::Ray;
-use crate::shape::Shape;
+use crate::rng::Rng;
+use crate::shape::{area_scale, shared_user_data, Shape, UserData};
 use crate::vector4::Vector4;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -11,14 +17,26 @@ pub struct Sphere {
     // Note: we store the inverse of the transform as an optimisation.
     inv_transform: Matrix<4>,
     material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
 }
 
 pub struct SphereBuilder {
     transform: Matrix<4>,
     material: Material,
+    name: Option<String>,
+    user_data: UserData,
 }
 
 impl Shape for Sphere {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
@@ -31,6 +49,24 @@ impl Shape for Sphere {
         &self.inv_transform
     }
 
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(Vector4::point(-1.0, -1.0, -1.0), Vector4::point(1.0, 1.0, 1.0))
+    }
+
     fn local_intersect(&self, transformed_ray: &Ray) -> Vec<f32> {
         let sphere_to_ray = transformed_ray.origin - Vector4::point(0.0, 0.0, 0.0);
         let a = transformed_ray.direction.dot(&transformed_ray.direction);
@@ -58,6 +94,35 @@ impl Shape for Sphere {
         object_point - Vector4::point(0.0, 0.0, 0.0)
     }
 
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        let from_centre = object_point - Vector4::point(0.0, 0.0, 0.0);
+        if from_centre.magnitude() < EPSILON {
+            // Every point on the surface is equally close to the centre
+            return Vector4::point(1.0, 0.0, 0.0);
+        }
+
+        Vector4::point(0.0, 0.0, 0.0) + from_centre.normalize()
+    }
+
+    fn surface_area(&self) -> Option<f32> {
+        // Exact under uniform scaling; non-uniformly scaled spheres are approximated
+        Some(4.0 * PI * self.transformation().determinant().abs().powf(2.0 / 3.0))
+    }
+
+    fn sample_surface(&self, rng: &mut Rng) -> Option<(Vector4, Vector4, f32)> {
+        // Even over the unit sphere, but a scale that stretches some of the surface more than the
+        // rest spreads the samples there more thinly, so the density is worked out at the point
+        let z = 1.0 - 2.0 * rng.next_f32();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.next_f32();
+        let object_normal = Vector4::vector(r * phi.cos(), r * phi.sin(), z);
+        let transform = self.transformation();
+        let point = transform * (Vector4::point(0.0, 0.0, 0.0) + object_normal);
+        let stretch = area_scale(&transform, &self.inv_transform, object_normal);
+
+        Some((point, self.normal_at(&point), 1.0 / (4.0 * PI * stretch)))
+    }
+
     fn lighting(
         &self,
         light: &PointLight,
@@ -76,9 +141,29 @@ impl SphereBuilder {
         Self {
             transform: Matrix::identity(),
             material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
         }
     }
 
+    /// A sphere made of glass, as used throughout the book's refraction examples.
+    pub fn glass() -> Self {
+        Self::new().with_material(presets::glass())
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
     pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
         self.transform = transform;
 
@@ -95,6 +180,8 @@ impl SphereBuilder {
         Sphere {
             inv_transform: self.transform.try_inverse().unwrap(),
             material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
         }
     }
 }
@@ -106,7 +193,7 @@ Tests
 
 #[cfg(test)]
 mod tests {
-    use std::f32::consts::{FRAC_1_SQRT_2, PI};
+    use std::f32::consts::FRAC_1_SQRT_2;
 
     use spectral::prelude::*;
 
@@ -347,4 +434,140 @@ mod tests {
 
         assert_that!(s.material()).is_equal_to(&m);
     }
+
+    #[test]
+    fn a_sphere_has_no_name_by_default() {
+        let s = SphereBuilder::new().build();
+
+        assert_that!(s.name()).is_none();
+    }
+
+    #[test]
+    fn a_sphere_may_be_assigned_a_name() {
+        let s = SphereBuilder::new().with_name("ball").build();
+
+        assert_that!(s.name()).is_some().is_equal_to("ball");
+    }
+
+    #[test]
+    fn a_helper_for_producing_a_sphere_with_a_glassy_material() {
+        let s = SphereBuilder::glass().build();
+
+        assert_that!(s.transformation()).is_equal_to(Matrix::identity());
+        assert_that!(s.material().transparency()).is_equal_to(1.0);
+        assert_that!(s.material().refractive_index()).is_equal_to(1.52);
+    }
+
+    #[test]
+    fn a_sphere_has_a_bounding_box() {
+        let s = SphereBuilder::new().build();
+
+        let b = s.bounds();
+
+        assert_that!(b.min).is_equal_to(Vector4::point(-1.0, -1.0, -1.0));
+        assert_that!(b.max).is_equal_to(Vector4::point(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn querying_a_shapes_bounding_box_in_its_parents_space() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(1.0, -3.0, 5.0) * Matrix::scaling(0.5, 2.0, 4.0))
+            .build();
+
+        let b = s.parent_space_bounds();
+
+        assert_that!(b.min).is_equal_to(Vector4::point(0.5, -5.0, 1.0));
+        assert_that!(b.max).is_equal_to(Vector4::point(1.5, -1.0, 9.0));
+    }
+
+    #[test]
+    fn the_closest_point_on_a_sphere() {
+        let s = SphereBuilder::new().build();
+
+        assert_that!(s.closest_point(&Vector4::point(0.0, 0.0, -5.0)))
+            .is_equal_to(Vector4::point(0.0, 0.0, -1.0));
+        assert_that!(s.distance_to(&Vector4::point(0.0, 0.0, -5.0))).is_close_to(4.0, EPSILON);
+        assert_that!(s.distance_to(&Vector4::point(0.0, 0.5, 0.0))).is_close_to(0.5, EPSILON);
+    }
+
+    #[test]
+    fn the_closest_point_on_a_transformed_sphere() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(5.0, 0.0, 0.0) * Matrix::scaling(2.0, 2.0, 2.0))
+            .build();
+
+        assert_that!(s.closest_point(&Vector4::point(0.0, 0.0, 0.0)))
+            .is_equal_to(Vector4::point(3.0, 0.0, 0.0));
+        assert_that!(s.distance_to(&Vector4::point(0.0, 0.0, 0.0))).is_close_to(3.0, EPSILON);
+    }
+
+    #[test]
+    fn the_surface_area_of_a_sphere() {
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(1.0, 2.0, 3.0) * Matrix::scaling(2.0, 2.0, 2.0))
+            .build();
+
+        assert_that!(s.surface_area()).is_some().is_close_to(16.0 * PI, 0.0001);
+    }
+
+    #[test]
+    fn samples_lie_on_the_sphere_with_outward_normals() {
+        let centre = Vector4::point(1.0, 2.0, 3.0);
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::translation(1.0, 2.0, 3.0) * Matrix::scaling(2.0, 2.0, 2.0))
+            .build();
+        let mut rng = Rng::new(11);
+
+        for _ in 0..100 {
+            let (point, normal, pdf) = s.sample_surface(&mut rng).unwrap();
+
+            assert_that!((point - centre).magnitude()).is_close_to(2.0, 0.0001);
+            assert_that!(normal).is_equal_to((point - centre).normalize());
+            assert_that!(pdf).is_close_to(1.0 / (16.0 * PI), 0.0001);
+        }
+    }
+
+    #[test]
+    fn samples_cover_the_whole_sphere() {
+        let s = SphereBuilder::new().build();
+        let mut rng = Rng::new(12);
+
+        let (mut above, mut below) = (0, 0);
+        for _ in 0..1000 {
+            let (point, _, _) = s.sample_surface(&mut rng).unwrap();
+            if point.y > 0.0 {
+                above += 1;
+            } else {
+                below += 1;
+            }
+        }
+
+        assert_that!(above).is_greater_than(400);
+        assert_that!(below).is_greater_than(400);
+    }
+
+    #[test]
+    fn samples_of_a_stretched_sphere_are_thinner_where_it_is_stretched() {
+        // Twice as tall, so the band round the middle is stretched to twice its area and the
+        // poles not at all
+        let s = SphereBuilder::new()
+            .with_transform(Matrix::scaling(1.0, 2.0, 1.0))
+            .build();
+        let mut rng = Rng::new(13);
+
+        let mut total = 0.0;
+        let count = 20000;
+        for _ in 0..count {
+            let (point, _, pdf) = s.sample_surface(&mut rng).unwrap();
+            let up = point.y.abs() / 2.0;
+            let stretch = (4.0 * (1.0 - up * up) + up * up).sqrt();
+
+            assert_that!(pdf).is_close_to(1.0 / (4.0 * PI * stretch), 0.0001);
+            total += 1.0 / pdf;
+        }
+
+        // The density integrates to 1 over the surface, so this estimates the true area of the
+        // spheroid, about 21.48
+        assert_that!(total / count as f32).is_close_to(21.48, 0.2);
+    }
 }
@@ -0,0 +1,356 @@
+use crate::camera::{Camera, CameraBuilder};
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::matrix::Matrix;
+use crate::settings::RenderSettings;
+use crate::transform::Transform;
+use crate::world::World;
+
+/// A pair of cameras, one for each eye, for viewing a scene in 3D.
+///
+/// The eyes sit either side of where a single camera with the same transform would be and look
+/// the same way. Their views are shifted towards each other so that anything at the convergence
+/// distance lines up in both images; nearer things pop out of the screen and further things sink
+/// into it.
+pub struct StereoCamera {
+    left: Camera,
+    right: Camera,
+    hsize: usize,
+    vsize: usize,
+}
+
+/// How the two eyes are combined into one red/cyan image.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AnaglyphMethod {
+    /// Red from the left eye and green and blue from the right. Keeps the most colour, but
+    /// saturated reds and cyans show up in one eye only.
+    Color,
+    /// Each eye in greyscale. No colour, but no retinal rivalry either.
+    Gray,
+    /// Eric Dubois's least-squares projection, which keeps much of the colour with little ghosting
+    /// through typical red/cyan glasses.
+    #[default]
+    Dubois,
+}
+
+// Dubois's red/cyan matrices, one row per output channel
+const DUBOIS_LEFT: [[f32; 3]; 3] = [
+    [0.456, 0.500, 0.176],
+    [-0.040, -0.038, -0.016],
+    [-0.015, -0.021, -0.005],
+];
+const DUBOIS_RIGHT: [[f32; 3]; 3] = [
+    [-0.043, -0.088, -0.002],
+    [0.378, 0.734, -0.018],
+    [-0.072, -0.113, 1.226],
+];
+
+pub struct StereoCameraBuilder {
+    hsize: usize,
+    vsize: usize,
+    field_of_view: f32,
+    transform: Matrix<4>,
+    interpupillary_distance: f32,
+    convergence: f32,
+}
+
+impl StereoCamera {
+    pub fn left(&self) -> &Camera {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Camera {
+        &self.right
+    }
+
+    /// Render the left and right eyes separately.
+    pub fn render_eyes(&self, world: &World, settings: &RenderSettings) -> (Canvas, Canvas) {
+        (
+            self.left.render(world, settings),
+            self.right.render(world, settings),
+        )
+    }
+
+    /// Render both eyes into one canvas twice as wide, with the left eye on the left.
+    pub fn render(&self, world: &World, settings: &RenderSettings) -> Canvas {
+        let (left, right) = self.render_eyes(world, settings);
+        let mut image = Canvas::new(self.hsize * 2, self.vsize);
+        image.blit(&left, 0, 0);
+        image.blit(&right, self.hsize as isize, 0);
+
+        image
+    }
+
+    /// Render both eyes and merge them into a single red/cyan anaglyph.
+    pub fn render_anaglyph(
+        &self,
+        world: &World,
+        settings: &RenderSettings,
+        method: AnaglyphMethod,
+    ) -> Canvas {
+        let (left, right) = self.render_eyes(world, settings);
+
+        anaglyph(&left, &right, method)
+    }
+}
+
+/// Merge the left and right eyes of a stereo pair into one image for viewing through red/cyan
+/// glasses. Both canvases must be the same size.
+pub fn anaglyph(left: &Canvas, right: &Canvas, method: AnaglyphMethod) -> Canvas {
+    assert!(
+        left.width() == right.width() && left.height() == right.height(),
+        "The eyes must be the same size"
+    );
+
+    let mut image = Canvas::new(left.width(), left.height());
+    for y in 0..left.height() {
+        for x in 0..left.width() {
+            let (l, r) = (left.pixel_at(x, y), right.pixel_at(x, y));
+            let color = match method {
+                AnaglyphMethod::Color => Color::new(l.r, r.g, r.b),
+                AnaglyphMethod::Gray => Color::new(l.luminance(), r.luminance(), r.luminance()),
+                AnaglyphMethod::Dubois => {
+                    (apply(&DUBOIS_LEFT, l) + apply(&DUBOIS_RIGHT, r)).clamp(0.0, 1.0)
+                }
+            };
+            image.write_pixel(x, y, &color);
+            image.write_alpha(x, y, left.alpha_at(x, y).max(right.alpha_at(x, y)));
+        }
+    }
+
+    image
+}
+
+fn apply(matrix: &[[f32; 3]; 3], color: Color) -> Color {
+    let row = |i: usize| matrix[i][0] * color.r + matrix[i][1] * color.g + matrix[i][2] * color.b;
+
+    Color::new(row(0), row(1), row(2))
+}
+
+impl StereoCameraBuilder {
+    pub fn new() -> Self {
+        Self {
+            hsize: 0,
+            vsize: 0,
+            field_of_view: 0.0,
+            transform: Matrix::identity(),
+            interpupillary_distance: 0.064,
+            convergence: 5.0,
+        }
+    }
+
+    /// The width of each eye's image.
+    pub fn with_hsize(mut self, hsize: usize) -> Self {
+        self.hsize = hsize;
+        self
+    }
+
+    pub fn with_vsize(mut self, vsize: usize) -> Self {
+        self.vsize = vsize;
+        self
+    }
+
+    pub fn with_field_of_view(mut self, field_of_view: f32) -> Self {
+        self.field_of_view = field_of_view;
+        self
+    }
+
+    /// The view transform of a single camera between the eyes.
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// How far apart the eyes are, in scene units.
+    pub fn with_interpupillary_distance(mut self, distance: f32) -> Self {
+        self.interpupillary_distance = distance;
+        self
+    }
+
+    /// How far in front of the eyes things line up in both images, in scene units.
+    pub fn with_convergence(mut self, distance: f32) -> Self {
+        self.convergence = distance;
+        self
+    }
+
+    pub fn build(self) -> StereoCamera {
+        let camera = || {
+            CameraBuilder::new()
+                .with_hsize(self.hsize)
+                .with_vsize(self.vsize)
+                .with_field_of_view(self.field_of_view)
+        };
+        let view_width = camera().build().view_width();
+        let half_distance = self.interpupillary_distance / 2.0;
+        // How far each eye's view must slide so the two line up at the convergence distance
+        let shift = half_distance / self.convergence / view_width;
+
+        // The camera looks down -z, so +x is to its left
+        let eye = |side: f32| {
+            camera()
+                .with_transform(
+                    Matrix::translation(-side * half_distance, 0.0, 0.0) * self.transform,
+                )
+                .with_lens_shift(side * shift)
+                .build()
+        };
+
+        StereoCamera {
+            left: eye(1.0),
+            right: eye(-1.0),
+            hsize: self.hsize,
+            vsize: self.vsize,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::rc::Rc;
+
+    use spectral::prelude::*;
+
+    use crate::color::Color;
+    use crate::light::PointLight;
+    use crate::sphere::SphereBuilder;
+    use crate::vector4::Vector4;
+    use crate::world::WorldBuilder;
+
+    use super::*;
+
+    /// A world with a small sphere at `centre` and nothing else.
+    fn marker_world(centre: Vector4) -> World {
+        WorldBuilder::new()
+            .with_light(PointLight::new(
+                Vector4::point(-10.0, 10.0, -10.0),
+                Color::white(),
+            ))
+            .with_object(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(
+                        Matrix::translation(centre.x, centre.y, centre.z)
+                            * Matrix::scaling(0.05, 0.05, 0.05),
+                    )
+                    .build(),
+            ))
+            .build()
+    }
+
+    fn rig(interpupillary_distance: f32) -> StereoCamera {
+        StereoCameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 3.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, -5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .with_interpupillary_distance(interpupillary_distance)
+            .with_convergence(5.0)
+            .build()
+    }
+
+    #[test]
+    fn the_eyes_line_up_at_the_convergence_distance() {
+        let world = marker_world(Vector4::point(0.0, 0.0, 0.0));
+
+        let (left, right) = rig(0.5).render_eyes(&world, &RenderSettings::default());
+
+        assert_that!(left.alpha_at(5, 5)).is_equal_to(1.0);
+        assert_that!(right.alpha_at(5, 5)).is_equal_to(1.0);
+    }
+
+    #[test]
+    fn nearer_objects_appear_further_right_to_the_left_eye() {
+        let world = marker_world(Vector4::point(0.0, 0.0, -4.0));
+
+        let (left, right) = rig(0.5).render_eyes(&world, &RenderSettings::default());
+
+        assert_that!(left.alpha_at(5, 5)).is_equal_to(0.0);
+        assert_that!(right.alpha_at(5, 5)).is_equal_to(0.0);
+        assert_that!((6..11).any(|x| left.alpha_at(x, 5) > 0.0)).is_true();
+        assert_that!((0..5).any(|x| right.alpha_at(x, 5) > 0.0)).is_true();
+    }
+
+    #[test]
+    fn rendering_side_by_side() {
+        let world = marker_world(Vector4::point(0.0, 0.0, 0.0));
+
+        let image = rig(0.5).render(&world, &RenderSettings::default());
+
+        assert_that!(image.width()).is_equal_to(22);
+        assert_that!(image.height()).is_equal_to(11);
+        assert_that!(image.alpha_at(5, 5)).is_equal_to(1.0);
+        assert_that!(image.alpha_at(16, 5)).is_equal_to(1.0);
+        assert_that!(image.alpha_at(0, 0)).is_equal_to(0.0);
+    }
+
+    fn pair(left: Color, right: Color) -> (Canvas, Canvas) {
+        let (mut l, mut r) = (Canvas::new(1, 1), Canvas::new(1, 1));
+        l.fill(&left);
+        r.fill(&right);
+
+        (l, r)
+    }
+
+    #[test]
+    fn a_color_anaglyph_takes_red_from_the_left_eye_and_cyan_from_the_right() {
+        let (left, right) = pair(Color::new(0.2, 0.4, 0.6), Color::new(0.7, 0.8, 0.9));
+
+        let image = anaglyph(&left, &right, AnaglyphMethod::Color);
+
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(Color::new(0.2, 0.8, 0.9));
+    }
+
+    #[test]
+    fn a_gray_anaglyph_uses_the_luminance_of_each_eye() {
+        let (left, right) = pair(Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0));
+
+        let image = anaglyph(&left, &right, AnaglyphMethod::Gray);
+
+        let expected = Color::new(0.2126, 0.0722, 0.0722);
+        assert_that!(image.pixel_at(0, 0)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn a_dubois_anaglyph_keeps_white_white() {
+        let (left, right) = pair(Color::white(), Color::white());
+
+        let image = anaglyph(&left, &right, AnaglyphMethod::Dubois);
+
+        let pixel = image.pixel_at(0, 0);
+        assert_that!(pixel.r).is_close_to(1.0, 0.002);
+        assert_that!(pixel.g).is_close_to(1.0, 0.002);
+        assert_that!(pixel.b).is_close_to(1.0, 0.002);
+    }
+
+    #[test]
+    fn a_dubois_anaglyph_shows_the_left_eye_in_red() {
+        let (left, right) = pair(Color::white(), Color::black());
+
+        let image = anaglyph(&left, &right, AnaglyphMethod::Dubois);
+
+        let pixel = image.pixel_at(0, 0);
+        assert_that!(pixel.r).is_close_to(1.0, 0.0001);
+        assert_that!(pixel.g).is_equal_to(0.0);
+        assert_that!(pixel.b).is_equal_to(0.0);
+    }
+
+    #[test]
+    fn rendering_an_anaglyph() {
+        let world = marker_world(Vector4::point(0.0, 0.0, 0.0));
+
+        let image =
+            rig(0.5).render_anaglyph(&world, &RenderSettings::default(), AnaglyphMethod::Dubois);
+
+        assert_that!(image.width()).is_equal_to(11);
+        assert_that!(image.alpha_at(5, 5)).is_equal_to(1.0);
+        assert_that!(image.alpha_at(0, 0)).is_equal_to(0.0);
+    }
+}
@@ -0,0 +1,536 @@
+//! Support for testing the renderer as a whole.
+//!
+//! Golden image regression tests render small canonical scenes at low resolution and compare them
+//! against reference images kept in `tests/golden`, so that changes to the renderer's internals
+//! can't quietly change what it draws. When a change to the output is intended, run the tests
+//! with `UPDATE_GOLDEN=1` to write new reference images, and check them in along with the change.
+//!
+//! Property tests check that something holds for many random values, made by `Arbitrary`, rather
+//! than for a few picked by hand.
+
+use std::convert::TryFrom;
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::camera::{Camera, CameraBuilder};
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::cube::CubeBuilder;
+use crate::cylinder::CylinderBuilder;
+use crate::group::GroupBuilder;
+use crate::light::PointLight;
+use crate::material::MaterialBuilder;
+use crate::matrix::Matrix;
+use crate::pattern::MarblePattern;
+use crate::plane::PlaneBuilder;
+use crate::rng::Rng;
+use crate::settings::RenderSettings;
+use crate::sphere::SphereBuilder;
+use crate::transform::Transform;
+use crate::vector4::Vector4;
+use crate::world::{World, WorldBuilder};
+
+/// How far a render may stray from its reference image and still match it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// The most any channel of a pixel may differ by, out of 255, before the pixel counts as
+    /// different.
+    pub channel: u8,
+    /// How many pixels may be different.
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    /// Enough to absorb floating point differences between platforms, but not a real change.
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            pixels: 4,
+        }
+    }
+}
+
+/// An image as 8-bit sRGB, the way it is saved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<(u8, u8, u8)>,
+}
+
+impl Image {
+    pub fn from_canvas(canvas: &Canvas) -> Self {
+        let pixels = (0..canvas.height())
+            .flat_map(|y| (0..canvas.width()).map(move |x| (x, y)))
+            .map(|(x, y)| canvas.pixel_at(x, y).to_srgb_u8())
+            .collect();
+
+        Self {
+            width: canvas.width(),
+            height: canvas.height(),
+            pixels,
+        }
+    }
+
+    /// Read a plain PPM, as written by `Canvas::save`.
+    pub fn parse_ppm(text: &str) -> Result<Self, String> {
+        let mut tokens = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(str::split_whitespace);
+        if tokens.next() != Some("P3") {
+            return Err("not a plain PPM".to_string());
+        }
+        let mut next = || -> Result<usize, String> {
+            let token = tokens.next().ok_or("the file ends too soon")?;
+            token
+                .parse()
+                .map_err(|_| format!("{} is not a number", token))
+        };
+        let (width, height, max) = (next()?, next()?, next()?);
+        if max != 255 {
+            return Err("only 8-bit images are supported".to_string());
+        }
+        let count = width.checked_mul(height).ok_or("the image is too big")?;
+        let mut channel = || -> Result<u8, String> {
+            let value = next()?;
+            u8::try_from(value).map_err(|_| format!("{} is more than 255", value))
+        };
+        let pixels = (0..count)
+            .map(|_| Ok((channel()?, channel()?, channel()?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// How many pixels differ from `other` by more than `channel` in any channel. Images of
+    /// different sizes differ in every pixel.
+    pub fn differing_pixels(&self, other: &Image, channel: u8) -> usize {
+        if (self.width, self.height) != (other.width, other.height) {
+            return self.pixels.len().max(other.pixels.len());
+        }
+
+        let differs = |a: u8, b: u8| a.abs_diff(b) > channel;
+        self.pixels
+            .iter()
+            .zip(&other.pixels)
+            .filter(|(a, b)| differs(a.0, b.0) || differs(a.1, b.1) || differs(a.2, b.2))
+            .count()
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.ppm", name))
+}
+
+/// Check `canvas` against the reference image called `name`, or replace the reference with it
+/// when `UPDATE_GOLDEN` is set.
+pub fn assert_matches_golden(name: &str, canvas: &Canvas, tolerance: Tolerance) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        canvas.save(&mut File::create(&path).unwrap()).unwrap();
+        return;
+    }
+
+    let mut text = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .unwrap_or_else(|e| {
+            panic!(
+                "Can't read {}: {}. Run with UPDATE_GOLDEN=1 to create it",
+                path.display(),
+                e
+            )
+        });
+    let reference = Image::parse_ppm(&text).unwrap();
+    let differing = Image::from_canvas(canvas).differing_pixels(&reference, tolerance.channel);
+
+    assert!(
+        differing <= tolerance.pixels,
+        "{} pixels differ from {}, more than the {} allowed",
+        differing,
+        path.display(),
+        tolerance.pixels
+    );
+}
+
+/// How many random values a property is checked against.
+const CASES: usize = 256;
+
+/// Something that can be made at random for a property test.
+///
+/// Values are kept to ranges where single precision arithmetic stays accurate, so that properties
+/// can be checked to a fixed tolerance.
+pub trait Arbitrary: Sized {
+    fn arbitrary(rng: &mut Rng) -> Self;
+}
+
+/// A number uniformly distributed in [min, max).
+pub fn between(rng: &mut Rng, min: f32, max: f32) -> f32 {
+    min + rng.next_f32() * (max - min)
+}
+
+impl Arbitrary for f32 {
+    fn arbitrary(rng: &mut Rng) -> Self {
+        between(rng, -10.0, 10.0)
+    }
+}
+
+impl<A: Arbitrary, B: Arbitrary> Arbitrary for (A, B) {
+    fn arbitrary(rng: &mut Rng) -> Self {
+        (A::arbitrary(rng), B::arbitrary(rng))
+    }
+}
+
+/// A vector of up to about 17 long that is not too short to normalize accurately.
+impl Arbitrary for Vector4 {
+    fn arbitrary(rng: &mut Rng) -> Self {
+        loop {
+            let v = Vector4::vector(
+                f32::arbitrary(rng),
+                f32::arbitrary(rng),
+                f32::arbitrary(rng),
+            );
+            if v.magnitude() > 0.1 {
+                return v;
+            }
+        }
+    }
+}
+
+/// A point within 10 of the origin along each axis.
+#[derive(Clone, Copy, Debug)]
+pub struct ArbitraryPoint(pub Vector4);
+
+impl Arbitrary for ArbitraryPoint {
+    fn arbitrary(rng: &mut Rng) -> Self {
+        Self(Vector4::point(
+            f32::arbitrary(rng),
+            f32::arbitrary(rng),
+            f32::arbitrary(rng),
+        ))
+    }
+}
+
+/// A well-conditioned matrix, with entries from -2 to 2 and a determinant of at least 1.
+impl Arbitrary for Matrix<4> {
+    fn arbitrary(rng: &mut Rng) -> Self {
+        loop {
+            let mut m = Matrix::<4>::new();
+            for row in 0..4 {
+                for col in 0..4 {
+                    m[[row, col]] = between(rng, -2.0, 2.0);
+                }
+            }
+            if m.determinant().abs() >= 1.0 {
+                return m;
+            }
+        }
+    }
+}
+
+/// A chain of up to four translations, scalings, rotations and shears, like an object's
+/// transform in a scene.
+#[derive(Clone, Copy, Debug)]
+pub struct ArbitraryTransform(pub Matrix<4>);
+
+impl Arbitrary for ArbitraryTransform {
+    fn arbitrary(rng: &mut Rng) -> Self {
+        let steps = 1 + rng.next_u32() % 4;
+        let mut transform = Matrix::identity();
+        for _ in 0..steps {
+            let kind = rng.next_u32() % 6;
+            let angle = between(rng, -PI, PI);
+            // Scales big enough to undo accurately, and sometimes mirrored
+            let scales: Vec<f32> = (0..3)
+                .map(|_| between(rng, 0.5, 2.0) * if rng.next_f32() < 0.5 { -1.0 } else { 1.0 })
+                .collect();
+            let shears: Vec<f32> = (0..6).map(|_| between(rng, -0.5, 0.5)).collect();
+            let step = match kind {
+                0 => Matrix::translation(
+                    f32::arbitrary(rng),
+                    f32::arbitrary(rng),
+                    f32::arbitrary(rng),
+                ),
+                1 => Matrix::scaling(scales[0], scales[1], scales[2]),
+                2 => Matrix::rotation_x(angle),
+                3 => Matrix::rotation_y(angle),
+                4 => Matrix::rotation_z(angle),
+                _ => Matrix::shearing(
+                    shears[0], shears[1], shears[2], shears[3], shears[4], shears[5],
+                ),
+            };
+            transform = step * transform;
+        }
+
+        Self(transform)
+    }
+}
+
+/// Check that `property` holds for many random values, panicking with the first that it doesn't
+/// hold for. The values are the same on every run, so failures can be reproduced.
+pub fn for_all<T: Arbitrary + Debug>(property: impl Fn(&T) -> bool) {
+    let mut rng = Rng::new(0x5eed);
+    for case in 0..CASES {
+        let value = T::arbitrary(&mut rng);
+        assert!(
+            property(&value),
+            "The property doesn't hold for case {}: {:?}",
+            case,
+            value
+        );
+    }
+}
+
+/// Random corruptions of `text`, for checking that a parser turns malformed input into errors
+/// rather than panicking. Each has a few characters replaced, removed or added, or a number
+/// swapped for one that is huge, negative or not a number at all.
+pub fn mutations(text: &str, count: usize) -> Vec<String> {
+    const REPLACEMENTS: [&str; 8] = ["", "-1", "0", "1e30", "NaN", "inf", "99999999999", "x"];
+    let mut rng = Rng::new(0xf022);
+    let pick = |rng: &mut Rng, n: usize| rng.next_u32() as usize % n.max(1);
+
+    (0..count)
+        .map(|_| {
+            let mut chars: Vec<char> = text.chars().collect();
+            for _ in 0..1 + pick(&mut rng, 3) {
+                let at = pick(&mut rng, chars.len());
+                match pick(&mut rng, 4) {
+                    0 if !chars.is_empty() => {
+                        chars.remove(at);
+                    }
+                    1 => chars.insert(at, (b' ' + pick(&mut rng, 95) as u8) as char),
+                    2 if !chars.is_empty() => chars[at] = (b'0' + pick(&mut rng, 10) as u8) as char,
+                    _ => {
+                        let replacement = REPLACEMENTS[pick(&mut rng, REPLACEMENTS.len())];
+                        let end = (at..chars.len())
+                            .find(|&i| chars[i].is_whitespace())
+                            .unwrap_or(chars.len());
+                        chars.splice(at..end, replacement.chars());
+                    }
+                }
+            }
+            chars.into_iter().collect()
+        })
+        .collect()
+}
+
+/// A small camera looking at the origin from `from`.
+fn camera(from: Vector4) -> Camera {
+    CameraBuilder::new()
+        .with_hsize(32)
+        .with_vsize(24)
+        .with_field_of_view(PI / 3.0)
+        .with_transform(Matrix::view_transform(
+            from,
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        ))
+        .build()
+}
+
+/// The book's default world: two concentric spheres.
+fn default_world() -> (World, Camera) {
+    (
+        World::default_world(),
+        camera(Vector4::point(0.0, 1.5, -5.0)),
+    )
+}
+
+/// A glass ball in front of a mirrored one, over a reflective floor.
+fn reflection_and_refraction() -> (World, Camera) {
+    let floor = PlaneBuilder::new()
+        .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+        .with_material(
+            MaterialBuilder::new()
+                .with_color(Color::new(0.8, 0.8, 0.6))
+                .with_reflective(0.3)
+                .build(),
+        )
+        .build();
+    let mirror = SphereBuilder::new()
+        .with_transform(Matrix::translation(1.0, 0.0, 2.0))
+        .with_material(
+            MaterialBuilder::new()
+                .with_color(Color::new(0.1, 0.1, 0.1))
+                .with_reflective(0.9)
+                .build(),
+        )
+        .build();
+    let glass = SphereBuilder::glass()
+        .with_transform(Matrix::translation(-0.5, -0.3, 0.0) * Matrix::scaling(0.7, 0.7, 0.7))
+        .build();
+
+    let world = WorldBuilder::new()
+        .with_light(PointLight::new(
+            Vector4::point(-10.0, 10.0, -10.0),
+            Color::white(),
+        ))
+        .with_object(Rc::new(floor))
+        .with_object(Rc::new(mirror))
+        .with_object(Rc::new(glass))
+        .build();
+
+    (world, camera(Vector4::point(0.0, 1.0, -5.0)))
+}
+
+/// A transformed group of a marbled cube and a closed cylinder.
+fn patterned_group() -> (World, Camera) {
+    let marble = MaterialBuilder::new()
+        .with_pattern(Rc::new(
+            MarblePattern::new(Color::new(0.9, 0.9, 0.85), Color::new(0.2, 0.2, 0.3))
+                .with_scale(2.0),
+        ))
+        .build();
+    let cube = CubeBuilder::new()
+        .with_transform(Matrix::translation(-1.0, 0.0, 0.0) * Matrix::scaling(0.6, 0.6, 0.6))
+        .with_material(marble)
+        .build();
+    let cylinder = CylinderBuilder::new()
+        .with_minimum(-0.6)
+        .with_maximum(0.6)
+        .with_closed(true)
+        .with_transform(Matrix::translation(1.0, 0.0, 0.0) * Matrix::scaling(0.6, 1.0, 0.6))
+        .with_material(
+            MaterialBuilder::new()
+                .with_color(Color::new(0.2, 0.5, 0.9))
+                .build(),
+        )
+        .build();
+    let group = GroupBuilder::new()
+        .with_transform(Matrix::rotation_y(PI / 6.0))
+        .with_child(Rc::new(cube))
+        .with_child(Rc::new(cylinder))
+        .build();
+
+    let world = WorldBuilder::new()
+        .with_light(PointLight::new(
+            Vector4::point(-5.0, 8.0, -10.0),
+            Color::white(),
+        ))
+        .with_object(Rc::new(group))
+        .build();
+
+    (world, camera(Vector4::point(0.0, 2.0, -5.0)))
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("default_world", default_world())]
+    #[case("reflection_and_refraction", reflection_and_refraction())]
+    #[case("patterned_group", patterned_group())]
+    fn renders_match_their_golden_images(#[case] name: &str, #[case] scene: (World, Camera)) {
+        let (world, camera) = scene;
+
+        let image = camera.render(&world, &RenderSettings::default());
+
+        assert_matches_golden(name, &image, Tolerance::default());
+    }
+
+    #[test]
+    fn a_saved_canvas_reads_back_the_same() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(0, 0, &Color::new(1.0, 0.5, 0.0));
+        canvas.write_pixel(2, 1, &Color::new(0.1, 0.2, 0.3));
+        let mut saved = Vec::new();
+        canvas.save(&mut saved).unwrap();
+
+        let image = Image::parse_ppm(&String::from_utf8(saved).unwrap()).unwrap();
+
+        assert_that!(image).is_equal_to(Image::from_canvas(&canvas));
+    }
+
+    #[rstest]
+    #[case("P6\n1 1\n255\n0 0 0")]
+    #[case("P3\n1 1\n65535\n0 0 0")]
+    #[case("P3\n2 1\n255\n0 0 0")]
+    #[case("P3\n1 1\n255\n0 x 0")]
+    fn reading_a_bad_ppm_fails(#[case] text: &str) {
+        assert_that!(Image::parse_ppm(text)).is_err();
+    }
+
+    #[rstest]
+    #[case(Color::new(0.5, 0.5, 0.5), 0, 0)]
+    #[case(Color::new(0.5, 0.52, 0.5), 0, 1)]
+    #[case(Color::new(0.5, 0.52, 0.5), 10, 0)]
+    #[case(Color::new(0.6, 0.5, 0.5), 2, 1)]
+    fn counting_differing_pixels(
+        #[case] color: Color,
+        #[case] channel: u8,
+        #[case] expected: usize,
+    ) {
+        let mut a = Canvas::new(2, 2);
+        let mut b = Canvas::new(2, 2);
+        a.fill(&Color::new(0.5, 0.5, 0.5));
+        b.fill(&Color::new(0.5, 0.5, 0.5));
+        b.write_pixel(1, 1, &color);
+
+        let differing = Image::from_canvas(&a).differing_pixels(&Image::from_canvas(&b), channel);
+
+        assert_that!(differing).is_equal_to(expected);
+    }
+
+    #[test]
+    fn images_of_different_sizes_differ_everywhere() {
+        let a = Image::from_canvas(&Canvas::new(2, 2));
+        let b = Image::from_canvas(&Canvas::new(3, 2));
+
+        assert_that!(a.differing_pixels(&b, 255)).is_equal_to(6);
+    }
+
+    #[test]
+    fn arbitrary_values_are_in_range() {
+        for_all(|&(x, v): &(f32, Vector4)| {
+            (-10.0..10.0).contains(&x) && v.is_vector() && v.magnitude() > 0.1
+        });
+        for_all(|m: &Matrix<4>| m.determinant().abs() >= 1.0);
+        for_all(|p: &ArbitraryPoint| p.0.is_point());
+    }
+
+    #[test]
+    #[should_panic(expected = "The property doesn't hold for case 0")]
+    fn a_property_that_fails_panics() {
+        for_all(|_: &f32| false);
+    }
+
+    #[test]
+    fn reading_a_corrupted_ppm_never_panics() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.fill(&Color::new(0.2, 0.4, 0.6));
+        let mut saved = Vec::new();
+        canvas.save(&mut saved).unwrap();
+
+        for text in mutations(&String::from_utf8(saved).unwrap(), 2000) {
+            let _ = Image::parse_ppm(&text);
+        }
+    }
+
+    #[rstest]
+    #[case("P3\n99999999999 99999999999\n255\n0 0 0")]
+    #[case("P3\n1 1\n255\n0 256 0")]
+    fn reading_an_impossible_ppm_fails(#[case] text: &str) {
+        assert_that!(Image::parse_ppm(text)).is_err();
+    }
+}
@@ -0,0 +1,184 @@
+//! Timed spans around the stages of a render, written to standard error as `key=value` lines
+//! when the `RAY_TRACER_LOG` environment variable is set, so slow stages can be found without
+//! adding prints. Spans are only logged when the crate is built with the `timing` feature, and
+//! cost nothing without it.
+//!
+//! Spans are made the way the `tracing` crate makes them, as in
+//! `info_span!("render.row", y = y).entered()`, with `%` in front of values that are only
+//! `Display`, and `Span::record` to add fields once the work is done, so that call sites can
+//! move over to `tracing` as they are.
+
+#[cfg(feature = "timing")]
+use std::cell::RefCell;
+use std::fmt::Display;
+#[cfg(feature = "timing")]
+use std::sync::OnceLock;
+#[cfg(feature = "timing")]
+use std::time::{Duration, Instant};
+
+/// A stage of the work, logged with how long it took when it is dropped.
+#[cfg(feature = "timing")]
+pub struct Span {
+    name: &'static str,
+    fields: RefCell<Vec<(&'static str, String)>>,
+    start: Instant,
+}
+
+/// A stage of the work. Nothing is logged without the `timing` feature.
+#[cfg(not(feature = "timing"))]
+pub struct Span;
+
+/// Start a span called `name`, with fields given as `key = value`, or `key = %value` for values
+/// that are only `Display`.
+#[macro_export]
+macro_rules! info_span {
+    ($name:expr $(,)?) => {
+        $crate::timing::span($name)
+    };
+    ($name:expr, $($fields:tt)+) => {
+        $crate::__span_fields!($crate::timing::span($name), $($fields)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __span_fields {
+    ($span:expr, $key:ident = % $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__span_fields!($span.with(stringify!($key), &$value) $(, $($rest)*)?)
+    };
+    ($span:expr, $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__span_fields!($span.with(stringify!($key), &$value) $(, $($rest)*)?)
+    };
+    ($span:expr $(,)?) => {
+        $span
+    };
+}
+
+/// Whether spans are being logged, decided once from the environment.
+pub fn enabled() -> bool {
+    #[cfg(feature = "timing")]
+    {
+        static ENABLED: OnceLock<bool> = OnceLock::new();
+        *ENABLED.get_or_init(|| std::env::var_os("RAY_TRACER_LOG").is_some_and(|v| v != "0"))
+    }
+    #[cfg(not(feature = "timing"))]
+    false
+}
+
+/// Start timing the stage called `name`. `info_span!` is the usual way in.
+#[cfg(feature = "timing")]
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        fields: RefCell::new(Vec::new()),
+        start: Instant::now(),
+    }
+}
+
+#[cfg(not(feature = "timing"))]
+pub fn span(_name: &'static str) -> Span {
+    Span
+}
+
+impl Span {
+    /// Add a field to the line logged for the span. Nothing is formatted unless logging is on.
+    pub fn with(self, key: &'static str, value: impl Display) -> Self {
+        self.record(key, value);
+
+        self
+    }
+
+    /// Add a field found out part way through the stage, such as how much work it turned out to
+    /// be.
+    #[allow(unused_variables)]
+    pub fn record(&self, key: &'static str, value: impl Display) {
+        #[cfg(feature = "timing")]
+        if enabled() {
+            self.fields.borrow_mut().push((key, value.to_string()));
+        }
+    }
+
+    /// The span, timing from when it was made until it is dropped.
+    pub fn entered(self) -> Self {
+        self
+    }
+}
+
+#[cfg(feature = "timing")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        if enabled() {
+            eprintln!(
+                "{}",
+                event(self.name, &self.fields.borrow(), self.start.elapsed())
+            );
+        }
+    }
+}
+
+/// The line logged for a finished span, as in `span=render.row y=3 elapsed_ms=1.250`.
+#[cfg(feature = "timing")]
+fn event(name: &str, fields: &[(&'static str, String)], elapsed: Duration) -> String {
+    let mut line = format!("span={}", name);
+    for (key, value) in fields {
+        if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+            line += &format!(" {}={:?}", key, value);
+        } else {
+            line += &format!(" {}={}", key, value);
+        }
+    }
+    line += &format!(" elapsed_ms={:.3}", elapsed.as_secs_f64() * 1000.0);
+
+    line
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(all(test, feature = "timing"))]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn an_event_lists_the_fields_and_the_time_taken() {
+        let fields = vec![("y", "3".to_string()), ("width", "640".to_string())];
+
+        let line = event("render.row", &fields, Duration::from_micros(1250));
+
+        assert_that!(line)
+            .is_equal_to("span=render.row y=3 width=640 elapsed_ms=1.250".to_string());
+    }
+
+    #[test]
+    fn values_with_spaces_are_quoted() {
+        let fields = vec![
+            ("path", "/tmp/my scene.ppm".to_string()),
+            ("name", String::new()),
+        ];
+
+        let line = event("output", &fields, Duration::ZERO);
+
+        assert_that!(line).is_equal_to(
+            "span=output path=\"/tmp/my scene.ppm\" name=\"\" elapsed_ms=0.000".to_string(),
+        );
+    }
+
+    #[test]
+    fn fields_are_given_in_the_form_tracing_takes() {
+        let path = std::path::Path::new("/tmp/a.ppm");
+
+        let span = crate::info_span!("output", frame = 3, path = %path.display(), ).entered();
+
+        assert_that!(span.name).is_equal_to("output");
+        // Fields are only kept while logging is on
+        if enabled() {
+            assert_that!(span.fields.borrow().clone()).is_equal_to(vec![
+                ("frame", "3".to_string()),
+                ("path", "/tmp/a.ppm".to_string()),
+            ]);
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! A record of every ray followed to colour one pixel, for tracking down shading bugs.
+
+use std::fmt::{Display, Formatter};
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::settings::RenderSettings;
+use crate::vector4::Vector4;
+use crate::world::World;
+
+/// Why a ray was cast.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayKind {
+    Primary,
+    Reflection,
+    Refraction,
+}
+
+/// What one light adds at a hit, found with a shadow ray towards it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightTerm {
+    /// The light's index in the world.
+    pub light: usize,
+    /// Whether something lies between the hit and the light.
+    pub blocked: bool,
+    /// The light's share of the surface colour, once shadowing is taken into account.
+    pub contribution: Color,
+}
+
+/// Where a ray struck a surface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub t: f32,
+    pub point: Vector4,
+    pub normal: Vector4,
+    /// The name of the shape that was hit, if it has one.
+    pub object: Option<String>,
+    /// Whether the ray was inside the shape when it hit.
+    pub inside: bool,
+    pub lights: Vec<LightTerm>,
+}
+
+/// One ray, what it hit, the colour it brought back and the rays cast from its hit.
+///
+/// Colours are those `World::color_at_depth` gives; Russian roulette is not applied to the
+/// secondary rays here, and dispersive materials are followed at their mean index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RayTrace {
+    pub kind: RayKind,
+    pub origin: Vector4,
+    pub direction: Vector4,
+    /// Every place the ray crosses a surface, behind its origin too, with the name of the shape.
+    pub intersections: Vec<(f32, Option<String>)>,
+    pub hit: Option<Hit>,
+    pub color: Color,
+    pub children: Vec<RayTrace>,
+}
+
+/// Follow the ray through the middle of pixel `(px, py)` and everything it leads to.
+pub fn trace_pixel(
+    world: &World,
+    camera: &Camera,
+    px: usize,
+    py: usize,
+    settings: &RenderSettings,
+) -> RayTrace {
+    let ray = camera.ray_for_pixel(px, py);
+
+    trace(world, &ray, RayKind::Primary, settings, settings.max_depth)
+}
+
+fn trace(
+    world: &World,
+    ray: &Ray,
+    kind: RayKind,
+    settings: &RenderSettings,
+    remaining: usize,
+) -> RayTrace {
+    let color = world.color_at_depth(ray, settings, remaining);
+    let intersections = world.intersect(ray);
+    let mut node = RayTrace {
+        kind,
+        origin: ray.origin,
+        direction: ray.direction,
+        intersections: intersections
+            .iter()
+            .map(|i| (i.t, i.object.name().map(str::to_string)))
+            .collect(),
+        hit: None,
+        color,
+        children: Vec::new(),
+    };
+
+    let hit = match intersections.hit() {
+        Some(hit) => hit,
+        None => return node,
+    };
+    let comps = hit.prepare_computations_biased(ray, &intersections, &settings.shadow_bias);
+    let links = world.light_links_of(&comps.object);
+    let lights = world
+        .lights()
+        .iter()
+        .enumerate()
+        .filter(|(_, light)| light.groups & links != 0)
+        .map(|(index, light)| {
+            let blocked = world.is_shadowed(light, &comps.over_point);
+            LightTerm {
+                light: index,
+                blocked,
+                contribution: comps.object.lighting(
+                    light,
+                    comps.point,
+                    comps.eye_vector,
+                    comps.normal_vector,
+                    blocked,
+                ),
+            }
+        })
+        .collect();
+    node.hit = Some(Hit {
+        t: comps.t,
+        point: comps.point,
+        normal: comps.normal_vector,
+        object: comps.object.name().map(str::to_string),
+        inside: comps.inside,
+        lights,
+    });
+    if remaining == 0 {
+        return node;
+    }
+
+    let material = comps.object.material();
+    if material.reflective() > 0.0 {
+        let reflect_ray = Ray::new(comps.over_point, comps.reflect_vector);
+        node.children.push(trace(
+            world,
+            &reflect_ray,
+            RayKind::Reflection,
+            settings,
+            remaining - 1,
+        ));
+    }
+    if material.transparency() > 0.0 {
+        let (n1, n2) = match comps.dispersion {
+            None => (comps.n1, comps.n2),
+            Some(indices) => {
+                let mean = |f: fn(&(f32, f32)) -> f32| indices.iter().map(f).sum::<f32>() / 3.0;
+                (mean(|i| i.0), mean(|i| i.1))
+            }
+        };
+        // Snell's law, leaving out rays that are totally internally reflected
+        let n_ratio = n1 / n2;
+        let cos_i = comps.eye_vector.dot(&comps.normal_vector);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+        if sin2_t <= 1.0 {
+            let cos_t = (1.0 - sin2_t).sqrt();
+            let direction =
+                comps.normal_vector * (n_ratio * cos_i - cos_t) - comps.eye_vector * n_ratio;
+            let refract_ray = Ray::new(comps.under_point, direction);
+            node.children.push(trace(
+                world,
+                &refract_ray,
+                RayKind::Refraction,
+                settings,
+                remaining - 1,
+            ));
+        }
+    }
+
+    node
+}
+
+impl RayTrace {
+    /// How many rays the trace holds, this one included.
+    pub fn ray_count(&self) -> usize {
+        1 + self.children.iter().map(RayTrace::ray_count).sum::<usize>()
+    }
+
+    fn write(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "  ".repeat(depth);
+        let (o, d, c) = (self.origin, self.direction, self.color);
+        writeln!(
+            f,
+            "{}{:?} from ({:.3}, {:.3}, {:.3}) along ({:.3}, {:.3}, {:.3}) -> colour ({:.3}, \
+             {:.3}, {:.3})",
+            indent, self.kind, o.x, o.y, o.z, d.x, d.y, d.z, c.r, c.g, c.b
+        )?;
+        if !self.intersections.is_empty() {
+            let crossings: Vec<String> = self
+                .intersections
+                .iter()
+                .map(|(t, name)| format!("{:.3} {}", t, name.as_deref().unwrap_or("(unnamed)")))
+                .collect();
+            writeln!(f, "{}  crosses {}", indent, crossings.join(", "))?;
+        }
+        match &self.hit {
+            None => writeln!(f, "{}  miss", indent)?,
+            Some(hit) => {
+                let (p, n) = (hit.point, hit.normal);
+                writeln!(
+                    f,
+                    "{}  hit {} at t {:.3}, point ({:.3}, {:.3}, {:.3}), normal ({:.3}, {:.3}, \
+                     {:.3}){}",
+                    indent,
+                    hit.object.as_deref().unwrap_or("(unnamed)"),
+                    hit.t,
+                    p.x,
+                    p.y,
+                    p.z,
+                    n.x,
+                    n.y,
+                    n.z,
+                    if hit.inside { ", inside" } else { "" }
+                )?;
+                for term in &hit.lights {
+                    let state = if term.blocked { "blocked" } else { "clear" };
+                    let c = term.contribution;
+                    writeln!(
+                        f,
+                        "{}  light {}: {}, adds ({:.3}, {:.3}, {:.3})",
+                        indent, term.light, state, c.r, c.g, c.b
+                    )?;
+                }
+            }
+        }
+        for child in &self.children {
+            child.write(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for RayTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::rc::Rc;
+
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::camera::CameraBuilder;
+    use crate::light::PointLight;
+    use crate::material::MaterialBuilder;
+    use crate::matrix::Matrix;
+    use crate::plane::PlaneBuilder;
+    use crate::sphere::SphereBuilder;
+    use crate::transform::Transform;
+    use crate::world::WorldBuilder;
+
+    #[fixture]
+    fn camera() -> Camera {
+        CameraBuilder::new()
+            .with_hsize(11)
+            .with_vsize(11)
+            .with_field_of_view(PI / 2.0)
+            .with_transform(Matrix::view_transform(
+                Vector4::point(0.0, 0.0, -5.0),
+                Vector4::point(0.0, 0.0, 0.0),
+                Vector4::vector(0.0, 1.0, 0.0),
+            ))
+            .build()
+    }
+
+    #[rstest]
+    fn tracing_a_pixel_matches_the_render(camera: Camera) {
+        let world = World::default_world();
+        let settings = RenderSettings::default();
+
+        let trace = trace_pixel(&world, &camera, 5, 5, &settings);
+
+        let expected = world.color_at_with(&camera.ray_for_pixel(5, 5), &settings);
+        assert_that!(trace.kind).is_equal_to(RayKind::Primary);
+        assert_that!(trace.color).is_equal_to(expected);
+        let hit = trace.hit.as_ref().unwrap();
+        assert_that!(hit.t).is_close_to(4.0, 0.0001);
+        assert_that!(hit.normal).is_equal_to(Vector4::vector(0.0, 0.0, -1.0));
+        assert_that!(hit.lights.len()).is_equal_to(1);
+        assert_that!(hit.lights[0].light).is_equal_to(0);
+        assert_that!(hit.lights[0].blocked).is_false();
+        assert_that!(hit.lights[0].contribution).is_equal_to(trace.color);
+        let crossings: Vec<f32> = trace.intersections.iter().map(|(t, _)| *t).collect();
+        assert_that!(crossings.len()).is_equal_to(4);
+        assert_that!(crossings[0]).is_close_to(4.0, 0.0001);
+        assert_that!(trace.ray_count()).is_equal_to(1);
+    }
+
+    #[rstest]
+    fn a_pixel_that_misses_everything(camera: Camera) {
+        let world = WorldBuilder::new().build();
+
+        let trace = trace_pixel(&world, &camera, 0, 0, &RenderSettings::default());
+
+        assert_that!(trace.hit).is_none();
+        assert_that!(trace.to_string()).contains("  miss");
+    }
+
+    #[rstest]
+    fn glass_casts_reflected_and_refracted_rays(camera: Camera) {
+        let glass = SphereBuilder::new()
+            .with_name("glass")
+            .with_material(
+                MaterialBuilder::new()
+                    .with_reflective(0.5)
+                    .with_transparency(0.9)
+                    .with_refractive_index(1.5)
+                    .build(),
+            )
+            .build();
+        let floor = PlaneBuilder::new()
+            .with_name("floor")
+            .with_transform(Matrix::translation(0.0, 0.0, 3.0) * Matrix::rotation_x(PI / 2.0))
+            .build();
+        let world = WorldBuilder::new()
+            .with_light(PointLight::new(
+                Vector4::point(0.0, 0.0, -10.0),
+                Color::new(1.0, 1.0, 1.0),
+            ))
+            .with_object(Rc::new(glass))
+            .with_object(Rc::new(floor))
+            .build();
+        let settings = RenderSettings::default();
+
+        let trace = trace_pixel(&world, &camera, 5, 5, &settings);
+
+        let kinds: Vec<RayKind> = trace.children.iter().map(|c| c.kind).collect();
+        assert_that!(kinds).is_equal_to(vec![RayKind::Reflection, RayKind::Refraction]);
+        // The refracted ray goes straight through the middle and out the far side
+        let inner = &trace.children[1];
+        assert_that!(inner.hit.as_ref().unwrap().inside).is_true();
+        assert_that!(inner.hit.as_ref().unwrap().t).is_close_to(2.0, 0.001);
+        assert_that!(trace.ray_count()).is_greater_than(3);
+        assert_that!(trace.to_string()).contains("  Refraction from (0.000, 0.000, -1.000) along");
+        assert_that!(trace.to_string()).contains("hit glass at t 4.000");
+    }
+}
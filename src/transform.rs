@@ -1,3 +1,6 @@
+use std::fmt::{Display, Formatter};
+
+use crate::consts::EPSILON;
 use crate::matrix::Matrix;
 use crate::vector4::Vector4;
 
@@ -8,7 +11,63 @@ pub trait Transform {
     fn rotation_y(r: f32) -> Matrix<4>;
     fn rotation_z(r: f32) -> Matrix<4>;
     fn shearing(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Matrix<4>;
+    /// Panics if the view is degenerate; see `try_view_transform`.
     fn view_transform(from: Vector4, to: Vector4, up: Vector4) -> Matrix<4>;
+    /// A transform for looking from `from` towards `to` with `up` roughly upwards. If `up` is
+    /// parallel to the line of sight, an arbitrary but consistent up direction is used instead.
+    /// Fails if `from` and `to` are the same point or any input isn't finite.
+    fn try_view_transform(
+        from: Vector4,
+        to: Vector4,
+        up: Vector4,
+    ) -> Result<Matrix<4>, DegenerateViewError>;
+    /// Split the transform into a scaling, then a rotation, then a translation.
+    fn decompose(&self) -> Decomposition;
+}
+
+/// A transform split into the steps that make it up, for showing to people.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vector4,
+    /// Rotations about x, then y, then z, in radians.
+    pub rotation: Vector4,
+    /// Negative along x if the transform mirrors.
+    pub scale: Vector4,
+    /// Whether there is shearing too, which the other steps leave out.
+    pub sheared: bool,
+}
+
+impl Display for Decomposition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Adding zero turns -0.0 into 0.0, which reads better
+        let (t, r, s) = (self.translation, self.rotation, self.scale);
+        let degrees = |radians: f32| radians.to_degrees() + 0.0;
+        write!(f, "translate ({:.2}, {:.2}, {:.2})", t.x + 0.0, t.y + 0.0, t.z + 0.0)?;
+        write!(
+            f,
+            " rotate ({:.1}°, {:.1}°, {:.1}°)",
+            degrees(r.x),
+            degrees(r.y),
+            degrees(r.z)
+        )?;
+        write!(f, " scale ({:.2}, {:.2}, {:.2})", s.x + 0.0, s.y + 0.0, s.z + 0.0)?;
+        if self.sheared {
+            write!(f, " sheared")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DegenerateViewError;
+
+impl std::error::Error for DegenerateViewError {}
+
+impl Display for DegenerateViewError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the view has no direction")
+    }
 }
 
 impl Transform for Matrix<4> {
@@ -67,9 +126,29 @@ impl Transform for Matrix<4> {
     }
 
     fn view_transform(from: Vector4, to: Vector4, up: Vector4) -> Matrix<4> {
+        Self::try_view_transform(from, to, up).expect("Can't look from a point to itself")
+    }
+
+    fn try_view_transform(
+        from: Vector4,
+        to: Vector4,
+        up: Vector4,
+    ) -> Result<Matrix<4>, DegenerateViewError> {
+        let finite = [from, to, up]
+            .iter()
+            .all(|v| v.x.is_finite() && v.y.is_finite() && v.z.is_finite());
+        if !finite || (to - from).magnitude() < EPSILON {
+            return Err(DegenerateViewError);
+        }
+
         let forward = (to - from).normalize();
-        let upn = up.normalize();
-        let left = forward.cross_product(&upn);
+        let mut left = Vector4::vector(0.0, 0.0, 0.0);
+        if up.magnitude() >= EPSILON {
+            left = forward.cross_product(&up.normalize());
+        }
+        if left.magnitude() < EPSILON {
+            left = forward.orthonormal_basis().0;
+        }
         let true_up = left.cross_product(&forward);
         let orientation: Matrix<4> = Matrix::from([
             [left.x, left.y, left.z, 0.0],
@@ -78,7 +157,39 @@ impl Transform for Matrix<4> {
             [0.0, 0.0, 0.0, 1.0],
         ]);
 
-        orientation * Matrix::translation(-from.x, -from.y, -from.z)
+        Ok(orientation * Matrix::translation(-from.x, -from.y, -from.z))
+    }
+
+    fn decompose(&self) -> Decomposition {
+        let m = self;
+        let column = |c: usize| Vector4::vector(m[[0, c]], m[[1, c]], m[[2, c]]);
+        let (x, y, z) = (column(0), column(1), column(2));
+        let mirrored = x.cross_product(&y).dot(&z) < 0.0;
+        let scale = Vector4::vector(
+            if mirrored { -x.magnitude() } else { x.magnitude() },
+            y.magnitude(),
+            z.magnitude(),
+        );
+        let (x, y, z) = (x / scale.x, y / scale.y, z / scale.z);
+        let sheared = [x.dot(&y), y.dot(&z), z.dot(&x)]
+            .iter()
+            .any(|d| d.abs() > 0.0001);
+
+        // The rotation is rz * ry * rx, whose bottom left entry is -sin(ry)
+        let ry = (-x.z).clamp(-1.0, 1.0).asin();
+        let (rx, rz) = if x.z.abs() < 0.9999 {
+            (y.z.atan2(z.z), x.y.atan2(x.x))
+        } else {
+            // Looking straight along y, x and z turn about the same axis, so put it all in z
+            (0.0, (-y.x).atan2(y.y))
+        };
+
+        Decomposition {
+            translation: Vector4::vector(m[[0, 3]], m[[1, 3]], m[[2, 3]]),
+            rotation: Vector4::vector(rx, ry, rz),
+            scale,
+            sheared,
+        }
     }
 }
 
@@ -91,18 +202,16 @@ mod tests {
     use std::f32::consts::PI;
 
     use spectral::assert_that;
+    use spectral::boolean::BooleanAssertions;
     use spectral::numeric::FloatAssertions;
 
     use crate::matrix::Matrix;
     use crate::vector4::Vector4;
 
     use super::*;
-
-    fn vector_values_are_close(actual: Vector4, expected: Vector4, tolerance: f32) {
-        for row in 0..4 {
-            assert_that!(actual[row]).is_close_to(expected[row], tolerance);
-        }
-    }
+    use crate::assert_approx_eq;
+    use crate::approx::ApproxEq;
+    use crate::testing::{for_all, ArbitraryPoint, ArbitraryTransform};
 
     #[test]
     fn multiplying_by_a_translation_matrix() {
@@ -168,12 +277,12 @@ mod tests {
         let half_quarter = Matrix::rotation_x(PI / 4.0);
         let full_quarter = Matrix::rotation_x(PI / 2.0);
 
-        vector_values_are_close(
+        assert_approx_eq!(
             half_quarter * p,
             Vector4::point(0.0, 2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
             0.0001,
         );
-        vector_values_are_close(full_quarter * p, Vector4::point(0.0, 0.0, 1.0), 0.00001);
+        assert_approx_eq!(full_quarter * p, Vector4::point(0.0, 0.0, 1.0), 0.00001);
     }
 
     #[test]
@@ -182,7 +291,7 @@ mod tests {
         let half_quarter = Matrix::rotation_x(PI / 4.0);
         let inv = half_quarter.try_inverse().unwrap();
 
-        vector_values_are_close(
+        assert_approx_eq!(
             inv * p,
             Vector4::point(0.0, 2.0_f32.sqrt() / 2.0, -2.0_f32.sqrt() / 2.0),
             0.00001,
@@ -195,12 +304,12 @@ mod tests {
         let half_quarter = Matrix::rotation_y(PI / 4.0);
         let full_quarter = Matrix::rotation_y(PI / 2.0);
 
-        vector_values_are_close(
+        assert_approx_eq!(
             half_quarter * p,
             Vector4::point(2.0_f32.sqrt() / 2.0, 0.0, 2.0_f32.sqrt() / 2.0),
             0.00001,
         );
-        vector_values_are_close(full_quarter * p, Vector4::point(1.0, 0.0, 0.0), 0.00001);
+        assert_approx_eq!(full_quarter * p, Vector4::point(1.0, 0.0, 0.0), 0.00001);
     }
 
     #[test]
@@ -209,12 +318,12 @@ mod tests {
         let half_quarter = Matrix::rotation_z(PI / 4.0);
         let full_quarter = Matrix::rotation_z(PI / 2.0);
 
-        vector_values_are_close(
+        assert_approx_eq!(
             half_quarter * p,
             Vector4::point(-2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0, 0.0),
             0.0001,
         );
-        vector_values_are_close(full_quarter * p, Vector4::point(-1.0, 0.0, 0.0), 0.0001);
+        assert_approx_eq!(full_quarter * p, Vector4::point(-1.0, 0.0, 0.0), 0.0001);
     }
 
     #[test]
@@ -274,15 +383,15 @@ mod tests {
 
         // apply rotation first
         let p2 = a * p;
-        vector_values_are_close(p2, Vector4::point(1.0, -1.0, 0.0), 0.00001);
+        assert_approx_eq!(p2, Vector4::point(1.0, -1.0, 0.0), 0.00001);
 
         // then apply scaling
         let p3 = b * p2;
-        vector_values_are_close(p3, Vector4::point(5.0, -5.0, 0.0), 0.00001);
+        assert_approx_eq!(p3, Vector4::point(5.0, -5.0, 0.0), 0.00001);
 
         // then apply translation
         let p4 = c * p3;
-        vector_values_are_close(p4, Vector4::point(15.0, 0.0, 7.0), 0.00001);
+        assert_approx_eq!(p4, Vector4::point(15.0, 0.0, 7.0), 0.00001);
     }
 
     #[test]
@@ -294,7 +403,7 @@ mod tests {
 
         let t = c * b * a;
 
-        vector_values_are_close(t * p, Vector4::point(15.0, 0.0, 7.0), 0.00001);
+        assert_approx_eq!(t * p, Vector4::point(15.0, 0.0, 7.0), 0.00001);
     }
 
     #[test]
@@ -347,4 +456,115 @@ mod tests {
 
         assert_that!(t).is_equal_to(expected);
     }
+
+    #[test]
+    fn a_view_transformation_looking_along_the_up_vector() {
+        let from = Vector4::point(0.0, 5.0, 0.0);
+        let to = Vector4::point(0.0, 0.0, 0.0);
+        let up = Vector4::vector(0.0, 1.0, 0.0);
+
+        let t = Matrix::view_transform(from, to, up);
+
+        // Whatever way up it is, the camera looks straight down and the world is not squashed
+        let forward = t.try_inverse().unwrap() * Vector4::vector(0.0, 0.0, -1.0);
+        assert_that!(forward).is_equal_to(Vector4::vector(0.0, -1.0, 0.0));
+        assert_that!(t.determinant()).is_close_to(1.0, 0.0001);
+    }
+
+    #[test]
+    fn a_view_transformation_with_no_up_vector() {
+        let from = Vector4::point(0.0, 0.0, 0.0);
+        let to = Vector4::point(1.0, 0.0, 0.0);
+        let up = Vector4::vector(0.0, 0.0, 0.0);
+
+        let t = Matrix::try_view_transform(from, to, up);
+
+        assert_that!(t.map(|t| t.determinant())).is_equal_to(Ok(1.0));
+    }
+
+    #[test]
+    fn a_view_transformation_needs_a_direction() {
+        let p = Vector4::point(1.0, 2.0, 3.0);
+        let up = Vector4::vector(0.0, 1.0, 0.0);
+
+        let t = Matrix::try_view_transform(p, p, up);
+
+        assert_that!(t).is_equal_to(Err(DegenerateViewError));
+    }
+
+    #[test]
+    fn transforms_keep_points_and_vectors_apart() {
+        for_all(|(t, (p, v)): &(ArbitraryTransform, (ArbitraryPoint, Vector4))| {
+            (t.0 * p.0).is_point() && (t.0 * *v).is_vector()
+        });
+    }
+
+    #[test]
+    fn a_transform_is_undone_by_its_inverse() {
+        for_all(|(t, p): &(ArbitraryTransform, ArbitraryPoint)| {
+            let inverse = t.0.try_inverse().unwrap();
+            (inverse * (t.0 * p.0)).approx_eq(&p.0, 0.01)
+        });
+    }
+
+    #[test]
+    fn rotations_preserve_length() {
+        for_all(|(angle, v): &(f32, Vector4)| {
+            [
+                Matrix::rotation_x(*angle),
+                Matrix::rotation_y(*angle),
+                Matrix::rotation_z(*angle),
+            ]
+            .iter()
+            .all(|r| ((*r * *v).magnitude() - v.magnitude()).abs() < 0.0001 * v.magnitude())
+        });
+    }
+
+    #[test]
+    fn decomposing_a_transform() {
+        let t = Matrix::translation(1.0, 2.0, 3.0)
+            * Matrix::rotation_z(0.3)
+            * Matrix::rotation_y(-0.5)
+            * Matrix::rotation_x(1.2)
+            * Matrix::scaling(2.0, 3.0, 4.0);
+
+        let d = t.decompose();
+
+        assert_approx_eq!(d.translation, Vector4::vector(1.0, 2.0, 3.0), 0.0001);
+        assert_approx_eq!(d.rotation, Vector4::vector(1.2, -0.5, 0.3), 0.0001);
+        assert_approx_eq!(d.scale, Vector4::vector(2.0, 3.0, 4.0), 0.0001);
+        assert_that!(d.sheared).is_false();
+    }
+
+    #[test]
+    fn decomposing_a_mirror_and_a_shear() {
+        let mirror = Matrix::scaling(-1.0, 1.0, 1.0).decompose();
+        let shear = Matrix::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0).decompose();
+
+        assert_approx_eq!(mirror.scale, Vector4::vector(-1.0, 1.0, 1.0), 0.0001);
+        assert_that!(shear.sheared).is_true();
+    }
+
+    #[test]
+    fn a_decomposition_recomposes_to_the_same_transform() {
+        for_all(|t: &ArbitraryTransform| {
+            let d = t.0.decompose();
+            let (tr, r, s) = (d.translation, d.rotation, d.scale);
+            let recomposed = Matrix::translation(tr.x, tr.y, tr.z)
+                * Matrix::rotation_z(r.z)
+                * Matrix::rotation_y(r.y)
+                * Matrix::rotation_x(r.x)
+                * Matrix::scaling(s.x, s.y, s.z);
+            d.sheared || recomposed.approx_eq(&t.0, 0.01)
+        });
+    }
+
+    #[test]
+    fn displaying_a_decomposition() {
+        let d = (Matrix::translation(1.0, 0.0, 0.0) * Matrix::rotation_y(PI / 2.0)).decompose();
+
+        assert_that!(d.to_string().as_str()).is_equal_to(
+            "translate (1.00, 0.00, 0.00) rotate (0.0°, 90.0°, 0.0°) scale (1.00, 1.00, 1.00)",
+        );
+    }
 }
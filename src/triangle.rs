@@ -0,0 +1,847 @@
+use std::rc::Rc;
+
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::consts::EPSILON;
+use crate::light::PointLight;
+use crate::material::{Material, MaterialBuilder};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::shape::{area_scale, shared_user_data, Shape, UserData};
+use crate::vector4::Vector4;
+
+/// How rays are tested against a triangle.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TriangleIntersection {
+    /// Möller–Trumbore: fast, but rays through an edge shared by two triangles can slip between
+    /// them at grazing angles.
+    #[default]
+    MollerTrumbore,
+    /// Woop, Benthin and Wald's watertight test: a ray through a shared edge or vertex always hits
+    /// at least one of the triangles, so meshes render without cracks.
+    Watertight,
+}
+
+/// A flat triangle between three points, shaded as if curved when given a normal at each.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Triangle {
+    // Note: we store the inverse of the transform as an optimisation.
+    inv_transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: Option<Rc<UserData>>,
+    intersection: TriangleIntersection,
+    p1: Vector4,
+    p2: Vector4,
+    p3: Vector4,
+    colors: Option<[Color; 3]>,
+    uvs: Option<[(f32, f32); 3]>,
+    normals: Option<[Vector4; 3]>,
+    face_material: Option<(Rc<Vec<Material>>, usize)>,
+    // Note: the edges and normal are precomputed as an optimisation.
+    e1: Vector4,
+    e2: Vector4,
+    normal: Vector4,
+}
+
+pub struct TriangleBuilder {
+    transform: Matrix<4>,
+    material: Material,
+    name: Option<String>,
+    user_data: UserData,
+    intersection: TriangleIntersection,
+    p1: Vector4,
+    p2: Vector4,
+    p3: Vector4,
+    colors: Option<[Color; 3]>,
+    uvs: Option<[(f32, f32); 3]>,
+    normals: Option<[Vector4; 3]>,
+    face_material: Option<(Rc<Vec<Material>>, usize)>,
+}
+
+impl Triangle {
+    pub fn vertices(&self) -> [Vector4; 3] {
+        [self.p1, self.p2, self.p3]
+    }
+
+    /// The index into the shared material list that this face takes its material from, if any.
+    pub fn material_index(&self) -> Option<usize> {
+        self.face_material.as_ref().map(|(_, index)| *index)
+    }
+
+    /// The weights `(u, v)` of `p2` and `p3` at a point on the triangle in object space; `p1`
+    /// has the rest.
+    pub fn barycentric(&self, object_point: Vector4) -> (f32, f32) {
+        let to_point = object_point - self.p1;
+        let (d11, d12, d22) = (
+            self.e1.dot(&self.e1),
+            self.e1.dot(&self.e2),
+            self.e2.dot(&self.e2),
+        );
+        let (dp1, dp2) = (to_point.dot(&self.e1), to_point.dot(&self.e2));
+        let denominator = d11 * d22 - d12 * d12;
+
+        (
+            (d22 * dp1 - d12 * dp2) / denominator,
+            (d11 * dp2 - d12 * dp1) / denominator,
+        )
+    }
+
+    /// The vertex colours blended at a point on the triangle in object space.
+    pub fn color_at(&self, object_point: Vector4) -> Option<Color> {
+        let [c1, c2, c3] = self.colors?;
+        let (u, v) = self.barycentric(object_point);
+
+        Some(c1 * (1.0 - u - v) + c2 * u + c3 * v)
+    }
+
+    /// The vertex texture coordinates blended at a point on the triangle in object space.
+    pub fn local_uv_at(&self, object_point: Vector4) -> Option<(f32, f32)> {
+        let [uv1, uv2, uv3] = self.uvs?;
+        let (u, v) = self.barycentric(object_point);
+        let blend = |a: f32, b: f32, c: f32| a * (1.0 - u - v) + b * u + c * v;
+
+        Some((blend(uv1.0, uv2.0, uv3.0), blend(uv1.1, uv2.1, uv3.1)))
+    }
+
+    /// dp/du and dp/dv in object space, which are the same all over the face. `None` if the
+    /// triangle has no texture coordinates, or they don't span an area.
+    pub fn local_uv_derivatives(&self) -> Option<(Vector4, Vector4)> {
+        let [uv1, uv2, uv3] = self.uvs?;
+        let (du1, dv1) = (uv2.0 - uv1.0, uv2.1 - uv1.1);
+        let (du2, dv2) = (uv3.0 - uv1.0, uv3.1 - uv1.1);
+        let determinant = du1 * dv2 - dv1 * du2;
+        if determinant.abs() < EPSILON {
+            return None;
+        }
+
+        Some((
+            (self.e1 * dv2 - self.e2 * dv1) / determinant,
+            (self.e2 * du1 - self.e1 * du2) / determinant,
+        ))
+    }
+
+    fn moller_trumbore(&self, ray: &Ray) -> Vec<f32> {
+        let dir_cross_e2 = ray.direction.cross_product(&self.e2);
+        let det = self.e1.dot(&dir_cross_e2);
+        if det.abs() < EPSILON {
+            return Vec::default();
+        }
+
+        let f = 1.0 / det;
+        let p1_to_origin = ray.origin - self.p1;
+        let u = f * p1_to_origin.dot(&dir_cross_e2);
+        if !(0.0..=1.0).contains(&u) {
+            return Vec::default();
+        }
+
+        let origin_cross_e1 = p1_to_origin.cross_product(&self.e1);
+        let v = f * ray.direction.dot(&origin_cross_e1);
+        if v < 0.0 || u + v > 1.0 {
+            return Vec::default();
+        }
+
+        vec![f * self.e2.dot(&origin_cross_e1)]
+    }
+
+    fn watertight(&self, ray: &Ray) -> Vec<f32> {
+        // Permute the axes so the ray travels along the largest component of its direction, which
+        // becomes z, keeping the winding of x and y.
+        let d = ray.direction;
+        let kz = (0..3)
+            .max_by(|&a, &b| d[a].abs().total_cmp(&d[b].abs()))
+            .unwrap();
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if d[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        // Shear the vertices, relative to the ray origin, so the ray runs along +z from (0, 0, 0)
+        let sx = d[kx] / d[kz];
+        let sy = d[ky] / d[kz];
+        let sz = 1.0 / d[kz];
+        let [a, b, c] = self.vertices().map(|p| p - ray.origin);
+        let shear = |p: Vector4| (p[kx] - sx * p[kz], p[ky] - sy * p[kz], sz * p[kz]);
+        let (ax, ay, az) = shear(a);
+        let (bx, by, bz) = shear(b);
+        let (cx, cy, cz) = shear(c);
+
+        // Scaled barycentric coordinates: the signed areas opposite each vertex, in the 2D plane
+        // the ray pierces
+        let mut u = cx * by - cy * bx;
+        let mut v = ax * cy - ay * cx;
+        let mut w = bx * ay - by * ax;
+        if u == 0.0 || v == 0.0 || w == 0.0 {
+            // The ray is on, or very near, an edge: redo the sums in double precision so
+            // neighbouring triangles agree on which side it falls.
+            let area = |px: f32, py: f32, qx: f32, qy: f32| {
+                (px as f64 * qy as f64 - py as f64 * qx as f64) as f32
+            };
+            u = area(cx, cy, bx, by);
+            v = area(ax, ay, cx, cy);
+            w = area(bx, by, ax, ay);
+        }
+
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return Vec::default();
+        }
+
+        let det = u + v + w;
+        if det == 0.0 {
+            return Vec::default();
+        }
+
+        vec![(u * az + v * bz + w * cz) / det]
+    }
+}
+
+impl Shape for Triangle {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn user_data(&self) -> Option<&Rc<UserData>> {
+        self.user_data.as_ref()
+    }
+
+    fn material(&self) -> &Material {
+        match &self.face_material {
+            Some((materials, index)) => &materials[*index],
+            None => &self.material,
+        }
+    }
+
+    fn transformation(&self) -> Matrix<4> {
+        self.inv_transform.try_inverse().unwrap()
+    }
+
+    fn inv_transform(&self) -> &Matrix<4> {
+        &self.inv_transform
+    }
+
+    fn transformed(&self, transform: &Matrix<4>) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            inv_transform: self.inv_transform * transform.try_inverse().unwrap(),
+            ..self.clone()
+        })
+    }
+
+    fn with_material(&self, material: &Material) -> Rc<dyn Shape> {
+        Rc::new(Self {
+            material: material.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for p in self.vertices() {
+            bounds.add_point(p);
+        }
+
+        bounds
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        match self.intersection {
+            TriangleIntersection::MollerTrumbore => self.moller_trumbore(ray),
+            TriangleIntersection::Watertight => self.watertight(ray),
+        }
+    }
+
+    fn triangle_count(&self) -> usize {
+        1
+    }
+
+    fn local_normal_at(&self, object_point: Vector4) -> Vector4 {
+        match self.normals {
+            Some([n1, n2, n3]) => {
+                let (u, v) = self.barycentric(object_point);
+                (n1 * (1.0 - u - v) + n2 * u + n3 * v).normalize()
+            }
+            None => self.normal,
+        }
+    }
+
+    fn uv_at(&self, world_point: &Vector4) -> Option<(f32, f32)> {
+        self.local_uv_at(self.inv_transform * *world_point)
+    }
+
+    fn uv_derivatives_at(&self, _world_point: &Vector4) -> Option<(Vector4, Vector4)> {
+        let (dpdu, dpdv) = self.local_uv_derivatives()?;
+        let transform = self.transformation();
+
+        Some((transform * dpdu, transform * dpdv))
+    }
+
+    fn local_closest_point(&self, object_point: Vector4) -> Vector4 {
+        // Work through the Voronoi regions of the vertices, then the edges, then the face
+        let (a, b, c) = (self.p1, self.p2, self.p3);
+        let ab = b - a;
+        let ac = c - a;
+
+        let ap = object_point - a;
+        let d1 = ab.dot(&ap);
+        let d2 = ac.dot(&ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+
+        let bp = object_point - b;
+        let d3 = ab.dot(&bp);
+        let d4 = ac.dot(&bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+
+        let cp = object_point - c;
+        let d5 = ab.dot(&cp);
+        let d6 = ac.dot(&cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+
+        let denominator = 1.0 / (va + vb + vc);
+        a + ab * (vb * denominator) + ac * (vc * denominator)
+    }
+
+    fn surface_area(&self) -> Option<f32> {
+        let local_area = self.e1.cross_product(&self.e2).magnitude() / 2.0;
+
+        Some(local_area * area_scale(&self.transformation(), &self.inv_transform, self.normal))
+    }
+
+    fn sample_surface(&self, rng: &mut Rng) -> Option<(Vector4, Vector4, f32)> {
+        let root_u = rng.next_f32().sqrt();
+        let v = rng.next_f32();
+        let object_point = self.p1 + self.e1 * (root_u * (1.0 - v)) + self.e2 * (root_u * v);
+        let point = self.transformation() * object_point;
+
+        Some((point, self.normal_at(&point), 1.0 / self.surface_area()?))
+    }
+
+    fn lighting(
+        &self,
+        light: &PointLight,
+        point: Vector4,
+        eye_vector: Vector4,
+        normal_vector: Vector4,
+        in_shadow: bool,
+    ) -> Color {
+        let material = self.material();
+        let object_point = self.inv_transform * point;
+        let color = self
+            .color_at(object_point)
+            .unwrap_or_else(|| material.color_at(point, self.local_uv_at(object_point)));
+
+        material.lighting_with_color(color, light, point, eye_vector, normal_vector, in_shadow)
+    }
+}
+
+impl TriangleBuilder {
+    pub fn new(p1: Vector4, p2: Vector4, p3: Vector4) -> Self {
+        Self {
+            transform: Matrix::identity(),
+            material: MaterialBuilder::new().build(),
+            name: None,
+            user_data: UserData::new(),
+            intersection: TriangleIntersection::default(),
+            p1,
+            p2,
+            p3,
+            colors: None,
+            uvs: None,
+            normals: None,
+            face_material: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Tag the shape with `value` under `key`, for tools downstream to find it by.
+    pub fn with_user_data(mut self, key: &str, value: &str) -> Self {
+        self.user_data.insert(key.to_string(), value.to_string());
+
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+
+        self
+    }
+
+    /// Colours for `p1`, `p2` and `p3`, blended across the face in place of the material's pattern.
+    pub fn with_vertex_colors(mut self, colors: [Color; 3]) -> Self {
+        self.colors = Some(colors);
+
+        self
+    }
+
+    /// Texture coordinates for `p1`, `p2` and `p3`, blended across the face.
+    pub fn with_uvs(mut self, uvs: [(f32, f32); 3]) -> Self {
+        self.uvs = Some(uvs);
+
+        self
+    }
+
+    /// Normals for `p1`, `p2` and `p3`, blended across the face so that a mesh of flat triangles
+    /// shades as the smooth surface it stands for.
+    pub fn with_normals(mut self, normals: [Vector4; 3]) -> Self {
+        self.normals = Some(normals.map(|n| n.normalize()));
+
+        self
+    }
+
+    /// Take the material from entry `index` of a list shared by the faces of a mesh, overriding
+    /// the triangle's own material.
+    pub fn with_face_material(mut self, materials: Rc<Vec<Material>>, index: usize) -> Self {
+        assert!(index < materials.len(), "Material index is out of range");
+        self.face_material = Some((materials, index));
+
+        self
+    }
+
+    pub fn with_intersection(mut self, intersection: TriangleIntersection) -> Self {
+        self.intersection = intersection;
+
+        self
+    }
+
+    pub fn build(self) -> Triangle {
+        let e1 = self.p2 - self.p1;
+        let e2 = self.p3 - self.p1;
+
+        Triangle {
+            inv_transform: self.transform.try_inverse().unwrap(),
+            material: self.material,
+            name: self.name,
+            user_data: shared_user_data(self.user_data),
+            intersection: self.intersection,
+            p1: self.p1,
+            p2: self.p2,
+            p3: self.p3,
+            colors: self.colors,
+            uvs: self.uvs,
+            normals: self.normals,
+            face_material: self.face_material,
+            e1,
+            e2,
+            normal: e2.cross_product(&e1).normalize(),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use crate::assert_approx_eq;
+    use crate::canvas::Canvas;
+    use crate::intersection::Intersection;
+    use crate::pattern::TexturePattern;
+    use crate::transform::Transform;
+
+    use super::*;
+
+    #[fixture]
+    fn t() -> Triangle {
+        TriangleBuilder::new(
+            Vector4::point(0.0, 1.0, 0.0),
+            Vector4::point(-1.0, 0.0, 0.0),
+            Vector4::point(1.0, 0.0, 0.0),
+        )
+        .build()
+    }
+
+    #[rstest]
+    fn constructing_a_triangle(t: Triangle) {
+        assert_that!(t.e1).is_equal_to(Vector4::vector(-1.0, -1.0, 0.0));
+        assert_that!(t.e2).is_equal_to(Vector4::vector(1.0, -1.0, 0.0));
+        assert_that!(t.normal).is_equal_to(Vector4::vector(0.0, 0.0, -1.0));
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 0.5, 0.0))]
+    #[case(Vector4::point(-0.5, 0.75, 0.0))]
+    #[case(Vector4::point(0.5, 0.25, 0.0))]
+    fn finding_the_normal_on_a_triangle(t: Triangle, #[case] point: Vector4) {
+        assert_that!(t.local_normal_at(point)).is_equal_to(t.normal);
+    }
+
+    #[test]
+    fn a_smooth_triangle_blends_its_vertex_normals() {
+        let t = TriangleBuilder::new(
+            Vector4::point(0.0, 1.0, 0.0),
+            Vector4::point(-1.0, 0.0, 0.0),
+            Vector4::point(1.0, 0.0, 0.0),
+        )
+        .with_normals([
+            Vector4::vector(0.0, 1.0, 0.0),
+            Vector4::vector(-1.0, 0.0, 0.0),
+            Vector4::vector(1.0, 0.0, 0.0),
+        ])
+        .build();
+
+        // The point weighted 0.3, 0.45 and 0.25 towards p1, p2 and p3
+        let n = t.local_normal_at(Vector4::point(-0.2, 0.3, 0.0));
+
+        assert_that!(n).is_equal_to(Vector4::vector(-0.5547, 0.83205, 0.0));
+    }
+
+    #[rstest]
+    fn intersecting_a_ray_parallel_to_the_triangle(t: Triangle) {
+        let r = Ray::new(
+            Vector4::point(0.0, -1.0, -2.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+
+        assert_that!(t.local_intersect(&r)).is_empty();
+    }
+
+    #[rstest]
+    #[case(Vector4::point(1.0, 1.0, -2.0))] // misses the p1-p3 edge
+    #[case(Vector4::point(-1.0, 1.0, -2.0))] // misses the p1-p2 edge
+    #[case(Vector4::point(0.0, -1.0, -2.0))] // misses the p2-p3 edge
+    fn a_ray_misses_the_triangle(t: Triangle, #[case] origin: Vector4) {
+        let r = Ray::new(origin, Vector4::vector(0.0, 0.0, 1.0));
+
+        assert_that!(t.local_intersect(&r)).is_empty();
+    }
+
+    #[rstest]
+    fn a_ray_strikes_a_triangle(t: Triangle) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.5, -2.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        assert_that!(t.local_intersect(&r)).is_equal_to(vec![2.0]);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 0.5, -3.0), Vector4::point(0.0, 0.5, 0.0))] // face
+    #[case(Vector4::point(0.0, 2.0, 0.0), Vector4::point(0.0, 1.0, 0.0))] // vertex
+    #[case(Vector4::point(0.0, -1.0, 0.0), Vector4::point(0.0, 0.0, 0.0))] // edge
+    fn the_closest_point_on_a_triangle(
+        t: Triangle,
+        #[case] point: Vector4,
+        #[case] expected: Vector4,
+    ) {
+        assert_that!(t.local_closest_point(point)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    fn the_surface_area_of_a_triangle(t: Triangle) {
+        assert_that!(t.surface_area())
+            .is_some()
+            .is_close_to(1.0, EPSILON);
+
+        let scaled = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_transform(Matrix::scaling(2.0, 3.0, 4.0))
+            .build();
+        assert_that!(scaled.surface_area())
+            .is_some()
+            .is_close_to(6.0, EPSILON);
+    }
+
+    #[rstest]
+    fn samples_lie_on_the_triangle(t: Triangle) {
+        let mut rng = Rng::new(3);
+
+        for _ in 0..100 {
+            let (point, normal, pdf) = t.sample_surface(&mut rng).unwrap();
+
+            assert_that!(t.local_closest_point(point)).is_equal_to(point);
+            assert_that!(normal).is_equal_to(t.normal);
+            assert_that!(pdf).is_close_to(1.0, EPSILON);
+        }
+    }
+
+    #[rstest]
+    #[case(Vector4::point(1.0, 1.0, -2.0), Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(0.0, -1.0, -2.0), Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(0.0, 0.5, -2.0), Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(0.2, 0.3, 2.0), Vector4::vector(0.1, -0.2, -1.0))]
+    #[case(Vector4::point(0.0, -1.0, -2.0), Vector4::vector(0.0, 1.0, 0.0))]
+    fn the_watertight_test_agrees_with_moller_trumbore(
+        t: Triangle,
+        #[case] origin: Vector4,
+        #[case] direction: Vector4,
+    ) {
+        let watertight = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_intersection(TriangleIntersection::Watertight)
+            .build();
+        let r = Ray::new(origin, direction);
+
+        let expected = t.local_intersect(&r);
+        let actual = watertight.local_intersect(&r);
+
+        assert_that!(actual.len()).is_equal_to(expected.len());
+        for (a, e) in actual.into_iter().zip(expected) {
+            assert_that!(a).is_close_to(e, EPSILON);
+        }
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.5, 0.5, -2.0))] // p1-p3 edge
+    #[case(Vector4::point(0.0, 0.0, -2.0))] // p2-p3 edge
+    #[case(Vector4::point(0.0, 1.0, -2.0))] // p1
+    #[case(Vector4::point(-1.0, 0.0, -2.0))] // p2
+    fn a_watertight_ray_through_an_edge_or_vertex_hits(t: Triangle, #[case] origin: Vector4) {
+        let watertight = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_intersection(TriangleIntersection::Watertight)
+            .build();
+        let r = Ray::new(origin, Vector4::vector(0.0, 0.0, 1.0));
+
+        assert_that!(watertight.local_intersect(&r)).is_equal_to(vec![2.0]);
+    }
+
+    #[test]
+    fn grazing_rays_through_a_shared_edge_always_hit_one_triangle() {
+        let (a, b, c, d) = (
+            Vector4::point(-0.3, 0.1, 0.2),
+            Vector4::point(1.7, 0.2, 0.2),
+            Vector4::point(1.1, 1.3, 0.2),
+            Vector4::point(0.1, 1.9, 0.2),
+        );
+        let triangles = [
+            TriangleBuilder::new(a, b, c)
+                .with_intersection(TriangleIntersection::Watertight)
+                .build(),
+            TriangleBuilder::new(a, c, d)
+                .with_intersection(TriangleIntersection::Watertight)
+                .build(),
+        ];
+        let mut rng = Rng::new(5);
+
+        // Möller–Trumbore lets about one in twenty of these through
+        for _ in 0..2000 {
+            let on_edge = a + (c - a) * (0.05 + 0.9 * rng.next_f32());
+            let direction = Vector4::vector(
+                rng.next_f32() - 0.5,
+                rng.next_f32() - 0.5,
+                (rng.next_f32() - 0.5) * 0.1,
+            )
+            .normalize();
+            let r = Ray::new(on_edge - direction * 2.0, direction);
+
+            let hits = triangles
+                .iter()
+                .filter(|t| !t.local_intersect(&r).is_empty())
+                .count();
+            assert_that!(hits).is_greater_than_or_equal_to(1);
+        }
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 1.0, 0.0), Color::new(1.0, 0.0, 0.0))]
+    #[case(Vector4::point(-1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0))]
+    #[case(Vector4::point(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0))]
+    #[case(Vector4::point(0.0, 0.0, 0.0), Color::new(0.0, 0.5, 0.5))]
+    #[case(Vector4::point(0.0, 0.5, 0.0), Color::new(0.5, 0.25, 0.25))]
+    fn vertex_colors_are_blended_across_the_face(
+        t: Triangle,
+        #[case] point: Vector4,
+        #[case] expected: Color,
+    ) {
+        let colored = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_vertex_colors([
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 1.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+            ])
+            .build();
+
+        assert_that!(colored.color_at(point))
+            .is_some()
+            .is_equal_to(expected);
+    }
+
+    #[rstest]
+    fn a_triangle_without_vertex_colors_has_no_color(t: Triangle) {
+        assert_that!(t.color_at(Vector4::point(0.0, 0.5, 0.0))).is_none();
+    }
+
+    #[rstest]
+    fn lighting_uses_the_vertex_colors(t: Triangle) {
+        let colored = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_transform(Matrix::translation(0.0, 0.0, 5.0))
+            .with_vertex_colors([Color::white(), Color::black(), Color::black()])
+            .build();
+        let light = PointLight::new(Vector4::point(0.0, 0.0, -10.0), Color::white());
+        let point = Vector4::point(0.0, 1.0, 5.0);
+
+        // Only the ambient term is left in shadow
+        let c = colored.lighting(
+            &light,
+            point,
+            Vector4::vector(0.0, 0.0, -1.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+            true,
+        );
+
+        assert_that!(c).is_equal_to(Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[rstest]
+    fn a_face_takes_its_material_from_the_shared_list(t: Triangle) {
+        let materials = Rc::new(vec![
+            MaterialBuilder::new().with_ambient(0.2).build(),
+            MaterialBuilder::new().with_ambient(0.3).build(),
+        ]);
+
+        let face = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_face_material(Rc::clone(&materials), 1)
+            .build();
+
+        assert_that!(face.material_index()).is_some().is_equal_to(1);
+        assert_that!(face.material()).is_equal_to(&materials[1]);
+        assert_that!(t.material_index()).is_none();
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 1.0, 5.0), (0.5, 1.0))]
+    #[case(Vector4::point(-1.0, 0.0, 5.0), (0.0, 0.0))]
+    #[case(Vector4::point(0.0, 0.0, 5.0), (0.5, 0.0))]
+    #[case(Vector4::point(0.0, 0.5, 5.0), (0.5, 0.5))]
+    fn texture_coordinates_are_blended_across_the_face(
+        t: Triangle,
+        #[case] point: Vector4,
+        #[case] expected: (f32, f32),
+    ) {
+        let textured = TriangleBuilder::new(t.p1, t.p2, t.p3)
+            .with_transform(Matrix::translation(0.0, 0.0, 5.0))
+            .with_uvs([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+            .build();
+
+        let (u, v) = textured.uv_at(&point).unwrap();
+
+        assert_that!(u).is_close_to(expected.0, EPSILON);
+        assert_that!(v).is_close_to(expected.1, EPSILON);
+        assert_that!(t.uv_at(&point)).is_none();
+    }
+
+    #[test]
+    fn the_hit_carries_the_texture_coordinates() {
+        let textured: Rc<dyn Shape> = Rc::new(
+            TriangleBuilder::new(
+                Vector4::point(0.0, 1.0, 0.0),
+                Vector4::point(-1.0, 0.0, 0.0),
+                Vector4::point(1.0, 0.0, 0.0),
+            )
+            .with_uvs([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+            .build(),
+        );
+        let r = Ray::new(
+            Vector4::point(0.0, 0.5, -2.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let comps = Intersection::new(2.0, textured).prepare_computations(&r);
+
+        assert_that!(comps.uv).is_equal_to(Some((0.5, 0.5)));
+    }
+
+    #[test]
+    fn the_hit_carries_the_texture_derivatives() {
+        let textured: Rc<dyn Shape> = Rc::new(
+            TriangleBuilder::new(
+                Vector4::point(0.0, 1.0, 0.0),
+                Vector4::point(-1.0, 0.0, 0.0),
+                Vector4::point(1.0, 0.0, 0.0),
+            )
+            .with_transform(Matrix::scaling(1.0, 3.0, 1.0))
+            .with_uvs([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+            .build(),
+        );
+        let r = Ray::new(
+            Vector4::point(0.0, 1.5, -2.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let comps = Intersection::new(2.0, textured).prepare_computations(&r);
+
+        // u runs from -1 to 1 across the base, and v up the 3 units the face is scaled to
+        let (dpdu, dpdv) = comps.uv_derivatives.unwrap();
+        assert_approx_eq!(dpdu, Vector4::vector(2.0, 0.0, 0.0), EPSILON);
+        assert_approx_eq!(dpdv, Vector4::vector(0.0, 3.0, 0.0), EPSILON);
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some([(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)]))]
+    fn texture_derivatives_need_coordinates_that_span_an_area(
+        t: Triangle,
+        #[case] uvs: Option<[(f32, f32); 3]>,
+    ) {
+        let builder = TriangleBuilder::new(t.p1, t.p2, t.p3);
+        let triangle = match uvs {
+            Some(uvs) => builder.with_uvs(uvs).build(),
+            None => builder.build(),
+        };
+
+        assert_that!(triangle.uv_derivatives_at(&Vector4::point(0.0, 0.5, 0.0))).is_none();
+    }
+
+    #[test]
+    fn lighting_looks_up_a_texture_by_uv() {
+        let mut texture = Canvas::new(2, 1);
+        texture.write_pixel(1, 0, &Color::white());
+        let material = MaterialBuilder::new()
+            .with_pattern(Rc::new(TexturePattern::new(texture)))
+            .with_ambient(1.0)
+            .build();
+        let textured = TriangleBuilder::new(
+            Vector4::point(0.0, 1.0, 0.0),
+            Vector4::point(-1.0, 0.0, 0.0),
+            Vector4::point(1.0, 0.0, 0.0),
+        )
+        .with_uvs([(0.75, 0.5), (0.75, 0.5), (0.75, 0.5)])
+        .with_material(material)
+        .build();
+        let light = PointLight::new(Vector4::point(0.0, 0.0, -10.0), Color::white());
+
+        // The world point would pick the black texel by planar projection
+        let c = textured.lighting(
+            &light,
+            Vector4::point(0.0, 0.5, 0.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+            Vector4::vector(0.0, 0.0, -1.0),
+            true,
+        );
+
+        assert_that!(c).is_equal_to(Color::white());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::consts::EPSILON;
+use crate::consts::nearly_equal;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Vector4 {
@@ -10,10 +10,10 @@ pub struct Vector4 {
 
 impl PartialEq for Vector4 {
     fn eq(&self, other: &Self) -> bool {
-        (self.x - other.x).abs() < EPSILON
-            && (self.y - other.y).abs() < EPSILON
-            && (self.z - other.z).abs() < EPSILON
-            && (self.w - other.w).abs() < EPSILON
+        nearly_equal(self.x, other.x)
+            && nearly_equal(self.y, other.y)
+            && nearly_equal(self.z, other.z)
+            && nearly_equal(self.w, other.w)
     }
 }
 
@@ -146,6 +146,20 @@ impl Vector4 {
         *self - *normal * 2_f32 * self.dot(normal)
     }
 
+    /// Two unit vectors at right angles to each other and to this one, which must be a unit
+    /// vector. Uses the branchless construction of Duff et al. (2017), which holds up for every
+    /// direction, including ones along the axes.
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        let sign = 1.0_f32.copysign(self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+
+        (
+            Self::vector(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Self::vector(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+
     pub fn magnitude(&self) -> f32 {
         (self.x.powi(2) + self.y.powi(2) + self.z.powi(2) + self.w.powi(2)).sqrt()
     }
@@ -160,17 +174,93 @@ impl Vector4 {
     }
 }
 
+/// Points and vectors say which they are, as `point(1.000, 2.000, 3.000)`; anything else shows all
+/// four components. The precision, 3 places unless given, applies to each component.
+impl std::fmt::Display for Vector4 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let places = f.precision().unwrap_or(3);
+        // Adding zero turns -0.0 into 0.0, which reads better
+        let (x, y, z) = (self.x + 0.0, self.y + 0.0, self.z + 0.0);
+        if self.w == 1.0 {
+            write!(f, "point({:.*}, {:.*}, {:.*})", places, x, places, y, places, z)
+        } else if self.w == 0.0 {
+            write!(f, "vector({:.*}, {:.*}, {:.*})", places, x, places, y, places, z)
+        } else {
+            let w = self.w + 0.0;
+            write!(f, "({:.*}, {:.*}, {:.*}, {:.*})", places, x, places, y, places, z, places, w)
+        }
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Vector4> for glam::Vec4 {
+    fn from(v: Vector4) -> Self {
+        glam::Vec4::new(v.x, v.y, v.z, v.w)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Vec4> for Vector4 {
+    fn from(v: glam::Vec4) -> Self {
+        Self {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+            w: v.w,
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Vector4> for nalgebra::Vector4<f32> {
+    fn from(v: Vector4) -> Self {
+        nalgebra::Vector4::new(v.x, v.y, v.z, v.w)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Vector4<f32>> for Vector4 {
+    fn from(v: nalgebra::Vector4<f32>) -> Self {
+        Self {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+            w: v.w,
+        }
+    }
+}
+
+/// As the array `[x, y, z, w]`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Vector4 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.x, self.y, self.z, self.w].serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Vector4 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [x, y, z, w] = <[f32; 4]>::deserialize(deserializer)?;
+
+        Ok(Self { x, y, z, w })
+    }
+}
+
 /* -------------------------------------------------------------------------------------------------
 Tests
 ------------------------------------------------------------------------------------------------- */
 
 #[cfg(test)]
 mod tests {
+    use rstest::*;
     use spectral::assert_that;
     use spectral::boolean::BooleanAssertions;
     use spectral::numeric::FloatAssertions;
 
     use super::*;
+    use crate::approx::ApproxEq;
+    use crate::testing::{for_all, ArbitraryPoint};
 
     #[test]
     fn point_has_three_coordinates() {
@@ -431,4 +521,112 @@ mod tests {
         assert_that!(r.z).is_close_to(expected.z, 0.0001);
         assert_that!(r.w).is_equal_to(expected.w);
     }
+
+    #[rstest]
+    #[case(Vector4::vector(0.0, 0.0, 1.0))]
+    #[case(Vector4::vector(0.0, 0.0, -1.0))]
+    #[case(Vector4::vector(0.0, 1.0, 0.0))]
+    #[case(Vector4::vector(1.0, 2.0, -3.0).normalize())]
+    fn an_orthonormal_basis_is_made_of_perpendicular_unit_vectors(#[case] n: Vector4) {
+        let (b1, b2) = n.orthonormal_basis();
+
+        assert_that!(b1.magnitude()).is_close_to(1.0, 0.0001);
+        assert_that!(b2.magnitude()).is_close_to(1.0, 0.0001);
+        assert_that!(b1.dot(&b2)).is_close_to(0.0, 0.0001);
+        assert_that!(b1.dot(&n)).is_close_to(0.0, 0.0001);
+        assert_that!(b2.dot(&n)).is_close_to(0.0, 0.0001);
+    }
+
+    #[test]
+    fn normalizing_is_idempotent() {
+        for_all(|v: &Vector4| {
+            let n = v.normalize();
+            (n.magnitude() - 1.0).abs() < 0.0001 && n.normalize().approx_eq(&n, 0.0001)
+        });
+    }
+
+    #[test]
+    fn reflecting_preserves_magnitude() {
+        for_all(|(v, n): &(Vector4, Vector4)| {
+            let r = v.reflect(&n.normalize());
+            (r.magnitude() - v.magnitude()).abs() < 0.001 * v.magnitude()
+        });
+    }
+
+    #[test]
+    fn a_cross_product_is_perpendicular_to_both_vectors() {
+        for_all(|(a, b): &(Vector4, Vector4)| {
+            let c = a.cross_product(b);
+            let tolerance = 0.001 * c.magnitude() * a.magnitude().max(b.magnitude());
+            c.dot(a).abs() <= tolerance && c.dot(b).abs() <= tolerance
+        });
+    }
+
+    #[test]
+    fn a_point_minus_a_point_is_a_vector() {
+        for_all(|(a, b): &(ArbitraryPoint, ArbitraryPoint)| (a.0 - b.0).is_vector());
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn converting_to_and_from_glam() {
+        let v = Vector4::vector(1.0, -2.0, 3.0);
+
+        let g = glam::Vec4::from(v);
+
+        assert_that!(g).is_equal_to(glam::Vec4::new(1.0, -2.0, 3.0, 0.0));
+        assert_that!(Vector4::from(g)).is_equal_to(v);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn converting_to_and_from_nalgebra() {
+        let p = Vector4::point(1.0, -2.0, 3.0);
+
+        let n = nalgebra::Vector4::from(p);
+
+        assert_that!(n).is_equal_to(nalgebra::Vector4::new(1.0, -2.0, 3.0, 1.0));
+        assert_that!(Vector4::from(n)).is_equal_to(p);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_from_an_array() {
+        use serde::de::value::{Error, SeqDeserializer};
+        use serde::Deserialize;
+
+        let v = Vector4::deserialize(SeqDeserializer::<_, Error>::new(
+            vec![1.0_f32, 2.0, 3.0, 1.0].into_iter(),
+        ));
+        let short = Vector4::deserialize(SeqDeserializer::<_, Error>::new(
+            vec![1.0_f32, 2.0, 3.0].into_iter(),
+        ));
+
+        assert_that!(v.unwrap()).is_equal_to(Vector4::point(1.0, 2.0, 3.0));
+        assert_that!(short.is_err()).is_true();
+    }
+
+    #[rstest]
+    #[case(Vector4::point(1.0, -2.5, 0.0), "point(1.000, -2.500, 0.000)")]
+    #[case(Vector4::vector(-0.0, 0.125, 3.0), "vector(0.000, 0.125, 3.000)")]
+    #[case(Vector4 { x: 1.0, y: 2.0, z: 3.0, w: 0.5 }, "(1.000, 2.000, 3.000, 0.500)")]
+    fn displaying_vectors(#[case] v: Vector4, #[case] expected: &str) {
+        assert_that!(v.to_string().as_str()).is_equal_to(expected);
+    }
+
+    #[test]
+    fn displaying_a_vector_to_a_given_precision() {
+        let v = Vector4::vector(1.0, 2.0, 3.0) / 3.0;
+
+        assert_that!(format!("{:.1}", v).as_str()).is_equal_to("vector(0.3, 0.7, 1.0)");
+    }
+
+    #[test]
+    fn far_away_points_compare_to_the_precision_f32_has_there() {
+        let p = Vector4::point(100_000.0, 0.0, 0.0);
+        let moved = p + Vector4::vector(0.25, 0.0, 0.0) - Vector4::vector(0.125, 0.0, 0.0);
+
+        assert_that!(moved).is_equal_to(Vector4::point(100_000.125, 0.0, 0.0));
+        assert_that!(moved).is_not_equal_to(Vector4::point(100_004.0, 0.0, 0.0));
+    }
 }
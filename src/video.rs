@@ -0,0 +1,178 @@
+//! Putting rendered frames together into a video. Frames are written as YUV4MPEG2 (`.y4m`), a
+//! plain uncompressed format most video tools read, and can be piped through `ffmpeg` to make a
+//! compressed file such as an MP4.
+
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::canvas::Canvas;
+
+/// Writes canvases one after another as the frames of a YUV4MPEG2 stream, in full-resolution
+/// 4:4:4 colour with the BT.709 matrix.
+pub struct Y4mWriter<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    fps: u32,
+    started: bool,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(out: W, width: usize, height: usize, fps: u32) -> Self {
+        Self {
+            out,
+            width,
+            height,
+            fps,
+            started: false,
+        }
+    }
+
+    /// Add a frame. Every frame must be the size given when the writer was made.
+    pub fn write_frame(&mut self, canvas: &Canvas) -> Result<(), Error> {
+        if (canvas.width(), canvas.height()) != (self.width, self.height) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "a {}x{} frame in a {}x{} video",
+                    canvas.width(),
+                    canvas.height(),
+                    self.width,
+                    self.height
+                ),
+            ));
+        }
+        if !self.started {
+            writeln!(
+                self.out,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                self.width, self.height, self.fps
+            )?;
+            self.started = true;
+        }
+
+        let pixels: Vec<(u8, u8, u8)> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| to_ycbcr(canvas.pixel_at(x, y).to_srgb_u8()))
+            .collect();
+        self.out.write_all(b"FRAME\n")?;
+        self.out
+            .write_all(&pixels.iter().map(|p| p.0).collect::<Vec<u8>>())?;
+        self.out
+            .write_all(&pixels.iter().map(|p| p.1).collect::<Vec<u8>>())?;
+        self.out
+            .write_all(&pixels.iter().map(|p| p.2).collect::<Vec<u8>>())
+    }
+
+    /// Finish the stream, handing back what it was written to.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+}
+
+/// An 8-bit sRGB colour as studio-range Y'CbCr, with luma from 16 to 235 and chroma from 16 to
+/// 240 about 128, using the BT.709 coefficients.
+fn to_ycbcr((r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let cb = (b - y) / 1.8556;
+    let cr = (r - y) / 1.5748;
+
+    (
+        (16.0 + 219.0 * y).round() as u8,
+        (128.0 + 224.0 * cb).round() as u8,
+        (128.0 + 224.0 * cr).round() as u8,
+    )
+}
+
+/// An `ffmpeg` process encoding the frames written to it into a video file, with the format
+/// chosen from the file's extension.
+pub struct FfmpegEncoder {
+    child: Child,
+    writer: Y4mWriter<ChildStdin>,
+}
+
+impl FfmpegEncoder {
+    /// Start `ffmpeg` writing to `output`, replacing it if it exists. Fails if `ffmpeg` can't be
+    /// run.
+    pub fn start(output: &Path, width: usize, height: usize, fps: u32) -> Result<Self, Error> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-y", "-f", "yuv4mpegpipe", "-i", "-"])
+            // Most players only manage 4:2:0
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::new(e.kind(), format!("can't run ffmpeg: {}", e)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+
+        Ok(Self {
+            child,
+            writer: Y4mWriter::new(stdin, width, height, fps),
+        })
+    }
+
+    pub fn write_frame(&mut self, canvas: &Canvas) -> Result<(), Error> {
+        self.writer.write_frame(canvas)
+    }
+
+    /// Close the stream and wait for `ffmpeg` to finish the file.
+    pub fn finish(mut self) -> Result<(), Error> {
+        drop(self.writer.finish()?);
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(Error::other(format!("ffmpeg failed with {}", status)));
+        }
+
+        Ok(())
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use super::*;
+    use crate::color::Color;
+
+    #[rstest]
+    #[case((0, 0, 0), (16, 128, 128))]
+    #[case((255, 255, 255), (235, 128, 128))]
+    #[case((255, 0, 0), (63, 102, 240))]
+    #[case((0, 0, 255), (32, 240, 118))]
+    fn converting_to_ycbcr(#[case] rgb: (u8, u8, u8), #[case] expected: (u8, u8, u8)) {
+        assert_that!(to_ycbcr(rgb)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn writing_frames() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(1, 0, &Color::white());
+        let mut writer = Y4mWriter::new(Vec::new(), 2, 1, 24);
+
+        writer.write_frame(&canvas).unwrap();
+        writer.write_frame(&canvas).unwrap();
+
+        let bytes = writer.finish().unwrap();
+        let header = b"YUV4MPEG2 W2 H1 F24:1 Ip A1:1 C444\n";
+        let frame = [b"FRAME\n".as_slice(), &[16, 235, 128, 128, 128, 128]].concat();
+        assert_that!(bytes).is_equal_to([header.as_slice(), &frame, &frame].concat());
+    }
+
+    #[test]
+    fn a_frame_of_the_wrong_size_is_refused() {
+        let mut writer = Y4mWriter::new(Vec::new(), 2, 2, 24);
+
+        let result = writer.write_frame(&Canvas::new(2, 1));
+
+        assert_that!(result.map_err(|e| e.kind())).is_equal_to(Err(ErrorKind::InvalidInput));
+    }
+}
@@ -0,0 +1,134 @@
+//! Noticing when a file is saved, so a scene can be rendered again as soon as it changes.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Keeps an eye on one file by looking at when it was last modified. Editors that save by
+/// writing a new file and renaming it over the old one are noticed too, and a file that goes
+/// missing for a moment part way through a save is not counted as a change until it is back.
+pub struct FileWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    /// Start watching `path` as it is now; only later changes are reported.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let last_modified = modified(&path).ok();
+
+        Self {
+            path,
+            last_modified,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file has been modified since it was last looked at. Errors other than the
+    /// file not being there are passed on.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        let now = match modified(&self.path) {
+            Ok(time) => time,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let changed = self.last_modified != Some(now);
+        self.last_modified = Some(now);
+
+        Ok(changed)
+    }
+
+    /// Block until the file changes, looking at it every `interval`.
+    pub fn wait(&mut self, interval: Duration) -> io::Result<()> {
+        while !self.poll()? {
+            thread::sleep(interval);
+        }
+
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use spectral::prelude::*;
+
+    use super::*;
+
+    /// A path of its own in the temporary directory for each test, with nothing there yet.
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("watch-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+
+    fn touch(path: &Path, seconds: u64) {
+        let file = File::create(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn an_untouched_file_has_not_changed() {
+        let path = scratch("untouched");
+        touch(&path, 1000);
+        let mut watcher = FileWatcher::new(&path);
+
+        assert_that!(watcher.poll().unwrap()).is_false();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn saving_a_file_is_a_change_seen_once() {
+        let path = scratch("saved");
+        touch(&path, 1000);
+        let mut watcher = FileWatcher::new(&path);
+
+        touch(&path, 2000);
+
+        assert_that!(watcher.poll().unwrap()).is_true();
+        assert_that!(watcher.poll().unwrap()).is_false();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_file_is_a_change_once_it_appears() {
+        let path = scratch("missing");
+        let mut watcher = FileWatcher::new(&path);
+
+        assert_that!(watcher.poll().unwrap()).is_false();
+        touch(&path, 1000);
+
+        assert_that!(watcher.poll().unwrap()).is_true();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn waiting_returns_once_the_file_has_changed() {
+        let path = scratch("waited");
+        touch(&path, 1000);
+        let mut watcher = FileWatcher::new(&path);
+        touch(&path, 2000);
+
+        watcher.wait(Duration::from_millis(1)).unwrap();
+
+        assert_that!(watcher.path()).is_equal_to(path.as_path());
+        fs::remove_file(&path).unwrap();
+    }
+}
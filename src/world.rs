@@ -1,300 +1,2534 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::mem;
 use std::rc::Rc;
 
+use crate::bounds::BoundingBox;
+use crate::clip::ClipPlane;
 use crate::color::Color;
+use crate::environment::Environment;
+use crate::filter;
+use crate::group::GroupBuilder;
 use crate::intersection::{Computations, Intersection, Intersections};
-use crate::light::PointLight;
+use crate::light::{PointLight, ALL_LIGHT_GROUPS};
+use crate::material::MaterialBuilder;
+use crate::matrix::Matrix;
+use crate::probe::ReflectionProbe;
 use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::settings::{LightSampling, RenderSettings};
 use crate::shape::Shape;
+use crate::sphere::SphereBuilder;
+use crate::transform::Transform;
 use crate::vector4::Vector4;
 
+/// Identifies an object in a `World`. Ids stay valid while other objects are added and removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId(pub(crate) usize);
+
+/// Something wrong with a world that would spoil a render of it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneProblem {
+    /// A shape, named if it has a name, whose transform can't be undone or isn't finite.
+    BadTransform(Option<String>),
+    /// The light at this index has a position or intensity that isn't finite.
+    BadLight(usize),
+    /// There is nothing to light the scene, so everything would be black.
+    NoLights,
+}
+
+impl Display for SceneProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneProblem::BadTransform(Some(name)) => {
+                write!(f, "the transform of {} is not invertible", name)
+            }
+            SceneProblem::BadTransform(None) => {
+                write!(f, "the transform of an unnamed shape is not invertible")
+            }
+            SceneProblem::BadLight(index) => {
+                write!(f, "light {} has a position or intensity that is not finite", index)
+            }
+            SceneProblem::NoLights => write!(f, "there are no lights"),
+        }
+    }
+}
+
+/// How much there is in a world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneSummary {
+    /// The objects at the top level, including groups.
+    pub objects: usize,
+    /// Every shape with a surface, including those inside groups.
+    pub shapes: usize,
+    pub lights: usize,
+    /// The box enclosing every object with finite bounds.
+    pub bounds: BoundingBox,
+}
+
+impl Display for SceneSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} objects ({} shapes), {} lights",
+            self.objects, self.shapes, self.lights
+        )?;
+        if self.bounds.is_bounded() {
+            let (min, max) = (self.bounds.min, self.bounds.max);
+            write!(
+                f,
+                "bounds ({:.2}, {:.2}, {:.2}) to ({:.2}, {:.2}, {:.2})",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )
+        } else {
+            write!(f, "no bounded objects")
+        }
+    }
+}
+
+/// What a world is made of and roughly how much memory it takes, for judging whether a large
+/// scene is worth starting to render.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneStats {
+    /// The shapes with a surface, including those inside groups, counted by type.
+    pub shapes: BTreeMap<&'static str, usize>,
+    pub triangles: usize,
+    /// Groups at every level, nulls included.
+    pub groups: usize,
+    /// The most groups nested inside one another.
+    pub depth: usize,
+    /// Bytes of image data in textures and the environment, counting shared textures once.
+    pub texture_bytes: usize,
+    /// A rough count of the bytes taken by the shapes and textures, counting shapes shared by
+    /// instances once.
+    pub estimated_bytes: usize,
+}
+
+impl Display for SceneStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let counts: Vec<String> = self
+            .shapes
+            .iter()
+            .map(|(name, count)| format!("{} {}", count, name))
+            .collect();
+        writeln!(
+            f,
+            "{} shapes: {}",
+            self.shapes.values().sum::<usize>(),
+            counts.join(", ")
+        )?;
+        writeln!(
+            f,
+            "{} triangles, {} groups nested {} deep",
+            self.triangles, self.groups, self.depth
+        )?;
+        write!(
+            f,
+            "textures {}, about {} in all",
+            in_units(self.texture_bytes),
+            in_units(self.estimated_bytes)
+        )
+    }
+}
+
+/// `bytes` in the largest unit that keeps it at least 1, such as "2.5 MB".
+fn in_units(bytes: usize) -> String {
+    let units = ["bytes", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
 pub struct World {
     objects: Vec<Rc<dyn Shape>>,
-    light_source: PointLight,
+    object_ids: Vec<ObjectId>, // Note: kept in step with `objects`
+    parents: Vec<Option<ObjectId>>, // Note: kept in step with `objects`
+    light_links: Vec<u32>,          // Note: kept in step with `objects`
+    next_object_id: usize,
+    lights: Vec<PointLight>,
+    environment: Option<Rc<dyn Environment>>,
+    clip_planes: Vec<ClipPlane>,
+    probes: Vec<ReflectionProbe>,
+    // The index of the top-level object that last blocked each light, tried first by the next
+    // shadow ray towards that light, since neighbouring points are usually shadowed by the same
+    // thing
+    shadow_hints: RefCell<Vec<Option<usize>>>,
+}
+
+pub struct WorldBuilder {
+    objects: Vec<Rc<dyn Shape>>,
+    lights: Vec<PointLight>,
+    environment: Option<Rc<dyn Environment>>,
+    clip_planes: Vec<ClipPlane>,
 }
 
-pub struct WorldBuilder {
-    objects: Vec<Rc<dyn Shape>>,
-    light_source: PointLight,
-}
+impl World {
+    /// The book's default world: two concentric spheres lit by the default light.
+    pub fn default_world() -> Self {
+        WorldBuilder::new().with_default_objects().build()
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Intersections {
+        let mut found = Intersections::default().with_range(ray.t_min, ray.t_max);
+
+        for o in self.objects.iter() {
+            self.intersect_object(o, ray, &mut found);
+        }
+
+        found
+    }
+
+    /// Does the ray hit anything in front of it before `t_max`? Stops at the first such hit
+    /// instead of collecting and sorting every intersection along the ray.
+    pub fn any_hit_before(&self, ray: &Ray, t_max: f32) -> bool {
+        let ray = ray.with_range(ray.t_min, t_max.min(ray.t_max));
+
+        self.objects.iter().any(|o| self.object_hit(o, &ray))
+    }
+
+    /// Does the ray hit the object, or a shape in it, anywhere in its range?
+    fn object_hit(&self, object: &Rc<dyn Shape>, ray: &Ray) -> bool {
+        let children = object.children();
+        if children.is_empty() {
+            self.clipped_hits(object, ray)
+                .iter()
+                .any(|&(t, _)| ray.in_range(t))
+        } else {
+            children.iter().any(|child| self.object_hit(child, ray))
+        }
+    }
+
+    /// Groups are intersected through their children so each hit refers to the shape actually hit.
+    fn intersect_object(&self, object: &Rc<dyn Shape>, ray: &Ray, found: &mut Intersections) {
+        let children = object.children();
+        if children.is_empty() {
+            for (t, cap) in self.clipped_hits(object, ray).into_iter() {
+                let shape = match cap {
+                    Some(plane) => self.clip_planes[plane].cap(object),
+                    None => Rc::clone(object),
+                };
+                found.push(Intersection::new(t, shape));
+            }
+        } else {
+            for child in children {
+                self.intersect_object(child, ray, found);
+            }
+        }
+    }
+
+    /// The shape's hits along the ray that the clipping planes leave, each with the index of the
+    /// plane if it is on a cap, where a capped plane cuts through the shape.
+    fn clipped_hits(&self, object: &Rc<dyn Shape>, ray: &Ray) -> Vec<(f32, Option<usize>)> {
+        let kept = |t: f32| !self.clip_planes.iter().any(|p| p.clips(ray.position(t)));
+        let mut hits: Vec<(f32, Option<usize>)> = Self::front_hits(object, ray)
+            .into_iter()
+            .filter(|&t| self.clip_planes.is_empty() || kept(t))
+            .map(|t| (t, None))
+            .collect();
+
+        for (index, plane) in self.clip_planes.iter().enumerate() {
+            if !plane.capped() {
+                continue;
+            }
+            let Some(t) = plane.crossing(ray) else {
+                continue;
+            };
+            // The ray is inside a closed shape where it has crossed its surface an odd number
+            // of times. The cap is only seen if no other plane clips it.
+            let crossings = object.intersect(ray).iter().filter(|&&x| x < t).count();
+            let others_keep = self
+                .clip_planes
+                .iter()
+                .enumerate()
+                .all(|(i, p)| i == index || !p.clips(ray.position(t)));
+            if crossings % 2 == 1 && others_keep {
+                hits.push((t, Some(index)));
+            }
+        }
+
+        hits
+    }
+
+    /// The shape's intersections with the ray, leaving out any on the back of a single-sided
+    /// surface.
+    fn front_hits(object: &Rc<dyn Shape>, ray: &Ray) -> Vec<f32> {
+        let mut xs = object.intersect(ray);
+        if !object.material().double_sided() {
+            xs.retain(|&t| object.normal_at(&ray.position(t)).dot(&ray.direction) < 0.0);
+        }
+
+        xs
+    }
+
+    pub fn shade_hit(
+        &self,
+        comps: Computations,
+        settings: &RenderSettings,
+        remaining: usize,
+    ) -> Color {
+        let (direct, indirect) = self.shade_hit_split(comps, settings, remaining);
+
+        direct + indirect
+    }
+
+    /// The light reaching the eye from a hit, split into the light falling straight on the surface
+    /// and the light reflected or refracted onto it from elsewhere.
+    pub fn shade_hit_split(
+        &self,
+        comps: Computations,
+        settings: &RenderSettings,
+        remaining: usize,
+    ) -> (Color, Color) {
+        let surface = self.direct_light(&comps, settings) + self.environment_light(&comps);
+        let reflected = self.reflected_color(&comps, settings, remaining);
+        let refracted = self.refracted_color(&comps, settings, remaining);
+
+        let material = comps.object.material();
+        if material.reflective() > 0.0 && material.transparency() > 0.0 {
+            let reflectance = comps.schlick();
+            return (surface, reflected * reflectance + refracted * (1.0 - reflectance));
+        }
+
+        (surface, reflected + refracted)
+    }
+
+    pub fn color_at(&self, ray: &Ray) -> Color {
+        self.color_at_with(ray, &RenderSettings::default())
+    }
+
+    pub fn color_at_with(&self, ray: &Ray, settings: &RenderSettings) -> Color {
+        self.color_at_depth(ray, settings, settings.max_depth)
+    }
+
+    /// The colour seen along a camera ray, and whether the ray hit anything at all.
+    pub fn sample(&self, ray: &Ray, settings: &RenderSettings) -> (Color, bool) {
+        let intersections = self.intersect(ray);
+        match intersections.hit() {
+            Some(hit) => {
+                let comps =
+                    hit.prepare_computations_biased(ray, &intersections, &settings.shadow_bias);
+                let color = self.shade_hit(comps, settings, settings.max_depth);
+                (settings.clamp_sample(color), true)
+            }
+            None => (settings.clamp_sample(self.miss_color(ray, settings)), false),
+        }
+    }
+
+    /// The colour seen along `ray`, following at most `remaining` reflections or refractions.
+    pub fn color_at_depth(&self, ray: &Ray, settings: &RenderSettings, remaining: usize) -> Color {
+        let intersections = self.intersect(ray);
+        if let Some(hit) = intersections.hit() {
+            let comps = hit.prepare_computations_biased(ray, &intersections, &settings.shadow_bias);
+            return self.shade_hit(comps, settings, remaining);
+        }
+
+        self.miss_color(ray, settings)
+    }
+
+    /// The colour of a ray that hits nothing: the environment if there is one, else the background.
+    pub fn miss_color(&self, ray: &Ray, settings: &RenderSettings) -> Color {
+        match &self.environment {
+            Some(environment) => environment.color_in_direction(ray.direction),
+            None => settings.background,
+        }
+    }
+
+    /// The light reaching a surface straight from the lights, from all of them or from a sample
+    /// weighted to give the same result on average.
+    fn direct_light(&self, comps: &Computations, settings: &RenderSettings) -> Color {
+        let links = self.light_links_of(&comps.object);
+        let light_at = |index: usize| {
+            let light = &self.lights[index];
+            if light.groups & links == 0 {
+                return Color::black();
+            }
+            let lit = |in_shadow| {
+                comps
+                    .object
+                    .lighting(light, comps.point, comps.eye_vector, comps.normal_vector, in_shadow)
+            };
+            match self.light_visibility(index, &comps.over_point, settings) {
+                v if v >= 1.0 => lit(false),
+                v if v <= 0.0 => lit(true),
+                v => lit(false) * v + lit(true) * (1.0 - v),
+            }
+        };
+        let (count, weights): (usize, Vec<f32>) = match settings.light_sampling {
+            LightSampling::Uniform(count) => (count, vec![1.0; self.lights.len()]),
+            LightSampling::Power(count) => (
+                count,
+                self.lights.iter().map(|l| l.intensity.luminance()).collect(),
+            ),
+            LightSampling::All => (self.lights.len(), vec![]),
+        };
+        if count == 0 || count >= self.lights.len() {
+            return (0..self.lights.len()).map(light_at).sum();
+        }
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return Color::black();
+        }
+
+        let mut rng = Rng::new(Self::seed(comps.point, settings));
+        (0..count)
+            .map(|_| {
+                let mut target = rng.next_f32() * total;
+                let index = weights
+                    .iter()
+                    .position(|w| {
+                        target -= w;
+                        target < 0.0
+                    })
+                    .unwrap_or(weights.len() - 1);
+                light_at(index) * (total / (weights[index] * count as f32))
+            })
+            .sum()
+    }
+
+    /// The ambient light the environment sheds on a surface. The environment is looked up along
+    /// the normal, a cheap stand-in for gathering it from the whole hemisphere above the surface.
+    fn environment_light(&self, comps: &Computations) -> Color {
+        let Some(environment) = &self.environment else {
+            return Color::black();
+        };
+
+        // Lighting a point that is in shadow leaves just the ambient term
+        let sky = PointLight::new(
+            comps.over_point + comps.normal_vector,
+            environment.color_in_direction(comps.normal_vector),
+        );
+        comps.object.lighting(
+            &sky,
+            comps.point,
+            comps.eye_vector,
+            comps.normal_vector,
+            true,
+        )
+    }
+
+    pub fn reflected_color(
+        &self,
+        comps: &Computations,
+        settings: &RenderSettings,
+        remaining: usize,
+    ) -> Color {
+        let reflective = comps.object.material().reflective();
+        if remaining == 0 || reflective == 0.0 {
+            return Color::black();
+        }
+
+        if settings.reflection_probes {
+            if let Some(probe) = self.nearest_probe(comps.over_point) {
+                return probe.color_in_direction(comps.reflect_vector) * reflective;
+            }
+        }
+
+        let Some(boost) = self.roulette(comps, settings, remaining, reflective) else {
+            return Color::black();
+        };
+
+        let reflect_ray = Ray::new(comps.over_point, comps.reflect_vector);
+        self.color_at_depth(&reflect_ray, settings, remaining - 1) * reflective * boost
+    }
+
+    pub fn refracted_color(
+        &self,
+        comps: &Computations,
+        settings: &RenderSettings,
+        remaining: usize,
+    ) -> Color {
+        let transparency = comps.object.material().transparency();
+        if remaining == 0 || transparency == 0.0 {
+            return Color::black();
+        }
+        let Some(boost) = self.roulette(comps, settings, remaining, transparency) else {
+            return Color::black();
+        };
+
+        let color = match comps.dispersion {
+            None => self.refracted_color_between(comps, comps.n1, comps.n2, settings, remaining),
+            Some(indices) => {
+                // Follow red, green and blue separately, keeping each one's own channel
+                let [red, green, blue] = indices.map(|(n1, n2)| {
+                    self.refracted_color_between(comps, n1, n2, settings, remaining)
+                });
+                Color::new(red.r, green.g, blue.b)
+            }
+        };
+
+        color * transparency * boost
+    }
+
+    /// Whether a secondary ray that adds `weight` of its colour survives Russian roulette, and if
+    /// so how much to brighten it by to make up for those that don't.
+    fn roulette(
+        &self,
+        comps: &Computations,
+        settings: &RenderSettings,
+        remaining: usize,
+        weight: f32,
+    ) -> Option<f32> {
+        let Some(roulette) = settings.roulette else {
+            return Some(1.0);
+        };
+        let depth = settings.max_depth.saturating_sub(remaining);
+        let survival = weight.clamp(roulette.min_survival, roulette.max_survival);
+        if depth < roulette.start_depth || survival >= 1.0 {
+            return Some(1.0);
+        }
+
+        let mut rng = Rng::with_stream(Self::seed(comps.point, settings), remaining as u64);
+        (rng.next_f32() < survival).then(|| 1.0 / survival)
+    }
+
+    /// A seed for the random choices made at `point`, so that renders are repeatable.
+    fn seed(point: Vector4, settings: &RenderSettings) -> u64 {
+        [point.x, point.y, point.z]
+            .iter()
+            .fold(settings.frame_seed(), |hash, v| hash.rotate_left(21) ^ v.to_bits() as u64)
+    }
+
+    fn refracted_color_between(
+        &self,
+        comps: &Computations,
+        n1: f32,
+        n2: f32,
+        settings: &RenderSettings,
+        remaining: usize,
+    ) -> Color {
+        // Snell's law: check for total internal reflection
+        let n_ratio = n1 / n2;
+        let cos_i = comps.eye_vector.dot(&comps.normal_vector);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+        if sin2_t > 1.0 {
+            return Color::black();
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = comps.normal_vector * (n_ratio * cos_i - cos_t) - comps.eye_vector * n_ratio;
+        let refract_ray = Ray::new(comps.under_point, direction);
+
+        self.color_at_depth(&refract_ray, settings, remaining - 1)
+    }
+
+    pub fn objects(&self) -> &[Rc<dyn Shape>] {
+        &self.objects
+    }
+
+    /// The box enclosing every object that has finite bounds. Planes and the like are left out.
+    pub fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for object in &self.objects {
+            let object_bounds = object.parent_space_bounds();
+            if object_bounds.is_bounded() {
+                bounds.merge(&object_bounds);
+            }
+        }
+
+        bounds
+    }
+
+    /// Look for anything that would spoil a render, without rendering.
+    pub fn check(&self) -> Vec<SceneProblem> {
+        fn check_shape(shape: &Rc<dyn Shape>, problems: &mut Vec<SceneProblem>) {
+            let inverse = shape.inv_transform();
+            let finite = (0..4).all(|row| (0..4).all(|col| inverse[[row, col]].is_finite()));
+            if !finite || !inverse.is_invertible() {
+                problems.push(SceneProblem::BadTransform(shape.name().map(String::from)));
+            }
+            for child in shape.children() {
+                check_shape(child, problems);
+            }
+        }
+
+        let mut problems = Vec::new();
+        for object in &self.objects {
+            check_shape(object, &mut problems);
+        }
+        if self.lights.is_empty() {
+            problems.push(SceneProblem::NoLights);
+        }
+        for (index, light) in self.lights.iter().enumerate() {
+            let p = light.position;
+            let i = light.intensity;
+            if ![p.x, p.y, p.z, i.r, i.g, i.b].iter().all(|v| v.is_finite()) {
+                problems.push(SceneProblem::BadLight(index));
+            }
+        }
+
+        problems
+    }
+
+    pub fn summary(&self) -> SceneSummary {
+        fn shapes(shape: &Rc<dyn Shape>) -> usize {
+            match shape.children() {
+                // Only empty groups, such as nulls, have no bounds at all
+                [] if shape.bounds().is_empty() => 0,
+                [] => 1,
+                children => children.iter().map(shapes).sum(),
+            }
+        }
+
+        SceneSummary {
+            objects: self.objects.len(),
+            shapes: self.objects.iter().map(shapes).sum(),
+            lights: self.lights.len(),
+            bounds: self.bounds(),
+        }
+    }
+
+    /// Count what the world is made of and estimate the memory it takes.
+    pub fn stats(&self) -> SceneStats {
+        fn visit(
+            shape: &Rc<dyn Shape>,
+            depth: usize,
+            stats: &mut SceneStats,
+            patterns: &mut HashSet<*const ()>,
+        ) {
+            let pattern = shape.material().pattern();
+            if patterns.insert(Rc::as_ptr(pattern) as *const ()) {
+                stats.texture_bytes += pattern.texture_memory();
+            }
+
+            let children = shape.children();
+            // Only empty groups, such as nulls, have no bounds at all
+            if !children.is_empty() || shape.bounds().is_empty() {
+                stats.groups += 1;
+                stats.depth = stats.depth.max(depth + 1);
+                for child in children {
+                    visit(child, depth + 1, stats, patterns);
+                }
+            } else {
+                *stats.shapes.entry(shape.type_name()).or_default() += 1;
+            }
+        }
+
+        let mut stats = SceneStats::default();
+        let mut patterns = HashSet::new();
+        let mut counted = HashSet::new();
+        for object in &self.objects {
+            visit(object, 0, &mut stats, &mut patterns);
+            stats.triangles += object.triangle_count();
+            // The shape itself, and the pointer and counts of the `Rc` holding it
+            stats.estimated_bytes += object.memory_size(&mut counted) + 3 * mem::size_of::<usize>();
+        }
+        if let Some(environment) = &self.environment {
+            stats.texture_bytes += environment.texture_memory();
+        }
+        stats.estimated_bytes += stats.texture_bytes;
+
+        stats
+    }
+
+    pub fn object(&self, id: ObjectId) -> Option<&Rc<dyn Shape>> {
+        self.position_of(id).map(|index| &self.objects[index])
+    }
+
+    pub fn object_by_name(&self, name: &str) -> Option<&Rc<dyn Shape>> {
+        self.objects.iter().find(|o| o.name() == Some(name))
+    }
+
+    /// The id of `object`, or of the top-level group that contains it.
+    pub fn object_id(&self, object: &Rc<dyn Shape>) -> Option<ObjectId> {
+        let index = self.objects.iter().position(|o| Self::contains(o, object))?;
+        Some(self.object_ids[index])
+    }
+
+    pub fn add_object(&mut self, object: Rc<dyn Shape>) -> ObjectId {
+        let id = ObjectId(self.next_object_id);
+        self.next_object_id += 1;
+        self.objects.push(object);
+        self.object_ids.push(id);
+        self.parents.push(None);
+        self.light_links.push(ALL_LIGHT_GROUPS);
+
+        id
+    }
+
+    /// Add a named node with no surface of its own, for other objects to be parented to.
+    pub fn add_null(&mut self, name: &str) -> ObjectId {
+        self.add_object(Rc::new(GroupBuilder::new().with_name(name).build()))
+    }
+
+    /// Remove an object. Anything parented to it is handed on to its own parent.
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<Rc<dyn Shape>> {
+        let index = self.position_of(id)?;
+        self.object_ids.remove(index);
+        self.light_links.remove(index);
+        let parent = self.parents.remove(index);
+        for p in self.parents.iter_mut().filter(|p| **p == Some(id)) {
+            *p = parent;
+        }
+
+        Some(self.objects.remove(index))
+    }
+
+    /// Swap the object with the given id for another, which keeps the id. Returns the old object.
+    pub fn replace_object(&mut self, id: ObjectId, object: Rc<dyn Shape>) -> Option<Rc<dyn Shape>> {
+        let index = self.position_of(id)?;
+
+        Some(std::mem::replace(&mut self.objects[index], object))
+    }
+
+    pub fn parent(&self, id: ObjectId) -> Option<ObjectId> {
+        self.parents[self.position_of(id)?]
+    }
+
+    /// The objects parented directly to the given one.
+    pub fn children_of(&self, id: ObjectId) -> Vec<ObjectId> {
+        self.object_ids
+            .iter()
+            .zip(self.parents.iter())
+            .filter(|(_, &parent)| parent == Some(id))
+            .map(|(&child, _)| child)
+            .collect()
+    }
+
+    /// Parent `child` to `parent`, or detach it with `None`, so that transforming the parent
+    /// carries the child along. This is separate from grouping: both stay objects in their own
+    /// right. Returns false if either isn't in the world or the link would make a loop.
+    pub fn set_parent(&mut self, child: ObjectId, parent: Option<ObjectId>) -> bool {
+        let Some(index) = self.position_of(child) else {
+            return false;
+        };
+        if let Some(parent) = parent {
+            if self.position_of(parent).is_none() || self.ancestors(parent).any(|a| a == child) {
+                return false;
+            }
+        }
+
+        self.parents[index] = parent;
+
+        true
+    }
+
+    /// Apply `transform` on top of the object's own, and of everything parented to it, however
+    /// deeply. Returns false if the object isn't in the world.
+    pub fn transform_object(&mut self, id: ObjectId, transform: &Matrix<4>) -> bool {
+        let Some(index) = self.position_of(id) else {
+            return false;
+        };
+
+        self.objects[index] = self.objects[index].transformed(transform);
+        for child in self.children_of(id) {
+            self.transform_object(child, transform);
+        }
+
+        true
+    }
+
+    /// The light groups whose lights illuminate the object.
+    pub fn light_links(&self, id: ObjectId) -> Option<u32> {
+        Some(self.light_links[self.position_of(id)?])
+    }
+
+    /// Link the object to the light groups in the `groups` bitmask, so that only lights in one
+    /// of them light it or see it as an occluder. Returns false if the object isn't in the world.
+    pub fn set_light_links(&mut self, id: ObjectId, groups: u32) -> bool {
+        let Some(index) = self.position_of(id) else {
+            return false;
+        };
+
+        self.light_links[index] = groups;
+
+        true
+    }
+
+    /// Flatten every group so that it holds its shapes directly instead of through nested groups,
+    /// and remove top-level groups with nothing in them. Group transforms are already baked into
+    /// their children, so this only shortens the hierarchy each ray walks; the image is the same.
+    /// Named groups, and groups that other objects are parented to, are kept even when empty.
+    pub fn flatten(&mut self) {
+        for object in self.objects.iter_mut() {
+            if let Some(flat) = object.flattened() {
+                *object = flat;
+            }
+        }
+
+        let empty: Vec<ObjectId> = self
+            .objects
+            .iter()
+            .zip(self.object_ids.iter())
+            .filter(|(o, _)| o.children().is_empty() && o.name().is_none())
+            .filter(|(o, &id)| o.flattened().is_some() && self.children_of(id).is_empty())
+            .map(|(_, &id)| id)
+            .collect();
+        for id in empty {
+            self.remove_object(id);
+        }
+    }
+
+    pub fn clip_planes(&self) -> &[ClipPlane] {
+        &self.clip_planes
+    }
+
+    pub fn add_clip_plane(&mut self, plane: ClipPlane) -> usize {
+        self.clip_planes.push(plane);
+
+        self.clip_planes.len() - 1
+    }
+
+    pub fn remove_clip_plane(&mut self, index: usize) -> Option<ClipPlane> {
+        if index >= self.clip_planes.len() {
+            return None;
+        }
+
+        Some(self.clip_planes.remove(index))
+    }
+
+    pub fn environment(&self) -> Option<&Rc<dyn Environment>> {
+        self.environment.as_ref()
+    }
+
+    pub fn set_environment(&mut self, environment: Option<Rc<dyn Environment>>) {
+        self.environment = environment;
+    }
+
+    pub fn probes(&self) -> &[ReflectionProbe] {
+        &self.probes
+    }
+
+    /// Capture a reflection probe `size` pixels square at each of `positions`, for reflections to
+    /// be looked up in when `RenderSettings::reflection_probes` is set. Probes already in the
+    /// world are seen by the new ones only if `settings` uses them.
+    pub fn capture_probes(
+        &mut self,
+        positions: &[Vector4],
+        size: usize,
+        settings: &RenderSettings,
+    ) {
+        let probes: Vec<ReflectionProbe> = positions
+            .iter()
+            .map(|&position| ReflectionProbe::capture(self, position, size, settings))
+            .collect();
+        self.probes.extend(probes);
+    }
+
+    /// The probe closest to `point`, if there are any.
+    fn nearest_probe(&self, point: Vector4) -> Option<&ReflectionProbe> {
+        let distance = |probe: &&ReflectionProbe| (probe.position() - point).magnitude();
+        self.probes
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+    }
+
+    pub fn lights(&self) -> &[PointLight] {
+        &self.lights
+    }
+
+    pub fn light(&self, index: usize) -> Option<&PointLight> {
+        self.lights.get(index)
+    }
+
+    pub fn light_mut(&mut self, index: usize) -> Option<&mut PointLight> {
+        self.lights.get_mut(index)
+    }
+
+    pub fn add_light(&mut self, light: PointLight) -> usize {
+        self.lights.push(light);
+
+        self.lights.len() - 1
+    }
+
+    pub fn remove_light(&mut self, index: usize) -> Option<PointLight> {
+        if index >= self.lights.len() {
+            return None;
+        }
+
+        Some(self.lights.remove(index))
+    }
+
+    /// Is anything that casts a shadow in the way between `from` and `to`? Stops at the first such
+    /// thing, and skips shapes whose material casts no shadow without intersecting them.
+    pub fn occluded(&self, from: Vector4, to: Vector4) -> bool {
+        let r = Self::segment(from, to);
+
+        self.objects.iter().any(|o| self.object_occludes(o, &r))
+    }
+
+    pub(crate) fn is_shadowed(&self, light: &PointLight, point: &Vector4) -> bool {
+        self.is_shadowed_towards(light, point, light.position)
+    }
+
+    /// Whether `target`, a point on `light`, is blocked from `point`.
+    fn is_shadowed_towards(&self, light: &PointLight, point: &Vector4, target: Vector4) -> bool {
+        let r = Self::segment(*point, target);
+        (0..self.objects.len()).any(|index| self.blocks(index, light, &r))
+    }
+
+    /// Whether the light at `index` is blocked from `point`, trying the object that last blocked
+    /// it first. The answer is the same as `is_shadowed` gives; it just comes sooner when
+    /// neighbouring points are shadowed by the same object.
+    #[cfg(test)]
+    pub(crate) fn is_shadowed_from(&self, index: usize, point: &Vector4) -> bool {
+        self.is_shadowed_from_towards(index, point, self.lights[index].position)
+    }
+
+    fn is_shadowed_from_towards(&self, index: usize, point: &Vector4, target: Vector4) -> bool {
+        let light = &self.lights[index];
+        let r = Self::segment(*point, target);
+        let hint = self.shadow_hints.borrow().get(index).copied().flatten();
+        if let Some(blocker) = hint {
+            if blocker < self.objects.len() && self.blocks(blocker, light, &r) {
+                return true;
+            }
+        }
+
+        let blocker = (0..self.objects.len())
+            .filter(|&i| Some(i) != hint)
+            .find(|&i| self.blocks(i, light, &r));
+        if blocker.is_some() {
+            let mut hints = self.shadow_hints.borrow_mut();
+            if hints.len() <= index {
+                hints.resize(self.lights.len(), None);
+            }
+            hints[index] = blocker;
+        }
+
+        blocker.is_some()
+    }
+
+    /// How much of the light at `index` can be seen from `point`, from 0 when all of it is hidden
+    /// to 1 when none of it is. A light with no radius is either seen or not. One with a radius
+    /// is checked at `shadow_samples` points spread across it, so its shadows soften at the edges.
+    pub(crate) fn light_visibility(
+        &self,
+        index: usize,
+        point: &Vector4,
+        settings: &RenderSettings,
+    ) -> f32 {
+        let light = &self.lights[index];
+        let shadowed = |target: Vector4| {
+            if settings.shadow_cache {
+                self.is_shadowed_from_towards(index, point, target)
+            } else {
+                self.is_shadowed_towards(light, point, target)
+            }
+        };
+        if light.radius <= 0.0 || light.shadow_samples == 0 {
+            return if shadowed(light.position) { 0.0 } else { 1.0 };
+        }
+
+        // A Halton pattern across the light, shifted differently at each point so the pattern
+        // doesn't show as banding in the penumbra
+        let mut rng = Rng::with_stream(Self::seed(*point, settings), index as u64);
+        let rotation = (rng.next_f32(), rng.next_f32());
+        let visible = (0..light.shadow_samples as u32)
+            .map(|i| filter::sample_offset(i, rotation))
+            .filter(|&(u, v)| !shadowed(light.sample_position(*point, u, v)))
+            .count();
+
+        visible as f32 / light.shadow_samples as f32
+    }
+
+    /// The ray from `from` towards `to`, seeing only what is between them.
+    fn segment(from: Vector4, to: Vector4) -> Ray {
+        let v = to - from;
+
+        Ray::new(from, v.normalize()).with_range(0.0, v.magnitude())
+    }
+
+    /// Does the top-level object at `index` cast a shadow from `light` across `ray`'s range?
+    fn blocks(&self, index: usize, light: &PointLight, ray: &Ray) -> bool {
+        self.light_links[index] & light.groups != 0
+            && self.object_occludes(&self.objects[index], ray)
+    }
+
+    /// Does the object, or a shape in it that casts shadows, cross the ray within its range?
+    fn object_occludes(&self, object: &Rc<dyn Shape>, ray: &Ray) -> bool {
+        let children = object.children();
+        if children.is_empty() {
+            object.material().casts_shadow() && self.object_hit(object, ray)
+        } else {
+            children
+                .iter()
+                .any(|child| self.object_occludes(child, ray))
+        }
+    }
+
+    /// The light links of the top-level object that `object` is, or is part of.
+    pub(crate) fn light_links_of(&self, object: &Rc<dyn Shape>) -> u32 {
+        // Skip the search when nothing has been linked
+        if self.light_links.iter().all(|&l| l == ALL_LIGHT_GROUPS) {
+            return ALL_LIGHT_GROUPS;
+        }
+
+        self.objects
+            .iter()
+            .position(|o| Self::contains(o, object))
+            .map_or(ALL_LIGHT_GROUPS, |index| self.light_links[index])
+    }
+
+    fn position_of(&self, id: ObjectId) -> Option<usize> {
+        self.object_ids.iter().position(|&i| i == id)
+    }
+
+    /// The object itself, then its parent, its parent's parent and so on.
+    fn ancestors(&self, id: ObjectId) -> impl Iterator<Item = ObjectId> + '_ {
+        std::iter::successors(Some(id), move |&i| self.parent(i))
+    }
+
+    fn contains(shape: &Rc<dyn Shape>, object: &Rc<dyn Shape>) -> bool {
+        Rc::ptr_eq(shape, object) || shape.children().iter().any(|c| Self::contains(c, object))
+    }
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            lights: Vec::new(),
+            environment: None,
+            clip_planes: Vec::new(),
+        }
+    }
+
+    /// Make `light_source` the only light in the world.
+    pub fn with_light_source(mut self, light_source: PointLight) -> Self {
+        self.lights = vec![light_source];
+
+        self
+    }
+
+    pub fn with_light(mut self, light: PointLight) -> Self {
+        self.lights.push(light);
+
+        self
+    }
+
+    /// Surround the world with `environment`, which is seen wherever rays escape the scene and
+    /// adds to the ambient light on every surface.
+    pub fn with_environment(mut self, environment: Rc<dyn Environment>) -> Self {
+        self.environment = Some(environment);
+
+        self
+    }
+
+    /// Cut away the part of every object in front of `plane`.
+    pub fn with_clip_plane(mut self, plane: ClipPlane) -> Self {
+        self.clip_planes.push(plane);
+
+        self
+    }
+
+    pub fn with_object(mut self, object: Rc<dyn Shape>) -> Self {
+        self.objects.push(object);
+
+        self
+    }
+
+    /// Add the two spheres of the default world.
+    pub fn with_default_objects(self) -> Self {
+        let material = MaterialBuilder::new()
+            .with_color(Color::new(0.8, 1.0, 0.6))
+            .with_diffuse(0.7)
+            .with_specular(0.2)
+            .build();
+        let outer = SphereBuilder::new().with_material(material).build();
+
+        let inner = SphereBuilder::new()
+            .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
+            .build();
+
+        self.with_object(Rc::new(outer)).with_object(Rc::new(inner))
+    }
+
+    /// Build the world. If no lights were given it is lit by the default `PointLight`.
+    pub fn build(self) -> World {
+        let lights = if self.lights.is_empty() {
+            vec![PointLight::default()]
+        } else {
+            self.lights
+        };
+        let mut world = World {
+            objects: Vec::new(),
+            object_ids: Vec::new(),
+            parents: Vec::new(),
+            light_links: Vec::new(),
+            next_object_id: 0,
+            lights,
+            environment: self.environment,
+            clip_planes: self.clip_planes,
+            probes: Vec::new(),
+            shadow_hints: RefCell::new(Vec::new()),
+        };
+        for object in self.objects {
+            world.add_object(object);
+        }
+
+        world
+    }
+}
+
+impl From<World> for WorldBuilder {
+    fn from(item: World) -> Self {
+        Self {
+            objects: item.objects,
+            lights: item.lights,
+            environment: item.environment,
+            clip_planes: item.clip_planes,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------------------------------
+Tests
+------------------------------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use rstest::*;
+    use spectral::prelude::*;
+
+    use crate::canvas::Canvas;
+    use crate::consts::{EPSILON, MAX_RECURSION_DEPTH};
+    use crate::instance::{Instance, InstanceBuilder};
+    use crate::intersection::SurfaceBias;
+    use crate::lazy_mesh::LazyMeshBuilder;
+    use crate::material::Material;
+    use crate::pattern::{Pattern, TexturePattern};
+    use crate::plane::PlaneBuilder;
+    use crate::settings::{OutlierRejection, RussianRoulette};
+    use crate::triangle::TriangleBuilder;
+
+    use super::*;
+    use crate::assert_approx_eq;
+
+    #[fixture]
+    fn default_world() -> World {
+        World::default_world()
+    }
+
+    #[rstest]
+    fn creating_a_world() {
+        let w = WorldBuilder::new().build();
+        let expected_light = PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+
+        assert_that!(w.objects).is_empty();
+        assert_that!(w.lights).is_equal_to(vec![expected_light]);
+    }
+
+    #[rstest]
+    fn intersect_a_world_with_a_ray(default_world: World) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = default_world.intersect(&r);
+
+        assert_that!(xs.len()).is_equal_to(4);
+        assert_that!(xs[0].t).is_equal_to(4.0);
+        assert_that!(xs[1].t).is_equal_to(4.5);
+        assert_that!(xs[2].t).is_equal_to(5.5);
+        assert_that!(xs[3].t).is_equal_to(6.0);
+    }
+
+    #[rstest]
+    fn shading_an_intersection(default_world: World) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let shape = &default_world.objects[0];
+        let i = Intersection::new(4.0, Rc::clone(shape));
+        let comps = i.prepare_computations(&r);
+        let expected = Color::new(0.38066, 0.47583, 0.2855);
+
+        let c = default_world.shade_hit(comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
+
+        assert_approx_eq!(c, expected, 0.0001);
+    }
+
+    fn many_lights_world(lights: Vec<PointLight>) -> World {
+        let mut world = World::default_world();
+        world.lights = lights;
+
+        world
+    }
+
+    fn shade_with(world: &World, light_sampling: LightSampling) -> Color {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let i = Intersection::new(4.0, Rc::clone(&world.objects[0]));
+        let settings = RenderSettings {
+            light_sampling,
+            ..RenderSettings::default()
+        };
+
+        world.shade_hit(i.prepare_computations(&r), &settings, MAX_RECURSION_DEPTH)
+    }
+
+    #[rstest]
+    #[case(LightSampling::Uniform(3))]
+    #[case(LightSampling::Power(5))]
+    fn sampling_at_least_as_many_lights_as_there_are_uses_them_all(
+        #[case] light_sampling: LightSampling,
+    ) {
+        let world = many_lights_world(vec![
+            PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white()),
+            PointLight::new(Vector4::point(10.0, 10.0, -10.0), Color::new(0.5, 0.0, 0.0)),
+            PointLight::new(Vector4::point(0.0, -10.0, -10.0), Color::new(0.0, 0.0, 0.5)),
+        ]);
+
+        let all = shade_with(&world, LightSampling::All);
+
+        assert_that!(shade_with(&world, light_sampling)).is_equal_to(all);
+    }
+
+    #[rstest]
+    fn sampling_one_of_two_identical_lights_is_weighted_to_match_both() {
+        let light = || PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+        let world = many_lights_world(vec![light(), light()]);
+
+        let all = shade_with(&world, LightSampling::All);
+        let sampled = shade_with(&world, LightSampling::Uniform(1));
+
+        assert_approx_eq!(sampled, all, 0.0001);
+    }
+
+    #[rstest]
+    fn power_sampling_never_picks_dark_lights() {
+        let mut lights = vec![PointLight::new(
+            Vector4::point(-10.0, 10.0, -10.0),
+            Color::white(),
+        )];
+        for i in 0..20 {
+            let position = Vector4::point(i as f32, 10.0, -10.0);
+            lights.push(PointLight::new(position, Color::black()));
+        }
+        let world = many_lights_world(lights);
+
+        let all = shade_with(&world, LightSampling::All);
+        let sampled = shade_with(&world, LightSampling::Power(1));
+
+        assert_approx_eq!(sampled, all, 0.0001);
+    }
+
+    #[test]
+    fn a_light_only_illuminates_objects_linked_to_its_groups() {
+        let key = || PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+        let rim = PointLight::new(Vector4::point(10.0, 10.0, -10.0), Color::new(1.0, 0.0, 0.0));
+        let mut world = many_lights_world(vec![key().with_groups(0b01), rim.with_groups(0b10)]);
+        let id = world.object_ids[0];
+
+        world.set_light_links(id, 0b01);
+
+        let expected = shade_with(&many_lights_world(vec![key()]), LightSampling::All);
+        assert_that!(shade_with(&world, LightSampling::All)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    fn objects_only_cast_shadows_from_lights_they_are_linked_to(mut default_world: World) {
+        let p = Vector4::point(10.0, -10.0, 10.0);
+        default_world.lights[0].groups = 0b01;
+
+        for id in default_world.object_ids.clone() {
+            default_world.set_light_links(id, 0b10);
+        }
+
+        let light = &default_world.lights[0];
+        assert_that!(default_world.is_shadowed(light, &p)).is_false();
+    }
+
+    #[rstest]
+    fn objects_are_linked_to_every_light_group_by_default(mut default_world: World) {
+        let id = default_world.object_ids[1];
+
+        assert_that!(default_world.light_links(id)).is_equal_to(Some(ALL_LIGHT_GROUPS));
+        default_world.remove_object(id);
+        assert_that!(default_world.light_links(id)).is_none();
+        assert_that!(default_world.set_light_links(id, 0b1)).is_false();
+    }
+
+    #[rstest]
+    fn shading_an_intersection_from_the_inside(default_world: World) {
+        let light = PointLight::new(Vector4::point(0.0, 0.25, 0.0), Color::white());
+        let world = WorldBuilder::from(default_world)
+            .with_light_source(light)
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let shape = &world.objects[1];
+        let i = Intersection::new(0.5, Rc::clone(shape));
+        let comps = i.prepare_computations(&r);
+        let expected = Color::new(0.90498, 0.90498, 0.90498);
+
+        let c = world.shade_hit(comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
+
+        assert_approx_eq!(c, expected, 0.0001);
+    }
+
+    #[test]
+    fn flattening_lifts_shapes_out_of_nested_groups() {
+        let sphere = Rc::new(SphereBuilder::new().build());
+        let inner = GroupBuilder::new()
+            .with_transform(Matrix::scaling(2.0, 2.0, 2.0))
+            .with_child(sphere)
+            .with_child(Rc::new(GroupBuilder::new().build()))
+            .build();
+        let outer = GroupBuilder::new()
+            .with_transform(Matrix::translation(0.0, 0.0, 3.0))
+            .with_child(Rc::new(inner))
+            .build();
+        let mut world = WorldBuilder::new().with_object(Rc::new(outer)).build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let before = world.color_at(&r);
+
+        world.flatten();
+
+        let children = world.objects[0].children();
+        assert_that!(children.len()).is_equal_to(1);
+        assert_that!(children[0].children().is_empty()).is_true();
+        assert_that!(children[0].flattened()).is_none();
+        assert_that!(world.color_at(&r)).is_equal_to(before);
+    }
+
+    #[test]
+    fn flattening_removes_empty_groups_that_nothing_refers_to() {
+        let mut world = WorldBuilder::new().build();
+        let empty = world.add_object(Rc::new(GroupBuilder::new().build()));
+        let null = world.add_null("pivot");
+        let parent = world.add_object(Rc::new(GroupBuilder::new().build()));
+        let child = world.add_object(Rc::new(SphereBuilder::new().build()));
+        world.set_parent(child, Some(parent));
+
+        world.flatten();
+
+        assert_that!(world.object(empty)).is_none();
+        assert_that!(world.object(null)).is_some();
+        assert_that!(world.object(parent)).is_some();
+        assert_that!(world.object(child)).is_some();
+    }
+
+    #[rstest]
+    #[case(true, 1)]
+    #[case(false, 0)]
+    fn single_sided_surfaces_are_culled_from_behind(
+        #[case] double_sided: bool,
+        #[case] hits: usize,
+    ) {
+        let plane = PlaneBuilder::new()
+            .with_material(MaterialBuilder::new().with_double_sided(double_sided).build())
+            .build();
+        let world = WorldBuilder::new().with_object(Rc::new(plane)).build();
+        let from_above = Ray::new(
+            Vector4::point(0.0, 1.0, 0.0),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+        let from_below = Ray::new(
+            Vector4::point(0.0, -1.0, 0.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+
+        assert_that!(world.intersect(&from_above).len()).is_equal_to(1);
+        assert_that!(world.intersect(&from_below).len()).is_equal_to(hits);
+        assert_that!(world.any_hit_before(&from_below, 2.0)).is_equal_to(hits > 0);
+    }
+
+    fn section_ray() -> Ray {
+        Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        )
+    }
+
+    #[rstest]
+    fn a_clip_plane_cuts_away_what_is_in_front_of_it(default_world: World) {
+        let plane = ClipPlane::new(Vector4::point(0.0, 0.0, 0.0), Vector4::vector(0.0, 0.0, -1.0));
+        let world = WorldBuilder::from(default_world)
+            .with_clip_plane(plane)
+            .build();
+
+        let xs = world.intersect(&section_ray());
+
+        let ts: Vec<f32> = xs.iter().map(|i| i.t).collect();
+        assert_that!(ts).is_equal_to(vec![5.5, 6.0]);
+    }
+
+    #[rstest]
+    fn a_capped_clip_plane_fills_in_the_cut_faces(default_world: World) {
+        let plane = ClipPlane::new(Vector4::point(0.0, 0.0, 0.0), Vector4::vector(0.0, 0.0, -1.0))
+            .with_caps(true);
+        let world = WorldBuilder::from(default_world)
+            .with_clip_plane(plane)
+            .build();
+
+        let xs = world.intersect(&section_ray());
+
+        let hit = xs.hit().unwrap();
+        assert_that!(hit.t).is_equal_to(5.0);
+        assert_that!(hit.object.normal_at(&Vector4::point(0.0, 0.0, 0.0)))
+            .is_equal_to(Vector4::vector(0.0, 0.0, -1.0));
+        assert_that!(xs.len()).is_equal_to(4);
+    }
+
+    #[rstest]
+    fn clipped_geometry_casts_no_shadows(mut default_world: World) {
+        let p = Vector4::point(10.0, -10.0, 10.0);
+
+        default_world.add_clip_plane(ClipPlane::new(
+            Vector4::point(0.0, 0.0, -2.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        ));
+
+        assert_that!(default_world.is_shadowed(&default_world.lights[0], &p)).is_false();
+        assert_that!(default_world.remove_clip_plane(0)).is_some();
+        assert_that!(default_world.is_shadowed(&default_world.lights[0], &p)).is_true();
+    }
+
+    #[rstest]
+    fn the_color_when_a_ray_misses(default_world: World) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+
+        let c = default_world.color_at(&r);
+
+        assert_that!(c).is_equal_to(Color::black());
+    }
+
+    #[rstest]
+    fn the_color_when_a_ray_hits(default_world: World) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let expected = Color::new(0.38066, 0.47583, 0.2855);
+
+        let c = default_world.color_at(&r);
+
+        assert_approx_eq!(c, expected, 0.0001);
+    }
+
+    #[rstest]
+    fn the_color_with_an_intersection_behind_the_ray() {
+        // Arrange
+        let outer_material = MaterialBuilder::new()
+            .with_color(Color::new(0.8, 1.0, 0.6))
+            .with_diffuse(0.7)
+            .with_specular(0.2)
+            .with_ambient(1.0)
+            .build();
+        let inner_material = MaterialBuilder::new().with_ambient(1.0).build();
+
+        let outer = SphereBuilder::new().with_material(outer_material).build();
+
+        let inner = SphereBuilder::new()
+            .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
+            .with_material(inner_material)
+            .build();
+
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(outer))
+            .with_object(Rc::new(inner))
+            .build();
+
+        // Act
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.75),
+            Vector4::vector(0.0, 0.0, -1.0),
+        );
+
+        // Assert
+        assert_that!(world.color_at(&r)).is_equal_to(Color::white());
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 10.0, 0.0), false)] // there_is_no_shadow_when_nothing_is_co_linear_with_point_and_light
+    #[case(Vector4::point(10.0, - 10.0, 10.0), true)] // the_shadow_when_an_object_is_between_the_point_and_the_light
+    #[case(Vector4::point(- 20.0, 20.0, - 20.0), false)] // there_is_no_shadow_when_an_object_is_behind_the_light
+    #[case(Vector4::point(- 2.0, 2.0, - 2.0), false)] // there_is_no_shadow_when_an_object_is_behind_the_point
+    fn test_is_shadowed(default_world: World, #[case] p: Vector4, #[case] expected: bool) {
+        let light = &default_world.lights[0];
+
+        assert_that!(default_world.is_shadowed(light, &p)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 0.0, -5.0), Vector4::point(0.0, 0.0, 5.0), true)]
+    #[case(Vector4::point(0.0, 0.0, -5.0), Vector4::point(0.0, 0.0, -2.0), false)]
+    #[case(Vector4::point(0.0, 5.0, -5.0), Vector4::point(0.0, 5.0, 5.0), false)]
+    fn testing_for_occlusion_between_two_points(
+        default_world: World,
+        #[case] from: Vector4,
+        #[case] to: Vector4,
+        #[case] expected: bool,
+    ) {
+        assert_that!(default_world.occluded(from, to)).is_equal_to(expected);
+    }
+
+    #[test]
+    fn shapes_that_cast_no_shadow_let_light_through() {
+        let pane = SphereBuilder::new()
+            .with_material(MaterialBuilder::new().with_casts_shadow(false).build())
+            .build();
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(
+                GroupBuilder::new().with_child(Rc::new(pane)).build(),
+            ))
+            .build();
+        let light = PointLight::new(Vector4::point(0.0, 0.0, -10.0), Color::white());
+
+        assert_that!(world.occluded(Vector4::point(0.0, 0.0, 5.0), light.position)).is_false();
+        assert_that!(world.is_shadowed(&light, &Vector4::point(0.0, 0.0, 5.0))).is_false();
+    }
+
+    #[test]
+    fn the_last_blocker_of_a_light_is_remembered() {
+        let wall = SphereBuilder::new()
+            .with_transform(Matrix::translation(3.0, 5.0, 0.0))
+            .build();
+        let world = WorldBuilder::new()
+            .with_light(PointLight::new(
+                Vector4::point(0.0, 10.0, 0.0),
+                Color::white(),
+            ))
+            .with_object(Rc::new(SphereBuilder::new().build()))
+            .with_object(Rc::new(wall))
+            .build();
+
+        assert_that!(world.is_shadowed_from(0, &Vector4::point(6.0, 0.0, 0.0))).is_true();
+        assert_that!(world.shadow_hints.borrow()[0]).is_equal_to(Some(1));
+        assert_that!(world.is_shadowed_from(0, &Vector4::point(0.0, -2.0, 0.0))).is_true();
+        assert_that!(world.shadow_hints.borrow()[0]).is_equal_to(Some(0));
+    }
+
+    #[rstest]
+    fn cached_shadows_match_uncached_ones(default_world: World) {
+        let light = &default_world.lights[0];
+
+        for x in -6..=6 {
+            for z in -6..=6 {
+                let p = Vector4::point(x as f32, -1.0 + (x * z) as f32 * 0.05, z as f32);
+                assert_that!(default_world.is_shadowed_from(0, &p))
+                    .is_equal_to(default_world.is_shadowed(light, &p));
+            }
+        }
+    }
+
+    fn world_with_light_of_radius(radius: f32) -> World {
+        WorldBuilder::new()
+            .with_light(
+                PointLight::new(Vector4::point(0.0, 10.0, 0.0), Color::white()).with_radius(radius),
+            )
+            .with_object(Rc::new(SphereBuilder::new().build()))
+            .build()
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, -2.0, 0.0), 0.0)]
+    #[case(Vector4::point(10.0, -2.0, 0.0), 1.0)]
+    fn a_light_without_a_radius_is_seen_or_not(#[case] point: Vector4, #[case] expected: f32) {
+        let world = world_with_light_of_radius(0.0);
+
+        let visibility = world.light_visibility(0, &point, &RenderSettings::default());
+
+        assert_that!(visibility).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, -2.0, 0.0), 0.0, 0.0)]
+    #[case(Vector4::point(1.2, -2.0, 0.0), 0.1, 0.9)]
+    #[case(Vector4::point(10.0, -2.0, 0.0), 1.0, 1.0)]
+    fn a_light_with_a_radius_casts_soft_shadows(
+        #[case] point: Vector4,
+        #[case] least: f32,
+        #[case] most: f32,
+        #[values(false, true)] shadow_cache: bool,
+    ) {
+        let world = world_with_light_of_radius(2.0);
+        let settings = RenderSettings {
+            shadow_cache,
+            ..RenderSettings::default()
+        };
+
+        let visibility = world.light_visibility(0, &point, &settings);
+
+        assert_that!(visibility).is_greater_than_or_equal_to(least);
+        assert_that!(visibility).is_less_than_or_equal_to(most);
+    }
+
+    #[test]
+    fn shade_hit_is_given_an_intersection_in_shadow() {
+        let mut w = WorldBuilder::new().build();
+        *w.light_mut(0).unwrap() = PointLight::new(Vector4::point(0.0, 0.0, -10.0), Color::white());
+        let s1: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+        let s2: Rc<dyn Shape> = Rc::new(
+            SphereBuilder::new()
+                .with_transform(Matrix::translation(0.0, 0.0, 10.0))
+                .build(),
+        );
+        w.add_object(s1);
+        w.add_object(Rc::clone(&s2));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let i = Intersection::new(4.0, Rc::clone(&s2));
+        let comps = i.prepare_computations(&r);
+
+        let c = w.shade_hit(comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
+
+        assert_that!(c).is_equal_to(Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn looking_up_an_object_by_name() {
+        let floor: Rc<dyn Shape> = Rc::new(SphereBuilder::new().with_name("floor").build());
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(SphereBuilder::new().build()))
+            .with_object(Rc::clone(&floor))
+            .build();
+
+        assert_that!(world.object_by_name("floor")).is_some().is_equal_to(&floor);
+        assert_that!(world.object_by_name("ceiling")).is_none();
+    }
+
+    #[test]
+    fn intersections_with_a_group_refer_to_its_children() {
+        let group = GroupBuilder::new()
+            .with_transform(Matrix::translation(0.0, 0.0, 2.0))
+            .with_child(Rc::new(SphereBuilder::new().with_name("ball").build()))
+            .build();
+        let world = WorldBuilder::new().with_object(Rc::new(group)).build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        let xs = world.intersect(&r);
+        let hit = xs.hit().unwrap();
+
+        assert_that!(hit.t).is_equal_to(6.0);
+        assert_that!(hit.object.name()).is_equal_to(Some("ball"));
+        assert_that!(world.object_id(&hit.object)).is_equal_to(world.object_id(&world.objects()[0]));
+    }
+
+    #[rstest]
+    #[case(Vector4::point(0.0, 0.0, -5.0), 4.0, false)]
+    #[case(Vector4::point(0.0, 0.0, -5.0), 4.5, true)]
+    #[case(Vector4::point(0.0, 0.0, 5.0), 100.0, false)]
+    #[case(Vector4::point(0.0, 0.0, 0.0), 0.1, false)]
+    #[case(Vector4::point(0.0, 0.0, 0.0), 1.0, true)]
+    fn checking_for_any_hit_before_a_distance(
+        default_world: World,
+        #[case] origin: Vector4,
+        #[case] t_max: f32,
+        #[case] expected: bool,
+    ) {
+        let r = Ray::new(origin, Vector4::vector(0.0, 0.0, 1.0));
+
+        assert_that!(default_world.any_hit_before(&r, t_max)).is_equal_to(expected);
+    }
+
+    #[rstest]
+    #[case(SurfaceBias::default(), false)]
+    #[case(SurfaceBias { absolute: EPSILON, relative: 0.0 }, true)] // a fixed offset gets lost
+    fn a_distant_surface_does_not_shadow_itself(
+        #[case] surface_bias: SurfaceBias,
+        #[case] expected: bool,
+    ) {
+        let far_away = 1.0e3;
+        let world = WorldBuilder::new()
+            .with_light_source(PointLight::new(Vector4::point(0.0, 0.0, 0.0), Color::white()))
+            .with_object(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(Matrix::translation(0.0, 0.0, far_away))
+                    .build(),
+            ))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = world.intersect(&r);
+
+        let comps = xs[0].prepare_computations_biased(&r, &xs, &surface_bias);
+
+        assert_that!(world.is_shadowed(&world.lights()[0], &comps.over_point))
+            .is_equal_to(expected);
+    }
+
+    #[test]
+    fn the_under_point_stays_inside_a_tiny_object() {
+        let radius = 5.0e-6;
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(
+                SphereBuilder::new()
+                    .with_transform(Matrix::scaling(radius, radius, radius))
+                    .build(),
+            ))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -1.0e-4),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let xs = world.intersect(&r);
+
+        let comps = xs[0].prepare_computations_biased(&r, &xs, &SurfaceBias::default());
+
+        let depth = radius - (comps.under_point - Vector4::point(0.0, 0.0, 0.0)).magnitude();
+        assert_that!(depth).is_greater_than(0.0);
+        assert_that!(depth).is_less_than(radius / 2.0);
+    }
+
+    #[test]
+    fn adding_objects_to_a_built_world() {
+        let mut world = WorldBuilder::new().build();
+        let s: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
+
+        let id = world.add_object(Rc::clone(&s));
+
+        assert_that!(world.objects().len()).is_equal_to(1);
+        assert_that!(world.object(id)).is_some().is_equal_to(&s);
+        assert_that!(world.object_id(&s)).is_some().is_equal_to(id);
+    }
+
+    #[rstest]
+    fn removing_an_object_keeps_other_ids_valid(default_world: World) {
+        let mut world = default_world;
+        let first = world.object_id(&world.objects()[0]).unwrap();
+        let second = world.object_id(&world.objects()[1]).unwrap();
+        let expected = Rc::clone(&world.objects()[1]);
+
+        let removed = world.remove_object(first);
+
+        assert_that!(removed).is_some();
+        assert_that!(world.objects().len()).is_equal_to(1);
+        assert_that!(world.object(first)).is_none();
+        assert_that!(world.object(second)).is_some().is_equal_to(&expected);
+        assert_that!(world.remove_object(first)).is_none();
+    }
+
+    #[rstest]
+    fn ids_are_not_reused_after_removal(default_world: World) {
+        let mut world = default_world;
+        let last = world.object_id(&world.objects()[1]).unwrap();
+        world.remove_object(last);
+
+        let id = world.add_object(Rc::new(SphereBuilder::new().build()));
+
+        assert_that!(id).is_not_equal_to(last);
+    }
+
+    #[rstest]
+    fn replacing_an_object_keeps_its_id(default_world: World) {
+        let mut world = default_world;
+        let id = world.object_id(&world.objects()[0]).unwrap();
+        let original = Rc::clone(&world.objects()[0]);
+        let replacement: Rc<dyn Shape> = Rc::new(SphereBuilder::new().with_name("new").build());
+
+        let old = world.replace_object(id, Rc::clone(&replacement));
+
+        assert_that!(old).is_some().is_equal_to(&original);
+        assert_that!(world.object(id)).is_some().is_equal_to(&replacement);
+    }
+
+    #[rstest]
+    fn editing_a_light_after_building(default_world: World) {
+        let mut world = default_world;
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+
+        world.light_mut(0).unwrap().intensity = Color::black();
+
+        assert_that!(world.color_at(&r)).is_equal_to(Color::black());
+    }
+
+    #[test]
+    fn adding_and_removing_lights() {
+        let mut world = WorldBuilder::new().build();
+        let light = PointLight::new(Vector4::point(0.0, 10.0, 0.0), Color::white());
+
+        let index = world.add_light(light);
+
+        assert_that!(index).is_equal_to(1);
+        assert_that!(world.lights().len()).is_equal_to(2);
+        assert_that!(world.remove_light(0)).is_some().is_equal_to(PointLight::default());
+        assert_that!(world.light(0).unwrap().position).is_equal_to(Vector4::point(0.0, 10.0, 0.0));
+        assert_that!(world.remove_light(5)).is_none();
+    }
+
+    fn sphere_at(x: f32) -> Rc<dyn Shape> {
+        Rc::new(
+            SphereBuilder::new()
+                .with_transform(Matrix::translation(x, 0.0, 0.0))
+                .build(),
+        )
+    }
+
+    fn position_of(world: &World, id: ObjectId) -> Vector4 {
+        world.object(id).unwrap().transformation() * Vector4::point(0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn moving_a_parent_moves_its_children() {
+        let mut world = WorldBuilder::new().build();
+        let sun = world.add_object(sphere_at(0.0));
+        let planet = world.add_object(sphere_at(5.0));
+        let moon = world.add_object(sphere_at(6.0));
+        assert_that!(world.set_parent(planet, Some(sun))).is_true();
+        assert_that!(world.set_parent(moon, Some(planet))).is_true();
+
+        world.transform_object(sun, &Matrix::translation(0.0, 10.0, 0.0));
+
+        assert_that!(position_of(&world, sun)).is_equal_to(Vector4::point(0.0, 10.0, 0.0));
+        assert_that!(position_of(&world, planet)).is_equal_to(Vector4::point(5.0, 10.0, 0.0));
+        assert_that!(position_of(&world, moon)).is_equal_to(Vector4::point(6.0, 10.0, 0.0));
+    }
+
+    #[test]
+    fn moving_a_child_leaves_its_parent_alone() {
+        let mut world = WorldBuilder::new().build();
+        let sun = world.add_object(sphere_at(0.0));
+        let planet = world.add_object(sphere_at(5.0));
+        world.set_parent(planet, Some(sun));
+
+        world.transform_object(planet, &Matrix::translation(0.0, 10.0, 0.0));
+
+        assert_that!(position_of(&world, sun)).is_equal_to(Vector4::point(0.0, 0.0, 0.0));
+        assert_that!(position_of(&world, planet)).is_equal_to(Vector4::point(5.0, 10.0, 0.0));
+    }
+
+    #[test]
+    fn orbiting_a_null_node() {
+        let mut world = WorldBuilder::new().build();
+        let orbit = world.add_null("orbit");
+        let planet = world.add_object(sphere_at(5.0));
+        world.set_parent(planet, Some(orbit));
+
+        world.transform_object(orbit, &Matrix::rotation_y(PI / 2.0));
+
+        assert_that!(position_of(&world, planet)).is_equal_to(Vector4::point(0.0, 0.0, -5.0));
+        let r = Ray::new(
+            Vector4::point(0.0, 10.0, 0.0),
+            Vector4::vector(0.0, -1.0, 0.0),
+        );
+        assert_that!(world.intersect(&r).hit()).is_none();
+    }
+
+    #[test]
+    fn parenting_cannot_make_a_loop() {
+        let mut world = WorldBuilder::new().build();
+        let a = world.add_object(sphere_at(0.0));
+        let b = world.add_object(sphere_at(1.0));
+        let c = world.add_object(sphere_at(2.0));
+        world.set_parent(b, Some(a));
+        world.set_parent(c, Some(b));
+
+        assert_that!(world.set_parent(a, Some(c))).is_false();
+        assert_that!(world.set_parent(a, Some(a))).is_false();
+        assert_that!(world.set_parent(a, Some(ObjectId(99)))).is_false();
+        assert_that!(world.parent(a)).is_none();
+    }
+
+    #[test]
+    fn removing_a_parent_hands_its_children_on() {
+        let mut world = WorldBuilder::new().build();
+        let sun = world.add_object(sphere_at(0.0));
+        let planet = world.add_object(sphere_at(5.0));
+        let moon = world.add_object(sphere_at(6.0));
+        world.set_parent(planet, Some(sun));
+        world.set_parent(moon, Some(planet));
+
+        world.remove_object(planet);
+
+        assert_that!(world.parent(moon)).is_equal_to(Some(sun));
+        assert_that!(world.children_of(sun)).is_equal_to(vec![moon]);
+    }
+
+    #[test]
+    fn detaching_a_child() {
+        let mut world = WorldBuilder::new().build();
+        let sun = world.add_object(sphere_at(0.0));
+        let planet = world.add_object(sphere_at(5.0));
+        world.set_parent(planet, Some(sun));
+
+        world.set_parent(planet, None);
+        world.transform_object(sun, &Matrix::translation(0.0, 10.0, 0.0));
+
+        assert_that!(position_of(&world, planet)).is_equal_to(Vector4::point(5.0, 0.0, 0.0));
+    }
+
+    #[derive(Debug)]
+    struct UniformEnvironment(Color);
+
+    impl Environment for UniformEnvironment {
+        fn color_in_direction(&self, _direction: Vector4) -> Color {
+            self.0
+        }
+    }
+
+    #[rstest]
+    fn a_ray_that_misses_sees_the_environment(default_world: World) {
+        let world = WorldBuilder::from(default_world)
+            .with_environment(Rc::new(UniformEnvironment(Color::new(0.2, 0.4, 0.6))))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
+
+        assert_that!(world.color_at(&r)).is_equal_to(Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[rstest]
+    fn the_environment_adds_ambient_light(default_world: World) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let without = default_world.color_at(&r);
+        let world = WorldBuilder::from(default_world)
+            .with_environment(Rc::new(UniformEnvironment(Color::white())))
+            .build();
+
+        let c = world.color_at(&r);
+
+        // The outer sphere is (0.8, 1.0, 0.6) with an ambient of 0.1
+        assert_that!(c.r).is_close_to(without.r + 0.08, 0.0001);
+        assert_that!(c.g).is_close_to(without.g + 0.1, 0.0001);
+        assert_that!(c.b).is_close_to(without.b + 0.06, 0.0001);
+    }
+
+    #[test]
+    fn a_mirror_reflects_the_environment() {
+        let mirror = PlaneBuilder::new()
+            .with_material(
+                MaterialBuilder::new()
+                    .with_color(Color::black())
+                    .with_ambient(0.0)
+                    .with_diffuse(0.0)
+                    .with_specular(0.0)
+                    .with_reflective(1.0)
+                    .build(),
+            )
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+            .build();
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(mirror))
+            .with_environment(Rc::new(UniformEnvironment(Color::new(0.2, 0.4, 0.6))))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -1.0, 1.0).normalize(),
+        );
+
+        assert_that!(world.color_at(&r)).is_equal_to(Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[rstest]
+    fn shading_sums_the_contribution_of_every_light(default_world: World) {
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let single = default_world.color_at(&r);
+        let world = WorldBuilder::from(default_world)
+            .with_light(PointLight::default())
+            .build();
+
+        let c = world.color_at(&r);
+
+        assert_that!(c.r).is_close_to(single.r * 2.0, 0.0001);
+        assert_that!(c.g).is_close_to(single.g * 2.0, 0.0001);
+        assert_that!(c.b).is_close_to(single.b * 2.0, 0.0001);
+    }
+
+    #[test]
+    fn the_default_world() {
+        let light = PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+        let material = MaterialBuilder::new()
+            .with_color(Color::new(0.8, 1.0, 0.6))
+            .with_diffuse(0.7)
+            .with_specular(0.2)
+            .build();
+        let s1: Rc<dyn Shape> = Rc::new(SphereBuilder::new().with_material(material).build());
+        let s2: Rc<dyn Shape> = Rc::new(
+            SphereBuilder::new()
+                .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
+                .build(),
+        );
+
+        let w = World::default_world();
 
-impl World {
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
-        let mut found: Intersections = Intersections::default();
+        assert_that!(w.lights).is_equal_to(vec![light]);
+        assert_that!(w.objects).has_length(2);
+        assert_that!(w.objects[0]).is_equal_to(&s1);
+        assert_that!(w.objects[1]).is_equal_to(&s2);
+    }
 
-        for o in self.objects.iter() {
-            let intersections = o.intersect(ray);
-            for intersection in intersections.into_iter() {
-                found.push(Intersection::new(intersection, Rc::clone(o)));
-            }
-        }
+    #[test]
+    fn default_objects_can_be_added_alongside_others() {
+        let w = WorldBuilder::new()
+            .with_object(Rc::new(SphereBuilder::new().with_name("extra").build()))
+            .with_default_objects()
+            .build();
 
-        found
+        assert_that!(w.objects).has_length(3);
+        assert_that!(w.object_by_name("extra")).is_some();
     }
 
-    pub fn shade_hit(&self, comps: Computations) -> Color {
-        comps.object.lighting(
-            &self.light_source,
-            comps.point,
-            comps.eye_vector,
-            comps.normal_vector,
-            self.is_shadowed(&comps.over_point),
+    fn with_material(world: &World, index: usize, material: Material) -> Rc<dyn Shape> {
+        let original = &world.objects[index];
+        let shape: Rc<dyn Shape> = Rc::new(
+            SphereBuilder::new()
+                .with_transform(original.transformation())
+                .with_material(material)
+                .build(),
+        );
+        shape
+    }
+
+    #[rstest]
+    fn the_reflected_color_for_a_nonreflective_material(default_world: World) {
+        let mut world = default_world;
+        let id = world.object_id(&world.objects[1]).unwrap();
+        let shape = with_material(&world, 1, MaterialBuilder::new().with_ambient(1.0).build());
+        world.replace_object(id, Rc::clone(&shape));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 0.0, 1.0),
+        );
+        let i = Intersection::new(1.0, shape);
+
+        let comps = i.prepare_computations(&r);
+        let color = world.reflected_color(&comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
+
+        assert_that!(color).is_equal_to(Color::black());
+    }
+
+    fn reflective_plane() -> Rc<dyn Shape> {
+        Rc::new(
+            PlaneBuilder::new()
+                .with_material(MaterialBuilder::new().with_reflective(0.5).build())
+                .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+                .build(),
         )
     }
 
-    pub fn color_at(&self, ray: &Ray) -> Color {
-        let intersections = self.intersect(ray);
-        if let Some(hit) = intersections.hit() {
-            let comps = hit.prepare_computations(ray);
-            return self.shade_hit(comps);
-        }
+    #[rstest]
+    fn the_reflected_color_for_a_reflective_material(default_world: World) {
+        let mut world = default_world;
+        let shape = reflective_plane();
+        world.add_object(Rc::clone(&shape));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f32.sqrt(), shape);
+
+        let comps = i.prepare_computations(&r);
+        let color = world.reflected_color(&comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
 
-        Color::black()
+        assert_approx_eq!(color, Color::new(0.19032, 0.2379, 0.14274), 0.0001);
     }
 
-    fn is_shadowed(&self, point: &Vector4) -> bool {
-        let v = self.light_source.position - *point;
-        let distance = v.magnitude();
-        let direction = v.normalize();
+    fn reflected_with(world: World, settings: &RenderSettings) -> Color {
+        let mut world = world;
+        let shape = reflective_plane();
+        world.add_object(Rc::clone(&shape));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f32.sqrt(), shape);
 
-        let r = Ray::new(point.clone(), direction);
-        let intersections = self.intersect(&r);
-        if let Some(h) = intersections.hit() {
-            return h.t < distance;
-        }
+        world.reflected_color(&i.prepare_computations(&r), settings, settings.max_depth)
+    }
+
+    #[rstest]
+    fn reflections_are_looked_up_in_the_nearest_probe(default_world: World) {
+        let settings = RenderSettings {
+            reflection_probes: true,
+            ..RenderSettings::default()
+        };
+        let mut world = default_world;
+        world.capture_probes(
+            &[Vector4::point(0.0, 5.0, 0.0), Vector4::point(0.0, -0.9, -1.2)],
+            8,
+            &RenderSettings::default(),
+        );
+        let shape = reflective_plane();
+        world.add_object(Rc::clone(&shape));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let comps = Intersection::new(2.0_f32.sqrt(), shape).prepare_computations(&r);
 
-        false
+        let color = world.reflected_color(&comps, &settings, MAX_RECURSION_DEPTH);
+
+        let expected = world.probes()[1].color_in_direction(comps.reflect_vector) * 0.5;
+        assert_that!(color).is_equal_to(expected);
+        assert_that!(color).is_not_equal_to(Color::black());
     }
-}
 
-impl WorldBuilder {
-    pub fn new() -> Self {
-        Self {
-            objects: Vec::new(),
-            light_source: PointLight::default(),
-        }
+    #[rstest]
+    fn without_probes_reflections_are_traced(default_world: World) {
+        let settings = RenderSettings {
+            reflection_probes: true,
+            ..RenderSettings::default()
+        };
+
+        let color = reflected_with(default_world, &settings);
+
+        assert_approx_eq!(color, Color::new(0.19032, 0.2379, 0.14274), 0.0001);
     }
 
-    pub fn with_light_source(mut self, light_source: PointLight) -> Self {
-        self.light_source = light_source;
+    #[rstest]
+    fn russian_roulette_waits_for_its_start_depth(default_world: World) {
+        let settings = RenderSettings {
+            roulette: Some(RussianRoulette {
+                start_depth: 1,
+                min_survival: 0.1,
+                max_survival: 0.1,
+            }),
+            ..RenderSettings::default()
+        };
 
-        self
+        let color = reflected_with(default_world, &settings);
+
+        assert_approx_eq!(color, Color::new(0.19032, 0.2379, 0.14274), 0.0001);
     }
 
-    pub fn with_object(mut self, object: Rc<dyn Shape>) -> Self {
-        self.objects.push(object);
+    #[rstest]
+    fn surviving_russian_roulette_makes_up_for_the_rays_that_dont(default_world: World) {
+        let settings = RenderSettings {
+            roulette: Some(RussianRoulette {
+                start_depth: 0,
+                min_survival: 0.5,
+                max_survival: 0.5,
+            }),
+            ..RenderSettings::default()
+        };
 
-        self
+        let color = reflected_with(default_world, &settings);
+
+        // Either ended, or twice as bright as without roulette
+        let doubled = Color::new(0.38064, 0.4758, 0.28548);
+        assert_that!(color == Color::black() || (color.r - doubled.r).abs() < 0.0001).is_true();
     }
 
-    pub fn build(self) -> World {
-        World {
-            objects: self.objects,
-            light_source: self.light_source,
-        }
+    #[rstest]
+    #[case(None, Color::new(4.0, 2.0, 0.0))]
+    #[case(Some(8.0), Color::new(4.0, 2.0, 0.0))]
+    #[case(Some(1.0), Color::new(1.0, 0.5, 0.0))]
+    fn bright_samples_are_clamped_keeping_their_hue(
+        #[case] max_sample_radiance: Option<f32>,
+        #[case] expected: Color,
+    ) {
+        let settings = RenderSettings {
+            max_sample_radiance,
+            ..RenderSettings::default()
+        };
+
+        assert_that!(settings.clamp_sample(Color::new(4.0, 2.0, 0.0))).is_equal_to(expected);
     }
-}
 
-impl From<World> for WorldBuilder {
-    fn from(item: World) -> Self {
-        Self {
-            objects: item.objects,
-            light_source: item.light_source,
-        }
+    #[test]
+    fn a_sample_far_brighter_than_the_rest_of_its_pixel_is_pulled_down() {
+        let settings = RenderSettings {
+            outlier_rejection: Some(OutlierRejection::default()),
+            ..RenderSettings::default()
+        };
+        let mut samples = vec![Color::new(0.5, 0.5, 0.5); 7];
+        samples.push(Color::new(40.0, 20.0, 0.0));
+
+        settings.reject_outliers(&mut samples);
+
+        assert_approx_eq!(samples[7].luminance(), 4.0, 0.0001);
+        assert_that!(samples[7].r / samples[7].g).is_close_to(2.0, 0.0001);
+        assert_that!(samples[0]).is_equal_to(Color::new(0.5, 0.5, 0.5));
     }
-}
 
-/* -------------------------------------------------------------------------------------------------
-Tests
-------------------------------------------------------------------------------------------------- */
+    #[test]
+    fn edges_keep_their_lit_samples() {
+        let settings = RenderSettings {
+            outlier_rejection: Some(OutlierRejection::default()),
+            ..RenderSettings::default()
+        };
+        let lit = Color::new(0.9, 0.8, 0.9);
+        let mut samples = vec![Color::black(), Color::black(), Color::black(), lit];
 
-#[cfg(test)]
-mod tests {
-    use rstest::*;
-    use spectral::prelude::*;
+        settings.reject_outliers(&mut samples);
 
-    use crate::intersection::Intersection;
-    use crate::material::MaterialBuilder;
-    use crate::matrix::Matrix;
-    use crate::sphere::SphereBuilder;
-    use crate::transform::Transform;
-    use crate::vector4::Vector4;
+        assert_that!(samples[3]).is_equal_to(lit);
+    }
 
-    use super::*;
+    #[rstest]
+    fn shade_hit_with_a_reflective_material(default_world: World) {
+        let mut world = default_world;
+        let shape = reflective_plane();
+        world.add_object(Rc::clone(&shape));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f32.sqrt(), shape);
 
-    #[fixture]
-    fn default_world() -> World {
-        let material = MaterialBuilder::new()
-            .with_color(Color::new(0.8, 1.0, 0.6))
-            .with_diffuse(0.7)
-            .with_specular(0.2)
-            .build();
-        let s1 = SphereBuilder::new().with_material(material).build();
+        let comps = i.prepare_computations(&r);
+        let color = world.shade_hit(comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
 
-        let s2 = SphereBuilder::new()
-            .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
+        assert_approx_eq!(color, Color::new(0.87677, 0.92436, 0.82918), 0.0001);
+    }
+
+    #[test]
+    fn color_at_with_mutually_reflective_surfaces() {
+        let mirror = || MaterialBuilder::new().with_reflective(1.0).build();
+        let lower = PlaneBuilder::new()
+            .with_material(mirror())
+            .with_transform(Matrix::translation(0.0, -1.0, 0.0))
             .build();
+        let upper = PlaneBuilder::new()
+            .with_material(mirror())
+            .with_transform(Matrix::translation(0.0, 1.0, 0.0))
+            .build();
+        let world = WorldBuilder::new()
+            .with_light_source(PointLight::new(Vector4::point(0.0, 0.0, 0.0), Color::white()))
+            .with_object(Rc::new(lower))
+            .with_object(Rc::new(upper))
+            .build();
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::vector(0.0, 1.0, 0.0),
+        );
 
-        WorldBuilder::new()
-            .with_object(Rc::new(s1))
-            .with_object(Rc::new(s2))
-            .build()
+        // Terminates rather than recursing forever
+        let _ = world.color_at(&r);
     }
 
     #[rstest]
-    fn creating_a_world() {
-        let w = WorldBuilder::new().build();
-        let expected_light = PointLight::new(Vector4::point(-10.0, 10.0, -10.0), Color::white());
+    fn the_reflected_color_at_the_maximum_recursive_depth(default_world: World) {
+        let mut world = default_world;
+        let shape = reflective_plane();
+        world.add_object(Rc::clone(&shape));
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f32.sqrt(), shape);
 
-        assert_that!(w.objects).is_empty();
-        assert_that!(w.light_source).is_equal_to(expected_light);
+        let comps = i.prepare_computations(&r);
+        let color = world.reflected_color(&comps, &RenderSettings::default(), 0);
+
+        assert_that!(color).is_equal_to(Color::black());
     }
 
     #[rstest]
-    fn intersect_a_world_with_a_ray(default_world: World) {
+    fn the_refracted_color_with_an_opaque_surface(default_world: World) {
+        let shape = Rc::clone(&default_world.objects[0]);
         let r = Ray::new(
             Vector4::point(0.0, 0.0, -5.0),
             Vector4::vector(0.0, 0.0, 1.0),
         );
+        let xs = Intersections::from(vec![
+            Intersection::new(4.0, Rc::clone(&shape)),
+            Intersection::new(6.0, shape),
+        ]);
 
-        let xs = default_world.intersect(&r);
+        let comps = xs[0].prepare_computations_with(&r, &xs);
+        let c = default_world.refracted_color(
+            &comps,
+            &RenderSettings::default(),
+            MAX_RECURSION_DEPTH,
+        );
 
-        assert_that!(xs.len()).is_equal_to(4);
-        assert_that!(xs[0].t).is_equal_to(4.0);
-        assert_that!(xs[1].t).is_equal_to(4.5);
-        assert_that!(xs[2].t).is_equal_to(5.5);
-        assert_that!(xs[3].t).is_equal_to(6.0);
+        assert_that!(c).is_equal_to(Color::black());
+    }
+
+    fn transparent_material() -> Material {
+        MaterialBuilder::new()
+            .with_color(Color::new(0.8, 1.0, 0.6))
+            .with_diffuse(0.7)
+            .with_specular(0.2)
+            .with_transparency(1.0)
+            .with_refractive_index(1.5)
+            .build()
     }
 
     #[rstest]
-    fn shading_an_intersection(default_world: World) {
+    fn the_refracted_color_at_the_maximum_recursive_depth(default_world: World) {
+        let shape = with_material(&default_world, 0, transparent_material());
         let r = Ray::new(
             Vector4::point(0.0, 0.0, -5.0),
             Vector4::vector(0.0, 0.0, 1.0),
         );
-        let shape = &default_world.objects[0];
-        let i = Intersection::new(4.0, Rc::clone(shape));
-        let comps = i.prepare_computations(&r);
-        let expected = Color::new(0.38066, 0.47583, 0.2855);
+        let xs = Intersections::from(vec![
+            Intersection::new(4.0, Rc::clone(&shape)),
+            Intersection::new(6.0, shape),
+        ]);
 
-        let c = default_world.shade_hit(comps);
+        let comps = xs[0].prepare_computations_with(&r, &xs);
+        let c = default_world.refracted_color(&comps, &RenderSettings::default(), 0);
 
-        assert_that!(c.r).is_close_to(expected.r, 0.0001);
-        assert_that!(c.g).is_close_to(expected.g, 0.0001);
-        assert_that!(c.b).is_close_to(expected.b, 0.0001);
+        assert_that!(c).is_equal_to(Color::black());
     }
 
     #[rstest]
-    fn shading_an_intersection_from_the_inside(default_world: World) {
-        let light = PointLight::new(Vector4::point(0.0, 0.25, 0.0), Color::white());
-        let world = WorldBuilder::from(default_world)
-            .with_light_source(light)
-            .build();
+    fn the_refracted_color_under_total_internal_reflection(default_world: World) {
+        let shape = with_material(&default_world, 0, transparent_material());
         let r = Ray::new(
-            Vector4::point(0.0, 0.0, 0.0),
-            Vector4::vector(0.0, 0.0, 1.0),
+            Vector4::point(0.0, 0.0, 2.0_f32.sqrt() / 2.0),
+            Vector4::vector(0.0, 1.0, 0.0),
         );
-        let shape = &world.objects[1];
-        let i = Intersection::new(0.5, Rc::clone(shape));
-        let comps = i.prepare_computations(&r);
-        let expected = Color::new(0.90498, 0.90498, 0.90498);
+        let xs = Intersections::from(vec![
+            Intersection::new(-2.0_f32.sqrt() / 2.0, Rc::clone(&shape)),
+            Intersection::new(2.0_f32.sqrt() / 2.0, shape),
+        ]);
 
-        let c = world.shade_hit(comps);
+        // Inside the sphere, so look at the second intersection
+        let comps = xs[1].prepare_computations_with(&r, &xs);
+        let c = default_world.refracted_color(
+            &comps,
+            &RenderSettings::default(),
+            MAX_RECURSION_DEPTH,
+        );
 
-        assert_that!(c.r).is_close_to(expected.r, 0.0001);
-        assert_that!(c.g).is_close_to(expected.g, 0.0001);
-        assert_that!(c.b).is_close_to(expected.b, 0.0001);
+        assert_that!(c).is_equal_to(Color::black());
     }
 
     #[rstest]
-    fn the_color_when_a_ray_misses(default_world: World) {
+    fn dispersion_refracts_each_channel_separately(default_world: World) {
+        let material = MaterialBuilder::new()
+            .with_transparency(1.0)
+            .with_refractive_index(1.4)
+            .with_dispersion([1.3, 1.4, 1.5])
+            .build();
+        let shape = with_material(&default_world, 0, material);
+        let world = WorldBuilder::new()
+            .with_object(Rc::clone(&shape))
+            .with_environment(Rc::new(UniformEnvironment(Color::white())))
+            .build();
         let r = Ray::new(
-            Vector4::point(0.0, 0.0, -5.0),
+            Vector4::point(0.0, 0.0, 2.0_f32.sqrt() / 2.0),
             Vector4::vector(0.0, 1.0, 0.0),
         );
+        let xs = Intersections::from(vec![
+            Intersection::new(-2.0_f32.sqrt() / 2.0, Rc::clone(&shape)),
+            Intersection::new(2.0_f32.sqrt() / 2.0, shape),
+        ]);
 
-        let c = default_world.color_at(&r);
+        // Leaving at 45 degrees, blue is totally internally reflected but red and green escape
+        let comps = xs[1].prepare_computations_with(&r, &xs);
+        let c = world.refracted_color(&comps, &RenderSettings::default(), 1);
 
-        assert_that!(c).is_equal_to(Color::black());
+        assert_that!(c).is_equal_to(Color::new(1.0, 1.0, 0.0));
+    }
+
+    fn glass_floor(reflective: f32) -> Rc<dyn Shape> {
+        Rc::new(
+            PlaneBuilder::new()
+                .with_transform(Matrix::translation(0.0, -1.0, 0.0))
+                .with_material(
+                    MaterialBuilder::new()
+                        .with_reflective(reflective)
+                        .with_transparency(0.5)
+                        .with_refractive_index(1.5)
+                        .build(),
+                )
+                .build(),
+        )
+    }
+
+    fn red_ball() -> Rc<dyn Shape> {
+        Rc::new(
+            SphereBuilder::new()
+                .with_transform(Matrix::translation(0.0, -3.5, -0.5))
+                .with_material(
+                    MaterialBuilder::new()
+                        .with_color(Color::new(1.0, 0.0, 0.0))
+                        .with_ambient(0.5)
+                        .build(),
+                )
+                .build(),
+        )
     }
 
     #[rstest]
-    fn the_color_when_a_ray_hits(default_world: World) {
+    fn shade_hit_with_a_transparent_material(default_world: World) {
+        let mut world = default_world;
+        let floor = glass_floor(0.0);
+        world.add_object(Rc::clone(&floor));
+        world.add_object(red_ball());
         let r = Ray::new(
-            Vector4::point(0.0, 0.0, -5.0),
-            Vector4::vector(0.0, 0.0, 1.0),
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
         );
-        let expected = Color::new(0.38066, 0.47583, 0.2855);
+        let xs = Intersections::from(vec![Intersection::new(2.0_f32.sqrt(), floor)]);
 
-        let c = default_world.color_at(&r);
+        let comps = xs[0].prepare_computations_with(&r, &xs);
+        let color = world.shade_hit(comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
 
-        assert_that!(c.r).is_close_to(expected.r, 0.0001);
-        assert_that!(c.g).is_close_to(expected.g, 0.0001);
-        assert_that!(c.b).is_close_to(expected.b, 0.0001);
+        assert_approx_eq!(color, Color::new(0.93642, 0.68642, 0.68642), 0.0001);
     }
 
     #[rstest]
-    fn the_color_with_an_intersection_behind_the_ray() {
-        // Arrange
-        let outer_material = MaterialBuilder::new()
-            .with_color(Color::new(0.8, 1.0, 0.6))
-            .with_diffuse(0.7)
-            .with_specular(0.2)
-            .with_ambient(1.0)
-            .build();
-        let inner_material = MaterialBuilder::new().with_ambient(1.0).build();
+    fn shade_hit_with_a_reflective_transparent_material(default_world: World) {
+        let mut world = default_world;
+        let floor = glass_floor(0.5);
+        world.add_object(Rc::clone(&floor));
+        world.add_object(red_ball());
+        let r = Ray::new(
+            Vector4::point(0.0, 0.0, -3.0),
+            Vector4::vector(0.0, -2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0),
+        );
+        let xs = Intersections::from(vec![Intersection::new(2.0_f32.sqrt(), floor)]);
 
-        let outer = SphereBuilder::new().with_material(outer_material).build();
+        let comps = xs[0].prepare_computations_with(&r, &xs);
+        let color = world.shade_hit(comps, &RenderSettings::default(), MAX_RECURSION_DEPTH);
 
-        let inner = SphereBuilder::new()
-            .with_transform(Matrix::scaling(0.5, 0.5, 0.5))
-            .with_material(inner_material)
-            .build();
+        assert_approx_eq!(color, Color::new(0.93391, 0.69643, 0.69243), 0.0001);
+    }
 
-        let world = WorldBuilder::new()
-            .with_object(Rc::new(outer))
-            .with_object(Rc::new(inner))
+    #[rstest]
+    fn the_default_world_has_no_problems(default_world: World) {
+        assert_that!(default_world.check()).is_equal_to(vec![]);
+    }
+
+    #[test]
+    fn checking_finds_problems() {
+        let mut world = WorldBuilder::new()
+            .with_object(Rc::new(
+                SphereBuilder::new()
+                    .with_name("lost")
+                    .with_transform(Matrix::translation(f32::NAN, 0.0, 0.0))
+                    .build(),
+            ))
             .build();
+        world.light_mut(0).unwrap().intensity = Color::new(f32::INFINITY, 1.0, 1.0);
+        world.add_light(PointLight::new(Vector4::point(0.0, 5.0, 0.0), Color::white()));
 
-        // Act
-        let r = Ray::new(
-            Vector4::point(0.0, 0.0, 0.75),
-            Vector4::vector(0.0, 0.0, -1.0),
-        );
+        assert_that!(world.check()).is_equal_to(vec![
+            SceneProblem::BadTransform(Some("lost".to_string())),
+            SceneProblem::BadLight(0),
+        ]);
 
-        // Assert
-        assert_that!(world.color_at(&r)).is_equal_to(Color::white());
+        world.remove_light(1);
+        world.remove_light(0);
+        assert_that!(world.check()).contains(SceneProblem::NoLights);
     }
 
     #[rstest]
-    #[case(Vector4::point(0.0, 10.0, 0.0), false)] // there_is_no_shadow_when_nothing_is_co_linear_with_point_and_light
-    #[case(Vector4::point(10.0, - 10.0, 10.0), true)] // the_shadow_when_an_object_is_between_the_point_and_the_light
-    #[case(Vector4::point(- 20.0, 20.0, - 20.0), false)] // there_is_no_shadow_when_an_object_is_behind_the_light
-    #[case(Vector4::point(- 2.0, 2.0, - 2.0), false)] // there_is_no_shadow_when_an_object_is_behind_the_point
-    fn test_is_shadowed(default_world: World, #[case] p: Vector4, #[case] expected: bool) {
-        assert_that!(default_world.is_shadowed(&p)).is_equal_to(expected);
+    fn summarising_a_world(default_world: World) {
+        let mut world = default_world;
+        world.add_null("pivot");
+        world.add_object(Rc::new(
+            GroupBuilder::new()
+                .with_child(Rc::new(SphereBuilder::new().build()))
+                .with_child(Rc::new(SphereBuilder::new().build()))
+                .build(),
+        ));
+
+        let summary = world.summary();
+
+        assert_that!(summary.objects).is_equal_to(4);
+        assert_that!(summary.shapes).is_equal_to(4);
+        assert_that!(summary.lights).is_equal_to(1);
+        let expected = "4 objects (4 shapes), 1 lights\n\
+                        bounds (-1.00, -1.00, -1.00) to (1.00, 1.00, 1.00)";
+        assert_that!(summary.to_string()).is_equal_to(expected.to_string());
     }
 
-    #[test]
-    fn shade_hit_is_given_an_intersection_in_shadow() {
-        let mut w = WorldBuilder::new().build();
-        w.light_source = PointLight::new(Vector4::point(0.0, 0.0, -10.0), Color::white());
-        let s1: Rc<dyn Shape> = Rc::new(SphereBuilder::new().build());
-        let s2: Rc<dyn Shape> = Rc::new(
-            SphereBuilder::new()
-                .with_transform(Matrix::translation(0.0, 0.0, 10.0))
+    #[rstest]
+    fn counting_what_a_world_is_made_of(default_world: World) {
+        let mut world = default_world;
+        world.add_null("pivot");
+        let triangle = TriangleBuilder::new(
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::point(1.0, 0.0, 0.0),
+            Vector4::point(0.0, 1.0, 0.0),
+        )
+        .build();
+        let inner = GroupBuilder::new()
+            .with_child(Rc::new(triangle.clone()))
+            .with_child(Rc::new(triangle))
+            .build();
+        world.add_object(Rc::new(
+            GroupBuilder::new()
+                .with_child(Rc::new(inner))
+                .with_child(Rc::new(PlaneBuilder::new().build()))
                 .build(),
-        );
-        w.objects.push(s1);
-        w.objects.push(Rc::clone(&s2));
-        let r = Ray::new(
-            Vector4::point(0.0, 0.0, 5.0),
-            Vector4::vector(0.0, 0.0, 1.0),
-        );
-        let i = Intersection::new(4.0, Rc::clone(&s2));
-        let comps = i.prepare_computations(&r);
+        ));
 
-        let c = w.shade_hit(comps);
+        let stats = world.stats();
 
-        assert_that!(c).is_equal_to(Color::new(0.1, 0.1, 0.1));
+        assert_that!(stats.shapes.get("Sphere")).is_equal_to(Some(&2));
+        assert_that!(stats.shapes.get("Triangle")).is_equal_to(Some(&2));
+        assert_that!(stats.shapes.get("Plane")).is_equal_to(Some(&1));
+        assert_that!(stats.triangles).is_equal_to(2);
+        assert_that!(stats.groups).is_equal_to(3);
+        assert_that!(stats.depth).is_equal_to(2);
+        assert_that!(stats.texture_bytes).is_equal_to(0);
+        assert_that!(stats.estimated_bytes).is_greater_than(0);
+    }
+
+    #[test]
+    fn meshes_and_instances_count_their_triangles_and_memory() {
+        let points = vec![
+            Vector4::point(0.0, 0.0, 0.0),
+            Vector4::point(1.0, 0.0, 0.0),
+            Vector4::point(0.0, 1.0, 0.0),
+            Vector4::point(1.0, 1.0, 0.0),
+        ];
+        let mesh: Rc<dyn Shape> =
+            Rc::new(LazyMeshBuilder::new(points, vec![[0, 1, 2], [1, 3, 2]]).build());
+        let instance = || Rc::new(InstanceBuilder::new(Rc::clone(&mesh)).build());
+        let once = WorldBuilder::new().with_object(instance()).build().stats();
+
+        let twice = WorldBuilder::new()
+            .with_object(instance())
+            .with_object(instance())
+            .build()
+            .stats();
+
+        assert_that!(once.triangles).is_equal_to(2);
+        assert_that!(twice.triangles).is_equal_to(4);
+        assert_that!(once.estimated_bytes).is_greater_than(mesh.memory_size(&mut HashSet::new()));
+        // The second instance shares the mesh, so only adds itself
+        assert_that!(twice.estimated_bytes - once.estimated_bytes)
+            .is_equal_to(mem::size_of::<Instance>() + 3 * mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn a_shared_texture_is_counted_once() {
+        let texture: Rc<dyn Pattern> = Rc::new(TexturePattern::new(Canvas::new(4, 4)));
+        let material = MaterialBuilder::new()
+            .with_shared_pattern(Rc::clone(&texture))
+            .build();
+        let world = WorldBuilder::new()
+            .with_object(Rc::new(SphereBuilder::new().with_material(material.clone()).build()))
+            .with_object(Rc::new(SphereBuilder::new().with_material(material).build()))
+            .build();
+
+        let stats = world.stats();
+
+        // 4x4, 2x2 and 1x1 levels of colour and coverage
+        let texel = mem::size_of::<Color>() + mem::size_of::<f32>();
+        assert_that!(stats.texture_bytes).is_equal_to(21 * texel);
+        assert_that!(stats.to_string()).contains("2 shapes: 2 Sphere\n");
     }
 }